@@ -0,0 +1,71 @@
+//! Benchmarks of the hot paths of an analysis run. See `prime_shield_analyzer::bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use prime_shield_analyzer::bench::{self, SegmentSieve};
+use prime_shield_analyzer::sieve::PrimalityChecker;
+use std::hint::black_box;
+
+/// The default --segment-size-kb.
+const SEGMENT_BYTES: usize = 128 * 1024;
+
+fn sieve_segment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sieve_segment");
+    group.throughput(Throughput::Elements((SEGMENT_BYTES * 8) as u64));
+    for start in [1_000_000_000u64, 1_000_000_000_000] {
+        let sieve = SegmentSieve::new(start, SEGMENT_BYTES);
+        group.bench_function(format!("128KiB@{:e}", start as f64), |b| {
+            b.iter(|| black_box(sieve.run()))
+        });
+    }
+    group.finish();
+}
+
+fn is_prime(c: &mut Criterion) {
+    const MAX_N: u64 = 1_000_000_000;
+    const LO: u64 = 1_000_000_000;
+    const LOOKUPS: usize = 4096;
+    let span = (SEGMENT_BYTES * 8) as u64;
+    let mut group = c.benchmark_group("is_prime");
+    group.throughput(Throughput::Elements(LOOKUPS as u64));
+
+    // Every key inside one stored segment: answered from the checker's local cache.
+    let hot_keys = bench::odd_keys(LO, LOOKUPS, span / LOOKUPS as u64);
+    let mut hot = bench::warm_checker(MAX_N, SEGMENT_BYTES, LO, LO + span);
+    group.bench_function("hot", |b| {
+        b.iter(|| {
+            for &n in &hot_keys {
+                black_box(hot.is_prime(n));
+            }
+        })
+    });
+
+    // A fresh store each time and keys spread over many segments: every miss sieves.
+    let cold_keys = bench::odd_keys(LO, 16, span);
+    group.throughput(Throughput::Elements(cold_keys.len() as u64));
+    group.bench_function("cold", |b| {
+        b.iter_batched(
+            || PrimalityChecker::new(2 * MAX_N, SEGMENT_BYTES),
+            |mut checker| {
+                for &n in &cold_keys {
+                    black_box(checker.is_prime(n));
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn analysis_loop(c: &mut Criterion) {
+    const MAX_N: u64 = 10_000_000;
+    let mut group = c.benchmark_group("analysis_loop");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(MAX_N));
+    group.bench_function("1e7", |b| {
+        b.iter(|| black_box(bench::analysis_loop(MAX_N, 1000, SEGMENT_BYTES)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, sieve_segment, is_prime, analysis_loop);
+criterion_main!(benches);
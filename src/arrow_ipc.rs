@@ -0,0 +1,183 @@
+//! Arrow IPC stream output of the oscillation series (`--arrow-bins`, `arrow` feature).
+//!
+//! Bins are appended to `oscillation_series.arrows` in record batches as soon as the prime
+//! stream passes them, and every batch is flushed, so `pyarrow.ipc.open_stream` or
+//! `polars.read_ipc_stream` can read the bins finished so far while the run continues.
+//! The columns match `oscillation_series.csv`.
+
+use crate::output::QUANTILE_COLUMNS;
+use crate::records::BinRecord;
+use crate::stats::{BinStats, Gap};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt64Array};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const ARROW_BINS_FILE: &str = "oscillation_series.arrows";
+
+/// Bins buffered before a record batch is written.
+const BATCH_ROWS: usize = 64;
+
+/// Streams bin records to `oscillation_series.arrows`.
+pub struct ArrowBinWriter {
+    writer: StreamWriter<BufWriter<File>>,
+    schema: SchemaRef,
+    target_gaps: Vec<Gap>,
+    pending: Vec<BinRecord>,
+}
+
+impl fmt::Debug for ArrowBinWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArrowBinWriter")
+            .field("target_gaps", &self.target_gaps)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+impl ArrowBinWriter {
+    pub fn create(output_dir: &str, target_gaps: &[Gap]) -> Result<Self, Box<dyn Error>> {
+        let mut fields = vec![
+            Field::new("bin_start", DataType::UInt64, false),
+            Field::new("bin_end", DataType::UInt64, false),
+            Field::new("prime_count_p", DataType::UInt64, false),
+            Field::new("prime_count_s", DataType::UInt64, false),
+            Field::new("ratio_s_p", DataType::Float64, false),
+        ];
+        for g in target_gaps {
+            fields.push(Field::new(
+                format!("gap_{}_rate", g),
+                DataType::Float64,
+                false,
+            ));
+        }
+        for name in QUANTILE_COLUMNS {
+            fields.push(Field::new(name, DataType::Float64, false));
+        }
+        let schema = Arc::new(Schema::new(fields));
+
+        let file = File::create(Path::new(output_dir).join(ARROW_BINS_FILE))?;
+        let mut writer = StreamWriter::try_new_buffered(file, &schema)?;
+        // Readers need the schema message before the first batch arrives.
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            schema,
+            target_gaps: target_gaps.to_vec(),
+            pending: Vec::with_capacity(BATCH_ROWS),
+        })
+    }
+
+    pub fn write_bin(&mut self, bin: &BinStats) -> Result<(), Box<dyn Error>> {
+        self.pending.push(BinRecord::new(bin, &self.target_gaps));
+        if self.pending.len() >= BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let records = std::mem::take(&mut self.pending);
+        let u64_column = |f: fn(&BinRecord) -> u64| -> ArrayRef {
+            Arc::new(records.iter().map(f).collect::<UInt64Array>())
+        };
+        let mut columns = vec![
+            u64_column(|r| r.bin_start),
+            u64_column(|r| r.bin_end),
+            u64_column(|r| r.prime_count_p),
+            u64_column(|r| r.prime_count_s),
+            Arc::new(
+                records
+                    .iter()
+                    .map(|r| r.ratio_s_p)
+                    .collect::<Float64Array>(),
+            ),
+        ];
+        for slot in 0..self.target_gaps.len() {
+            columns.push(Arc::new(
+                records
+                    .iter()
+                    .map(|r| r.gap_rates[slot].rate)
+                    .collect::<Float64Array>(),
+            ));
+        }
+        for index in 0..QUANTILE_COLUMNS.len() {
+            columns.push(Arc::new(
+                records
+                    .iter()
+                    .map(|r| r.quantiles()[index])
+                    .collect::<Float64Array>(),
+            ));
+        }
+
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+        self.writer.flush()?;
+        self.pending = records;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining bins and the end-of-stream marker.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.write_batch()?;
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sketch::DdSketch;
+    use arrow_ipc::reader::StreamReader;
+
+    #[test]
+    fn test_bins_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        let mut writer = ArrowBinWriter::create(out, &[Gap(2), Gap(4)]).unwrap();
+        for i in 0..(BATCH_ROWS as u64 + 6) {
+            writer
+                .write_bin(&BinStats {
+                    bin_start: i * 10,
+                    bin_end: i * 10 + 9,
+                    prime_count_p: 4,
+                    prime_count_s: 1,
+                    gap_successes: vec![1, 0],
+                    gap_occurrences: vec![2, 1],
+                    gap_sketch: DdSketch::new(),
+                    merit_sketch: DdSketch::new(),
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let file = File::open(dir.path().join(ARROW_BINS_FILE)).unwrap();
+        let batches: Vec<RecordBatch> = StreamReader::try_new(file, None)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).sum::<usize>(),
+            BATCH_ROWS + 6
+        );
+        let schema = batches[0].schema();
+        assert_eq!(schema.field(5).name(), "gap_2_rate");
+        let rates = batches[0]
+            .column(5)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(rates.value(0), 0.5);
+    }
+}
@@ -0,0 +1,98 @@
+//! Fixtures for the Criterion suite in `benches/`, exposing the hot paths of a run with
+//! the setup done up front so benchmarks time only the work itself.
+//!
+//! Run with `cargo bench`; compare two branches with `cargo bench -- --save-baseline main`
+//! on one and `cargo bench -- --baseline main` on the other.
+
+use crate::sieve::{self, PrimalityChecker, PrimeIterator, SegmentBits};
+use crate::stats::{Gap, Prime, Statistics};
+
+/// One segment of the segmented sieve at a fixed position.
+#[derive(Debug, Clone)]
+pub struct SegmentSieve {
+    start: u64,
+    end: u64,
+    base_primes: Vec<u32>,
+}
+
+impl SegmentSieve {
+    /// A segment of `segment_size_bytes` starting at `start`, with the base primes up to
+    /// the square root of its end.
+    pub fn new(start: u64, segment_size_bytes: usize) -> Self {
+        let end = start + (segment_size_bytes * 8) as u64;
+        let sqrt_end = (end as f64).sqrt() as u64 + 1;
+        let base_primes = sieve::base_sieve(sqrt_end)
+            .iter_ones()
+            .map(|i| i as u32)
+            .collect();
+        Self {
+            start,
+            end,
+            base_primes,
+        }
+    }
+
+    /// Sieves the segment on the heap.
+    pub fn run(&self) -> SegmentBits {
+        PrimeIterator::sieve_segment(self.start, self.end, &self.base_primes, None)
+    }
+}
+
+/// A checker for sums up to `2 * max_n` whose store already holds the segments covering
+/// `[lo, hi]`, as it would mid-run.
+pub fn warm_checker(max_n: u64, segment_size_bytes: usize, lo: u64, hi: u64) -> PrimalityChecker {
+    let mut checker = PrimalityChecker::new(2 * max_n, segment_size_bytes);
+    checker.ensure_range(lo, hi);
+    checker
+}
+
+/// `count` odd numbers from `lo`, spaced `stride` apart (rounded up to even), as lookup
+/// keys; a stride wider than a segment makes every lookup land in a different segment.
+pub fn odd_keys(lo: u64, count: usize, stride: u64) -> Vec<u64> {
+    let stride = stride.max(2).next_multiple_of(2);
+    (0..count as u64).map(|i| (lo | 1) + i * stride).collect()
+}
+
+/// The analysis loop of a complete run up to `max_n` (sieve, prefetched sums, statistics)
+/// without checkpoints, dumps or any output.
+pub fn analysis_loop(max_n: u64, bins: usize, segment_size_bytes: usize) -> Statistics {
+    let gaps = [Gap(2), Gap(4), Gap(6), Gap(12), Gap(30)];
+    let mut stats = Statistics::new(max_n, bins, &gaps, 2048);
+    let mut checker = PrimalityChecker::new(2 * max_n, segment_size_bytes);
+    let segment_span = (segment_size_bytes * 8) as u64;
+
+    let mut primes = PrimeIterator::new(max_n, segment_size_bytes).map(Prime);
+    let Some(mut p_prev) = primes.next() else {
+        return stats;
+    };
+    stats.record_first_prime();
+    let mut next_mark = p_prev.0 + segment_span;
+    checker.ensure_range(2 * p_prev.0, 2 * next_mark);
+    checker.prefetch_range(2 * next_mark, 2 * (next_mark + segment_span));
+    for p_current in primes {
+        if p_current.0 >= next_mark {
+            next_mark = p_prev.0 + segment_span;
+            checker.ensure_range(2 * p_prev.0, 2 * next_mark);
+            checker.prefetch_range(2 * next_mark, 2 * (next_mark + segment_span));
+        }
+        let s = p_current.0 + p_prev.0 - 1;
+        let s_is_prime = checker.is_prime(s);
+        stats.record_pair(p_prev, p_current, s, s_is_prime);
+        p_prev = p_current;
+    }
+    stats.analyzed_up_to = max_n;
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis_loop_matches_a_run() {
+        // The self-test fixture: π(10^6) and the prime sums of a full run to 10^6.
+        let stats = analysis_loop(1_000_000, 100, 16 * 1024);
+        assert_eq!(stats.total_primes, 78498);
+        assert_eq!(stats.total_s_primes, 14526);
+    }
+}
@@ -0,0 +1,51 @@
+//! The `completions` subcommand: shell completions and a man page generated from the
+//! [`Cli`] definition itself, so they always match the flags of the build that printed them.
+
+use crate::config::{Cli, CompletionTarget, CompletionsConfig};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::error::Error;
+use std::io::{self, Write};
+
+/// Writes the completion script or man page for `target` to `out`.
+pub fn write(target: CompletionTarget, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    match target {
+        CompletionTarget::Bash => clap_complete::generate(Shell::Bash, &mut command, name, out),
+        CompletionTarget::Zsh => clap_complete::generate(Shell::Zsh, &mut command, name, out),
+        CompletionTarget::Fish => clap_complete::generate(Shell::Fish, &mut command, name, out),
+        CompletionTarget::Man => clap_mangen::Man::new(command).render(out)?,
+    }
+    Ok(())
+}
+
+pub fn run(config: &CompletionsConfig) -> Result<(), Box<dyn Error>> {
+    write(config.shell, &mut io::stdout().lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outputs_cover_every_flag() {
+        let command = Cli::command();
+        for target in [
+            CompletionTarget::Bash,
+            CompletionTarget::Zsh,
+            CompletionTarget::Fish,
+            CompletionTarget::Man,
+        ] {
+            let mut out = Vec::new();
+            write(target, &mut out).unwrap();
+            // The man page escapes hyphens.
+            let text = String::from_utf8(out).unwrap().replace("\\-", "-");
+            for arg in command.get_arguments() {
+                if let Some(long) = arg.get_long() {
+                    assert!(text.contains(long), "{:?} lacks --{}", target, long);
+                }
+            }
+        }
+    }
+}
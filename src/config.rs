@@ -1,30 +1,1136 @@
-use clap::Parser;
+use crate::factoring::FactorClassifier;
+use crate::model;
+use crate::shielding::{ShieldModel, SMALL_PRIMES};
+use crate::sieve;
+use crate::stats;
+use crate::thresholds::{self, FailIf};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+/// Color theme of the HTML report.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Black on white with heavy borders and lines, for low vision and projectors.
+    #[serde(rename = "high-contrast")]
+    HighContrast,
+    /// Follow the viewer's operating system preference.
+    Auto,
+}
+
+/// Largest gap in the report's spectrum chart, set with `--spectrum-chart-max`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpectrumChartMax {
+    /// The smallest gap such that the gaps up to it make up [`SPECTRUM_CHART_COVERAGE`] of
+    /// all prime pairs.
+    Auto,
+    Gap(u64),
+}
+
+/// Share of the prime pairs the spectrum chart covers under [`SpectrumChartMax::Auto`].
+pub const SPECTRUM_CHART_COVERAGE: f64 = 0.999;
+
+/// Parses a prime of `--shield-exclude`, one of the odd primes the shield model uses.
+fn parse_shield_prime(s: &str) -> Result<u32, String> {
+    let q: u32 = s.trim().parse().map_err(|e| format!("{}", e))?;
+    if !SMALL_PRIMES.contains(&q) {
+        return Err(format!("{} is not one of the odd primes up to 97", q));
+    }
+    Ok(q)
+}
+
+/// Parses `--spectrum-chart-max`: `auto` or a gap size.
+fn parse_spectrum_chart_max(s: &str) -> Result<SpectrumChartMax, String> {
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(SpectrumChartMax::Auto);
+    }
+    s.trim()
+        .parse()
+        .map(SpectrumChartMax::Gap)
+        .map_err(|_| format!("expected a gap size or 'auto', got '{}'", s))
+}
+
+/// Format of the plot specs written by `--plot-spec`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlotSpec {
+    /// A gnuplot script (`plots.gp`) rendering PNGs.
+    Gnuplot,
+    /// Vega-Lite JSON specs (`*.vl.json`).
+    Vega,
+}
+
+/// Destination of the result tables, added with `--sink`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SinkKind {
+    /// The CSV files, which are always written.
+    Csv,
+    /// One `<table>.ndjson` file per table.
+    Json,
+    /// NDJSON on stdout, each row tagged with its table and max N; implies `--quiet`.
+    Stream,
+    /// `results.sqlite`, one table per CSV file (`sqlite` feature).
+    Sqlite,
+    /// One `<table>.parquet` file per table (`parquet` feature).
+    Parquet,
+}
+
+/// Landmarks that `--bin-align` puts bin boundaries on.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinAlign {
+    /// 10, 100, 1000, …
+    Pow10,
+    /// 2, 4, 8, …
+    Pow2,
+    /// 2, 6, 30, 210, …
+    Primorial,
+}
+
+/// Expected success rate that `--baseline` makes the primary one in `gap_spectrum.csv`
+/// and the report.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Baseline {
+    /// 1 / ln N.
+    #[value(name = "ln-n")]
+    LnN,
+    /// 2 / ln N: S is always odd.
+    #[value(name = "2-ln-n")]
+    TwoLnN,
+    /// 2 / ln S averaged over the pairs of the run.
+    #[value(name = "local-ln-s")]
+    LocalLnS,
+    /// Hardy–Littlewood: `local-ln-s` times the gap's exact residue-class boost.
+    Hl,
+}
+
+impl Baseline {
+    /// The name given on the command line, also written to `gap_spectrum.csv`.
+    pub fn label(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// The range the bins of the oscillation series partition.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BinDomain {
+    /// Primes p are counted by p and prime sums by S.
+    #[default]
+    P,
+    /// Every pair, prime sum or not, is counted in the bin of its S.
+    S,
+}
+
+/// The number a prime pair is placed by in the bins of the oscillation series.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeBy {
+    /// p_n.
+    Prev,
+    /// p_{n+1}.
+    #[default]
+    Curr,
+    /// S = p_n + p_{n+1} - 1.
+    S,
+    /// (p_n + p_{n+1}) / 2.
+    Midpoint,
+}
+
+impl AttributeBy {
+    /// Where the pair `(p_prev, p_curr)` lies under this rule.
+    pub fn position(self, p_prev: u64, p_curr: u64) -> u64 {
+        match self {
+            AttributeBy::Prev => p_prev,
+            AttributeBy::Curr => p_curr,
+            AttributeBy::S => p_prev + p_curr - 1,
+            AttributeBy::Midpoint => p_prev + (p_curr - p_prev) / 2,
+        }
+    }
+}
+
+/// The sum of consecutive primes whose primality a run tests.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Formula {
+    /// S = p_n + p_{n+1} - 1.
+    Pair,
+    /// S_3 = p_n + p_{n+1} + p_{n+2} - 2, keyed by both gaps.
+    Triple,
+}
+
+/// Weight that `--pair-weight` gives each prime pair `(p_n, p_{n+1})` in the weighted columns.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairWeight {
+    /// 1 / ln p_n, the logarithmic measure of many analytic statements.
+    InverseLog,
+    /// 1 / ln² p_n, which flattens the falling density of prime pairs.
+    InverseLogSquared,
+}
+
+impl PairWeight {
+    pub fn weight(self, p: u64) -> f64 {
+        let ln_p = (p as f64).ln();
+        match self {
+            PairWeight::InverseLog => 1.0 / ln_p,
+            PairWeight::InverseLogSquared => 1.0 / (ln_p * ln_p),
+        }
+    }
+}
+
+/// The exponent E of an analysis bound N = 10^E, integer or fractional (`9.5`).
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Exponent(pub f64);
+
+/// Integer exponents stay integers in manifests and the registry, as before fractional ones.
+impl Serialize for Exponent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.fract() == 0.0 {
+            serializer.serialize_u64(self.0 as u64)
+        } else {
+            serializer.serialize_f64(self.0)
+        }
+    }
+}
+
+impl Exponent {
+    /// Largest exponent whose range of sums S, up to 2 · 10^E, fits in a u64.
+    pub const MAX: f64 = 18.9;
+
+    /// 10^E, exact for integer exponents and rounded to the nearest integer otherwise.
+    pub fn max_n(self) -> u64 {
+        if self.0.fract() == 0.0 {
+            10u64.pow(self.0 as u32)
+        } else {
+            10f64.powf(self.0).round() as u64
+        }
+    }
+}
+
+impl std::fmt::Display for Exponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Slice I of K equal slices of the range of p (`--shard I/K`, I from 0). A shard analyzes
+/// the pairs whose first prime lies in its slice, so the K shards cover every pair once.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    /// Job array variables of the shard of this task: task SLURM_ARRAY_TASK_ID -
+    /// SLURM_ARRAY_TASK_MIN of SLURM_ARRAY_TASK_COUNT.
+    pub fn from_slurm_env() -> Result<Self, String> {
+        let var = |name: &str| -> Result<u64, String> {
+            let value = std::env::var(name)
+                .map_err(|_| format!("--slurm-array needs {} (run as a Slurm job array)", name))?;
+            value
+                .parse()
+                .map_err(|_| format!("{} is not a number: '{}'", name, value))
+        };
+        let (id, min, count) = (
+            var("SLURM_ARRAY_TASK_ID")?,
+            var("SLURM_ARRAY_TASK_MIN")?,
+            var("SLURM_ARRAY_TASK_COUNT")?,
+        );
+        match id.checked_sub(min) {
+            Some(index) if index < count => Ok(Self { index, count }),
+            _ => Err(format!(
+                "task {} lies outside the array of {} tasks from {}; submit it as --array=0-{}",
+                id,
+                count,
+                min,
+                count.saturating_sub(1)
+            )),
+        }
+    }
+
+    /// The slice `[start, end)` of p for max N `max_n`; the last one ends at `max_n`.
+    pub fn range(self, max_n: u64) -> (u64, u64) {
+        let bound = |i: u64| (max_n as u128 * i as u128 / self.count as u128) as u64;
+        (bound(self.index), bound(self.index + 1))
+    }
+
+    /// Directory of the shard's outputs inside the output directory, e.g. `shard_03` of 16.
+    pub fn dir_name(self) -> String {
+        let width = (self.count - 1).max(1).ilog10() as usize + 1;
+        format!("shard_{:0width$}", self.index, width = width)
+    }
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Parses a `--shard` written as `I/K`.
+fn parse_shard(s: &str) -> Result<Shard, String> {
+    let invalid = || {
+        format!(
+            "expected a shard like 3/16 (I from 0 to K - 1), got '{}'",
+            s
+        )
+    };
+    let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+    match (index.trim().parse(), count.trim().parse()) {
+        (Ok(index), Ok(count)) if index < count => Ok(Shard { index, count }),
+        _ => Err(invalid()),
+    }
+}
+
+/// What `--out-of-range` does with a sum S above the primality checker's limit.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutOfRange {
+    /// Count it as composite.
+    Composite,
+    /// Test it with deterministic Miller–Rabin instead.
+    MillerRabin,
+}
+
+/// What `--disk-check` does when the outputs of a run may not fit on the disk.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiskCheck {
+    /// Refuse to start the run.
+    Abort,
+    /// Print a warning and run anyway.
+    Warn,
+    /// Skip the check.
+    Off,
+}
+
+/// Encoding of the values in `--dump-primes` / `--dump-s-primes` streams.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One decimal value per line (`.txt.gz`).
+    Text,
+    /// Little-endian u64 values (`.bin.gz`).
+    Binary,
+}
+
+/// Parses a `KEY=VALUE` pair of `--meta`.
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
+/// Parses a sampling rate written as `1/K` (or just `K`).
+fn parse_sample_rate(s: &str) -> Result<u64, String> {
+    let k = s.strip_prefix("1/").unwrap_or(s);
+    match k.trim().parse::<u64>() {
+        Ok(k) if k >= 1 => Ok(k),
+        _ => Err(format!("expected a rate like 1/100, got '{}'", s)),
+    }
+}
 
 /// A high-performance Rust tool for analyzing structural bias in consecutive prime sums.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Without a subcommand, runs the exhaustive analysis up to 10^E.
+    #[command(flatten)]
+    pub run: Option<Config>,
+
+    /// Seed of every random choice (sampled pairs, probe windows), so a run can be
+    /// repeated exactly; recorded in manifest.json.
+    #[arg(long, global = true, value_name = "SEED")]
+    pub seed: Option<u64>,
+}
+
+impl Cli {
+    /// Hands `--seed` to the options that draw random numbers. Each feature gets its own
+    /// stream derived from the seed, except the probe, which takes it as is.
+    pub fn apply_seed(&mut self) {
+        let Some(seed) = self.seed else {
+            return;
+        };
+        if let Some(run) = self.run.as_mut() {
+            run.apply_seed(seed);
+        }
+        match self.command.as_mut() {
+            Some(Command::Probe(probe)) => probe.seed = seed,
+            Some(Command::Ingest(ingest)) => ingest.run.apply_seed(seed),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Estimate the statistics far beyond sieving reach from random windows, using
+    /// Miller-Rabin for every primality test.
+    Probe(ProbeConfig),
+    /// Compare two result directories gap by gap and bin by bin with two-proportion tests.
+    Diff(DiffConfig),
+    /// Plot how the global S/P ratio and gap rates evolve with max N across registered runs.
+    Trends(TrendsConfig),
+    /// Delete the prime dumps, checkpoints and write-ahead logs of registered runs older
+    /// than a retention window, keeping their CSV summaries and manifests.
+    Prune(PruneConfig),
+    /// Analyze prime pairs and primality verdicts computed elsewhere (CSV or NDJSON of
+    /// p_prev, p_curr, s_is_prime), writing the regular outputs and report.
+    Ingest(Box<IngestConfig>),
+    /// Split an analysis into shards for a cluster: list them, or print a Slurm job array
+    /// script that runs one shard per task (`--slurm`).
+    Plan(PlanConfig),
+    /// Check a run's first-occurrence gaps, maximal gaps and π(10^k) against OEIS tables.
+    CheckOeis(CheckOeisConfig),
+    /// Upgrade an output directory written by an older version (missing columns, no
+    /// manifest) to the current layout.
+    Migrate(MigrateConfig),
+    /// Run a canned analysis up to 10^6 with every optional output, list what each output
+    /// file holds and open the report.
+    Demo(DemoConfig),
+    /// Run a tiny built-in analysis (N = 10^6) and compare every output value against
+    /// golden fixtures, to verify a build before trusting it with a big run.
+    SelfTest(SelfTestConfig),
+    /// Print π(x), the number of primes up to x (e.g. `pi 1e10`).
+    Pi(PiConfig),
+    /// Print the nth prime (e.g. `nth-prime 10^9`).
+    NthPrime(NthPrimeConfig),
+    /// Print shell completions or the man page, generated from these argument definitions.
+    Completions(CompletionsConfig),
+}
+
+/// Options of the exhaustive analysis.
+#[derive(Args, Debug, Clone)]
 pub struct Config {
     /// The upper bound N, expressed as an exponent for 10^N.
-    /// E.g., if --max-exponent 10 is provided, N will be 10^10.
-    #[arg(short = 'E', long)]
-    pub max_exponent: u32,
+    /// E.g., if --max-exponent 10 is provided, N will be 10^10. Fractional exponents
+    /// (9.5) are accepted, and a list (8,9,10) analyzes up to the largest in one pass,
+    /// writing the complete outputs of each smaller one to `e<E>/` in the output directory.
+    #[arg(
+        short = 'E',
+        long = "max-exponent",
+        value_name = "E",
+        value_delimiter = ',',
+        required = true,
+        value_parser = parse_exponent
+    )]
+    pub max_exponents: Vec<Exponent>,
+
+    /// The sum to test: the pair sum of the regular analysis, or the sum of three
+    /// consecutive primes with its own shielding model, written to `triple_spectrum.csv`.
+    #[arg(long, value_enum, default_value_t = Formula::Pair)]
+    pub formula: Formula,
 
     /// Number of resolution bins for the time-series output.
     #[arg(short, long, default_value_t = 1000)]
     pub bins: usize,
 
+    /// Put bin boundaries on powers of ten, powers of two or primorials: each landmark
+    /// replaces the nearest regular boundary, or adds a bin where landmarks are closer
+    /// together than the bins.
+    #[arg(long, value_enum, value_name = "LANDMARKS")]
+    pub bin_align: Option<BinAlign>,
+
+    /// The bin a prime pair's gap counts and gap quantiles go to, when p_n and p_{n+1}
+    /// straddle a boundary: that of p_n, p_{n+1}, S or their midpoint. Prime counts stay
+    /// with the bins of p and S.
+    #[arg(long, value_enum, default_value_t = AttributeBy::Curr)]
+    pub attribute_by: AttributeBy,
+
+    /// Bin by p (the primes p and the prime sums S each in their own bin) or by S (every
+    /// pair in the bin of its sum, so that a bin's ratio is the share of its sums S that
+    /// are prime).
+    #[arg(long, value_enum, default_value_t = BinDomain::P)]
+    pub bin_domain: BinDomain,
+
+    /// Write each bin to oscillation_series.csv as soon as the prime stream passes it and
+    /// free its memory, instead of holding every bin until the end of the run.
+    #[arg(long)]
+    pub stream_bins: bool,
+
     /// Directory for output files.
     #[arg(short, long, default_value = "results")]
     pub output_dir: String,
+
+    /// Print only errors and warnings: no banner, progress bar or stage messages.
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print more detail: -v adds stage timings and primality cache statistics, -vv a line
+    /// per sieve segment.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Also stream the oscillation series to `oscillation_series.arrows` (Arrow IPC) in
+    /// record batches while the run progresses. Requires the `arrow` feature.
+    #[arg(long)]
+    pub arrow_bins: bool,
+
     /// Manually set the sieve segment size in Kilobytes (KB).
     #[arg(long, default_value_t = 128)]
     pub segment_size_kb: usize,
 
+    /// Sum segments the primality checker keeps in its own cache in front of the shared
+    /// store; perf.json reports its hits, misses and evictions.
+    #[arg(long, value_name = "N", default_value_t = sieve::DEFAULT_CHECKER_CACHE_SEGMENTS,
+          value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub checker_cache_segments: usize,
+
+    /// Adjust the sieve segment size between segments to maximize measured primes/sec,
+    /// starting from --segment-size-kb.
+    #[arg(long)]
+    pub adaptive_segment_size: bool,
+
+    /// Back sieve segments with memory-mapped scratch files in DIR instead of RAM, so
+    /// very large --segment-size-kb values can page to disk.
+    #[arg(long, value_name = "DIR")]
+    pub mmap_segments: Option<String>,
+
+    /// Pin sieve worker threads to cores and give each a fixed chunk of every segment,
+    /// keeping memory traffic NUMA-local on multi-socket machines.
+    #[arg(long)]
+    pub pin_threads: bool,
+
+    /// Split the marking of each sum segment across the thread pool even when the segments
+    /// themselves are sieved in parallel (by default each is marked by one thread).
+    #[arg(long)]
+    pub nested_parallel_marking: bool,
+
+    /// Make identical configurations produce byte-identical output directories: fixed
+    /// segment-marking partitions, synchronous prefetching, no wall-clock timings or peak
+    /// RSS in perf.json, and no throughput.csv. Cannot be combined with --adaptive-segment-size.
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Print the exact number of primes and sieve segments of the run (π(N) by
+    /// Meissel–Lehmer) with rough memory and runtime estimates, then exit without sieving.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// What to do when the estimated size of the outputs (dumps, snapshots, bins, state
+    /// files) exceeds the free space of the output directory's filesystem.
+    #[arg(long, value_enum, default_value_t = DiskCheck::Abort)]
+    pub disk_check: DiskCheck,
+
+    /// Sample a CPU profile of the run and write it as flamegraph.svg next to perf.json.
+    /// Requires the `profile` feature.
+    #[arg(long)]
+    pub profile: bool,
+
     /// A comma-separated list of prime gap sizes to track (e.g., "2,4,6,12"). All gaps must be even and > 0.
     #[arg(long, default_value = "2,4,6,12,30", value_delimiter = ',')]
     pub gaps: Vec<u64>,
 
+    /// Also track gap 1, the only odd gap (between 2 and 3), next to `--gaps`: per bin, in
+    /// the streaks and in gap_bins.csv. Meant for small runs checked by hand.
+    #[arg(long)]
+    pub include_gap_one: bool,
+
+    /// Also classify every sum S as prime, semiprime, 3-almost-prime or more by trial
+    /// division, per gap size in `almost_prime_spectrum.csv`.
+    #[arg(long)]
+    pub almost_primes: bool,
+
+    /// Largest trial divisor of `--almost-primes` (default: the cube root of 2N, which
+    /// classifies every S exactly; sums a smaller bound cannot settle count as unresolved).
+    #[arg(long, value_name = "B", requires = "almost_primes")]
+    pub factor_bound: Option<u64>,
+
+    /// Log every analyzed prime pair with a gap of at least G to `large_gap_events.csv`,
+    /// with its sum S, the smallest prime factor of a composite S and the gaps on either side.
+    #[arg(long, value_name = "G")]
+    pub large_gap_threshold: Option<u64>,
+
+    /// Also pool the gap spectrum into residue families g mod M for each of these moduli
+    /// (e.g. `6,30`), with counts, rates and boosts in `gap_families.csv`.
+    #[arg(long, value_name = "MODULI", value_delimiter = ',',
+          value_parser = clap::value_parser!(u64).range(2..))]
+    pub gap_families: Vec<u64>,
+
+    /// Split the range at p = N for the early and late columns of `gap_spectrum.csv`, which
+    /// compare each gap's rate below and above it (e.g. 1e8; default: half of max N).
+    #[arg(long, value_name = "N", value_parser = parse_magnitude)]
+    pub split_at: Option<u64>,
+
+    /// Largest gap size counted in the dense gap spectrum array (default: the conjectured
+    /// maximal gap below N, 2e^-γ ln² N). Larger gaps are still recorded exactly, but
+    /// through a slower overflow list.
+    #[arg(long, value_name = "G")]
+    pub spectrum_max_gap: Option<u64>,
+
+    /// Analyze only a sample of the prime pairs, written as 1/K; pair counts are scaled by K
+    /// and global_stats.csv reports the resulting sampling error.
+    #[arg(long, value_name = "1/K", default_value = "1/1", value_parser = parse_sample_rate)]
+    pub sample: u64,
+
+    /// Also count every pair with this weight, written next to the plain counts in
+    /// gap_spectrum.csv and global_stats.csv.
+    #[arg(long, value_enum, value_name = "WEIGHT")]
+    pub pair_weight: Option<PairWeight>,
+
+    /// Pick the sampled pairs at random with this seed instead of taking every K-th pair
+    /// (default with --seed: a seed derived from it).
+    #[arg(long, value_name = "SEED")]
+    pub sample_seed: Option<u64>,
+
+    /// Analyze only the pairs whose p lies in slice I of K equal slices of [0, N), I from 0,
+    /// writing to `shard_<I>/` inside the output directory (see the `plan` subcommand).
+    #[arg(long, value_name = "I/K", value_parser = parse_shard)]
+    pub shard: Option<Shard>,
+
+    /// Take `--shard` from the Slurm job array of this task: SLURM_ARRAY_TASK_ID (less
+    /// SLURM_ARRAY_TASK_MIN) of SLURM_ARRAY_TASK_COUNT.
+    #[arg(long, conflicts_with = "shard")]
+    pub slurm_array: bool,
+
+    /// The global `--seed`, if given.
+    #[arg(skip)]
+    pub seed: Option<u64>,
+
+    /// Add the mod-5 trap to the shield model's theoretical boost.
+    #[arg(long)]
+    pub mod5_trap: bool,
+
+    /// Add the divisor interaction terms (small primes dividing the gap) to the shield
+    /// model's theoretical boost.
+    #[arg(long)]
+    pub divisor_interactions: bool,
+
+    /// Leave the terms of 3 out of the shield model: the Mod 3 Rule shield of gaps
+    /// g ≡ 1 (mod 3) and, with `--divisor-interactions`, the factor 3/4 for gaps divisible
+    /// by 3. The same model as `--shield-exclude 3`, under its own label.
+    #[arg(long)]
+    pub no_mod3_penalty: bool,
+
+    /// Count 2 as a shield of every even gap in the shield model: S is always odd, which
+    /// doubles the theoretical boost.
+    #[arg(long)]
+    pub shield_include_two: bool,
+
+    /// Odd primes up to 97 left out of the shield model entirely: as shields, from the
+    /// mod-5 trap and from the divisor interactions (e.g. `3,5`).
+    #[arg(long, value_name = "PRIMES", value_delimiter = ',', value_parser = parse_shield_prime)]
+    pub shield_exclude: Vec<u32>,
+
+    /// Largest prime q of the primorial 3·5·…·q whose residue classes are enumerated for
+    /// each gap's `exact_boost` (at most 19).
+    #[arg(long, value_name = "Q", default_value_t = model::DEFAULT_PRIME_LIMIT,
+          value_parser = clap::value_parser!(u64).range(3..=model::MAX_PRIME_LIMIT))]
+    pub residue_prime_limit: u64,
+
+    /// Expected success rate written as `expected_rate_heuristic` and compared against in
+    /// the report.
+    #[arg(long, value_enum, default_value_t = Baseline::LnN)]
+    pub baseline: Baseline,
+
+    /// How to test a sum S above the primality checker's limit (2N). Such sums are always
+    /// counted per gap in `gap_spectrum.csv`'s `out_of_range` column, with a warning.
+    #[arg(long, value_enum, default_value_t = OutOfRange::Composite)]
+    pub out_of_range: OutOfRange,
+
+    /// Split gap_spectrum.csv and oscillation_series.csv into numbered parts of at most
+    /// ROWS rows, listed in a `_index.json` file (0 never splits).
+    #[arg(long, value_name = "ROWS", default_value_t = 1_000_000)]
+    pub partition_rows: u64,
+
+    /// Also send the result tables to this sink (repeatable, e.g. `--sink json --sink
+    /// sqlite`). The CSV files are always written.
+    #[arg(long = "sink", value_enum, value_name = "SINK")]
+    pub sinks: Vec<SinkKind>,
+
+    /// Write every prime p up to N to `primes.txt.gz` (or `primes.bin.gz`).
+    #[arg(long)]
+    pub dump_primes: bool,
+
+    /// Write every sum S that tested prime to `s_primes.txt.gz` (or `s_primes.bin.gz`).
+    #[arg(long)]
+    pub dump_s_primes: bool,
+
+    /// Encoding of the dump streams, both gzip-compressed.
+    #[arg(long, value_enum, default_value_t = DumpFormat::Text)]
+    pub dump_format: DumpFormat,
+
+    /// Save the final statistics as a versioned binary state file (`statistics.state`)
+    /// in the output directory.
+    #[arg(long)]
+    pub save_state: bool,
+
+    /// Write `checkpoint.state` to the output directory at most every SECS seconds
+    /// (0 disables checkpoints).
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub checkpoint_every: u64,
+
+    /// Rewrite the result files in the output directory with the bins and gaps completed
+    /// so far at most every MINUTES minutes (0 disables intermediate results).
+    #[arg(long, value_name = "MINUTES", default_value_t = 0)]
+    pub flush_every: u64,
+
+    /// Write the complete CSV outputs of the cumulative statistics to `snapshots/n_<N>/`
+    /// each time the analysis passes a multiple of STEP (e.g. 1e8 or 10^8), for
+    /// convergence plots from a single run.
+    #[arg(
+        long,
+        value_name = "STEP",
+        value_parser = parse_magnitude,
+        conflicts_with_all = ["stream_bins", "snapshot_every_primes"]
+    )]
+    pub snapshot_every: Option<u64>,
+
+    /// Like `--snapshot-every`, but every M primes.
+    #[arg(long, value_name = "M", conflicts_with = "stream_bins")]
+    pub snapshot_every_primes: Option<u64>,
+
+    /// Append a record to the write-ahead log (`analysis.wal`) after every sieve segment,
+    /// so a resumed run loses at most one segment of work.
+    #[arg(long)]
+    pub wal: bool,
+
+    /// Continue an interrupted run from `checkpoint.state` and `analysis.wal` in the
+    /// output directory.
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Report a stall when the analysis has not reached a new segment for SECS seconds:
+    /// the current segment range, cache state and thread states go to stderr and
+    /// `watchdog.log`. 0 disables the watchdog.
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub watchdog_secs: u64,
+
+    /// Abort the process (leaving a core dump where enabled) after reporting a stall.
+    #[arg(long)]
+    pub watchdog_abort: bool,
+
+    /// Record a summary of this run in the given run registry (e.g. `runs.json`), for the
+    /// `trends` subcommand.
+    #[arg(long, value_name = "PATH")]
+    pub registry: Option<String>,
+
+    /// A human-readable label for the run (e.g. "formula B, shield radius 2"), stored in
+    /// manifest.json and the registry and shown in the report header.
+    #[arg(long)]
+    pub label: Option<String>,
+
+    /// Free-form metadata as KEY=VALUE, stored alongside --label (repeatable).
+    #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub meta: Vec<(String, String)>,
+
+    /// Keep only these columns of one CSV output, as FILE=COL,COL,… with FILE the name
+    /// without `.csv`, e.g. `gap_spectrum=gap_size,count,successes,success_rate` (repeatable).
+    #[arg(long = "columns", value_name = "FILE=COLUMNS", value_parser = parse_key_value,
+          conflicts_with_all = ["web_report", "report_pdf", "plot_spec"])]
+    pub columns: Vec<(String, String)>,
+
+    /// Leave the derived columns (confidence intervals, p-values, expected rates, shielding)
+    /// out of every CSV output.
+    #[arg(long, conflicts_with_all = ["web_report", "report_pdf", "plot_spec"])]
+    pub slim: bool,
+
+    /// Fail the run with exit code 3 if this condition on the final statistics holds, e.g.
+    /// `gap4.rate < 1.1*baseline`: metrics are `gap<G>.<column>` of gap_spectrum.csv (with
+    /// `rate`, `expected` and `boost` shorthands), columns of global_stats.csv (`ratio`)
+    /// and `baseline`, the pooled success rate (repeatable).
+    #[arg(long, value_name = "CONDITION", value_parser = thresholds::parse_fail_if)]
+    pub fail_if: Vec<FailIf>,
+
+    /// Also write ready-to-run plot specs for the report's main charts that read the CSVs
+    /// in the output directory.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub plot_spec: Option<PlotSpec>,
+
     /// Generate a self-contained HTML report with interactive charts.
     #[arg(long)]
     pub web_report: bool,
+
+    /// Also keep a snapshot of the report each time the analysis passes a power of ten
+    /// (`index_e6.html`, `index_e7.html`, …), listed in `snapshots.html`.
+    #[arg(long, requires = "web_report")]
+    pub report_snapshots: bool,
+
+    /// Embed copies of the CSV outputs and of the chart data in the HTML report so it can be
+    /// shared as a single file.
+    #[arg(long)]
+    pub embed_data: bool,
+
+    /// Also print the HTML report to `report.pdf` (implies --web-report).
+    /// Requires a Chromium-based browser on the PATH or in CHROME_PATH.
+    #[arg(long)]
+    pub report_pdf: bool,
+
+    /// A comma-separated list of gaps to emphasize in the report's Theory Verification chart.
+    #[arg(long, default_value = "4,34", value_delimiter = ',')]
+    pub highlight_gaps: Vec<u64>,
+
+    /// Largest gap in the report's success rate spectrum chart, or `auto` for the gaps that
+    /// make up 99.9% of the prime pairs. The gap table always lists every gap.
+    #[arg(long, value_name = "G|auto", default_value = "auto", value_parser = parse_spectrum_chart_max)]
+    pub spectrum_chart_max: SpectrumChartMax,
+
+    /// Maximum number of rows in the report's oscillation and throughput charts, shared by
+    /// all their series. Longer series are downsampled with LTTB; the CSV always keeps full
+    /// resolution.
+    #[arg(long, default_value_t = 2000)]
+    pub report_max_points: usize,
+
+    /// Color theme of the HTML report. Viewers can still toggle it in the page.
+    #[arg(long, value_enum, default_value_t = Theme::Light)]
+    pub theme: Theme,
+
+    /// BCP 47 locale used for number formatting in the report (e.g. "de-DE", "fr-FR").
+    #[arg(long, default_value = "en-US")]
+    pub locale: String,
+}
+
+impl Config {
+    /// Records the global `--seed` and derives the sampling seed from it.
+    fn apply_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.sample_seed = self
+            .sample_seed
+            .or(Some(stats::derive_seed(seed, "sample")));
+    }
+
+    /// The largest `--max-exponent`, which bounds the run.
+    pub fn max_exponent(&self) -> Exponent {
+        self.max_exponents
+            .iter()
+            .copied()
+            .fold(Exponent(0.0), |a, b| if b > a { b } else { a })
+    }
+
+    /// `--quiet`, which `--sink stream` implies to keep stdout to its rows.
+    pub fn quiet(&self) -> bool {
+        self.quiet || self.sinks.contains(&SinkKind::Stream)
+    }
+
+    /// N = 10^E of the largest `--max-exponent`.
+    pub fn max_n(&self) -> u64 {
+        self.max_exponent().max_n()
+    }
+
+    /// `--spectrum-max-gap`, or the maximal-gap bound for a run up to `max_n`.
+    pub fn spectrum_max_gap(&self, max_n: u64) -> u64 {
+        self.spectrum_max_gap
+            .unwrap_or_else(|| stats::max_gap_bound(max_n))
+    }
+
+    /// The smaller `--max-exponent` values, ascending: milestones of the run whose outputs
+    /// are written on the way.
+    pub fn milestone_exponents(&self) -> Vec<Exponent> {
+        let max_n = self.max_n();
+        let mut milestones: Vec<Exponent> = self
+            .max_exponents
+            .iter()
+            .copied()
+            .filter(|e| e.max_n() < max_n)
+            .collect();
+        milestones.sort_by(|a, b| a.0.total_cmp(&b.0));
+        milestones.dedup_by_key(|e| e.max_n());
+        milestones
+    }
+
+    /// `--gaps`, led by gap 1 under `--include-gap-one`.
+    pub fn target_gaps(&self) -> Vec<u64> {
+        let mut gaps = self.gaps.clone();
+        if self.include_gap_one && !gaps.contains(&1) {
+            gaps.insert(0, 1);
+        }
+        gaps
+    }
+
+    /// The shield model selected by the correction term flags and the `--shield-*` changes
+    /// to the shield primes.
+    pub fn shield_model(&self) -> ShieldModel {
+        ShieldModel {
+            mod5_trap: self.mod5_trap,
+            divisor_interactions: self.divisor_interactions,
+            mod3_penalty: !self.no_mod3_penalty,
+            include_two: self.shield_include_two,
+            excluded: 0,
+        }
+        .excluding(&self.shield_exclude)
+    }
+
+    /// The trial division classifier of `--almost-primes`, if enabled.
+    pub fn factor_classifier(&self) -> Option<FactorClassifier> {
+        self.almost_primes.then(|| {
+            FactorClassifier::new(
+                self.factor_bound
+                    .unwrap_or_else(|| FactorClassifier::exact_bound(2 * self.max_n())),
+            )
+        })
+    }
+}
+
+/// Parses a `--max-exponent` value between 0 and [`Exponent::MAX`].
+fn parse_exponent(s: &str) -> Result<Exponent, String> {
+    let exponent: f64 = s
+        .parse()
+        .map_err(|_| format!("expected an exponent like 9 or 9.5, got '{}'", s))?;
+    if !(0.0..=Exponent::MAX).contains(&exponent) {
+        return Err(format!(
+            "the exponent must be between 0 and {}",
+            Exponent::MAX
+        ));
+    }
+    Ok(Exponent(exponent))
+}
+
+/// Parses a magnitude written as an integer, as `XeY` (e.g. `1e16`) or as `B^E` (e.g. `10^9`).
+fn parse_magnitude(s: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "expected an integer like 10000000000000000, 1e16 or 10^16, got '{}'",
+            s
+        )
+    };
+    if let Some((base, exponent)) = s.split_once('^') {
+        let base: u64 = base.parse().map_err(|_| invalid())?;
+        let exponent: u32 = exponent.parse().map_err(|_| invalid())?;
+        return base.checked_pow(exponent).ok_or_else(invalid);
+    }
+    match s.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => {
+            let mantissa: u64 = mantissa.parse().map_err(|_| invalid())?;
+            let exponent: u32 = exponent.parse().map_err(|_| invalid())?;
+            10u64
+                .checked_pow(exponent)
+                .and_then(|p| p.checked_mul(mantissa))
+                .ok_or_else(invalid)
+        }
+        None => s.parse().map_err(|_| invalid()),
+    }
+}
+
+/// Options of the `probe` subcommand.
+#[derive(Args, Debug)]
+pub struct ProbeConfig {
+    /// Lower end of the range windows are drawn from (e.g. 1e16).
+    #[arg(long, value_parser = parse_magnitude, required_unless_present = "from_index")]
+    pub from: Option<u64>,
+
+    /// Upper end of the range windows are drawn from.
+    #[arg(long, value_parser = parse_magnitude, required_unless_present = "from_index")]
+    pub to: Option<u64>,
+
+    /// Draw windows from the range of primes by index instead: from the nth prime (e.g.
+    /// 10^9) on, for --count primes.
+    #[arg(long, value_name = "N", value_parser = parse_magnitude,
+          conflicts_with_all = ["from", "to"], requires = "count")]
+    pub from_index: Option<u64>,
+
+    /// Number of primes in the --from-index range.
+    #[arg(long, value_parser = parse_magnitude, requires = "from_index")]
+    pub count: Option<u64>,
+
+    /// Number of random windows to analyze.
+    #[arg(long, default_value_t = 100)]
+    pub windows: usize,
+
+    /// Width of each window.
+    #[arg(long, default_value = "1e6", value_parser = parse_magnitude)]
+    pub width: u64,
+
+    /// Seed for the window positions: the global `--seed`, or 0.
+    #[arg(skip)]
+    pub seed: u64,
+
+    /// A comma-separated list of prime gap sizes to report per window.
+    #[arg(long, default_value = "2,4,6,12,30", value_delimiter = ',')]
+    pub gaps: Vec<u64>,
+
+    /// Directory for output files.
+    #[arg(short, long, default_value = "results")]
+    pub output_dir: String,
+}
+
+/// Options of the `diff` subcommand.
+#[derive(Args, Debug)]
+pub struct DiffConfig {
+    /// Output directory of the first run.
+    pub dir_a: String,
+
+    /// Output directory of the second run.
+    pub dir_b: String,
+
+    /// Directory for diff_report.csv and diff.html.
+    #[arg(short, long, default_value = "diff")]
+    pub output_dir: String,
+}
+
+/// Options of the `trends` subcommand.
+#[derive(Args, Debug)]
+pub struct TrendsConfig {
+    /// Run registry written by `--registry`.
+    #[arg(long, default_value = "runs.json")]
+    pub registry: String,
+
+    /// Gap sizes whose rates to plot (default: every gap tracked by the registered runs).
+    #[arg(long, value_delimiter = ',')]
+    pub gaps: Vec<u64>,
+
+    /// Directory for trends.csv and trends.html.
+    #[arg(short, long, default_value = "trends")]
+    pub output_dir: String,
+}
+
+/// Input format of the `ingest` subcommand.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IngestFormat {
+    /// CSV with a header row.
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+/// Options of the `ingest` subcommand.
+#[derive(Args, Debug)]
+pub struct IngestConfig {
+    /// File of pairs, or `-` for standard input.
+    pub input: String,
+
+    /// Input format (default: `ndjson` for `.ndjson` and `.jsonl` files, CSV otherwise).
+    #[arg(long, value_enum)]
+    pub format: Option<IngestFormat>,
+
+    /// The analysis options; those that shape the statistics and outputs apply, sieve and
+    /// recovery options are ignored.
+    #[command(flatten)]
+    pub run: Config,
+}
+
+/// Options of the `plan` subcommand.
+#[derive(Args, Debug)]
+pub struct PlanConfig {
+    /// Number of shards, one per array task.
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    pub shards: u64,
+
+    /// Print a Slurm batch script that runs the shards as a job array, instead of listing
+    /// them. Each task checkpoints and resumes where it can, so a requeued task picks up where
+    /// it was.
+    #[arg(long)]
+    pub slurm: bool,
+
+    /// Wall-clock limit of each task in the script (Slurm's `--time`).
+    #[arg(long, default_value = "24:00:00")]
+    pub time: String,
+
+    /// CPUs of each task in the script (Slurm's `--cpus-per-task`).
+    #[arg(long, default_value_t = 1)]
+    pub cpus: u32,
+
+    /// The options of the analysis to split, after `--` (e.g. `-- -E 12 --gaps 2,4,6`).
+    #[arg(last = true, required = true, value_name = "ANALYSIS")]
+    pub args: Vec<String>,
+}
+
+/// Options of the `prune` subcommand.
+#[derive(Args, Debug)]
+pub struct PruneConfig {
+    /// Run registry written by `--registry`.
+    #[arg(long, default_value = "runs.json")]
+    pub registry: String,
+
+    /// Retention window: prune runs registered more than DAYS days ago.
+    #[arg(long, value_name = "DAYS")]
+    pub older_than: u64,
+
+    /// Also prune incomplete runs, whose checkpoints `--resume` would need.
+    #[arg(long)]
+    pub include_incomplete: bool,
+
+    /// List what would be deleted without deleting it.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Options of the `check-oeis` subcommand.
+#[derive(Args, Debug)]
+pub struct CheckOeisConfig {
+    /// Output directory of the run to check.
+    #[arg(default_value = "results")]
+    pub dir: String,
+}
+
+/// Options of the `migrate` subcommand.
+#[derive(Args, Debug)]
+pub struct MigrateConfig {
+    /// Output directory to upgrade in place.
+    #[arg(default_value = "results")]
+    pub dir: String,
+
+    /// List what would change without writing anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Options of the `demo` subcommand.
+#[derive(Args, Debug)]
+pub struct DemoConfig {
+    /// Write the outputs to DIR instead of a new temporary directory.
+    #[arg(long, value_name = "DIR")]
+    pub dir: Option<String>,
+
+    /// Print where the report is instead of serving and opening it.
+    #[arg(long)]
+    pub no_open: bool,
+}
+
+/// Options of the `self-test` subcommand.
+#[derive(Args, Debug)]
+pub struct SelfTestConfig {
+    /// Keep the outputs of the built-in analysis in DIR instead of a temporary directory.
+    #[arg(long, value_name = "DIR")]
+    pub keep: Option<String>,
+}
+
+/// Options of the `pi` subcommand.
+#[derive(Args, Debug)]
+pub struct PiConfig {
+    /// Upper bound x (e.g. 1e10 or 10^10), at most 10^15.
+    #[arg(value_parser = parse_magnitude)]
+    pub x: u64,
+
+    /// Count by sieving every prime up to x instead of the Meissel–Lehmer method.
+    #[arg(long)]
+    pub sieve: bool,
+}
+
+/// Options of the `nth-prime` subcommand.
+#[derive(Args, Debug)]
+pub struct NthPrimeConfig {
+    /// Index n of the prime, counted from p_1 = 2 (e.g. 10^9).
+    #[arg(value_parser = parse_magnitude)]
+    pub n: u64,
+}
+
+/// What the `completions` subcommand prints.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionTarget {
+    Bash,
+    Zsh,
+    Fish,
+    /// A roff man page (`prime_shield_analyzer.1`).
+    Man,
+}
+
+/// Options of the `completions` subcommand.
+#[derive(Args, Debug)]
+pub struct CompletionsConfig {
+    /// Shell to complete for, or `man` for the man page.
+    #[arg(value_enum)]
+    pub shell: CompletionTarget,
 }
@@ -0,0 +1,245 @@
+//! Sums of k consecutive primes, `S_k = p_n + … + p_{n+k-1} - (k - 1)`, and the
+//! `--formula triple` analysis of `S_3 = p_n + p_{n+1} + p_{n+2} - 2`.
+//!
+//! [`PrimeWindow`] slides over the prime stream and hands out each full window of `k`
+//! primes; the statistics of a window are keyed by its `k - 1` gaps, whose shielding
+//! comes from [`shielding::window_shielding_info`]. `S_3` reaches `3N`, so the run keeps
+//! its own [`PrimalityChecker`] up to that bound.
+
+use crate::config::Config;
+use crate::shielding;
+use crate::sieve::{PrimalityChecker, PrimeIterator};
+use crate::sinks::{CsvSink, OutputSink};
+use crate::stats;
+use crate::table::Table;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+/// The last `K` primes of a stream.
+#[derive(Debug, Clone)]
+pub struct PrimeWindow<const K: usize> {
+    primes: [u64; K],
+    len: usize,
+}
+
+impl<const K: usize> Default for PrimeWindow<K> {
+    fn default() -> Self {
+        Self {
+            primes: [0; K],
+            len: 0,
+        }
+    }
+}
+
+impl<const K: usize> PrimeWindow<K> {
+    /// Appends the next prime, returning the window once it holds `K` primes.
+    pub fn push(&mut self, p: u64) -> Option<&[u64; K]> {
+        if self.len == K {
+            self.primes.rotate_left(1);
+            self.primes[K - 1] = p;
+        } else {
+            self.primes[self.len] = p;
+            self.len += 1;
+        }
+        (self.len == K).then_some(&self.primes)
+    }
+}
+
+/// `S_k` of a window of consecutive primes: their sum minus `k - 1`.
+pub fn window_sum(primes: &[u64]) -> u64 {
+    primes.iter().sum::<u64>() - (primes.len() as u64 - 1)
+}
+
+/// Windows and prime sums of one pair of gaps `(g_1, g_2)`.
+#[derive(Debug, Clone, Copy, Default)]
+struct TripleCounts {
+    count: u64,
+    successes: u64,
+    /// Sum of `2 / ln S_3` over the windows, for the expected rate.
+    density_sum: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TripleSpectrumRecord {
+    pub gap_1: u64,
+    pub gap_2: u64,
+    pub count: u64,
+    pub successes: u64,
+    pub success_rate: f64,
+    pub success_ci_low: f64,
+    pub success_ci_high: f64,
+    /// `theoretical_boost` times `2 / ln S_3` averaged over the windows.
+    pub expected_rate: f64,
+    pub shield_score: u32,
+    pub shield_primes: Vec<u32>,
+    /// The largest of `shield_primes`, empty without shields; `shield_score` counts them.
+    pub max_shield_prime: Option<u32>,
+    pub theoretical_boost: f64,
+    /// `theoretical_boost` as an exact fraction.
+    pub theoretical_boost_fraction: String,
+    pub shield_model: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TripleGlobalRecord {
+    pub max_n: u64,
+    pub total_primes: u64,
+    pub total_windows: u64,
+    pub total_s_primes: u64,
+    pub success_rate: f64,
+    pub expected_rate: f64,
+}
+
+/// Statistics of `S_3` over every window of three consecutive primes up to `max_n`.
+#[derive(Debug, Clone, Default)]
+pub struct TripleStatistics {
+    pub max_n: u64,
+    pub total_primes: u64,
+    spectrum: BTreeMap<(u64, u64), TripleCounts>,
+}
+
+impl TripleStatistics {
+    pub fn new(max_n: u64) -> Self {
+        Self {
+            max_n,
+            ..Self::default()
+        }
+    }
+
+    pub fn record_window(&mut self, primes: &[u64; 3], s: u64, s_is_prime: bool) {
+        let counts = self
+            .spectrum
+            .entry((primes[1] - primes[0], primes[2] - primes[1]))
+            .or_default();
+        counts.count += 1;
+        counts.successes += s_is_prime as u64;
+        counts.density_sum += 2.0 / (s as f64).ln();
+    }
+
+    /// One row per pair of gaps, ordered by `g_1` and then `g_2`.
+    pub fn spectrum_records(&self) -> Vec<TripleSpectrumRecord> {
+        self.spectrum
+            .iter()
+            .map(|(&(gap_1, gap_2), counts)| {
+                let info = shielding::window_shielding_info(&[0, gap_1, gap_1 + gap_2]);
+                let (success_ci_low, success_ci_high) =
+                    stats::wilson_interval(counts.successes, counts.count, stats::Z_95);
+                TripleSpectrumRecord {
+                    gap_1,
+                    gap_2,
+                    count: counts.count,
+                    successes: counts.successes,
+                    success_rate: counts.successes as f64 / counts.count as f64,
+                    success_ci_low,
+                    success_ci_high,
+                    expected_rate: info.theoretical_boost * counts.density_sum
+                        / counts.count as f64,
+                    shield_score: info.shield_score,
+                    shield_primes: info.shield_primes,
+                    max_shield_prime: info.max_shield_prime,
+                    theoretical_boost: info.theoretical_boost,
+                    theoretical_boost_fraction: info.theoretical_boost_exact.to_string(),
+                    shield_model: info.shield_model,
+                }
+            })
+            .collect()
+    }
+
+    pub fn global_record(&self) -> TripleGlobalRecord {
+        let records = self.spectrum_records();
+        let total_windows: u64 = records.iter().map(|r| r.count).sum();
+        let total_s_primes: u64 = records.iter().map(|r| r.successes).sum();
+        let expected: f64 = records
+            .iter()
+            .map(|r| r.expected_rate * r.count as f64)
+            .sum();
+        let per_window = |x: f64| {
+            if total_windows > 0 {
+                x / total_windows as f64
+            } else {
+                0.0
+            }
+        };
+        TripleGlobalRecord {
+            max_n: self.max_n,
+            total_primes: self.total_primes,
+            total_windows,
+            total_s_primes,
+            success_rate: per_window(total_s_primes as f64),
+            expected_rate: per_window(expected),
+        }
+    }
+}
+
+/// Tests `S_3` of every window of three consecutive primes up to `max_n`.
+pub fn analyze_triples(max_n: u64, segment_size_bytes: usize) -> TripleStatistics {
+    let mut stats = TripleStatistics::new(max_n);
+    let mut checker = PrimalityChecker::new(3 * max_n, segment_size_bytes);
+    let segment_span = (segment_size_bytes * 8) as u64;
+    let mut next_mark = 0;
+    let mut window = PrimeWindow::<3>::default();
+    for p in PrimeIterator::new(max_n, segment_size_bytes) {
+        stats.total_primes += 1;
+        if p >= next_mark {
+            // The sums of the windows ending in [p, p + span) lie below 3 (p + span).
+            next_mark = p + segment_span;
+            checker.ensure_range(3 * p.saturating_sub(segment_span), 3 * next_mark);
+            checker.prefetch_range(3 * next_mark, 3 * (next_mark + segment_span));
+        }
+        if let Some(primes) = window.push(p) {
+            let s = window_sum(primes);
+            let s_is_prime = checker.is_prime(s);
+            stats.record_window(primes, s, s_is_prime);
+        }
+    }
+    stats
+}
+
+/// The `--formula triple` run: writes `triple_spectrum.csv` and `triple_global_stats.csv`.
+pub fn run_triple(config: &Config) -> Result<(), Box<dyn Error>> {
+    let max_n = config.max_n();
+    let stats = analyze_triples(max_n, config.segment_size_kb * 1024);
+
+    fs::create_dir_all(&config.output_dir)?;
+    let mut csv = CsvSink::new(&config.output_dir, 0);
+    csv.write_table(&Table::new(
+        "triple_spectrum.csv",
+        &stats.spectrum_records(),
+    )?)?;
+    let global = stats.global_record();
+    csv.write_table(&Table::new(
+        "triple_global_stats.csv",
+        std::slice::from_ref(&global),
+    )?)?;
+
+    crate::info!(
+        "S_3 primes: {} of {} windows ({:.4}, expected {:.4})",
+        global.total_s_primes,
+        global.total_windows,
+        global.success_rate,
+        global.expected_rate
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::is_prime_u64;
+
+    #[test]
+    fn test_triples_match_a_direct_count() {
+        let primes: Vec<u64> = PrimeIterator::new(100_000, 1024).collect();
+        let expected = primes
+            .windows(3)
+            .filter(|w| is_prime_u64(w[0] + w[1] + w[2] - 2))
+            .count() as u64;
+
+        let global = analyze_triples(100_000, 1024).global_record();
+        assert_eq!(global.total_primes, primes.len() as u64);
+        assert_eq!(global.total_windows, primes.len() as u64 - 2);
+        assert_eq!(global.total_s_primes, expected);
+    }
+}
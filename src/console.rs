@@ -0,0 +1,59 @@
+//! Console verbosity of an analysis run (`-q`, `-v`, `-vv`).
+//!
+//! The levels follow the usual tracing levels: errors and warnings always print, the
+//! banner, progress bar and stage messages are `Info`, stage timings and cache statistics
+//! are `Debug`, and a line per sieve segment is `Trace`. Result files are never affected.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// The level selected by `-q` and the number of `-v` flags.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Level::Warn,
+            (false, 0) => Level::Info,
+            (false, 1) => Level::Debug,
+            (false, _) => Level::Trace,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are printed.
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Prints a line to stdout at [`Level::Info`]: hidden by `-q`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::console::enabled($crate::console::Level::Info) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a line to stdout at [`Level::Debug`]: shown with `-v`.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::console::enabled($crate::console::Level::Debug) {
+            println!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,222 @@
+//! The `pi` and `nth-prime` subcommands: π(x) by the Meissel–Lehmer method (or by
+//! sieving every prime up to x), and the nth prime.
+//!
+//! Meissel–Lehmer needs only the primes up to about x^(2/3), which [`PrimeIterator`]
+//! sieves up front; every π(y) of the recursion below that bound is a binary search.
+//! Analysis runs use it at startup for π(N), the denominator of their progress bar and
+//! `--dry-run` estimate, in seconds where sieving to N would take hours.
+
+use crate::config::{NthPrimeConfig, PiConfig};
+use crate::sieve::{is_prime_u64, PrimeIterator};
+use std::error::Error;
+
+/// Largest x accepted by [`prime_pi`], and largest value [`nth_prime`] may return.
+pub const MAX_X: u64 = 1_000_000_000_000_000;
+
+/// π([`MAX_X`]): the largest n accepted by [`nth_prime`].
+pub const MAX_N: u64 = 29_844_570_422_669;
+
+/// Cap of the prime table: primes up to 2^30 take about 200 MB.
+const MAX_TABLE_LIMIT: u64 = 1 << 30;
+
+const SEGMENT_SIZE_BYTES: usize = 128 * 1024;
+
+/// 2·3·5·7·11·13 and the count of residues coprime to it, below each residue.
+const PRIMORIAL: u64 = 30_030;
+const PRIMORIAL_PRIMES: usize = 6;
+
+/// Primes up to a limit and the φ table for the first [`PRIMORIAL_PRIMES`] primes; answers
+/// π(y) for every `y` up to the limit it was built for.
+#[derive(Debug, Clone)]
+pub struct PrimeCounter {
+    primes: Vec<u32>,
+    table_limit: u64,
+    /// `coprime_below[r]`: integers in `[1, r]` coprime to [`PRIMORIAL`].
+    coprime_below: Vec<u32>,
+}
+
+impl PrimeCounter {
+    /// Sieves the tables for queries up to `x`.
+    pub fn new(x: u64) -> Self {
+        let two_thirds = (x as f64).powf(2.0 / 3.0) as u64;
+        let table_limit = two_thirds
+            .min(MAX_TABLE_LIMIT)
+            .max(x.isqrt())
+            .max(PRIMORIAL);
+        let primes = PrimeIterator::new(table_limit, SEGMENT_SIZE_BYTES)
+            .map(|p| p as u32)
+            .collect();
+        let mut coprime_below = Vec::with_capacity(PRIMORIAL as usize);
+        let mut count = 0;
+        for r in 0..PRIMORIAL {
+            if r > 0 && [2, 3, 5, 7, 11, 13].iter().all(|&q| r % q != 0) {
+                count += 1;
+            }
+            coprime_below.push(count);
+        }
+        Self {
+            primes,
+            table_limit,
+            coprime_below,
+        }
+    }
+
+    /// π(y) for `y` up to the table limit.
+    fn pi_table(&self, y: u64) -> u64 {
+        self.primes.partition_point(|&p| p as u64 <= y) as u64
+    }
+
+    /// The ath prime (1-based).
+    fn prime(&self, a: u64) -> u64 {
+        self.primes[a as usize - 1] as u64
+    }
+
+    /// φ(y, a): integers in `[1, y]` divisible by none of the first `a` primes.
+    fn phi(&self, y: u64, a: u64) -> u64 {
+        if a == 0 || y == 0 {
+            return y;
+        }
+        if a as usize == PRIMORIAL_PRIMES {
+            let totient = self.coprime_below[PRIMORIAL as usize - 1] as u64;
+            return y / PRIMORIAL * totient + self.coprime_below[(y % PRIMORIAL) as usize] as u64;
+        }
+        if y <= self.prime(a) {
+            return 1;
+        }
+        // Composites left after removing the first a primes are at least p_{a+1}², so
+        // below that only 1 and the primes past p_a remain.
+        let next = self.prime(a + 1);
+        if y < next * next && y <= self.table_limit {
+            return self.pi_table(y) - a + 1;
+        }
+        self.phi(y, a - 1) - self.phi(y / self.prime(a), a - 1)
+    }
+
+    /// π(y) by Lehmer's formula, for `y` up to the limit the counter was built for.
+    pub fn pi(&self, y: u64) -> u64 {
+        if y <= self.table_limit {
+            return self.pi_table(y);
+        }
+        let a = self.pi(iroot(y, 4));
+        let b = self.pi(y.isqrt());
+        let c = self.pi(iroot(y, 3));
+        let mut sum = self.phi(y, a) + (b + a - 2) * (b - a + 1) / 2;
+        for i in a + 1..=b {
+            let w = y / self.prime(i);
+            sum -= self.pi(w);
+            if i <= c {
+                let b_i = self.pi(w.isqrt());
+                for j in i..=b_i {
+                    sum -= self.pi(w / self.prime(j)) - (j - 1);
+                }
+            }
+        }
+        sum
+    }
+}
+
+/// ⌊y^(1/k)⌋.
+fn iroot(y: u64, k: u32) -> u64 {
+    let mut r = (y as f64).powf(1.0 / k as f64) as u64;
+    while r.checked_pow(k).is_none_or(|v| v > y) {
+        r -= 1;
+    }
+    while (r + 1).checked_pow(k).is_some_and(|v| v <= y) {
+        r += 1;
+    }
+    r
+}
+
+/// π(x), the number of primes up to `x`, by the Meissel–Lehmer method.
+pub fn prime_pi(x: u64) -> u64 {
+    PrimeCounter::new(x).pi(x)
+}
+
+/// π(x) by sieving and counting every prime up to `x`.
+pub fn prime_pi_sieved(x: u64) -> u64 {
+    if x < 2 {
+        return 0;
+    }
+    PrimeIterator::new(x, SEGMENT_SIZE_BYTES).count() as u64
+}
+
+/// The index n of the prime `p` (so that `p` is the nth prime), or `None` if `p` is not
+/// prime; the inverse of [`nth_prime`].
+pub fn prime_index(p: u64) -> Option<u64> {
+    is_prime_u64(p).then(|| prime_pi(p))
+}
+
+/// The nth prime (1-based): π of Dusart's lower bound, then sieving up to his upper bound.
+pub fn nth_prime(n: u64) -> u64 {
+    assert!(n >= 1, "primes are counted from 1");
+    let (lo, hi) = if n < 6 {
+        (0, 13)
+    } else {
+        let n_f = n as f64;
+        let log_terms = n_f.ln() + n_f.ln().ln();
+        // p_n > n (ln n + ln ln n - 1) for n >= 2, and p_n < n (ln n + ln ln n - 0.9484)
+        // for n >= 39017 (n (ln n + ln ln n) from 6 on). A unit of slack covers rounding.
+        let upper = if n >= 39_017 {
+            log_terms - 0.9484
+        } else {
+            log_terms
+        };
+        (
+            (n_f * (log_terms - 1.0)) as u64 - 1,
+            (n_f * upper) as u64 + 1,
+        )
+    };
+    let mut count = prime_pi(lo);
+    for p in PrimeIterator::new(hi, SEGMENT_SIZE_BYTES).starting_at(lo + 1) {
+        count += 1;
+        if count == n {
+            return p;
+        }
+    }
+    unreachable!("p_{} lies below {}", n, hi)
+}
+
+pub fn run_pi(config: &PiConfig) -> Result<(), Box<dyn Error>> {
+    if config.x > MAX_X {
+        return Err(format!("x must be at most {}", MAX_X).into());
+    }
+    let count = if config.sieve {
+        prime_pi_sieved(config.x)
+    } else {
+        prime_pi(config.x)
+    };
+    println!("{}", count);
+    Ok(())
+}
+
+pub fn run_nth_prime(config: &NthPrimeConfig) -> Result<(), Box<dyn Error>> {
+    if config.n == 0 || config.n > MAX_N {
+        return Err(format!("n must be between 1 and π({}) = {}", MAX_X, MAX_N).into());
+    }
+    println!("{}", nth_prime(config.n));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prime_pi_matches_the_sieve() {
+        for x in [0, 1, 2, 3, 30_029, 30_030, 1_000_003, 2_345_678, 9_999_991] {
+            assert_eq!(prime_pi(x), prime_pi_sieved(x), "x = {}", x);
+        }
+        assert_eq!(prime_pi(1_000_000_000), 50_847_534);
+    }
+
+    #[test]
+    fn test_nth_prime() {
+        assert_eq!(nth_prime(1), 2);
+        assert_eq!(nth_prime(5), 11);
+        assert_eq!(nth_prime(6), 13);
+        assert_eq!(nth_prime(39_017), 467_473);
+        assert_eq!(nth_prime(1_000_000), 15_485_863);
+        assert_eq!(prime_index(15_485_863), Some(1_000_000));
+        assert_eq!(prime_index(15_485_865), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! The `demo` subcommand: a canned analysis up to 10^6 with every optional output turned on,
+//! followed by a tour of the files it wrote and the HTML report.
+//!
+//! The report fetches its JSON files, which browsers refuse to do for `file://` pages, so
+//! the demo serves the output directory on localhost and opens the report from there.
+
+use crate::self_test;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::Command;
+
+/// Command line of the demo analysis: the self-test analysis plus the optional outputs.
+pub fn analysis_args(output_dir: &str) -> Vec<String> {
+    let mut args = self_test::analysis_args(output_dir);
+    args.extend(
+        [
+            "--quiet",
+            "--almost-primes",
+            "--large-gap-threshold",
+            "72",
+            "--gap-families",
+            "6,30",
+            "--plot-spec",
+            "vega",
+        ]
+        .iter()
+        .map(|s| s.to_string()),
+    );
+    if cfg!(feature = "report") {
+        args.push("--web-report".to_string());
+    }
+    args
+}
+
+/// What each output of the demo holds, in the order of the tour.
+const TOUR: &[(&str, &str)] = &[
+    ("index.html", "the interactive report of everything below"),
+    (
+        "global_stats.csv",
+        "primes p, prime sums S and the global S/P ratio",
+    ),
+    (
+        "gap_spectrum.csv",
+        "success rate of every gap against the shielding model",
+    ),
+    (
+        "oscillation_series.csv",
+        "S/P ratio, gap rates and gap quantiles per bin",
+    ),
+    (
+        "gap_bins.csv",
+        "the target gaps' counts per bin, one row each",
+    ),
+    (
+        "conditional_rates.csv",
+        "target gap rates by residue of p modulo each shield prime",
+    ),
+    (
+        "neighbor_gaps.csv",
+        "target gap rates by the gap before and the gap after",
+    ),
+    (
+        "first_occurrences.csv",
+        "the first prime p with each gap and whether it is a maximal gap",
+    ),
+    ("prime_counts.csv", "π(10^k) for every power of ten reached"),
+    (
+        "streaks.csv",
+        "runs of prime and composite sums by length, against independence",
+    ),
+    (
+        "success_spacing.csv",
+        "spacing between consecutive prime sums, against independence",
+    ),
+    (
+        "tail_fit.csv",
+        "exponential fit to the tail of the gap counts",
+    ),
+    ("gap_cdf.csv", "cumulative distribution of the gaps"),
+    (
+        "rate_correlations.csv",
+        "correlations between the per-bin rate series",
+    ),
+    (
+        "almost_prime_spectrum.csv",
+        "Ω(S) classes of each gap's sums (--almost-primes)",
+    ),
+    (
+        "large_gap_events.csv",
+        "every gap of 72 or more in context (--large-gap-threshold)",
+    ),
+    (
+        "gap_families.csv",
+        "gaps pooled by residue mod 6 and mod 30 (--gap-families)",
+    ),
+    ("throughput.csv", "sieving speed segment by segment"),
+    (
+        "diagnostics.csv",
+        "what the outputs leave out, such as primes above the last bin",
+    ),
+    ("perf.json", "throughput, cache hits and peak memory"),
+    ("manifest.json", "what was analyzed and how far the run got"),
+    ("schema.json", "version and columns of the CSV layouts"),
+];
+
+fn describe(name: &str) -> &'static str {
+    match TOUR.iter().find(|(file, _)| *file == name) {
+        Some((_, description)) => description,
+        None if name.ends_with(".vl.json") => "Vega-Lite spec of a report chart (--plot-spec)",
+        None if name.ends_with(".json") => "data the report loads",
+        None => "",
+    }
+}
+
+/// Lists the outputs in `dir`, the ones on the tour first, with what they hold.
+pub fn print_tour(dir: &str) -> Result<(), Box<dyn Error>> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<_, _>>()?;
+    names.sort_by_key(|name| {
+        (
+            TOUR.iter()
+                .position(|(file, _)| file == name)
+                .unwrap_or(TOUR.len()),
+            name.clone(),
+        )
+    });
+    println!("Outputs in {}:", dir);
+    for name in names {
+        println!("  {:<28} {}", name, describe(&name));
+    }
+    Ok(())
+}
+
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit('.').next() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("csv") => "text/csv; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Answers one request with a file directly inside `dir`, or 404.
+fn respond(dir: &Path, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Drain the headers so closing the connection does not reset it.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let name = target
+        .trim_start_matches('/')
+        .split('?')
+        .next()
+        .unwrap_or("");
+    let name = if name.is_empty() { "index.html" } else { name };
+    // Only plain file names: nothing outside `dir` is reachable.
+    let body = if name.contains(['/', '\\']) || name.starts_with('.') {
+        None
+    } else {
+        fs::read(dir.join(name)).ok()
+    };
+    match body {
+        Some(body) => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                content_type(name),
+                body.len()
+            )?;
+            stream.write_all(&body)
+        }
+        None => stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+    }
+}
+
+fn open_in_browser(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(url).spawn().map(|_| ())
+}
+
+/// Serves `dir` on localhost and opens its report, until the process is interrupted.
+pub fn serve_report(dir: &str) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    let url = format!("http://{}/index.html", listener.local_addr()?);
+    println!("Serving the report at {} (Ctrl-C to stop).", url);
+    if open_in_browser(&url).is_err() {
+        println!("No browser could be started; open the address above in one.");
+    }
+    for stream in listener.incoming().flatten() {
+        let _ = respond(Path::new(dir), stream);
+    }
+    Ok(())
+}
@@ -0,0 +1,306 @@
+//! The `diff` subcommand: compares two result directories with two-proportion tests.
+//!
+//! Gaps are aligned by size (a gap missing from one run counts as zero trials there) and
+//! bins by their `[bin_start, bin_end)` range; bins whose ranges differ between the runs
+//! are left out. Rates compared are the gap success rates and each bin's S/P ratio.
+
+use crate::config::DiffConfig;
+use crate::output::read_rows;
+use crate::stats;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Two-sided p-values below this are highlighted in the HTML page.
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+#[derive(Deserialize)]
+struct GapRow {
+    gap_size: u64,
+    count: u64,
+    successes: u64,
+}
+
+#[derive(Deserialize)]
+struct GlobalRow {
+    // Runs from before --sample existed were always complete.
+    #[serde(default = "full_run")]
+    sample_every: u64,
+}
+
+fn full_run() -> u64 {
+    1
+}
+
+/// (trials, successes)
+type Counts = (u64, u64);
+
+#[derive(Deserialize)]
+struct BinRow {
+    bin_start: u64,
+    bin_end: u64,
+    prime_count_p: u64,
+    prime_count_s: u64,
+}
+
+/// One compared rate, one row of `diff_report.csv`.
+#[derive(Serialize, Debug)]
+pub struct DiffRecord {
+    /// `global`, `gap` or `bin`.
+    pub kind: &'static str,
+    /// Gap size, or `bin_start-bin_end`.
+    pub key: String,
+    pub trials_a: u64,
+    pub successes_a: u64,
+    pub rate_a: f64,
+    pub trials_b: u64,
+    pub successes_b: u64,
+    pub rate_b: f64,
+    pub rate_diff: f64,
+    pub z_score: f64,
+    pub p_value: f64,
+}
+
+impl DiffRecord {
+    fn new(kind: &'static str, key: String, (n_a, s_a): Counts, (n_b, s_b): Counts) -> Self {
+        let rate = |s: u64, n: u64| if n > 0 { s as f64 / n as f64 } else { 0.0 };
+        Self {
+            kind,
+            key,
+            trials_a: n_a,
+            successes_a: s_a,
+            rate_a: rate(s_a, n_a),
+            trials_b: n_b,
+            successes_b: s_b,
+            rate_b: rate(s_b, n_b),
+            rate_diff: rate(s_a, n_a) - rate(s_b, n_b),
+            z_score: stats::two_proportion_z_score(s_a, n_a, s_b, n_b),
+            p_value: stats::two_proportion_p_value(s_a, n_a, s_b, n_b),
+        }
+    }
+}
+
+/// Aligns and tests two result directories.
+pub fn compare(dir_a: &str, dir_b: &str) -> Result<Vec<DiffRecord>, Box<dyn Error>> {
+    let gaps_a: Vec<GapRow> = read_rows(dir_a, "gap_spectrum.csv")?;
+    let gaps_b: Vec<GapRow> = read_rows(dir_b, "gap_spectrum.csv")?;
+    let bins_a: Vec<BinRow> = read_rows(dir_a, "oscillation_series.csv")?;
+    let bins_b: Vec<BinRow> = read_rows(dir_b, "oscillation_series.csv")?;
+
+    // Sampled runs store counts scaled up by K; tests need the pairs actually analyzed.
+    let sample_every = |dir: &str| -> Result<u64, Box<dyn Error>> {
+        let rows: Vec<GlobalRow> = read_rows(dir, "global_stats.csv")?;
+        Ok(rows.first().map_or(1, |r| r.sample_every.max(1)))
+    };
+    let (k_a, k_b) = (sample_every(dir_a)?, sample_every(dir_b)?);
+    let unscale = |(n, s): Counts, k: u64| (n / k, s / k);
+
+    let totals = |rows: &[GapRow]| {
+        rows.iter()
+            .fold((0, 0), |(n, s), r| (n + r.count, s + r.successes))
+    };
+    let mut records = vec![DiffRecord::new(
+        "global",
+        "all pairs".to_string(),
+        unscale(totals(&gaps_a), k_a),
+        unscale(totals(&gaps_b), k_b),
+    )];
+
+    // (a, b) counts per gap size, over the union of both spectra.
+    let mut gaps: BTreeMap<u64, (Counts, Counts)> = BTreeMap::new();
+    for r in &gaps_a {
+        gaps.entry(r.gap_size).or_default().0 = (r.count, r.successes);
+    }
+    for r in &gaps_b {
+        gaps.entry(r.gap_size).or_default().1 = (r.count, r.successes);
+    }
+    records.extend(gaps.into_iter().map(|(gap, (a, b))| {
+        DiffRecord::new("gap", gap.to_string(), unscale(a, k_a), unscale(b, k_b))
+    }));
+
+    let bins_b: BTreeMap<(u64, u64), &BinRow> = bins_b
+        .iter()
+        .map(|r| ((r.bin_start, r.bin_end), r))
+        .collect();
+    records.extend(bins_a.iter().filter_map(|a| {
+        let b = bins_b.get(&(a.bin_start, a.bin_end))?;
+        Some(DiffRecord::new(
+            "bin",
+            format!("{}-{}", a.bin_start, a.bin_end),
+            unscale((a.prime_count_p, a.prime_count_s), k_a),
+            unscale((b.prime_count_p, b.prime_count_s), k_b),
+        ))
+    }));
+
+    Ok(records)
+}
+
+pub fn run(config: &DiffConfig) -> Result<(), Box<dyn Error>> {
+    let records = compare(&config.dir_a, &config.dir_b)?;
+    fs::create_dir_all(&config.output_dir)?;
+
+    let mut wtr = csv::Writer::from_path(Path::new(&config.output_dir).join("diff_report.csv"))?;
+    for record in &records {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()?;
+
+    let significant = records
+        .iter()
+        .filter(|r| r.p_value < SIGNIFICANCE_LEVEL)
+        .count();
+    println!(
+        "Compared {} rates; {} differ at p < {}.",
+        records.len(),
+        significant,
+        SIGNIFICANCE_LEVEL
+    );
+
+    #[cfg(feature = "report")]
+    write_html(config, &records)?;
+    println!(
+        "Diff written to {}/diff_report.csv{}",
+        config.output_dir,
+        if cfg!(feature = "report") {
+            " and diff.html"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+#[cfg(feature = "report")]
+fn write_html(config: &DiffConfig, records: &[DiffRecord]) -> Result<(), Box<dyn Error>> {
+    let data = serde_json::json!({
+        "dir_a": config.dir_a,
+        "dir_b": config.dir_b,
+        "alpha": SIGNIFICANCE_LEVEL,
+        "records": records,
+    });
+    // A literal "</script>" inside a directory name would end the JSON block early.
+    let data_json = serde_json::to_string(&data)?.replace("</", "<\\/");
+    let html_content = r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Prime Sum Run Comparison</title>
+    <script src="https://cdn.jsdelivr.net/npm/chart.js"></script>
+    <style>
+        :root {
+            --page-bg: #f8f9fa; --card-bg: #fff; --text: #212529; --heading: #343a40; --muted: #6c757d;
+            --accent: #007bff; --border: #dee2e6; --table-head: #e9ecef; --shadow: rgba(0,0,0,0.1);
+            --significant: rgba(220, 53, 69, 0.15);
+        }
+        body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, Helvetica, Arial, sans-serif; margin: 0; background-color: var(--page-bg); color: var(--text); }
+        .container { max-width: 1200px; margin: 2rem auto; padding: 2rem; background-color: var(--card-bg); border-radius: 8px; box-shadow: 0 4px 6px var(--shadow); }
+        h1, h2 { text-align: center; color: var(--heading); }
+        .summary { text-align: center; margin-bottom: 2rem; color: var(--muted); font-size: 1.1em; }
+        .chart-container { margin-top: 2rem; }
+        .table-controls { text-align: center; margin: 1rem 0; }
+        .table-scroll { max-height: 480px; overflow-y: auto; }
+        table.diff-table { width: 100%; border-collapse: collapse; font-size: 0.9em; }
+        .diff-table th, .diff-table td { padding: 0.3rem 0.6rem; border-bottom: 1px solid var(--border); text-align: right; }
+        .diff-table th { position: sticky; top: 0; background-color: var(--table-head); }
+        .diff-table tr.significant { background-color: var(--significant); }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>Run Comparison</h1>
+        <div class="summary" id="summary"></div>
+
+        <div class="chart-container">
+            <h2>Gap Success Rate Difference (A − B)</h2>
+            <canvas id="gapDiffChart"></canvas>
+        </div>
+
+        <div class="chart-container">
+            <h2>All Compared Rates</h2>
+            <div class="table-controls">
+                <label><input type="checkbox" id="onlySignificant"> Only significant differences</label>
+            </div>
+            <div class="table-scroll">
+                <table class="diff-table" id="diffTable">
+                    <thead>
+                        <tr>
+                            <th>Kind</th><th>Key</th><th>Rate A</th><th>Trials A</th><th>Rate B</th><th>Trials B</th><th>Difference</th><th>z</th><th>p-value</th>
+                        </tr>
+                    </thead>
+                    <tbody></tbody>
+                </table>
+            </div>
+        </div>
+    </div>
+
+    <script id="diffData" type="application/json">__DIFF_DATA__</script>
+
+    <script>
+        const data = JSON.parse(document.getElementById('diffData').textContent);
+        const isSignificant = r => r.p_value < data.alpha;
+
+        const significant = data.records.filter(isSignificant).length;
+        document.getElementById('summary').innerText =
+            `A: ${data.dir_a} | B: ${data.dir_b}\n` +
+            `${significant} of ${data.records.length} rates differ at p < ${data.alpha} (two-proportion z-test, uncorrected for multiple comparisons).`;
+
+        function renderTable() {
+            const only = document.getElementById('onlySignificant').checked;
+            const tbody = document.querySelector('#diffTable tbody');
+            tbody.innerHTML = '';
+            for (const r of data.records) {
+                if (only && !isSignificant(r)) continue;
+                const tr = document.createElement('tr');
+                if (isSignificant(r)) tr.className = 'significant';
+                const cells = [
+                    r.kind, r.key, r.rate_a.toFixed(5), r.trials_a.toLocaleString(),
+                    r.rate_b.toFixed(5), r.trials_b.toLocaleString(), r.rate_diff.toFixed(5),
+                    r.z_score.toFixed(2), r.p_value.toExponential(2),
+                ];
+                for (const value of cells) {
+                    const td = document.createElement('td');
+                    td.textContent = value;
+                    tr.appendChild(td);
+                }
+                tbody.appendChild(tr);
+            }
+        }
+        document.getElementById('onlySignificant').addEventListener('change', renderTable);
+        renderTable();
+
+        const gapRows = data.records.filter(r => r.kind === 'gap' && r.trials_a > 0 && r.trials_b > 0);
+        new Chart(document.getElementById('gapDiffChart'), {
+            type: 'bar',
+            data: {
+                labels: gapRows.map(r => r.key),
+                datasets: [{
+                    label: 'Rate A − Rate B',
+                    data: gapRows.map(r => r.rate_diff),
+                    backgroundColor: gapRows.map(r => isSignificant(r) ? 'rgba(220, 53, 69, 0.7)' : 'rgba(108, 117, 125, 0.5)'),
+                }]
+            },
+            options: {
+                plugins: {
+                    tooltip: { callbacks: { afterLabel: ctx => `p = ${gapRows[ctx.dataIndex].p_value.toExponential(2)}` } }
+                },
+                scales: {
+                    x: { title: { display: true, text: 'Gap size' } },
+                    y: { title: { display: true, text: 'Rate difference' } }
+                }
+            }
+        });
+    </script>
+</body>
+</html>
+"#
+    .replace("__DIFF_DATA__", &data_json);
+    fs::write(
+        Path::new(&config.output_dir).join("diff.html"),
+        html_content,
+    )?;
+    Ok(())
+}
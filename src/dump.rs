@@ -0,0 +1,68 @@
+//! `--dump-primes` / `--dump-s-primes`: gzip-compressed streams of the primes the sieve
+//! produced and of the sums S that tested prime, for reuse and external spot checks.
+//!
+//! Text dumps hold one decimal value per line; binary dumps hold consecutive little-endian
+//! `u64` values. Either way the values are in ascending order.
+
+use crate::config::DumpFormat;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+fn extension(format: DumpFormat) -> &'static str {
+    match format {
+        DumpFormat::Text => "txt.gz",
+        DumpFormat::Binary => "bin.gz",
+    }
+}
+
+/// Stem of the `--dump-primes` stream.
+pub const PRIME_DUMP: &str = "primes";
+
+/// Stem of the `--dump-s-primes` stream.
+pub const S_PRIME_DUMP: &str = "s_primes";
+
+/// `<stem>.txt.gz` or `<stem>.bin.gz`.
+pub fn file_name(stem: &str, format: DumpFormat) -> String {
+    format!("{}.{}", stem, extension(format))
+}
+
+/// A gzip-compressed stream of ascending values.
+pub struct ValueDump {
+    wtr: BufWriter<GzEncoder<File>>,
+    format: DumpFormat,
+}
+
+impl ValueDump {
+    /// Creates `<stem>.txt.gz` or `<stem>.bin.gz` in `output_dir`.
+    pub fn create(
+        output_dir: &str,
+        stem: &str,
+        format: DumpFormat,
+    ) -> Result<Self, Box<dyn Error>> {
+        let path = Path::new(output_dir).join(file_name(stem, format));
+        let file = File::create(path)?;
+        Ok(Self {
+            wtr: BufWriter::new(GzEncoder::new(file, Compression::fast())),
+            format,
+        })
+    }
+
+    #[inline]
+    pub fn write(&mut self, value: u64) -> Result<(), Box<dyn Error>> {
+        match self.format {
+            DumpFormat::Text => writeln!(self.wtr, "{}", value)?,
+            DumpFormat::Binary => self.wtr.write_all(&value.to_le_bytes())?,
+        }
+        Ok(())
+    }
+
+    /// Flushes the buffer and writes the gzip trailer.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.wtr.into_inner()?.finish()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+//! `--dry-run`: the exact work of an analysis run and a rough runtime and memory estimate,
+//! without sieving to N.
+//!
+//! π(N) comes from Meissel–Lehmer ([`crate::counting`]). The runtime is extrapolated from
+//! sieving one segment of p and one of the sums S in the middle of their ranges, plus the
+//! per-prime cost of a small complete run; it ignores the overlap of prefetching with the
+//! analysis, so it is a guide to the order of magnitude only.
+//!
+//! The disk estimate ([`disk_usage`]) sizes each output from its rows and the bytes per row
+//! measured on runs up to 10^9, rounded up, and is checked against the free space of the
+//! output directory before a run starts (`--disk-check`).
+
+use crate::bench;
+use crate::config::{Config, DumpFormat, SinkKind};
+use crate::counting::PrimeCounter;
+use crate::memory::{format_bytes, MemoryUsage};
+use crate::sieve::{base_primes, PrimeIterator};
+use crate::stats::Statistics;
+use std::fmt;
+use std::path::Path;
+use std::time::Instant;
+
+/// Sum segments held at once: the current span's sums and the prefetched next span's, each
+/// covering up to three segments.
+const STORED_SUM_SEGMENTS: u64 = 6;
+
+/// Limit of the small complete run timing the per-prime work.
+const CALIBRATION_N: u64 = 1_000_000;
+
+/// Gzip-compressed bytes per value of the prime dumps: about 2.8 (primes) to 3.4 (sums S)
+/// for text and 1.6 to 2.4 for binary from 10^7 to 10^9.
+const TEXT_DUMP_BYTES_PER_VALUE: f64 = 3.5;
+const BINARY_DUMP_BYTES_PER_VALUE: f64 = 2.5;
+
+/// Bytes per bin of `oscillation_series.csv`, plus those per target gap, and of
+/// `gap_bins.csv`, one row per target gap.
+const SERIES_BYTES_PER_BIN: u64 = 100;
+const SERIES_BYTES_PER_BIN_AND_GAP: u64 = 40;
+
+/// Bytes per row of the gap spectrum in the tables indexed by gap (`gap_spectrum.csv`,
+/// `gap_cdf.csv`, `streaks.csv`, `neighbor_gaps.csv`, …), plus those per target gap.
+const TABLE_BYTES_PER_GAP: u64 = 400;
+const TABLE_BYTES_PER_GAP_AND_TARGET: u64 = 120;
+
+/// The HTML report: the page and its fixed data, plus its per-bin series.
+const REPORT_BYTES: u64 = 300_000;
+const REPORT_BYTES_PER_BIN: u64 = 270;
+const REPORT_BYTES_PER_BIN_AND_GAP: u64 = 25;
+
+/// A WAL record holds the spectrum and the bins a segment touched.
+const WAL_BYTES_PER_SEGMENT: u64 = 4096;
+
+#[derive(Debug, Clone)]
+pub struct RunEstimate {
+    pub max_n: u64,
+    /// π(N), every prime p of the run.
+    pub prime_count: u64,
+    pub counting_secs: f64,
+    /// Pairs (p_n, p_{n+1}) whose sum S is tested, after sampling.
+    pub sum_checks: u64,
+    pub p_segments: u64,
+    pub s_segments: u64,
+    pub segment_size_bytes: usize,
+    pub memory: MemoryUsage,
+    pub estimated_secs: f64,
+}
+
+/// Counts and times the work of a run up to `max_n`; `stats` is the run's fresh statistics.
+pub fn estimate(
+    max_n: u64,
+    stats: &Statistics,
+    segment_size_bytes: usize,
+    sample_every: u64,
+) -> RunEstimate {
+    let counting_started = Instant::now();
+    let prime_count = PrimeCounter::new(max_n).pi(max_n);
+    let counting_secs = counting_started.elapsed().as_secs_f64();
+
+    let segment_bits = (segment_size_bytes * 8) as u64;
+    let p_segments = max_n.div_ceil(segment_bits);
+    let s_segments = (2 * max_n).div_ceil(segment_bits);
+
+    let time_segment = |limit: u64, start: u64| {
+        let base = base_primes(limit);
+        let started = Instant::now();
+        PrimeIterator::sieve_segment(start, start + segment_bits, &base, None);
+        (started.elapsed().as_secs_f64(), base.len() as u64 * 4)
+    };
+    let (p_segment_secs, p_base_bytes) = time_segment(max_n, max_n / 2);
+    let (s_segment_secs, s_base_bytes) = time_segment(2 * max_n, max_n);
+
+    let calibration_started = Instant::now();
+    let calibration = bench::analysis_loop(CALIBRATION_N, stats.num_bins(), segment_size_bytes);
+    let per_prime_secs =
+        calibration_started.elapsed().as_secs_f64() / calibration.total_primes as f64;
+
+    // The bins' sketches fill up as the run goes, to about 6x the fresh bins at 10^6; the
+    // range of gaps and merits their buckets span grows about as ln N, so the calibration
+    // run's filled sketches (with the same bins) are scaled from its limit.
+    let filled_sketch_bytes = calibration.sketches_memory_bytes() as f64
+        * (max_n.max(2) as f64).ln()
+        / (CALIBRATION_N as f64).ln();
+
+    let sum_checks = prime_count.saturating_sub(1) / sample_every;
+    RunEstimate {
+        max_n,
+        prime_count,
+        counting_secs,
+        sum_checks,
+        p_segments,
+        s_segments,
+        segment_size_bytes,
+        memory: MemoryUsage {
+            peak_rss_bytes: None,
+            sieve_segment_bytes: p_base_bytes + segment_size_bytes as u64,
+            sum_segment_bytes: s_base_bytes
+                + (2 * max_n).isqrt() / 8
+                + STORED_SUM_SEGMENTS * segment_size_bytes as u64,
+            bins_bytes: stats.bins_memory_bytes() + filled_sketch_bytes as u64,
+            spectrum_bytes: stats.gap_spectrum.memory_bytes(),
+        },
+        estimated_secs: p_segments as f64 * p_segment_secs
+            + s_segments as f64 * s_segment_secs
+            + prime_count as f64 * per_prime_secs,
+    }
+}
+
+/// Bytes the outputs of a run will take on disk, by kind.
+#[derive(Debug, Clone, Default)]
+pub struct DiskUsage {
+    /// The result tables, in every `--sink` that writes files, and the per-bin series.
+    pub results: u64,
+    pub report: u64,
+    pub dumps: u64,
+    /// `--snapshot-every(-primes)`, `--report-snapshots` and the milestone directories.
+    pub snapshots: u64,
+    /// `statistics.state`, `checkpoint.state` and `analysis.wal`.
+    pub state: u64,
+    /// Free space of the filesystem of the output directory, where it can be read.
+    pub available: Option<u64>,
+}
+
+impl DiskUsage {
+    pub fn total(&self) -> u64 {
+        self.results + self.report + self.dumps + self.snapshots + self.state
+    }
+
+    /// Whether the outputs may not fit in the free space.
+    pub fn exceeds_available(&self) -> bool {
+        self.available
+            .is_some_and(|available| self.total() > available)
+    }
+}
+
+/// Free space of the filesystem holding `dir`, or of its closest existing ancestor if the
+/// directory is yet to be created.
+fn available_space(dir: &str) -> Option<u64> {
+    let dir = Path::new(dir);
+    let existing = dir
+        .ancestors()
+        .find(|path| path.as_os_str().is_empty() || path.exists())?;
+    let existing = if existing.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        existing
+    };
+    fs4::available_space(existing).ok()
+}
+
+/// Estimates the disk usage of the outputs `config` asks for; `stats` is the run's fresh
+/// statistics. The prime count is approximated by N / (ln N - 1), so no sieving is needed.
+pub fn disk_usage(config: &Config, stats: &Statistics) -> DiskUsage {
+    let max_n = stats.max_n();
+    let ln_n = (max_n.max(10) as f64).ln();
+    let primes = max_n as f64 / (ln_n - 1.0);
+    let bins = stats.num_bins() as u64;
+    let gaps = stats.target_gaps.len() as u64;
+
+    let series = bins * (SERIES_BYTES_PER_BIN + gaps * SERIES_BYTES_PER_BIN_AND_GAP);
+    let table_rows = config.spectrum_max_gap(max_n) / 2 + 1;
+    let csv_tables = table_rows * (TABLE_BYTES_PER_GAP + gaps * TABLE_BYTES_PER_GAP_AND_TARGET);
+    // NDJSON repeats the column names in every row; SQLite stores about what the CSV does.
+    let mut tables = csv_tables;
+    if config.sinks.contains(&SinkKind::Json) {
+        tables += 2 * csv_tables;
+    }
+    if config.sinks.contains(&SinkKind::Sqlite) {
+        tables += csv_tables;
+    }
+    let mut results = tables + series;
+    if config.arrow_bins {
+        results += series;
+    }
+
+    let report = if config.web_report || config.report_pdf {
+        REPORT_BYTES + bins * (REPORT_BYTES_PER_BIN + gaps * REPORT_BYTES_PER_BIN_AND_GAP)
+    } else {
+        0
+    };
+
+    let bytes_per_value = match config.dump_format {
+        DumpFormat::Text => TEXT_DUMP_BYTES_PER_VALUE,
+        DumpFormat::Binary => BINARY_DUMP_BYTES_PER_VALUE,
+    };
+    let mut dumped_values = 0.0;
+    if config.dump_primes {
+        dumped_values += primes;
+    }
+    if config.dump_s_primes {
+        // About 2.5 / ln N of the sums S are prime up to 10^9.
+        dumped_values += primes * 2.5 / ln_n / config.sample as f64;
+    }
+    let dumps = (dumped_values * bytes_per_value) as u64;
+
+    let snapshot_count = match (config.snapshot_every, config.snapshot_every_primes) {
+        (Some(step), _) => max_n / step.max(1),
+        (_, Some(primes_per_snapshot)) => primes as u64 / primes_per_snapshot.max(1),
+        _ => 0,
+    };
+    let milestones = config.milestone_exponents().len() as u64;
+    let mut snapshots = (snapshot_count + milestones) * (tables + series) + milestones * report;
+    if config.report_snapshots {
+        snapshots += max_n.max(1).ilog10() as u64 * report;
+    }
+
+    // A state file is about twice the fresh statistics, as the bins' gap sketches fill up;
+    // a checkpoint is written next to the one it replaces.
+    let state_file = 2 * (stats.bins_memory_bytes() + stats.gap_spectrum.memory_bytes());
+    let mut state = 0;
+    if config.save_state {
+        state += state_file;
+    }
+    if config.checkpoint_every > 0 {
+        state += 2 * state_file;
+    }
+    if config.wal {
+        let segments = max_n.div_ceil(config.segment_size_kb as u64 * 1024 * 8);
+        state += segments * (WAL_BYTES_PER_SEGMENT + stats.gap_spectrum.memory_bytes());
+    }
+
+    DiskUsage {
+        results,
+        report,
+        dumps,
+        snapshots,
+        state,
+        available: available_space(&config.output_dir),
+    }
+}
+
+impl fmt::Display for DiskUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "  disk:              about {} (results {}, report {}, dumps {}, snapshots {}, state {})",
+            format_bytes(self.total()),
+            format_bytes(self.results),
+            format_bytes(self.report),
+            format_bytes(self.dumps),
+            format_bytes(self.snapshots),
+            format_bytes(self.state)
+        )?;
+        if let Some(available) = self.available {
+            write!(f, ", {} free", format_bytes(available))?;
+        }
+        Ok(())
+    }
+}
+
+/// `3h 12m`, `4m 05s` or `12.3s`.
+fn format_duration(secs: f64) -> String {
+    let whole = secs as u64;
+    match whole {
+        0..60 => format!("{:.1}s", secs),
+        60..3600 => format!("{}m {:02}s", whole / 60, whole % 60),
+        _ => format!("{}h {:02}m", whole / 3600, whole % 3600 / 60),
+    }
+}
+
+impl fmt::Display for RunEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dry run up to N = {}", self.max_n)?;
+        writeln!(
+            f,
+            "  primes p:          {} (π(N) by Meissel–Lehmer in {:.1}s)",
+            self.prime_count, self.counting_secs
+        )?;
+        writeln!(f, "  sums S tested:     {}", self.sum_checks)?;
+        writeln!(
+            f,
+            "  sieve segments:    {} for p, {} for S ({} each)",
+            self.p_segments,
+            self.s_segments,
+            format_bytes(self.segment_size_bytes as u64)
+        )?;
+        let memory = &self.memory;
+        writeln!(
+            f,
+            "  memory:            sieve segment {}, sum segments {}, bins {}, spectrum {}",
+            format_bytes(memory.sieve_segment_bytes),
+            format_bytes(memory.sum_segment_bytes),
+            format_bytes(memory.bins_bytes),
+            format_bytes(memory.spectrum_bytes)
+        )?;
+        write!(
+            f,
+            "  estimated runtime: about {}",
+            format_duration(self.estimated_secs)
+        )
+    }
+}
@@ -0,0 +1,220 @@
+//! `--almost-primes`: classifies each sum S by Ω(S), its number of prime factors counted
+//! with multiplicity, as prime, semiprime, 3-almost-prime or more.
+//!
+//! Factors are found by trial division by the primes up to a bound B. Once the cofactor
+//! left is below the cube of the smallest prime it could still contain, it has at most
+//! two prime factors and one primality test settles it. With B³ > 2N (the default) every
+//! S is classified exactly; a smaller B leaves some sums [`FactorClass::Unresolved`].
+//!
+//! [`smallest_factor`] factors a single number completely, for the rare sums where the
+//! factor itself is wanted.
+
+use crate::sieve::{is_prime_u64, PrimeIterator};
+use serde::Serialize;
+
+/// Ω(S) of a sum, as far as trial division up to the bound can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FactorClass {
+    Prime,
+    Semiprime,
+    /// Exactly three prime factors.
+    AlmostPrime3,
+    /// Four or more.
+    Higher,
+    /// Two or three, with a cofactor above the bound's reach.
+    Unresolved,
+}
+
+impl FactorClass {
+    /// Number of classes, the length of per-class count arrays.
+    pub const COUNT: usize = 5;
+
+    /// Position of the class in per-class count arrays.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Trial divisor primes up to a bound.
+#[derive(Debug, Clone)]
+pub struct FactorClassifier {
+    primes: Vec<u32>,
+    bound: u64,
+}
+
+impl FactorClassifier {
+    pub fn new(bound: u64) -> Self {
+        let primes = PrimeIterator::new(bound, 16 * 1024)
+            .map(|p| p as u32)
+            .collect();
+        Self { primes, bound }
+    }
+
+    /// The smallest bound that classifies every number up to `limit` exactly.
+    pub fn exact_bound(limit: u64) -> u64 {
+        let mut bound = (limit as f64).cbrt() as u64;
+        while bound.saturating_mul(bound).saturating_mul(bound) < limit {
+            bound += 1;
+        }
+        bound.max(2)
+    }
+
+    pub fn bound(&self) -> u64 {
+        self.bound
+    }
+
+    /// Classifies `s`; a known primality verdict skips the trial division of primes.
+    pub fn classify(&self, s: u64, s_is_prime: bool) -> FactorClass {
+        if s_is_prime {
+            return FactorClass::Prime;
+        }
+        let mut rest = s;
+        let mut factors = 0u32;
+        // Every prime factor of `rest` is at least `smallest`.
+        let mut smallest = self.bound + 1;
+        for &q in &self.primes {
+            let q = q as u64;
+            if rest == 1 || q.saturating_mul(q).saturating_mul(q) > rest {
+                smallest = q;
+                break;
+            }
+            while rest.is_multiple_of(q) {
+                rest /= q;
+                factors += 1;
+            }
+            if factors >= 4 {
+                return FactorClass::Higher;
+            }
+        }
+        let omega = if rest == 1 {
+            factors
+        } else if is_prime_u64(rest) {
+            factors + 1
+        } else if smallest.saturating_mul(smallest).saturating_mul(smallest) > rest {
+            factors + 2
+        } else if factors + 2 >= 4 {
+            return FactorClass::Higher;
+        } else {
+            return FactorClass::Unresolved;
+        };
+        match omega {
+            0 | 1 => FactorClass::Prime,
+            2 => FactorClass::Semiprime,
+            3 => FactorClass::AlmostPrime3,
+            _ => FactorClass::Higher,
+        }
+    }
+}
+
+/// Odd trial divisors tried by [`smallest_factor`] before Pollard's rho.
+const TRIAL_DIVISION_LIMIT: u64 = 1000;
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// A nontrivial factor of the odd composite `n`, by Pollard's rho with Floyd's cycle
+/// detection, trying the next constant whenever a cycle closes on `n` itself.
+fn rho_factor(n: u64) -> u64 {
+    for c in 1..n {
+        let step = |x: u64| ((mul_mod(x, x, n) as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = step(x);
+            y = step(step(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+    unreachable!("{} is composite", n)
+}
+
+fn smallest_prime_factor_of_rough(n: u64) -> u64 {
+    if n == 1 || is_prime_u64(n) {
+        return n;
+    }
+    let d = rho_factor(n);
+    smallest_prime_factor_of_rough(d).min(smallest_prime_factor_of_rough(n / d))
+}
+
+/// The smallest prime factor of `n`: `n` itself if it is prime, and 1 for 0 and 1.
+pub fn smallest_factor(n: u64) -> u64 {
+    if n < 2 {
+        return 1;
+    }
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+    let mut q = 3;
+    while q <= TRIAL_DIVISION_LIMIT && q * q <= n {
+        if n.is_multiple_of(q) {
+            return q;
+        }
+        q += 2;
+    }
+    if q * q > n {
+        return n;
+    }
+    // Every prime factor left is above the trial divisors.
+    smallest_prime_factor_of_rough(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let exact = FactorClassifier::new(FactorClassifier::exact_bound(1_000_000));
+        assert_eq!(exact.bound(), 100);
+        assert_eq!(exact.classify(999_983, true), FactorClass::Prime);
+        assert_eq!(exact.classify(999_983, false), FactorClass::Prime);
+        assert_eq!(exact.classify(997 * 991, false), FactorClass::Semiprime);
+        assert_eq!(
+            exact.classify(3 * 101 * 103, false),
+            FactorClass::AlmostPrime3
+        );
+        assert_eq!(exact.classify(3 * 3 * 7, false), FactorClass::AlmostPrime3);
+        assert_eq!(exact.classify(3 * 5 * 7 * 11, false), FactorClass::Higher);
+
+        // With primes up to 10, 101 · 103 · 107 cannot be split.
+        let small = FactorClassifier::new(10);
+        assert_eq!(
+            small.classify(101 * 103 * 107, false),
+            FactorClass::Unresolved
+        );
+        assert_eq!(small.classify(9 * 101 * 103, false), FactorClass::Higher);
+        assert_eq!(small.classify(11 * 13, false), FactorClass::Semiprime);
+    }
+
+    #[test]
+    fn test_smallest_factor() {
+        assert_eq!(smallest_factor(1), 1);
+        assert_eq!(smallest_factor(2), 2);
+        assert_eq!(smallest_factor(999_983), 999_983);
+        assert_eq!(smallest_factor(3 * 999_983), 3);
+        assert_eq!(smallest_factor(997 * 991), 991);
+        // Beyond trial division: two primes above 10^9, and a large prime.
+        assert_eq!(
+            smallest_factor(1_000_000_007 * 1_000_000_009),
+            1_000_000_007
+        );
+        assert_eq!(
+            smallest_factor(1_000_003 * 1_000_033 * 1_000_037),
+            1_000_003
+        );
+        assert_eq!(
+            smallest_factor(18_446_744_073_709_551_557),
+            18_446_744_073_709_551_557
+        );
+    }
+}
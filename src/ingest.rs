@@ -0,0 +1,179 @@
+//! The `ingest` subcommand: runs prime pairs and primality verdicts computed elsewhere
+//! (another tool, or an earlier run's dumps) through [`Statistics`] and the regular
+//! outputs and report, so those can serve as a standalone analysis frontend.
+//!
+//! The input holds one pair per row as `p_prev, p_curr, s_is_prime`, in CSV with a header
+//! row or as newline-delimited JSON objects, in ascending order. Pairs may skip stretches
+//! of primes; the statistics then only cover the pairs given.
+
+use crate::config::{AttributeBy, BinDomain, IngestConfig, IngestFormat};
+use crate::console::{self, Level};
+use crate::output;
+use crate::plot_spec;
+use crate::registry::{Registry, RunEntry};
+use crate::sieve::is_prime_u64;
+use crate::stats::{Gap, Prime, Statistics};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+/// Numbers above the last pair searched for a further prime below 10^E; beyond it the
+/// run is recorded as incomplete.
+const MAX_TAIL_SCAN: u64 = 100_000;
+
+/// A primality verdict written as `true`/`false` or `1`/`0`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Verdict {
+    Bool(bool),
+    Int(u8),
+}
+
+#[derive(Deserialize)]
+struct IngestedPair {
+    p_prev: u64,
+    p_curr: u64,
+    s_is_prime: Verdict,
+}
+
+/// Format of `path`: as given, else `ndjson` for `.ndjson` and `.jsonl` files and CSV
+/// otherwise.
+fn format_of(config: &IngestConfig) -> IngestFormat {
+    config.format.unwrap_or_else(|| {
+        if config.input.ends_with(".ndjson") || config.input.ends_with(".jsonl") {
+            IngestFormat::Ndjson
+        } else {
+            IngestFormat::Csv
+        }
+    })
+}
+
+fn open(input: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    if input == "-" {
+        return Ok(Box::new(io::stdin()));
+    }
+    let file = File::open(input).map_err(|e| format!("{}: {}", input, e))?;
+    Ok(Box::new(file))
+}
+
+/// Calls `record` for every pair of the input, in order, with its 1-based row number.
+fn for_each_pair(
+    config: &IngestConfig,
+    mut record: impl FnMut(u64, IngestedPair) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let reader = open(&config.input)?;
+    let mut row = 0;
+    match format_of(config) {
+        IngestFormat::Csv => {
+            for pair in csv::Reader::from_reader(reader).deserialize() {
+                row += 1;
+                record(row, pair.map_err(|e| format!("row {}: {}", row, e))?)?;
+            }
+        }
+        IngestFormat::Ndjson => {
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                row += 1;
+                let pair =
+                    serde_json::from_str(&line).map_err(|e| format!("row {}: {}", row, e))?;
+                record(row, pair)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn run(config: &IngestConfig) -> Result<(), Box<dyn Error>> {
+    let run = &config.run;
+    console::set_level(Level::from_flags(run.quiet(), run.verbose));
+    if !run.milestone_exponents().is_empty() {
+        return Err("ingest takes a single --max-exponent".into());
+    }
+    if run.bin_domain == BinDomain::S
+        && !matches!(run.attribute_by, AttributeBy::Curr | AttributeBy::S)
+    {
+        return Err(
+            "--bin-domain s cannot be combined with --attribute-by prev or midpoint".into(),
+        );
+    }
+    let max_n = run.max_n();
+    let target_gaps: Vec<Gap> = run.target_gaps().into_iter().map(Gap).collect();
+    let mut stats = Statistics::new(max_n, run.bins, &target_gaps, run.spectrum_max_gap(max_n));
+    stats.attribute_by = run.attribute_by;
+    stats.bin_domain = run.bin_domain;
+    stats.pair_weight = run.pair_weight;
+    stats.large_gap_threshold = run.large_gap_threshold;
+    if let Some(split_at) = run.split_at {
+        stats.split_at = split_at;
+    }
+    if let Some(align) = run.bin_align {
+        stats = stats.with_bin_align(align);
+    }
+
+    let factor_classifier = run.factor_classifier();
+    let (mut last, mut pairs) = (0, 0u64);
+    for_each_pair(config, |row, pair| {
+        let (p_prev, p_curr) = (pair.p_prev, pair.p_curr);
+        if p_prev >= p_curr || p_prev < last {
+            return Err(format!(
+                "row {}: pairs must be ascending with p_prev < p_curr, got ({}, {}) after {}",
+                row, p_prev, p_curr, last
+            )
+            .into());
+        }
+        if p_curr > max_n {
+            return Err(format!(
+                "row {}: p_curr = {} exceeds 10^{}",
+                row,
+                p_curr,
+                run.max_exponent()
+            )
+            .into());
+        }
+        if p_prev == 2 {
+            stats.record_first_prime();
+        }
+        let s_is_prime = match pair.s_is_prime {
+            Verdict::Bool(b) => b,
+            Verdict::Int(i) => i != 0,
+        };
+        let s = p_prev + p_curr - 1;
+        if let Some(classifier) = &factor_classifier {
+            stats.record_factor_class(Gap(p_curr - p_prev), classifier.classify(s, s_is_prime));
+        }
+        stats.record_pair(Prime(p_prev), Prime(p_curr), s, s_is_prime);
+        last = p_curr;
+        pairs += 1;
+        Ok(())
+    })?;
+    if pairs == 0 {
+        return Err(format!("{} holds no prime pairs", config.input).into());
+    }
+    // The input covers the whole range if it ends at the last prime below 10^E.
+    if max_n - last <= MAX_TAIL_SCAN && !(last + 1..=max_n).any(is_prime_u64) {
+        stats.analyzed_up_to = max_n;
+    }
+
+    output::write_results(&stats, run)?;
+    if let Some(registry) = &run.registry {
+        Registry::record(registry, RunEntry::new(&stats, run))?;
+    }
+    if let Some(format) = run.plot_spec {
+        plot_spec::write(format, &run.output_dir, &stats.target_gaps)?;
+    }
+    #[cfg(feature = "report")]
+    if run.web_report {
+        crate::report::generate_report(run, max_n)?;
+    }
+    crate::info!(
+        "Ingested {} pairs up to {} into {}",
+        pairs,
+        stats.analyzed_up_to,
+        run.output_dir
+    );
+    crate::thresholds::check(&run.fail_if, &run.output_dir)
+}
@@ -0,0 +1,85 @@
+//! Sieve, statistics, and output machinery behind the `prime_shield_analyzer` binary.
+//!
+//! Without the default `std` feature only [`sieve_core`] is built, for `no_std` targets.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "arrow")]
+pub mod arrow_ipc;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod completions;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod consecutive;
+#[cfg(feature = "std")]
+pub mod console;
+#[cfg(feature = "std")]
+pub mod counting;
+#[cfg(feature = "std")]
+pub mod demo;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod dump;
+#[cfg(feature = "std")]
+pub mod estimate;
+#[cfg(feature = "std")]
+pub mod factoring;
+#[cfg(feature = "std")]
+pub mod ingest;
+#[cfg(feature = "std")]
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod migrate;
+#[cfg(feature = "std")]
+pub mod model;
+#[cfg(feature = "std")]
+pub mod oeis;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod plan;
+#[cfg(feature = "std")]
+pub mod plot_spec;
+#[cfg(feature = "std")]
+pub mod probe;
+#[cfg(feature = "profile")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod prune;
+#[cfg(feature = "std")]
+pub mod records;
+#[cfg(feature = "std")]
+pub mod recovery;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "report")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod self_test;
+#[cfg(feature = "std")]
+pub mod shielding;
+#[cfg(feature = "std")]
+pub mod sieve;
+pub mod sieve_core;
+#[cfg(feature = "std")]
+pub mod sinks;
+#[cfg(feature = "std")]
+pub mod sketch;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod table;
+#[cfg(feature = "std")]
+pub mod thresholds;
+#[cfg(feature = "std")]
+pub mod throughput;
+#[cfg(feature = "std")]
+pub mod trends;
+#[cfg(feature = "std")]
+pub mod watchdog;
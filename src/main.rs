@@ -1,141 +1,910 @@
-mod config;
-mod output;
-mod report;
-mod sieve;
-mod stats;
-
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use indicatif::ProgressBar;
-use sieve::{PrimalityChecker, PrimeIterator};
-use stats::Statistics;
-
-use crate::config::Config;
+use prime_shield_analyzer::config::{
+    AttributeBy, BinDomain, Cli, Command, Config, DemoConfig, DiskCheck, Exponent, Formula,
+    OutOfRange, SelfTestConfig, Shard, SinkKind,
+};
+use prime_shield_analyzer::console::{self, Level};
+use prime_shield_analyzer::dump::{self, ValueDump};
+use prime_shield_analyzer::memory::{self, format_bytes, MemoryUsage};
+use prime_shield_analyzer::records::DiagnosticRecord;
+use prime_shield_analyzer::registry::{Registry, RunEntry};
+#[cfg(feature = "report")]
+use prime_shield_analyzer::report;
+use prime_shield_analyzer::sieve::{self, PrimalityChecker, PrimeIterator, SegmentStore};
+use prime_shield_analyzer::stats::{self, Gap, Prime, Statistics};
+use prime_shield_analyzer::throughput::ThroughputTracker;
+use prime_shield_analyzer::watchdog::{Snapshot, Watchdog};
+use prime_shield_analyzer::{
+    completions, consecutive, counting, demo, diff, estimate, ingest, migrate, oeis, output, plan,
+    plot_spec, probe, prune, recovery, self_test, thresholds, trends,
+};
+use prime_shield_analyzer::{debug, info};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::parse();
+    let mut cli = Cli::parse();
+    cli.apply_seed();
+    // Like the conflicts of `--slim` and `--columns`, but with the columns each reads.
+    let ingest_run = match &cli.command {
+        Some(Command::Ingest(ingest_config)) => Some(&ingest_config.run),
+        _ => None,
+    };
+    for run in cli.run.iter().chain(ingest_run) {
+        let target_gaps: Vec<Gap> = run.target_gaps().into_iter().map(Gap).collect();
+        if let Err(e) = output::ColumnSelection::new(run).validate(&target_gaps) {
+            Cli::command()
+                .error(clap::error::ErrorKind::InvalidValue, e)
+                .exit();
+        }
+        if let Err(e) = thresholds::check_columns(&run.fail_if, run) {
+            Cli::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, e)
+                .exit();
+        }
+    }
+    let result = match (cli.command, cli.run) {
+        (Some(Command::Probe(probe_config)), _) => probe::run(&probe_config),
+        (Some(Command::Diff(diff_config)), _) => diff::run(&diff_config),
+        (Some(Command::Trends(trends_config)), _) => trends::run(&trends_config),
+        (Some(Command::Prune(prune_config)), _) => prune::run(&prune_config),
+        (Some(Command::Ingest(ingest_config)), _) => ingest::run(&ingest_config),
+        (Some(Command::Plan(plan_config)), _) => plan::run(&plan_config),
+        (Some(Command::CheckOeis(check_config)), _) => oeis::run(&check_config),
+        (Some(Command::Migrate(migrate_config)), _) => migrate::run(&migrate_config),
+        (Some(Command::Demo(demo_config)), _) => run_demo(&demo_config),
+        (Some(Command::SelfTest(self_test_config)), _) => run_self_test(&self_test_config),
+        (Some(Command::Pi(pi_config)), _) => counting::run_pi(&pi_config),
+        (Some(Command::NthPrime(nth_config)), _) => counting::run_nth_prime(&nth_config),
+        (Some(Command::Completions(completions_config)), _) => {
+            completions::run(&completions_config)
+        }
+        (None, Some(config)) => run_analysis(config),
+        (None, None) => Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided:\n  --max-exponent <MAX_EXPONENT>",
+            )
+            .exit(),
+    };
+    // A threshold that held is a finding, not an error: it gets its own exit code.
+    if let Some(failed) = result
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<thresholds::ThresholdsFailed>())
+    {
+        eprintln!("Error: {}", failed);
+        std::process::exit(thresholds::FAIL_IF_EXIT_CODE);
+    }
+    result
+}
 
+/// Runs the built-in analysis of [`self_test`] and checks its outputs against the fixtures.
+fn run_self_test(config: &SelfTestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = match &config.keep {
+        Some(dir) => dir.clone(),
+        None => std::env::temp_dir()
+            .join(format!("prime_shield_self_test_{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let cli = Cli::try_parse_from(self_test::analysis_args(&output_dir))?;
+    let analysis = cli
+        .run
+        .ok_or("self-test arguments did not parse to an analysis")?;
+
+    let result = run_analysis(analysis).and_then(|()| self_test::run(&output_dir));
+    if config.keep.is_none() {
+        let _ = std::fs::remove_dir_all(&output_dir);
+    }
+    result?;
+    println!("Self-test passed.");
+    Ok(())
+}
+
+fn run_demo(config: &DemoConfig) -> Result<(), Box<dyn std::error::Error>> {
+    // Kept after the demo, for a look at the files.
+    let output_dir = match &config.dir {
+        Some(dir) => dir.clone(),
+        None => std::env::temp_dir()
+            .join(format!("prime_shield_demo_{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let cli = Cli::try_parse_from(demo::analysis_args(&output_dir))?;
+    let analysis = cli
+        .run
+        .ok_or("demo arguments did not parse to an analysis")?;
+    println!("Analyzing every prime pair up to 10^6...");
+    run_analysis(analysis)?;
+    demo::print_tour(&output_dir)?;
+
+    let report = std::path::Path::new(&output_dir).join("index.html");
+    if !report.exists() {
+        println!("This build has no HTML report (the `report` feature is off).");
+    } else if config.no_open {
+        println!(
+            "Report: {} (serve the directory over HTTP to view it, e.g. `python3 -m http.server`)",
+            report.display()
+        );
+    } else {
+        demo::serve_report(&output_dir)?;
+    }
+    Ok(())
+}
+
+/// Writes the complete outputs of a smaller `--max-exponent` once the prime stream has
+/// passed its bound.
+fn write_milestone(
+    mut stats: Statistics,
+    exponent: Exponent,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    stats.analyzed_up_to = exponent.max_n();
+    let mut milestone_config = config.clone();
+    milestone_config.max_exponents = vec![exponent];
+    milestone_config.output_dir = output::milestone_dir(&config.output_dir, exponent);
+    output::write_results(&stats, &milestone_config)?;
+    #[cfg(feature = "report")]
+    if config.web_report {
+        report::generate_report(&milestone_config, exponent.max_n())?;
+    }
+    info!(
+        "Outputs up to 10^{} written to {}",
+        exponent, milestone_config.output_dir
+    );
+    Ok(())
+}
+
+fn run_analysis(mut config: Config) -> Result<(), Box<dyn std::error::Error>> {
     // Validate gaps
-    if config.gaps.is_empty() {
+    let target_gaps = config.target_gaps();
+    if target_gaps.is_empty() {
         eprintln!("Error: No gap sizes provided. Please provide at least one gap size.");
         std::process::exit(1);
     }
-    for &gap in &config.gaps {
+    for &gap in &target_gaps {
         if gap == 0 {
             eprintln!("Error: Gap size cannot be 0.");
             std::process::exit(1);
         }
         if gap % 2 != 0 && gap != 1 {
-            // Allow gap of 1 only for special cases, but statistical analysis expects even.
+            // Gap 1 (between 2 and 3) is the only odd gap; see --include-gap-one.
             eprintln!(
-                "Error: Gap size {} is odd. All relevant prime gaps (except for the first, between 2 and 3) are even. Please provide even gap sizes.",
+                "Error: Gap size {} is odd. All prime gaps except the first (1, between 2 and 3) are even. Please provide even gap sizes.",
                 gap
             );
             std::process::exit(1);
         }
     }
 
-    let target_gaps_set: std::collections::HashSet<u64> = config.gaps.iter().cloned().collect();
-    let mut sorted_target_gaps = config.gaps.clone();
+    let mut sorted_target_gaps: Vec<Gap> = target_gaps.iter().map(|&g| Gap(g)).collect();
     sorted_target_gaps.sort_unstable(); // For consistent CSV/HTML output order
+    sorted_target_gaps.dedup();
+
+    if config.stream_bins && (config.wal || config.resume || config.checkpoint_every > 0) {
+        eprintln!("Error: --stream-bins cannot be combined with checkpoints, --wal or --resume.");
+        std::process::exit(1);
+    }
+
+    if config.bin_domain == BinDomain::S
+        && !matches!(config.attribute_by, AttributeBy::Curr | AttributeBy::S)
+    {
+        eprintln!("Error: --bin-domain s places every pair by its S; it cannot be combined with --attribute-by prev or midpoint.");
+        std::process::exit(1);
+    }
 
-    let max_n = 10u64.pow(config.max_exponent);
+    if config.snapshot_every == Some(0) || config.snapshot_every_primes == Some(0) {
+        eprintln!("Error: --snapshot-every and --snapshot-every-primes must be at least 1.");
+        std::process::exit(1);
+    }
+
+    if config.resume && !config.milestone_exponents().is_empty() {
+        eprintln!("Error: --resume cannot be combined with several --max-exponent values.");
+        std::process::exit(1);
+    }
+
+    if config.resume && (config.dump_primes || config.dump_s_primes) {
+        eprintln!("Error: --dump-primes and --dump-s-primes cannot be combined with --resume.");
+        std::process::exit(1);
+    }
+
+    if config.arrow_bins && !cfg!(feature = "arrow") {
+        eprintln!("Error: --arrow-bins requires a build with `--features arrow`.");
+        std::process::exit(1);
+    }
+
+    if (config.web_report || config.report_pdf) && !cfg!(feature = "report") {
+        eprintln!(
+            "Error: --web-report and --report-pdf require a build with the `report` feature."
+        );
+        std::process::exit(1);
+    }
+
+    if config.sinks.contains(&SinkKind::Sqlite) && !cfg!(feature = "sqlite") {
+        eprintln!("Error: --sink sqlite requires a build with `--features sqlite`.");
+        std::process::exit(1);
+    }
+
+    if config.profile && !cfg!(feature = "profile") {
+        eprintln!("Error: --profile requires a build with `--features profile`.");
+        std::process::exit(1);
+    }
+
+    if config.deterministic && config.adaptive_segment_size {
+        eprintln!("Error: --adaptive-segment-size tunes on wall-clock timings and cannot be combined with --deterministic.");
+        std::process::exit(1);
+    }
+
+    if config.slurm_array {
+        match Shard::from_slurm_env() {
+            Ok(shard) => config.shard = Some(shard),
+            Err(e) => {
+                eprintln!("Error: {}.", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(shard) = config.shard {
+        if !config.milestone_exponents().is_empty() {
+            eprintln!("Error: --shard and --slurm-array take a single --max-exponent.");
+            std::process::exit(1);
+        }
+        // Each shard writes its own directory, so the tasks of an array never collide.
+        config.output_dir = std::path::Path::new(&config.output_dir)
+            .join(shard.dir_name())
+            .to_string_lossy()
+            .into_owned();
+    }
+
+    let triple = config.formula == Formula::Triple;
+    if triple
+        && (config.resume
+            || config.wal
+            || config.checkpoint_every > 0
+            || config.stream_bins
+            || config.snapshot_every.is_some()
+            || config.snapshot_every_primes.is_some()
+            || config.sample > 1
+            || config.pair_weight.is_some()
+            || config.large_gap_threshold.is_some()
+            || config.dump_primes
+            || config.dump_s_primes
+            || config.almost_primes
+            || !config.gap_families.is_empty()
+            || config.split_at.is_some()
+            || !config.sinks.is_empty()
+            || config.shard.is_some()
+            || config.save_state
+            || config.web_report
+            || config.report_pdf
+            || config.plot_spec.is_some()
+            || config.registry.is_some()
+            || config.dry_run
+            || !config.milestone_exponents().is_empty())
+    {
+        eprintln!("Error: --formula triple writes only triple_spectrum.csv and triple_global_stats.csv; it cannot be combined with checkpoints, snapshots, sampling, --pair-weight, dumps, --almost-primes, --gap-families, --split-at, --sink, --shard, reports, the registry, --dry-run or several --max-exponent values.");
+        std::process::exit(1);
+    }
+
+    if config.pin_threads {
+        sieve::pin_threads()?;
+    }
+    if config.deterministic {
+        sieve::use_fixed_partitioning();
+    }
+    if config.nested_parallel_marking {
+        sieve::use_nested_parallel_marking();
+    }
+    #[cfg(feature = "profile")]
+    let profiler = if config.profile {
+        Some(prime_shield_analyzer::profile::Profiler::start()?)
+    } else {
+        None
+    };
+
+    console::set_level(Level::from_flags(config.quiet(), config.verbose));
+    if triple {
+        info!(
+            "Max N (10^{}): {}, S_3 = p_n + p_{{n+1}} + p_{{n+2}} - 2",
+            config.max_exponent(),
+            config.max_n()
+        );
+        return consecutive::run_triple(&config);
+    }
+    let max_n = config.max_n();
 
     // Use the user-defined segment size, converting from KB to Bytes.
     let segment_size_bytes = config.segment_size_kb * 1024;
 
-    println!("Max N (10^{}): {}", config.max_exponent, max_n);
-    println!("Bins: {}", config.bins);
-    println!("Output Dir: {}", config.output_dir);
-    println!("Using Segment Size: {} KB", config.segment_size_kb);
-    println!("Tracking Gaps: {:?}", sorted_target_gaps);
+    let new_stats = |max_n: u64| {
+        let mut stats = Statistics::new(
+            max_n,
+            config.bins,
+            &sorted_target_gaps,
+            config.spectrum_max_gap(max_n),
+        );
+        stats.attribute_by = config.attribute_by;
+        stats.bin_domain = config.bin_domain;
+        match config.bin_align {
+            Some(align) => stats.with_bin_align(align),
+            None => stats,
+        }
+    };
+    let mut stats = new_stats(max_n);
+    // Each smaller --max-exponent gets statistics of its own, so that its outputs match a
+    // separate run up to its bound.
+    let mut milestones: Vec<(Exponent, Statistics)> = config
+        .milestone_exponents()
+        .into_iter()
+        .map(|exponent| (exponent, new_stats(exponent.max_n())))
+        .collect();
+
+    let disk = estimate::disk_usage(&config, &stats);
+    if config.dry_run {
+        println!(
+            "{}",
+            estimate::estimate(max_n, &stats, segment_size_bytes, config.sample)
+        );
+        println!("{}", disk);
+        return Ok(());
+    }
+    // Hours of sieving are lost if the final write fails, so a run that may not fit stops
+    // before it starts.
+    if config.disk_check != DiskCheck::Off && disk.exceeds_available() {
+        let message = format!(
+            "the outputs of this run may take about {}, but only {} is free under {}",
+            format_bytes(disk.total()),
+            format_bytes(disk.available.unwrap_or_default()),
+            config.output_dir
+        );
+        if config.disk_check == DiskCheck::Abort {
+            eprintln!("Error: {}. Free some space, choose another --output-dir or pass --disk-check warn.", message);
+            std::process::exit(1);
+        }
+        eprintln!("Warning: {}.", message);
+    }
+
+    info!("Max N (10^{}): {}", config.max_exponent(), max_n);
+    let shard_end = config.shard.map_or(u64::MAX, |shard| shard.range(max_n).1);
+    if let Some(shard) = config.shard {
+        let (start, end) = shard.range(max_n);
+        info!("Shard {}: pairs with p in [{}, {})", shard, start, end);
+    }
+    if !milestones.is_empty() {
+        info!(
+            "Milestones: {}",
+            milestones
+                .iter()
+                .map(|(exponent, _)| format!("10^{}", exponent))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    info!("Bins: {}", config.bins);
+    info!("Output Dir: {}", config.output_dir);
+    match disk.available {
+        Some(available) => info!(
+            "Disk: about {} of outputs, {} free",
+            format_bytes(disk.total()),
+            format_bytes(available)
+        ),
+        None => info!("Disk: about {} of outputs", format_bytes(disk.total())),
+    }
+    info!("Using Segment Size: {} KB", config.segment_size_kb);
+    info!(
+        "Tracking Gaps: {:?}",
+        sorted_target_gaps.iter().map(|g| g.0).collect::<Vec<_>>()
+    );
 
     // The sieve for generating p_n only needs to go up to max_n.
     let mut prime_iterator = PrimeIterator::new(max_n, segment_size_bytes);
+    if config.adaptive_segment_size {
+        prime_iterator = prime_iterator.with_adaptive_segment_size();
+    }
 
     // The checker needs to handle sums S = p_n + p_{n+1} - 1.
     // So S can be close to 2 * max_n.
     let analysis_limit = max_n * 2;
-    let mut primality_checker = PrimalityChecker::new(analysis_limit, segment_size_bytes);
+    let mut segment_store = SegmentStore::new(analysis_limit, segment_size_bytes);
 
-    let mut stats = Statistics::new(max_n, config.bins, &sorted_target_gaps);
+    if let Some(dir) = &config.mmap_segments {
+        std::fs::create_dir_all(dir)?;
+        prime_iterator = prime_iterator.with_mmap_dir(dir);
+        segment_store = segment_store.with_mmap_dir(dir);
+    }
+    let mut primality_checker = PrimalityChecker::with_store(Arc::new(segment_store))
+        .with_cache_size(config.checker_cache_segments);
+    if config.deterministic {
+        primality_checker = primality_checker.with_synchronous_prefetch();
+    }
 
-    let bar = ProgressBar::new(max_n);
-    bar.set_style(indicatif::ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}/{len:7} ({eta})")?
-        .progress_chars("#>-"));
+    stats.sample_every = config.sample;
+    stats.sample_seed = config.sample_seed;
+    stats.counts_almost_primes = config.almost_primes;
+    stats.shield_model = config.shield_model();
+    stats.pair_weight = config.pair_weight;
+    stats.large_gap_threshold = config.large_gap_threshold;
+    if let Some(split_at) = config.split_at {
+        stats.split_at = split_at;
+    }
+    stats.shard = config.shard;
+    for (_, milestone) in &mut milestones {
+        milestone.sample_every = config.sample;
+        milestone.pair_weight = config.pair_weight;
+        milestone.large_gap_threshold = config.large_gap_threshold;
+        if let Some(split_at) = config.split_at {
+            milestone.split_at = split_at;
+        }
+    }
+    let mut sampler = stats::PairSampler::new(config.sample, config.sample_seed);
+    let checker_limit = primality_checker.limit();
+    let factor_classifier = config.factor_classifier();
+    if config.sample > 1 {
+        info!(
+            "Sampling 1 in {} prime pairs ({})",
+            config.sample,
+            match config.sample_seed {
+                Some(seed) => format!("random, seed {}", seed),
+                None => "systematic".to_string(),
+            }
+        );
+    }
 
-    let mut p_prev = 2; // The first prime
+    if config.resume {
+        let recovered = recovery::recover(&config.output_dir, stats)?;
+        stats = recovered.stats;
+        info!(
+            "Resuming at p = {} ({}, {} WAL records replayed)",
+            stats.analyzed_up_to,
+            if recovered.from_checkpoint {
+                "from checkpoint"
+            } else {
+                "no checkpoint"
+            },
+            recovered.wal_records
+        );
+    }
 
-    // Manually handle the first prime (2) since our loop starts with the second one
-    if max_n >= 2 {
-        stats.total_primes += 1;
-        if let Some(bin_index) = stats.get_bin_index(2) {
-            stats.bins[bin_index].prime_count_p += 1;
+    // A shard from p > 2 on stands where a resumed run would: at its first prime, with the
+    // pairs before it left to the earlier shards.
+    if let Some(shard) = config.shard.filter(|_| stats.analyzed_up_to < 2) {
+        let (start, _) = shard.range(max_n);
+        if start > 2 {
+            prime_iterator = prime_iterator.starting_at(start);
+            let first = prime_iterator.next().unwrap_or(max_n);
+            stats.start_at(Prime(first), counting::prime_pi);
         }
-        prime_iterator.next(); // Consume '2' from iterator
     }
 
-    for p_current in prime_iterator {
-        stats.total_primes += 1;
-
-        // Update stats for p_current
-        if let Some(bin_index) = stats.get_bin_index(p_current) {
-            stats.bins[bin_index].prime_count_p += 1;
+    let recovering = config.wal || config.checkpoint_every > 0;
+    let mut wal = None;
+    if recovering {
+        std::fs::create_dir_all(&config.output_dir)?;
+        // Fold any replayed records into a fresh checkpoint before starting a new log.
+        if config.resume {
+            recovery::write_checkpoint(&stats, &config.output_dir)?;
         }
+        if config.wal {
+            wal = Some(recovery::WalWriter::create(&config.output_dir, &mut stats)?);
+        }
+    }
+    let checkpoint_every = Duration::from_secs(config.checkpoint_every);
+    let mut last_checkpoint = Instant::now();
+    let flush_every = Duration::from_secs(config.flush_every * 60);
+    let mut last_flush = Instant::now();
+    // The next power of ten at which --report-snapshots keeps a report; a resumed run goes
+    // on from the first one it has not passed.
+    #[cfg(feature = "report")]
+    let mut next_report_snapshot = {
+        let mut n = 10u64;
+        while n < stats.analyzed_up_to {
+            n = n.saturating_mul(10);
+        }
+        n
+    };
+    // The next N, or count of primes, at which --snapshot-every writes a snapshot.
+    let mut next_snapshot_n = config
+        .snapshot_every
+        .map(|step| (stats.analyzed_up_to / step + 1) * step);
+    let mut next_snapshot_primes = config
+        .snapshot_every_primes
+        .map(|step| (stats.total_primes / step + 1) * step);
+    let started = Instant::now();
+    let first_prime_count = stats.total_primes;
+    // Checkpoints and WAL records are taken once per segment's worth of primes.
+    let segment_span = (segment_size_bytes * 8) as u64;
 
-        // Calculate gap and S
-        let gap = p_current - p_prev;
-        let s = p_current + p_prev - 1;
+    if config.stream_bins {
+        std::fs::create_dir_all(&config.output_dir)?;
+        stats.stream_bins_to(output::OscillationWriter::create(
+            &config.output_dir,
+            &stats.target_gaps,
+            config.partition_rows,
+            &output::ColumnSelection::new(&config),
+        )?);
+    }
+    #[cfg(feature = "arrow")]
+    if config.arrow_bins {
+        std::fs::create_dir_all(&config.output_dir)?;
+        stats.stream_bins_to_arrow(prime_shield_analyzer::arrow_ipc::ArrowBinWriter::create(
+            &config.output_dir,
+            &stats.target_gaps,
+        )?);
+    }
 
-        // Update gap spectrum (occurrences)
-        stats.gap_spectrum.entry(gap).or_insert((0, 0)).0 += 1;
+    let open_dump =
+        |enabled: bool, stem: &str| -> Result<Option<ValueDump>, Box<dyn std::error::Error>> {
+            if !enabled {
+                return Ok(None);
+            }
+            std::fs::create_dir_all(&config.output_dir)?;
+            Ok(Some(ValueDump::create(
+                &config.output_dir,
+                stem,
+                config.dump_format,
+            )?))
+        };
+    let mut prime_dump = open_dump(config.dump_primes, dump::PRIME_DUMP)?;
+    let mut s_prime_dump = open_dump(config.dump_s_primes, dump::S_PRIME_DUMP)?;
 
-        // Update high-interest gap occurrences in the correct bin
-        // The occurrence is tied to the location of p_current
-        if target_gaps_set.contains(&gap) {
-            if let Some(bin_index) = stats.get_bin_index(p_current) {
-                *stats.bins[bin_index]
-                    .gap_occurrences
-                    .entry(gap)
-                    .or_insert(0) += 1;
+    // Counted in primes: π(N) by Meissel–Lehmer takes seconds even where sieving takes hours.
+    let bar = if console::enabled(Level::Info) {
+        ProgressBar::new(counting::prime_pi(max_n))
+    } else {
+        ProgressBar::hidden()
+    };
+    bar.set_style(indicatif::ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}/{len:7} primes ({eta})")?
+        .progress_chars("#>-"));
+
+    let (mut p_prev, prime_iterator) = if stats.analyzed_up_to >= 2 {
+        // Resumed: every pair up to `analyzed_up_to` (a prime) is already accounted for.
+        let p_prev = Prime(stats.analyzed_up_to);
+        bar.set_position(stats.total_primes);
+        (p_prev, prime_iterator.starting_at(p_prev.0 + 1))
+    } else {
+        let mut prime_iterator = prime_iterator;
+        // Manually handle the first prime (2) since our loop starts with the second one
+        if max_n >= 2 {
+            stats.record_first_prime();
+            for (_, milestone) in milestones.iter_mut().filter(|(e, _)| e.max_n() >= 2) {
+                milestone.record_first_prime();
+            }
+            prime_iterator.next(); // Consume '2' from iterator
+            if let Some(dump) = prime_dump.as_mut() {
+                dump.write(2)?;
             }
         }
+        (Prime(2), prime_iterator) // The first prime
+    };
+    let mut next_mark = p_prev.0 + segment_span;
+    let mut segment_boundaries = 0u64;
+
+    // Throughput rows are wall-clock measurements, so deterministic runs leave them out.
+    let mut throughput = if config.deterministic {
+        None
+    } else {
+        std::fs::create_dir_all(&config.output_dir)?;
+        Some(ThroughputTracker::create(
+            &config.output_dir,
+            config.partition_rows,
+            prime_iterator.sieve_time(),
+        )?)
+    };
 
-        // Check if S is prime
-        if primality_checker.is_prime(s) {
-            stats.total_s_primes += 1;
+    // The sums S for p in [a, b] fall in [2a, 2b]: sieve the current span's sums up front
+    // and the next span's on a background thread while this one is analyzed.
+    let sum_sieve_started = Instant::now();
+    primality_checker.ensure_range(2 * p_prev.0, 2 * next_mark);
+    primality_checker.prefetch_range(2 * next_mark, 2 * (next_mark + segment_span));
+    if let Some(throughput) = throughput.as_mut() {
+        throughput.add_sum_sieve_time(sum_sieve_started.elapsed());
+    }
+
+    let mut memory_usage = MemoryUsage::default();
+    let sample_memory = |prime_iterator: &PrimeIterator,
+                         primality_checker: &PrimalityChecker,
+                         stats: &Statistics| MemoryUsage {
+        peak_rss_bytes: None,
+        sieve_segment_bytes: prime_iterator.memory_bytes(),
+        sum_segment_bytes: primality_checker.memory_bytes(),
+        bins_bytes: stats.bins_memory_bytes(),
+        spectrum_bytes: stats.gap_spectrum.memory_bytes(),
+    };
 
-            // Update gap spectrum (successes)
-            stats.gap_spectrum.entry(gap).or_insert((0, 0)).1 += 1;
+    let watchdog = (config.watchdog_secs > 0).then(|| {
+        Watchdog::spawn(
+            Duration::from_secs(config.watchdog_secs),
+            config.watchdog_abort,
+            &config.output_dir,
+        )
+    });
 
-            // Update bin stats for S
-            if let Some(bin_index) = stats.get_bin_index(s) {
-                stats.bins[bin_index].prime_count_s += 1;
+    let mut prime_iterator = prime_iterator;
+    while let Some(p_current) = prime_iterator.next().map(Prime) {
+        if p_prev.0 >= shard_end {
+            break;
+        }
+        stats.flush_completed_bins(p_current.0)?;
+        while milestones
+            .first()
+            .is_some_and(|(exponent, _)| exponent.max_n() < p_current.0)
+        {
+            let (exponent, milestone) = milestones.remove(0);
+            bar.suspend(|| write_milestone(milestone, exponent, &config))?;
+        }
+        // Stats cover every prime below p_current, so up to the snapshot's N.
+        if let (Some(n), Some(step)) = (next_snapshot_n, config.snapshot_every) {
+            if p_current.0 > n {
+                bar.suspend(|| output::write_cumulative_snapshot(&mut stats, &config, n))?;
+                next_snapshot_n = Some((p_current.0 - 1) / step * step + step);
             }
+        }
+        if let (Some(count), Some(step)) = (next_snapshot_primes, config.snapshot_every_primes) {
+            if stats.total_primes >= count {
+                bar.suspend(|| output::write_cumulative_snapshot(&mut stats, &config, p_prev.0))?;
+                next_snapshot_primes = Some(count + step);
+            }
+        }
+        // Like the milestones, each report snapshot covers exactly 10^k; the final report
+        // writes the one of max N.
+        #[cfg(feature = "report")]
+        if config.report_snapshots
+            && p_current.0 > next_report_snapshot
+            && next_report_snapshot < max_n
+        {
+            let exponent = Exponent(next_report_snapshot.ilog10() as f64);
+            output::write_snapshot(&mut stats, &config)?;
+            bar.suspend(|| report::generate_snapshot(&config, exponent))?;
+            next_report_snapshot = next_report_snapshot.saturating_mul(10);
+        }
 
-            // Update high-interest gap successes in the correct bin
-            // The success is also tied to the location of p_current
-            if target_gaps_set.contains(&gap) {
-                if let Some(bin_index) = stats.get_bin_index(p_current) {
-                    *stats.bins[bin_index].gap_successes.entry(gap).or_insert(0) += 1;
+        // At segment boundaries, stats cover every pair up to p_prev.
+        if p_current.0 >= next_mark {
+            memory_usage.observe(sample_memory(&prime_iterator, &primality_checker, &stats));
+            if let Some(wal) = wal.as_mut() {
+                wal.append(&mut stats)?;
+            }
+            if !checkpoint_every.is_zero() && last_checkpoint.elapsed() >= checkpoint_every {
+                recovery::write_checkpoint(&stats, &config.output_dir)?;
+                if config.wal {
+                    wal = Some(recovery::WalWriter::create(&config.output_dir, &mut stats)?);
                 }
+                last_checkpoint = Instant::now();
+            }
+            if !flush_every.is_zero() && last_flush.elapsed() >= flush_every {
+                output::write_snapshot(&mut stats, &config)?;
+                last_flush = Instant::now();
+            }
+            if let Some(throughput) = throughput.as_mut() {
+                throughput.end_segment(p_prev.0, prime_iterator.sieve_time())?;
+            }
+            next_mark = p_prev.0 + segment_span;
+            segment_boundaries += 1;
+            let sum_sieve_started = Instant::now();
+            primality_checker.ensure_range(2 * p_prev.0, 2 * next_mark);
+            primality_checker.prefetch_range(2 * next_mark, 2 * (next_mark + segment_span));
+            if let Some(throughput) = throughput.as_mut() {
+                throughput.add_sum_sieve_time(sum_sieve_started.elapsed());
+            }
+            if console::enabled(Level::Trace) {
+                bar.suspend(|| {
+                    println!(
+                        "entering segment p in [{}, {}): {} primes so far, {:.1}s elapsed",
+                        p_prev.0,
+                        next_mark,
+                        stats.total_primes,
+                        started.elapsed().as_secs_f64()
+                    )
+                });
+            }
+            if let Some(watchdog) = &watchdog {
+                watchdog.progress(Snapshot {
+                    analyzed_up_to: p_prev.0,
+                    segment: (p_prev.0, next_mark),
+                    sum_range: (2 * p_prev.0, 2 * next_mark),
+                    cache: primality_checker.cache_stats(),
+                    memory: memory_usage,
+                });
+            }
+        }
+
+        let sampled = sampler.take();
+        if sampled {
+            // Calculate S and check if it is prime
+            let s = p_current.0 + p_prev.0 - 1;
+            let s_is_prime = if s <= checker_limit {
+                primality_checker.is_prime(s)
+            } else {
+                if stats.out_of_range.is_empty() {
+                    bar.suspend(|| {
+                        eprintln!(
+                            "Warning: S = {} exceeds the primality checker's limit {}; such sums are {}.",
+                            s,
+                            checker_limit,
+                            match config.out_of_range {
+                                OutOfRange::Composite => "counted as composite",
+                                OutOfRange::MillerRabin => "tested with Miller-Rabin",
+                            }
+                        )
+                    });
+                }
+                stats.record_out_of_range(Gap::between(p_prev, p_current));
+                config.out_of_range == OutOfRange::MillerRabin && sieve::is_prime_u64(s)
+            };
+            if let Some(classifier) = &factor_classifier {
+                let gap = Gap::between(p_prev, p_current);
+                let class = classifier.classify(s, s_is_prime);
+                stats.record_factor_class(gap, class);
+                for (_, milestone) in &mut milestones {
+                    milestone.record_factor_class(gap, class);
+                }
+            }
+            stats.record_pair(p_prev, p_current, s, s_is_prime);
+            for (_, milestone) in &mut milestones {
+                milestone.record_pair(p_prev, p_current, s, s_is_prime);
+            }
+            if s_is_prime {
+                if let Some(dump) = s_prime_dump.as_mut() {
+                    dump.write(s)?;
+                }
+            }
+        } else {
+            stats.record_unsampled_prime(p_prev, p_current);
+            for (_, milestone) in &mut milestones {
+                milestone.record_unsampled_prime(p_prev, p_current);
             }
         }
+        if let Some(dump) = prime_dump.as_mut() {
+            dump.write(p_current.0)?;
+        }
+        if let Some(throughput) = throughput.as_mut() {
+            throughput.record_prime(p_current.0, sampled);
+        }
 
         p_prev = p_current;
-        bar.set_position(p_current);
+        bar.set_position(stats.total_primes);
+    }
+    if let Some(watchdog) = watchdog {
+        watchdog.stop();
+    }
+    if let Some(mut throughput) = throughput {
+        throughput.end_segment(p_prev.0, prime_iterator.sieve_time())?;
+        throughput.finish()?;
+    }
+    // The iterator is exhausted (or the shard ended), so every p up to there has been seen.
+    for (exponent, milestone) in milestones {
+        write_milestone(milestone, exponent, &config)?;
+    }
+    stats.analyzed_up_to = max_n.min(shard_end);
+    memory_usage.observe(sample_memory(&prime_iterator, &primality_checker, &stats));
+    stats.finish_bin_stream()?;
+    for dump in [prime_dump, s_prime_dump].into_iter().flatten() {
+        dump.finish()?;
     }
     bar.finish_with_message("Sieving and analysis complete.");
+    let analysis_secs = started.elapsed().as_secs_f64();
+    debug!("Sieving and analysis: {:.2}s", analysis_secs);
 
-    println!("Writing results to disk...");
-    output::write_results(&stats, &config, max_n)?;
-    println!("Done.");
+    let (overflow_count, _) = stats.gap_spectrum.overflow();
+    if overflow_count > 0 {
+        info!(
+            "Note: {} gaps ({:.4}% of pairs, {} distinct sizes) exceeded --spectrum-max-gap {} and were tracked in the slower overflow list.",
+            overflow_count,
+            100.0 * overflow_count as f64 / stats.total_primes.max(1) as f64,
+            stats.gap_spectrum.overflow_gap_count(),
+            stats.gap_spectrum.max_dense_gap()
+        );
+    } else {
+        debug!(
+            "Gap spectrum: every gap within --spectrum-max-gap {}",
+            stats.gap_spectrum.max_dense_gap()
+        );
+    }
 
-    if config.web_report {
-        println!("Generating HTML report...");
+    if config.sample > 1 {
+        let global = stats.global_stats();
+        info!(
+            "Estimated S primes: {} ± {:.0} (1 standard error from sampling)",
+            global.total_primes_s, global.total_primes_s_std_error
+        );
+    }
+
+    info!("Writing results to disk...");
+    output::write_results(&stats, &config)?;
+    debug!(
+        "Writing results: {:.2}s",
+        started.elapsed().as_secs_f64() - analysis_secs
+    );
+    let out_of_range = stats.total_out_of_range();
+    if out_of_range > 0 {
+        eprintln!(
+            "Warning: {} sums S exceeded the primality checker's limit {}; see the out_of_range column of gap_spectrum.csv.",
+            out_of_range, checker_limit
+        );
+    }
+    let cache = primality_checker.cache_stats();
+    debug!(
+        "Primality cache ({} segments): {} local hits, {} store hits, {} misses, {} evictions",
+        cache.cache_segments, cache.local_hits, cache.store_hits, cache.misses, cache.evictions
+    );
+    let elapsed = (!config.deterministic).then(|| started.elapsed().as_secs_f64());
+    if !config.deterministic {
+        memory_usage.peak_rss_bytes = memory::peak_rss_bytes();
+    }
+    info!("{}", memory_usage.summary());
+    output::write_perf(
+        &output::PerfReport {
+            elapsed_secs: elapsed,
+            primes_per_sec: elapsed
+                .map(|secs| (stats.total_primes - first_prime_count) as f64 / secs.max(1e-9)),
+            primality_cache: primality_checker.cache_stats(),
+            memory: memory_usage,
+        },
+        &config.output_dir,
+    )?;
+    output::write_diagnostics(
+        &stats,
+        vec![
+            DiagnosticRecord::new(
+                "segment_boundaries",
+                segment_boundaries,
+                format!(
+                    "spans of p crossed, each prefetching the sums S of the next ({} bytes)",
+                    segment_size_bytes
+                ),
+            ),
+            DiagnosticRecord::new(
+                "sum_cache_misses",
+                cache.misses,
+                "sums S whose sieve segment was not prefetched and was sieved on demand"
+                    .to_string(),
+            ),
+        ],
+        &config,
+    )?;
+    #[cfg(feature = "profile")]
+    if let Some(profiler) = profiler {
+        profiler.finish(&config.output_dir)?;
+        info!(
+            "CPU profile written to {}/{}",
+            config.output_dir,
+            prime_shield_analyzer::profile::FLAMEGRAPH_FILE
+        );
+    }
+    if config.save_state {
+        stats.save(std::path::Path::new(&config.output_dir).join(stats::STATE_FILE))?;
+    }
+    if recovering || config.resume {
+        recovery::clear(&config.output_dir)?;
+    }
+    if let Some(registry) = &config.registry {
+        Registry::record(registry, RunEntry::new(&stats, &config))?;
+    }
+    if let Some(format) = config.plot_spec {
+        let files = plot_spec::write(format, &config.output_dir, &stats.target_gaps)?;
+        info!("Plot specs written: {}", files.join(", "));
+    }
+    info!("Done.");
+
+    #[cfg(feature = "report")]
+    if config.web_report || config.report_pdf {
+        info!("Generating HTML report...");
         report::generate_report(&config, max_n)?;
-        println!("Report generated at {}/index.html", config.output_dir);
+        if config.report_snapshots {
+            report::generate_snapshot(&config, config.max_exponent())?;
+        }
+        info!("Report generated at {}/index.html", config.output_dir);
     }
 
-    Ok(())
+    #[cfg(feature = "report")]
+    if config.report_pdf {
+        info!("Printing report to PDF...");
+        let pdf_path = report::export_pdf(&config)?;
+        info!("PDF report written to {}", pdf_path.display());
+    }
+
+    thresholds::check(&config.fail_if, &config.output_dir)
 }
@@ -0,0 +1,87 @@
+//! Peak resident set size and the sizes of the run's major data structures, reported in
+//! `perf.json` and the terminal summary to help size `--bins` and `--segment-size-kb`.
+//!
+//! Structure sizes count the memory their contents occupy (bits, counters, vector
+//! headers) and leave out allocator overhead, so they are slight underestimates.
+
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Peak sizes of the major data structures, in bytes, plus the process's peak RSS.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct MemoryUsage {
+    /// Highest resident set size of the process, where the platform reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_rss_bytes: Option<u64>,
+    /// The prime iterator's base primes and current segment.
+    pub sieve_segment_bytes: u64,
+    /// Segments of sums S held by the shared store and the checker's local cache.
+    pub sum_segment_bytes: u64,
+    /// Oscillation bins held in memory (streamed bins are freed).
+    pub bins_bytes: u64,
+    /// Dense gap spectrum plus its overflow list.
+    pub spectrum_bytes: u64,
+}
+
+impl MemoryUsage {
+    /// Raises each structure size to `sample`'s where that is larger.
+    pub fn observe(&mut self, sample: MemoryUsage) {
+        self.sieve_segment_bytes = self.sieve_segment_bytes.max(sample.sieve_segment_bytes);
+        self.sum_segment_bytes = self.sum_segment_bytes.max(sample.sum_segment_bytes);
+        self.bins_bytes = self.bins_bytes.max(sample.bins_bytes);
+        self.spectrum_bytes = self.spectrum_bytes.max(sample.spectrum_bytes);
+    }
+
+    /// One-line summary for the terminal.
+    pub fn summary(&self) -> String {
+        let mut line = String::from("Memory:");
+        if let Some(rss) = self.peak_rss_bytes {
+            let _ = write!(line, " peak RSS {},", format_bytes(rss));
+        }
+        let _ = write!(
+            line,
+            " sieve segment {}, sum segments {}, bins {}, spectrum {}",
+            format_bytes(self.sieve_segment_bytes),
+            format_bytes(self.sum_segment_bytes),
+            format_bytes(self.bins_bytes),
+            format_bytes(self.spectrum_bytes)
+        );
+        line
+    }
+}
+
+/// The process's peak resident set size (`VmHWM`), on Linux.
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// `bytes` in binary units, e.g. `12.3 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(128 * 1024), "128.0 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 + 512 * 1024), "3.5 MiB");
+    }
+}
@@ -0,0 +1,410 @@
+//! The `migrate` subcommand: upgrades an output directory written by an older version of the
+//! crate to the current layout, so the early runs of a long study stay usable by `diff`,
+//! `check-oeis`, the report and side-by-side comparisons with new runs.
+//!
+//! A column added since is filled in where its value follows from the row (the ln N baseline
+//! and first-order shield model older runs used, the exact boost of the gap, no sampling)
+//! and left empty where it would take the primes themselves. A missing manifest is inferred
+//! from `oscillation_series.csv`. Migrating a directory twice changes nothing.
+
+use crate::config::{Baseline, Exponent, MigrateConfig};
+use crate::model::{self, ResidueModel};
+use crate::output::{self, RunManifest, QUANTILE_COLUMNS};
+use crate::shielding::{self, ShieldModel};
+use crate::stats::Gap;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// How a column missing from an older output is filled in.
+#[derive(Debug, Clone, Copy)]
+enum Fill {
+    /// Written by every version; an output without it cannot be migrated.
+    Required,
+    /// The same value in every row.
+    Value(&'static str),
+    /// Computed from the row's `gap_size`.
+    FromGap(fn(u64) -> String),
+    /// Unknown without the primes, left empty.
+    Empty,
+}
+
+fn ln_n_baseline(_: u64) -> String {
+    Baseline::LnN.label()
+}
+
+fn first_order_model(_: u64) -> String {
+    ShieldModel::FIRST_ORDER.label()
+}
+
+fn exact_boost(gap: u64) -> String {
+    let boost = ResidueModel::new(model::DEFAULT_PRIME_LIMIT).boost(gap);
+    format!("{:?}", boost)
+}
+
+fn wheel_position(gap: u64, primorial: u64) -> String {
+    (gap % primorial).to_string()
+}
+
+fn over_primorial(gap: u64, primorial: u64) -> String {
+    format!("{:?}", gap as f64 / primorial as f64)
+}
+
+fn max_shield_prime(gap: u64) -> String {
+    shielding::shield_primes(gap)
+        .last()
+        .map(|q| q.to_string())
+        .unwrap_or_default()
+}
+
+/// Columns of `gap_spectrum.csv`, in order.
+const GAP_SPECTRUM_COLUMNS: [(&str, Fill); 38] = [
+    ("gap_size", Fill::Required),
+    ("count", Fill::Required),
+    ("successes", Fill::Required),
+    ("success_rate", Fill::Required),
+    ("success_ci_low", Fill::Required),
+    ("success_ci_high", Fill::Required),
+    ("p_value", Fill::Required),
+    ("expected_rate_heuristic", Fill::Required),
+    ("baseline", Fill::FromGap(ln_n_baseline)),
+    ("expected_count_hl", Fill::Empty),
+    ("shield_score", Fill::Required),
+    ("shield_primes", Fill::Required),
+    ("max_shield_prime", Fill::FromGap(max_shield_prime)),
+    ("theoretical_boost", Fill::Required),
+    ("theoretical_boost_fraction", Fill::Empty),
+    ("shield_model", Fill::FromGap(first_order_model)),
+    ("exact_boost", Fill::FromGap(exact_boost)),
+    // Every sum S was tested before the checker had a limit.
+    ("out_of_range", Fill::Value("0")),
+    ("square_free", Fill::Empty),
+    ("square_free_rate", Fill::Empty),
+    ("expected_square_free_rate", Fill::Empty),
+    ("early_count", Fill::Empty),
+    ("early_successes", Fill::Empty),
+    ("early_rate", Fill::Empty),
+    ("late_count", Fill::Empty),
+    ("late_successes", Fill::Empty),
+    ("late_rate", Fill::Empty),
+    ("rate_change", Fill::Empty),
+    ("rate_change_z", Fill::Empty),
+    ("rate_change_p_value", Fill::Empty),
+    ("weighted_count", Fill::Empty),
+    ("weighted_successes", Fill::Empty),
+    ("weighted_success_rate", Fill::Empty),
+    ("wheel_30", Fill::FromGap(|g| wheel_position(g, 30))),
+    ("wheel_210", Fill::FromGap(|g| wheel_position(g, 210))),
+    ("gap_over_6", Fill::FromGap(|g| over_primorial(g, 6))),
+    ("gap_over_30", Fill::FromGap(|g| over_primorial(g, 30))),
+    ("gap_over_210", Fill::FromGap(|g| over_primorial(g, 210))),
+];
+
+/// Columns of `global_stats.csv`, in order.
+const GLOBAL_STATS_COLUMNS: [(&str, Fill); 11] = [
+    ("total_primes_p", Fill::Required),
+    ("total_primes_s", Fill::Required),
+    ("global_ratio_s_p", Fill::Required),
+    ("spectrum_max_gap", Fill::Empty),
+    ("spectrum_overflow_count", Fill::Empty),
+    ("spectrum_overflow_successes", Fill::Empty),
+    // Runs from before --sample existed analyzed every pair.
+    ("sample_every", Fill::Value("1")),
+    ("total_primes_s_std_error", Fill::Value("0.0")),
+    ("weighted_pairs", Fill::Empty),
+    ("weighted_primes_s", Fill::Empty),
+    ("weighted_ratio_s_p", Fill::Empty),
+];
+
+/// Rewrites every part of the CSV output `file` with the columns of `columns` it lacks,
+/// in their current place; columns `columns` does not know keep their place after them.
+/// Returns the columns added.
+fn migrate_columns(
+    dir: &str,
+    file: &str,
+    columns: &[(&str, Fill)],
+    dry_run: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut added = Vec::new();
+    for part in output::data_files(dir, file)? {
+        let path = Path::new(dir).join(&part);
+        if !path.exists() {
+            continue;
+        }
+        let mut reader = csv::Reader::from_path(&path)?;
+        let headers = reader.headers()?.clone();
+        let position = |name: &str| headers.iter().position(|h| h == name);
+        let missing: Vec<&str> = columns
+            .iter()
+            .filter(|(name, _)| position(name).is_none())
+            .map(|&(name, fill)| match fill {
+                Fill::Required => Err(format!("{}: lacks the column {}", part, name)),
+                _ => Ok(name),
+            })
+            .collect::<Result<_, _>>()?;
+        if missing.is_empty() {
+            continue;
+        }
+        if added.is_empty() {
+            added = missing.iter().map(|name| name.to_string()).collect();
+        }
+        if dry_run {
+            continue;
+        }
+
+        let mut order: Vec<&str> = columns.iter().map(|&(name, _)| name).collect();
+        order.extend(
+            headers
+                .iter()
+                .filter(|h| !columns.iter().any(|c| c.0 == *h)),
+        );
+        let temp = path.with_extension("csv.migrating");
+        let mut wtr = csv::Writer::from_path(&temp)?;
+        wtr.write_record(&order)?;
+        for row in reader.records() {
+            let row = row?;
+            let gap = position("gap_size")
+                .and_then(|i| row.get(i))
+                .and_then(|g| g.parse().ok());
+            let cells = order.iter().map(|&name| {
+                if let Some(cell) = position(name).and_then(|i| row.get(i)) {
+                    return cell.to_string();
+                }
+                match columns.iter().find(|c| c.0 == name).map(|c| c.1) {
+                    Some(Fill::Value(value)) => value.to_string(),
+                    Some(Fill::FromGap(value)) => gap.map(value).unwrap_or_default(),
+                    _ => String::new(),
+                }
+            });
+            wtr.write_record(cells)?;
+        }
+        wtr.flush()?;
+        drop(wtr);
+        fs::rename(temp, path)?;
+    }
+    Ok(added)
+}
+
+/// The manifest of a run that did not write one: its bins and target gaps from
+/// `oscillation_series.csv`. Such runs always completed.
+fn infer_manifest(dir: &str) -> Result<RunManifest, Box<dyn Error>> {
+    let table = output::ColumnTable::read(dir, "oscillation_series.csv")
+        .map_err(|e| format!("cannot infer a manifest: {}", e))?;
+    table.require(&["bin_start", "bin_end"])?;
+    let mut max_n = 0;
+    let mut bins = 0;
+    for row in table.rows() {
+        max_n = max_n.max(row.get::<u64>("bin_end")?);
+        bins += 1;
+    }
+    if bins == 0 {
+        return Err("cannot infer a manifest: oscillation_series.csv has no bins".into());
+    }
+    let target_gaps = headers(dir, "oscillation_series.csv")?
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|h| h.strip_prefix("gap_")?.strip_suffix("_rate")?.parse().ok())
+        .map(Gap)
+        .collect();
+
+    // Round to the precision exponents are given with, so 10^E reads back as E.
+    let exponent = ((max_n as f64).log10() * 100.0).round() / 100.0;
+    Ok(RunManifest {
+        crate_version: "unknown".to_string(),
+        max_exponent: Exponent(exponent),
+        max_n,
+        bins,
+        target_gaps,
+        analyzed_up_to: max_n,
+        complete: true,
+        deterministic: false,
+        label: None,
+        meta: BTreeMap::new(),
+        seed: None,
+        sample_seed: None,
+        attribute_by: Default::default(),
+        bin_domain: Default::default(),
+        migrated_by: Some(env!("CARGO_PKG_VERSION").to_string()),
+        shield_model: None,
+        split_at: None,
+        shard: None,
+    })
+}
+
+/// Header of the first part of the CSV output `file`, if it exists.
+fn headers(dir: &str, file: &str) -> Result<Option<csv::StringRecord>, Box<dyn Error>> {
+    let Some(part) = output::data_files(dir, file)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let path = Path::new(dir).join(part);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(csv::Reader::from_path(path)?.headers()?.clone()))
+}
+
+pub fn run(config: &MigrateConfig) -> Result<(), Box<dyn Error>> {
+    let dir = &config.dir;
+    if let Some(schema) = output::read_schema(dir)? {
+        if schema.schema_version > output::CSV_SCHEMA_VERSION {
+            return Err(format!(
+                "{} was written with CSV schema version {}, newer than version {} this build knows",
+                dir,
+                schema.schema_version,
+                output::CSV_SCHEMA_VERSION
+            )
+            .into());
+        }
+    }
+
+    // The per-gap rate columns differ from run to run and are kept as they are.
+    let osc_headers = headers(dir, "oscillation_series.csv")?.unwrap_or_default();
+    let mut osc_columns: Vec<(&str, Fill)> = osc_headers
+        .iter()
+        .filter(|h| !QUANTILE_COLUMNS.contains(h))
+        .map(|h| (h, Fill::Required))
+        .collect();
+    osc_columns.extend(QUANTILE_COLUMNS.iter().map(|&c| (c, Fill::Empty)));
+
+    let mut changed = false;
+    for (file, columns) in [
+        ("gap_spectrum.csv", &GAP_SPECTRUM_COLUMNS[..]),
+        ("global_stats.csv", &GLOBAL_STATS_COLUMNS[..]),
+        ("oscillation_series.csv", &osc_columns[..]),
+    ] {
+        let added = migrate_columns(dir, file, columns, config.dry_run)?;
+        if !added.is_empty() {
+            println!("{}: added {}", file, added.join(", "));
+            changed = true;
+        }
+    }
+
+    if output::read_manifest(dir)?.is_none() {
+        let manifest = infer_manifest(dir)?;
+        println!(
+            "{}: inferred max N {} and {} bins from oscillation_series.csv",
+            output::MANIFEST_FILE,
+            manifest.max_n,
+            manifest.bins
+        );
+        if !config.dry_run {
+            let path = Path::new(dir).join(output::MANIFEST_FILE);
+            fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+        }
+        changed = true;
+    }
+
+    if !config.dry_run {
+        output::write_schema(dir)?;
+    }
+    if !changed {
+        println!("{} already has the current layout.", dir);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(fixture: &str) -> String {
+        fixture.lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_migrates_a_legacy_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        let write =
+            |file: &str, contents: &str| fs::write(dir.path().join(file), contents).unwrap();
+        write(
+            "gap_spectrum.csv",
+            "gap_size,count,successes,success_rate,success_ci_low,success_ci_high,p_value,\
+             expected_rate_heuristic,shield_score,shield_primes,theoretical_boost\n\
+             2,10,3,0.3,0.1,0.6,0.5,0.2,0,,1.0\n\
+             4,10,4,0.4,0.1,0.7,0.5,0.2,1,3,2.0\n",
+        );
+        write(
+            "global_stats.csv",
+            "total_primes_p,total_primes_s,global_ratio_s_p\n100,20,0.2\n",
+        );
+        write(
+            "oscillation_series.csv",
+            "bin_start,bin_end,prime_count_p,prime_count_s,ratio_s_p,gap_2_rate,gap_4_rate\n\
+             0,499,50,10,0.2,0.3,0.4\n\
+             500,1000,50,10,0.2,0.3,0.4\n",
+        );
+
+        run(&MigrateConfig {
+            dir: out.to_string(),
+            dry_run: false,
+        })
+        .unwrap();
+        let read = |file: &str| fs::read_to_string(dir.path().join(file)).unwrap();
+        for (file, fixture) in [
+            (
+                "gap_spectrum.csv",
+                include_str!("../fixtures/self_test/gap_spectrum.csv"),
+            ),
+            (
+                "global_stats.csv",
+                include_str!("../fixtures/self_test/global_stats.csv"),
+            ),
+        ] {
+            assert_eq!(header(&read(file)), header(fixture), "{}", file);
+        }
+        let gaps = read("gap_spectrum.csv");
+        let row: Vec<&str> = gaps.lines().nth(2).unwrap().split(',').collect();
+        assert_eq!(
+            &row[row.len() - 5..],
+            [
+                "4",
+                "4",
+                "0.6666666666666666",
+                "0.13333333333333333",
+                "0.01904761904761905"
+            ]
+        );
+        assert_eq!(row[8], "ln-n");
+        assert_eq!(row[12], "5");
+        assert_eq!(row[15], "first_order");
+        assert_eq!(
+            row[16].parse::<f64>().unwrap(),
+            ResidueModel::new(model::DEFAULT_PRIME_LIMIT).boost(4)
+        );
+        assert!(header(&read("oscillation_series.csv"))
+            .ends_with(",gap_4_rate,gap_p50,gap_p90,gap_p99,merit_p50,merit_p90,merit_p99"));
+
+        let manifest = output::read_manifest(out).unwrap().unwrap();
+        assert_eq!((manifest.max_n, manifest.bins), (1000, 2));
+        assert_eq!(manifest.max_exponent.0, 3.0);
+        assert_eq!(manifest.target_gaps, [Gap(2), Gap(4)]);
+        assert!(output::read_schema(out).unwrap().is_some());
+
+        // A second pass has nothing left to do.
+        let before: Vec<String> = [
+            "gap_spectrum.csv",
+            "global_stats.csv",
+            "oscillation_series.csv",
+        ]
+        .iter()
+        .map(|f| read(f))
+        .collect();
+        run(&MigrateConfig {
+            dir: out.to_string(),
+            dry_run: false,
+        })
+        .unwrap();
+        for (file, contents) in [
+            "gap_spectrum.csv",
+            "global_stats.csv",
+            "oscillation_series.csv",
+        ]
+        .iter()
+        .zip(before)
+        {
+            assert_eq!(read(file), contents);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! The `check-oeis` subcommand: an end-to-end correctness gate for the sieve that compares
+//! a run's first occurrences of each gap, its maximal gaps and π(10^k) against bundled
+//! OEIS reference data.
+//!
+//! The gap tables cover every prime pair up to [`REFERENCE_LIMIT`]; comparisons stop at
+//! the smaller of that limit and how far the run got.
+
+use crate::config::CheckOeisConfig;
+use crate::output::{self, read_rows};
+use crate::records::{FirstOccurrenceRecord, PrimeCountRecord};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Prime pairs `(p, p + g)` with `p + g` up to this bound are covered by the gap tables.
+pub const REFERENCE_LIMIT: u64 = 10_000_000_000;
+
+/// A000230: `(g, p)` where p is the smallest prime followed by a gap of exactly g
+/// (with g = 1 for the pair (2, 3)), for every gap occurring below [`REFERENCE_LIMIT`].
+const A000230: &[(u64, u64)] = &[
+    (1, 2),
+    (2, 3),
+    (4, 7),
+    (6, 23),
+    (8, 89),
+    (10, 139),
+    (12, 199),
+    (14, 113),
+    (16, 1831),
+    (18, 523),
+    (20, 887),
+    (22, 1129),
+    (24, 1669),
+    (26, 2477),
+    (28, 2971),
+    (30, 4297),
+    (32, 5591),
+    (34, 1327),
+    (36, 9551),
+    (38, 30593),
+    (40, 19333),
+    (42, 16141),
+    (44, 15683),
+    (46, 81463),
+    (48, 28229),
+    (50, 31907),
+    (52, 19609),
+    (54, 35617),
+    (56, 82073),
+    (58, 44293),
+    (60, 43331),
+    (62, 34061),
+    (64, 89689),
+    (66, 162143),
+    (68, 134513),
+    (70, 173359),
+    (72, 31397),
+    (74, 404597),
+    (76, 212701),
+    (78, 188029),
+    (80, 542603),
+    (82, 265621),
+    (84, 461717),
+    (86, 155921),
+    (88, 544279),
+    (90, 404851),
+    (92, 927869),
+    (94, 1100977),
+    (96, 360653),
+    (98, 604073),
+    (100, 396733),
+    (102, 1444309),
+    (104, 1388483),
+    (106, 1098847),
+    (108, 2238823),
+    (110, 1468277),
+    (112, 370261),
+    (114, 492113),
+    (116, 5845193),
+    (118, 1349533),
+    (120, 1895359),
+    (122, 3117299),
+    (124, 6752623),
+    (126, 1671781),
+    (128, 3851459),
+    (130, 5518687),
+    (132, 1357201),
+    (134, 6958667),
+    (136, 6371401),
+    (138, 3826019),
+    (140, 7621259),
+    (142, 10343761),
+    (144, 11981443),
+    (146, 6034247),
+    (148, 2010733),
+    (150, 13626257),
+    (152, 8421251),
+    (154, 4652353),
+    (156, 17983717),
+    (158, 49269581),
+    (160, 33803689),
+    (162, 39175217),
+    (164, 20285099),
+    (166, 83751121),
+    (168, 37305713),
+    (170, 27915737),
+    (172, 38394127),
+    (174, 52721113),
+    (176, 38089277),
+    (178, 39389989),
+    (180, 17051707),
+    (182, 36271601),
+    (184, 79167733),
+    (186, 147684137),
+    (188, 134065829),
+    (190, 142414669),
+    (192, 123454691),
+    (194, 166726367),
+    (196, 70396393),
+    (198, 46006769),
+    (200, 378043979),
+    (202, 107534587),
+    (204, 112098817),
+    (206, 232423823),
+    (208, 192983851),
+    (210, 20831323),
+    (212, 215949407),
+    (214, 253878403),
+    (216, 202551667),
+    (218, 327966101),
+    (220, 47326693),
+    (222, 122164747),
+    (224, 409866323),
+    (226, 519653371),
+    (228, 895858039),
+    (230, 607010093),
+    (232, 525436489),
+    (234, 189695659),
+    (236, 216668603),
+    (238, 673919143),
+    (240, 391995431),
+    (242, 367876529),
+    (244, 693103639),
+    (246, 555142061),
+    (248, 191912783),
+    (250, 387096133),
+    (252, 630045137),
+    (254, 1202442089),
+    (256, 1872851947),
+    (258, 1316355323),
+    (260, 944192807),
+    (262, 1649328997),
+    (264, 2357881993),
+    (266, 1438779821),
+    (268, 1579306789),
+    (270, 1391048047),
+    (272, 1851255191),
+    (274, 1282463269),
+    (276, 649580171),
+    (278, 4260928601),
+    (280, 1855047163),
+    (282, 436273009),
+    (284, 1667186459),
+    (286, 2842739311),
+    (288, 1294268491),
+    (290, 1948819133),
+    (292, 1453168141),
+    (294, 5692630189),
+    (296, 5260030511),
+    (298, 8650524583),
+    (300, 4758958741),
+    (302, 6675573497),
+    (304, 2433630109),
+    (306, 3917587237),
+    (308, 5490459101),
+    (310, 4024713661),
+    (312, 6570018347),
+    (314, 8948418749),
+    (318, 4372999721),
+    (320, 2300942549),
+    (322, 7961074441),
+    (326, 5837935373),
+    (330, 6291356009),
+    (332, 5893180121),
+    (336, 3842610773),
+    (340, 8605261447),
+    (354, 4302407359),
+];
+
+/// A005250 with A002386: the maximal gaps g and the primes p that start them, below
+/// [`REFERENCE_LIMIT`].
+const A005250: &[(u64, u64)] = &[
+    (1, 2),
+    (2, 3),
+    (4, 7),
+    (6, 23),
+    (8, 89),
+    (14, 113),
+    (18, 523),
+    (20, 887),
+    (22, 1129),
+    (34, 1327),
+    (36, 9551),
+    (44, 15683),
+    (52, 19609),
+    (72, 31397),
+    (86, 155921),
+    (96, 360653),
+    (112, 370261),
+    (114, 492113),
+    (118, 1349533),
+    (132, 1357201),
+    (148, 2010733),
+    (154, 4652353),
+    (180, 17051707),
+    (210, 20831323),
+    (220, 47326693),
+    (222, 122164747),
+    (234, 189695659),
+    (248, 191912783),
+    (250, 387096133),
+    (282, 436273009),
+    (288, 1294268491),
+    (292, 1453168141),
+    (320, 2300942549),
+    (336, 3842610773),
+    (354, 4302407359),
+];
+
+/// A006880: π(10^k) for k = 1..=18.
+const PRIME_COUNTS: &[(u64, u64)] = &[
+    (10, 4),
+    (100, 25),
+    (1_000, 168),
+    (10_000, 1_229),
+    (100_000, 9_592),
+    (1_000_000, 78_498),
+    (10_000_000, 664_579),
+    (100_000_000, 5_761_455),
+    (1_000_000_000, 50_847_534),
+    (10_000_000_000, 455_052_511),
+    (100_000_000_000, 4_118_054_813),
+    (1_000_000_000_000, 37_607_912_018),
+    (10_000_000_000_000, 346_065_536_839),
+    (100_000_000_000_000, 3_204_941_750_802),
+    (1_000_000_000_000_000, 29_844_570_422_669),
+    (10_000_000_000_000_000, 279_238_341_033_925),
+    (100_000_000_000_000_000, 2_623_557_157_654_233),
+    (1_000_000_000_000_000_000, 24_739_954_287_740_860),
+];
+
+/// One compared value; `None` means the value is missing on that side.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub table: &'static str,
+    pub key: u64,
+    pub expected: Option<u64>,
+    pub observed: Option<u64>,
+}
+
+/// Number of values compared per table, and every mismatch found.
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub checked: Vec<(&'static str, usize)>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl CheckReport {
+    /// Compares `observed` against `expected` over the union of their keys.
+    fn compare(
+        &mut self,
+        table: &'static str,
+        expected: BTreeMap<u64, u64>,
+        mut observed: BTreeMap<u64, u64>,
+    ) {
+        let mut checked = 0;
+        for (key, value) in expected {
+            checked += 1;
+            let seen = observed.remove(&key);
+            if seen != Some(value) {
+                self.mismatches.push(Mismatch {
+                    table,
+                    key,
+                    expected: Some(value),
+                    observed: seen,
+                });
+            }
+        }
+        for (key, value) in observed {
+            checked += 1;
+            self.mismatches.push(Mismatch {
+                table,
+                key,
+                expected: None,
+                observed: Some(value),
+            });
+        }
+        self.checked.push((table, checked));
+    }
+}
+
+/// Checks the output directory of a run against the reference tables.
+pub fn check(dir: &str) -> Result<CheckReport, Box<dyn Error>> {
+    let manifest = output::read_manifest(dir)?.ok_or_else(|| {
+        format!(
+            "{}: no manifest.json; rerun the analysis with this version",
+            dir
+        )
+    })?;
+    let firsts: Vec<FirstOccurrenceRecord> = read_rows(dir, "first_occurrences.csv")?;
+    let counts: Vec<PrimeCountRecord> = read_rows(dir, "prime_counts.csv")?;
+
+    // Pairs are only final once the run has passed their upper prime.
+    let limit = manifest.analyzed_up_to.min(REFERENCE_LIMIT);
+    let within = |&(g, p): &(u64, u64)| p + g <= limit;
+    let reference = |table: &[(u64, u64)]| table.iter().copied().filter(within).collect();
+    let run = |maximal_only: bool| {
+        firsts
+            .iter()
+            .filter(|r| r.maximal || !maximal_only)
+            .map(|r| (r.gap_size.0, r.first_p))
+            .filter(within)
+            .collect()
+    };
+
+    let mut report = CheckReport::default();
+    report.compare("A000230 first occurrence", reference(A000230), run(false));
+    report.compare("A005250 maximal gap", reference(A005250), run(true));
+    report.compare(
+        "A006880 prime count",
+        PRIME_COUNTS
+            .iter()
+            .copied()
+            .filter(|&(n, _)| n <= manifest.analyzed_up_to)
+            .collect(),
+        counts.iter().map(|r| (r.n, r.prime_count)).collect(),
+    );
+    Ok(report)
+}
+
+pub fn run(config: &CheckOeisConfig) -> Result<(), Box<dyn Error>> {
+    let report = check(&config.dir)?;
+    for (table, checked) in &report.checked {
+        let failed = report
+            .mismatches
+            .iter()
+            .filter(|m| m.table == *table)
+            .count();
+        println!(
+            "{:<26} {:>6} checked  {}",
+            table,
+            checked,
+            if failed == 0 {
+                "OK".to_string()
+            } else {
+                format!("{} MISMATCHED", failed)
+            }
+        );
+    }
+
+    if report.mismatches.is_empty() {
+        return Ok(());
+    }
+    let show = |v: Option<u64>| v.map_or("missing".to_string(), |v| v.to_string());
+    println!();
+    for m in &report.mismatches {
+        println!(
+            "{} {}: expected {}, observed {}",
+            m.table,
+            m.key,
+            show(m.expected),
+            show(m.observed)
+        );
+    }
+    Err(format!(
+        "{} values differ from the OEIS reference tables",
+        report.mismatches.len()
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maximal_gaps_agree_with_first_occurrences() {
+        // A gap is maximal exactly when no larger gap occurs before it.
+        let maximal: Vec<(u64, u64)> = A000230
+            .iter()
+            .copied()
+            .filter(|&(g, p)| A000230.iter().all(|&(g2, p2)| g2 <= g || p2 > p))
+            .collect();
+        assert_eq!(maximal, A005250);
+    }
+}
@@ -1,156 +1,782 @@
-use crate::config::Config;
-use crate::stats::Statistics;
+use crate::config::{AttributeBy, BinDomain, Config, Exponent, Shard};
+use crate::memory::MemoryUsage;
+use crate::model::ResidueModel;
+use crate::records::{
+    AlmostPrimeRecord, BinRecord, ConditionalRateRecord, DiagnosticRecord, FirstOccurrenceRecord,
+    GapBinRecord, GapCdfRecord, GapFamilyRecord, GapSpectrumRecord, GlobalStats, NeighborGapRecord,
+    PrimeCountRecord, SpacingRecord, StreakRecord, TailFitRecord,
+};
+use crate::sieve::CacheStats;
+use crate::sinks::Sinks;
+use crate::stats::{BinStats, Gap, SeriesCorrelations, Statistics};
+use crate::table::{columns_of, Cell, Table};
 use csv::Writer;
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 
-pub fn write_results(
+pub fn write_results(stats: &Statistics, config: &Config) -> Result<(), Box<dyn Error>> {
+    write_results_in(stats, config, &config.output_dir, true)?;
+    // Left behind if the run died while writing a snapshot.
+    let staging = Path::new(&config.output_dir).join(SNAPSHOT_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(staging)?;
+    }
+    Ok(())
+}
+
+/// Writes every output to `dir`, the tables to each sink of `config`; `stream` says whether
+/// `--sink stream` takes part.
+fn write_results_in(
     stats: &Statistics,
     config: &Config,
-    max_n: u64,
+    dir: &str,
+    stream: bool,
 ) -> Result<(), Box<dyn Error>> {
-    fs::create_dir_all(&config.output_dir)?;
+    fs::create_dir_all(dir)?;
 
-    write_global_stats(stats, config)?;
-    write_gap_spectrum(stats, config, max_n)?;
-    write_oscillation_series(stats, config)?;
+    let mut sinks = Sinks::open(config, dir, stats.max_n(), stream)?;
+    write_tables(stats, config, &mut sinks)?;
+    write_oscillation_series(stats, config, dir)?;
+    write_schema(dir)?;
+    write_manifest(stats, config, dir)?;
 
     Ok(())
 }
 
-#[derive(Serialize)]
-struct GlobalStatsRecord {
-    total_primes_p: u64,
-    total_primes_s: u64,
-    global_ratio_s_p: f64,
+/// Directory of the outputs of a smaller `--max-exponent` inside `output_dir`.
+pub fn milestone_dir(output_dir: &str, exponent: Exponent) -> String {
+    Path::new(output_dir)
+        .join(format!("e{}", exponent))
+        .to_string_lossy()
+        .into_owned()
 }
 
-fn write_global_stats(stats: &Statistics, config: &Config) -> Result<(), Box<dyn Error>> {
-    let path = Path::new(&config.output_dir).join("global_stats.csv");
-    let mut wtr = Writer::from_path(path)?;
+/// Staging directory of [`write_snapshot`], inside the output directory.
+pub const SNAPSHOT_DIR: &str = ".snapshot";
 
-    let ratio = if stats.total_primes > 0 {
-        stats.total_s_primes as f64 / stats.total_primes as f64
-    } else {
-        0.0
-    };
+/// Outputs that may be split into parts with an index.
+pub const PARTITIONED_FILES: [&str; 3] =
+    ["gap_spectrum.csv", "oscillation_series.csv", "gap_bins.csv"];
 
-    let record = GlobalStatsRecord {
-        total_primes_p: stats.total_primes,
-        total_primes_s: stats.total_s_primes,
-        global_ratio_s_p: ratio,
-    };
+/// Writes the results so far during a run (`--flush-every`). Every file is written to a
+/// staging directory first and then renamed over its counterpart, the manifest last, so
+/// the output directory only holds complete files and the manifest never claims more than
+/// they contain. A streamed oscillation series is flushed in place instead.
+pub fn write_snapshot(stats: &mut Statistics, config: &Config) -> Result<(), Box<dyn Error>> {
+    stats.flush_bin_stream()?;
+    let dir = Path::new(&config.output_dir);
+    let staging = dir.join(SNAPSHOT_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    write_results_in(stats, config, &staging.to_string_lossy(), false)?;
 
-    wtr.serialize(record)?;
+    // An output that no longer needs its partition index must not keep a stale one.
+    for file in PARTITIONED_FILES {
+        let index = partition_index_path(&staging, file);
+        if !index.exists() {
+            let _ = fs::remove_file(partition_index_path(dir, file));
+        }
+    }
+    let mut files = fs::read_dir(&staging)?
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.sort_by_key(|name| name == MANIFEST_FILE);
+    for name in files {
+        fs::rename(staging.join(&name), dir.join(&name))?;
+    }
+    fs::remove_dir(staging)?;
+    Ok(())
+}
+
+/// Directory of the cumulative snapshots of `--snapshot-every`, inside the output directory.
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Lists the snapshots in [`SNAPSHOTS_DIR`], one `n, prime_count, dir` row each.
+pub const SNAPSHOTS_INDEX: &str = "index.csv";
+
+/// One row of the snapshot index.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotRecord {
+    /// Every prime up to `n` is counted in the snapshot.
+    pub n: u64,
+    pub prime_count: u64,
+    /// Directory of the snapshot, relative to [`SNAPSHOTS_DIR`].
+    pub dir: String,
+}
+
+/// Writes the complete outputs of the statistics so far, which cover every prime up to
+/// `up_to`, to `snapshots/n_<up_to>/` (`--snapshot-every`) and lists them in the index.
+pub fn write_cumulative_snapshot(
+    stats: &mut Statistics,
+    config: &Config,
+    up_to: u64,
+) -> Result<(), Box<dyn Error>> {
+    let snapshots = Path::new(&config.output_dir).join(SNAPSHOTS_DIR);
+    let name = format!("n_{}", up_to);
+    let analyzed_up_to = std::mem::replace(&mut stats.analyzed_up_to, up_to);
+    let written = write_results_in(
+        stats,
+        config,
+        &snapshots.join(&name).to_string_lossy(),
+        false,
+    );
+    stats.analyzed_up_to = analyzed_up_to;
+    written?;
+
+    let index = snapshots.join(SNAPSHOTS_INDEX);
+    let new_index = !index.exists();
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(index)?;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(new_index)
+        .from_writer(file);
+    wtr.serialize(SnapshotRecord {
+        n: up_to,
+        prime_count: stats.total_primes,
+        dir: name,
+    })?;
     wtr.flush()?;
     Ok(())
 }
 
-#[derive(Debug)]
-struct ShieldingInfo {
-    shield_score: u32,
-    shield_primes: String,
-    theoretical_boost: f64,
+/// Describes the run and how far it got, so readers of the output directory
+/// (e.g. the report) can tell complete results from partial ones.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RunManifest {
+    pub crate_version: String,
+    pub max_exponent: Exponent,
+    pub max_n: u64,
+    pub bins: usize,
+    pub target_gaps: Vec<Gap>,
+    pub analyzed_up_to: u64,
+    pub complete: bool,
+    /// Written with `--deterministic`; manifests from before the flag lack it.
+    #[serde(default)]
+    pub deterministic: bool,
+    /// `--label` and `--meta` of the run.
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+    /// The global `--seed` and the seed the sampled pairs were drawn with, if any.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub sample_seed: Option<u64>,
+    /// `--attribute-by`; manifests from before the flag attributed pairs by p_curr.
+    #[serde(default)]
+    pub attribute_by: AttributeBy,
+    /// `--bin-domain`; manifests from before the flag binned by p.
+    #[serde(default)]
+    pub bin_domain: BinDomain,
+    /// Version of the crate that inferred this manifest with `migrate`, if it did.
+    #[serde(default)]
+    pub migrated_by: Option<String>,
+    /// Label of the shield model of `theoretical_boost`, as in `gap_spectrum.csv`;
+    /// manifests from before it was recorded lack it.
+    #[serde(default)]
+    pub shield_model: Option<String>,
+    /// `--split-at` of the early and late columns of `gap_spectrum.csv`; manifests from
+    /// before the split lack it.
+    #[serde(default)]
+    pub split_at: Option<u64>,
+    /// The `--shard` (or `--slurm-array` task) of a run covering one slice of p.
+    #[serde(default)]
+    pub shard: Option<Shard>,
 }
 
-// Pre-compute primes up to 100 for the shielding calculation.
-const SMALL_PRIMES: &[u32] = &[
-    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
-];
+pub const MANIFEST_FILE: &str = "manifest.json";
 
-fn calculate_shielding_info(g: u64) -> ShieldingInfo {
-    let mut shield_score = 0;
-    let mut shield_primes_vec = Vec::new();
-    let mut theoretical_boost = 1.0;
+fn write_manifest(stats: &Statistics, config: &Config, dir: &str) -> Result<(), Box<dyn Error>> {
+    let manifest = RunManifest {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        max_exponent: config.max_exponent(),
+        max_n: stats.max_n(),
+        bins: stats.num_bins(),
+        target_gaps: stats.target_gaps.clone(),
+        analyzed_up_to: stats.analyzed_up_to,
+        complete: stats.is_complete(),
+        deterministic: config.deterministic,
+        label: config.label.clone(),
+        meta: config.meta.iter().cloned().collect(),
+        seed: config.seed,
+        sample_seed: (config.sample > 1).then_some(config.sample_seed).flatten(),
+        attribute_by: stats.attribute_by,
+        bin_domain: stats.bin_domain,
+        migrated_by: None,
+        shield_model: Some(config.shield_model().label()),
+        split_at: Some(stats.split_at),
+        shard: stats.shard,
+    };
+    let path = Path::new(dir).join(MANIFEST_FILE);
+    fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+    Ok(())
+}
+
+/// Version of the CSV layouts, recorded in [`SCHEMA_FILE`]. Readers look columns up by
+/// name, so adding a column keeps the version; renaming, removing or redefining one bumps it.
+pub const CSV_SCHEMA_VERSION: u32 = 1;
+
+/// Sidecar with the CSV schema version and the header of every CSV output in a directory.
+pub const SCHEMA_FILE: &str = "schema.json";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SchemaSidecar {
+    pub schema_version: u32,
+    /// Columns of each CSV output, by the name of the unsplit file.
+    pub files: BTreeMap<String, Vec<String>>,
+}
 
-    // The Mod 3 Rule
-    if g % 3 == 1 {
-        shield_score += 1;
-        shield_primes_vec.push(3);
-        theoretical_boost *= 3.0 / 2.0;
+/// Name of the CSV output a partition `<stem>_part_NNN.csv` belongs to.
+fn unsplit_name(file: &str) -> String {
+    let stem = file.trim_end_matches(".csv");
+    match stem.rsplit_once("_part_") {
+        Some((base, n)) if n.bytes().all(|b| b.is_ascii_digit()) => format!("{}.csv", base),
+        _ => file.to_string(),
     }
+}
 
-    // The General Rule (q >= 5)
-    for &q in SMALL_PRIMES.iter().skip(1) {
-        // Skip 3 as it's already handled
-        let q_u64 = q as u64;
-        if g % q_u64 == q_u64 - 1 {
-            shield_score += 1;
-            shield_primes_vec.push(q);
-            theoretical_boost *= q_u64 as f64 / (q_u64 - 1) as f64;
+/// Writes [`SCHEMA_FILE`] from the headers of the CSV outputs in `dir`.
+pub fn write_schema(dir: &str) -> Result<(), Box<dyn Error>> {
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if !name.ends_with(".csv") {
+            continue;
         }
+        let mut reader = csv::Reader::from_path(Path::new(dir).join(&name))?;
+        let headers = reader.headers()?.iter().map(str::to_string).collect();
+        files.entry(unsplit_name(&name)).or_insert(headers);
     }
+    let schema = SchemaSidecar {
+        schema_version: CSV_SCHEMA_VERSION,
+        files,
+    };
+    let path = Path::new(dir).join(SCHEMA_FILE);
+    fs::write(path, serde_json::to_string_pretty(&schema)?)?;
+    Ok(())
+}
 
-    let shield_primes = shield_primes_vec
-        .iter()
-        .map(|p| p.to_string())
-        .collect::<Vec<String>>()
-        .join(",");
+/// Reads the schema sidecar of an output directory, if the run wrote one.
+pub fn read_schema(output_dir: &str) -> Result<Option<SchemaSidecar>, Box<dyn Error>> {
+    let path = Path::new(output_dir).join(SCHEMA_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
 
-    ShieldingInfo {
-        shield_score,
-        shield_primes,
-        theoretical_boost,
+/// Reads the manifest of an output directory, if the run wrote one.
+pub fn read_manifest(output_dir: &str) -> Result<Option<RunManifest>, Box<dyn Error>> {
+    let path = Path::new(output_dir).join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
     }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Throughput and cache behaviour of the analysis loop, written to `perf.json`.
+#[derive(Serialize, Debug)]
+pub struct PerfReport {
+    /// Left out under `--deterministic`, like the throughput.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primes_per_sec: Option<f64>,
+    pub primality_cache: CacheStats,
+    pub memory: MemoryUsage,
 }
 
-#[derive(Serialize)]
-struct GapSpectrumRecord {
-    gap_size: u64,
-    count: u64,
-    successes: u64,
-    success_rate: f64,
-    expected_rate_heuristic: f64,
-    shield_score: u32,
-    shield_primes: String,
-    theoretical_boost: f64,
+pub const PERF_FILE: &str = "perf.json";
+
+pub fn write_perf(report: &PerfReport, output_dir: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(output_dir).join(PERF_FILE);
+    fs::write(path, serde_json::to_string_pretty(report)?)?;
+    Ok(())
 }
 
-fn write_gap_spectrum(
+/// Writes the result tables, the optional ones only under their flags.
+fn write_tables(
     stats: &Statistics,
     config: &Config,
-    max_n: u64,
+    sinks: &mut Sinks,
 ) -> Result<(), Box<dyn Error>> {
-    let path = Path::new(&config.output_dir).join("gap_spectrum.csv");
-    let mut wtr = Writer::from_path(path)?;
+    let residues = ResidueModel::new(config.residue_prime_limit);
+    let model = config.shield_model();
+    sinks.write_records("global_stats.csv", &[stats.global_stats()])?;
+    let gap_records: Vec<_> = stats
+        .iter_gap_records(model, &residues, config.baseline)
+        .collect();
+    sinks.write_records("gap_spectrum.csv", &gap_records)?;
+    let conditional: Vec<_> = stats.iter_conditional_records().collect();
+    sinks.write_records("conditional_rates.csv", &conditional)?;
+    let neighbors: Vec<_> = stats.iter_neighbor_gap_records().collect();
+    sinks.write_records(NEIGHBOR_GAPS_FILE, &neighbors)?;
+    sinks.write_records("first_occurrences.csv", &stats.first_occurrence_records())?;
+    let prime_counts: Vec<_> = stats.iter_prime_count_records().collect();
+    sinks.write_records("prime_counts.csv", &prime_counts)?;
+    sinks.write_records("streaks.csv", &stats.streak_records())?;
+    sinks.write_records("success_spacing.csv", &stats.spacing_records())?;
+    sinks.write_records("tail_fit.csv", &stats.tail_fit_records())?;
+    sinks.write_records("gap_cdf.csv", &stats.gap_cdf_records())?;
+    if config.almost_primes {
+        sinks.write_records(
+            "almost_prime_spectrum.csv",
+            &stats.almost_prime_records(model),
+        )?;
+    }
+    if stats.large_gap_threshold.is_some() {
+        sinks.write_records("large_gap_events.csv", &stats.large_gap_events)?;
+    }
+    if !config.gap_families.is_empty() {
+        let families =
+            stats.gap_family_records(&config.gap_families, model, &residues, config.baseline);
+        sinks.write_records(GAP_FAMILIES_FILE, &families)?;
+    }
+    Ok(())
+}
 
-    let expected_rate = 1.0 / (max_n as f64).ln();
+/// Writes `diagnostics.csv` (and its table to the other sinks): the statistics' edge
+/// events followed by `run_events`, those only the analysis loop sees.
+pub fn write_diagnostics(
+    stats: &Statistics,
+    run_events: Vec<DiagnosticRecord>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut records = stats.diagnostic_records();
+    records.extend(run_events);
+    Sinks::open(config, &config.output_dir, stats.max_n(), true)?
+        .write_records("diagnostics.csv", &records)
+}
+
+/// Columns of the CSV output `file` that `--columns` and `--slim` apply to, in order, or
+/// `None` for the outputs they leave alone.
+fn selectable_columns(file: &str, target_gaps: &[Gap]) -> Option<Vec<String>> {
+    let columns = match file {
+        "global_stats.csv" => columns_of::<GlobalStats>(),
+        "gap_spectrum.csv" => columns_of::<GapSpectrumRecord>(),
+        "conditional_rates.csv" => columns_of::<ConditionalRateRecord>(),
+        NEIGHBOR_GAPS_FILE => columns_of::<NeighborGapRecord>(),
+        "first_occurrences.csv" => columns_of::<FirstOccurrenceRecord>(),
+        "prime_counts.csv" => columns_of::<PrimeCountRecord>(),
+        "streaks.csv" => columns_of::<StreakRecord>(),
+        "success_spacing.csv" => columns_of::<SpacingRecord>(),
+        "tail_fit.csv" => columns_of::<TailFitRecord>(),
+        "gap_cdf.csv" => columns_of::<GapCdfRecord>(),
+        "almost_prime_spectrum.csv" => columns_of::<AlmostPrimeRecord>(),
+        GAP_FAMILIES_FILE => columns_of::<GapFamilyRecord>(),
+        "oscillation_series.csv" => return Some(oscillation_columns(target_gaps)),
+        "gap_bins.csv" => columns_of::<GapBinRecord>(),
+        _ => return None,
+    };
+    Some(columns.iter().map(|c| c.to_string()).collect())
+}
+
+/// Derived columns that `--slim` leaves out, keeping identities, counts and rates.
+const SLIM_DROPPED: &[&str] = &[
+    "wheel_30",
+    "wheel_210",
+    "gap_over_6",
+    "gap_over_30",
+    "gap_over_210",
+    "success_ci_low",
+    "success_ci_high",
+    "p_value",
+    "z_score",
+    "rate_change_z",
+    "rate_change_p_value",
+    "expected_rate_heuristic",
+    "baseline",
+    "expected_count_hl",
+    "shield_score",
+    "shield_primes",
+    "max_shield_prime",
+    "theoretical_boost",
+    "theoretical_boost_fraction",
+    "shield_model",
+    "exact_boost",
+    "total_primes_s_std_error",
+    "expected_runs",
+    "expected",
+];
+
+/// The columns of the result tables that `--columns` and `--slim` keep. Every writer
+/// applies it, so the CSV files and the other sinks get the same columns.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnSelection {
+    columns: Vec<(String, String)>,
+    slim: bool,
+}
+
+impl ColumnSelection {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            columns: config.columns.clone(),
+            slim: config.slim,
+        }
+    }
+
+    /// The columns chosen for the output `file` with `--columns`, none if all of them.
+    fn chosen<'a>(&'a self, file: &'a str) -> impl Iterator<Item = &'a str> {
+        let stem = file.trim_end_matches(".csv");
+        self.columns
+            .iter()
+            .filter(move |(key, _)| key == stem)
+            .flat_map(|(_, columns)| columns.split(','))
+            .map(str::trim)
+    }
+
+    /// Whether the output `file` keeps `column`.
+    pub fn keeps(&self, file: &str, column: &str) -> bool {
+        if self.columns.is_empty() && !self.slim || selectable_columns(file, &[]).is_none() {
+            return true;
+        }
+        let mut chosen = self.chosen(file).peekable();
+        (chosen.peek().is_none() || chosen.any(|c| c == column))
+            && !(self.slim && SLIM_DROPPED.contains(&column))
+    }
+
+    /// Checks every `--columns` file and column against the columns a run with
+    /// `target_gaps` writes, so a typo is caught before the analysis starts.
+    pub fn validate(&self, target_gaps: &[Gap]) -> Result<(), String> {
+        for (stem, _) in &self.columns {
+            let file = format!("{}.csv", stem);
+            let Some(columns) = selectable_columns(&file, target_gaps) else {
+                return Err(format!("--columns: unknown output file '{}'", stem));
+            };
+            let missing = self.chosen(&file).find(|c| !columns.iter().any(|h| h == c));
+            if let Some(missing) = missing {
+                return Err(format!("--columns: {} has no column '{}'", file, missing));
+            }
+        }
+        Ok(())
+    }
 
-    let sorted_gaps: BTreeMap<_, _> = stats.gap_spectrum.iter().collect();
+    /// Indices of the kept columns among `columns` of the output `file`, or `None` if it
+    /// keeps them all.
+    pub fn kept(&self, file: &str, columns: &[String]) -> Option<Vec<usize>> {
+        let kept: Vec<usize> = (0..columns.len())
+            .filter(|&i| self.keeps(file, &columns[i]))
+            .collect();
+        (kept.len() < columns.len()).then_some(kept)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PartitionEntry {
+    pub file: String,
+    pub rows: u64,
+}
+
+/// Lists the parts of a CSV output `<stem>.csv` that was split up, in row order.
+/// Written as `<stem>_index.json` next to the parts.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PartitionIndex {
+    pub parts: Vec<PartitionEntry>,
+}
+
+fn partition_index_path(output_dir: &Path, file: &str) -> PathBuf {
+    output_dir.join(format!("{}_index.json", file.trim_end_matches(".csv")))
+}
+
+/// The files holding the CSV output `file` (e.g. `gap_spectrum.csv`): its parts in order
+/// if it was partitioned, otherwise just `file`.
+pub fn data_files(output_dir: &str, file: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let index_path = partition_index_path(Path::new(output_dir), file);
+    if !index_path.exists() {
+        return Ok(vec![file.to_string()]);
+    }
+    let index: PartitionIndex = serde_json::from_str(&fs::read_to_string(index_path)?)?;
+    Ok(index.parts.into_iter().map(|part| part.file).collect())
+}
 
-    for (&gap_size, &(count, successes)) in sorted_gaps {
-        let success_rate = if count > 0 {
-            successes as f64 / count as f64
-        } else {
-            0.0
+/// Reads every row of the CSV output `file`, following its partition index if it has one.
+pub fn read_rows<T: DeserializeOwned>(
+    output_dir: &str,
+    file: &str,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    for part in data_files(output_dir, file)? {
+        let path = Path::new(output_dir).join(part);
+        let mut reader =
+            csv::Reader::from_path(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for row in reader.deserialize() {
+            rows.push(row?);
+        }
+    }
+    Ok(rows)
+}
+
+/// Rows of a CSV output looked up by column name, so a reader depends only on the columns
+/// it uses: columns added since are ignored and reordered ones are still found.
+#[derive(Debug)]
+pub struct ColumnTable {
+    file: String,
+    columns: BTreeMap<String, usize>,
+    rows: Vec<csv::StringRecord>,
+}
+
+/// One row of a [`ColumnTable`].
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnRow<'a> {
+    table: &'a ColumnTable,
+    /// Data row, 1-based after the header.
+    row: usize,
+    record: &'a csv::StringRecord,
+}
+
+impl ColumnTable {
+    /// Reads the CSV output `file`, following its partition index if it has one.
+    pub fn read(output_dir: &str, file: &str) -> Result<Self, Box<dyn Error>> {
+        let mut table = ColumnTable {
+            file: file.to_string(),
+            columns: BTreeMap::new(),
+            rows: Vec::new(),
         };
-        let shielding_info = calculate_shielding_info(gap_size);
-
-        let record = GapSpectrumRecord {
-            gap_size,
-            count,
-            successes,
-            success_rate,
-            expected_rate_heuristic: expected_rate,
-            shield_score: shielding_info.shield_score,
-            shield_primes: shielding_info.shield_primes,
-            theoretical_boost: shielding_info.theoretical_boost,
+        for part in data_files(output_dir, file)? {
+            let path = Path::new(output_dir).join(part);
+            let mut reader =
+                csv::Reader::from_path(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if table.columns.is_empty() {
+                for (i, column) in reader.headers()?.iter().enumerate() {
+                    table.columns.insert(column.to_string(), i);
+                }
+            }
+            for row in reader.records() {
+                table.rows.push(row?);
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn column_names(&self) -> impl Iterator<Item = &str> {
+        self.columns.keys().map(String::as_str)
+    }
+
+    pub fn has(&self, column: &str) -> bool {
+        self.columns.contains_key(column)
+    }
+
+    /// Errors naming every one of `columns` the file lacks.
+    pub fn require(&self, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+        let missing: Vec<&str> = columns.iter().copied().filter(|c| !self.has(c)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(format!("{} lacks the column(s) {}", self.file, missing.join(", ")).into())
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = ColumnRow<'_>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(move |(i, record)| ColumnRow {
+                table: self,
+                row: i + 1,
+                record,
+            })
+    }
+}
+
+impl ColumnRow<'_> {
+    fn cell(&self, column: &str) -> Option<&str> {
+        let &i = self.table.columns.get(column)?;
+        self.record.get(i).filter(|cell| !cell.is_empty())
+    }
+
+    fn parse<T: std::str::FromStr>(&self, column: &str, cell: &str) -> Result<T, Box<dyn Error>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        cell.parse().map_err(|e| {
+            format!(
+                "{} row {} {}: {:?}: {}",
+                self.table.file, self.row, column, cell, e
+            )
+            .into()
+        })
+    }
+
+    /// The value of `column`, which must be present and parse as `T`.
+    pub fn get<T: std::str::FromStr>(&self, column: &str) -> Result<T, Box<dyn Error>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        let cell = self
+            .cell(column)
+            .ok_or_else(|| format!("{} row {} has no {}", self.table.file, self.row, column))?;
+        self.parse(column, cell)
+    }
+
+    /// The value of `column`, or `default` where the column or the cell is missing.
+    pub fn get_or<T: std::str::FromStr>(
+        &self,
+        column: &str,
+        default: T,
+    ) -> Result<T, Box<dyn Error>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        match self.cell(column) {
+            Some(cell) => self.parse(column, cell),
+            None => Ok(default),
+        }
+    }
+}
+
+/// CSV writer that splits its output into `<stem>_part_001.csv`, `<stem>_part_002.csv`, …
+/// of at most `max_rows` rows each, listed in `<stem>_index.json`. Output that fits in one
+/// part stays a single `<stem>.csv`. Every part starts with the header row.
+#[derive(Debug)]
+pub struct PartitionedWriter {
+    output_dir: PathBuf,
+    file: &'static str,
+    max_rows: u64,                // 0 never partitions
+    headers: Option<Vec<String>>, // None lets `serialize` write the headers
+    wtr: Writer<File>,
+    parts: Vec<PartitionEntry>, // The last entry is the part being written
+}
+
+impl PartitionedWriter {
+    /// Starts writing the CSV output `file` (e.g. `gap_spectrum.csv`), removing the parts
+    /// of an earlier partitioned run with the same name.
+    pub fn create(
+        output_dir: &str,
+        file: &'static str,
+        max_rows: u64,
+        headers: Option<Vec<String>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let output_dir = PathBuf::from(output_dir);
+        let index_path = partition_index_path(&output_dir, file);
+        if index_path.exists() {
+            let index: PartitionIndex = serde_json::from_str(&fs::read_to_string(&index_path)?)?;
+            for part in index.parts {
+                let _ = fs::remove_file(output_dir.join(part.file));
+            }
+            fs::remove_file(index_path)?;
+        }
+
+        let mut writer = Self {
+            wtr: Writer::from_path(output_dir.join(file))?,
+            output_dir,
+            file,
+            max_rows,
+            headers,
+            parts: vec![PartitionEntry {
+                file: file.to_string(),
+                rows: 0,
+            }],
         };
-        wtr.serialize(record)?;
+        writer.write_headers()?;
+        Ok(writer)
     }
 
-    wtr.flush()?;
-    Ok(())
+    fn part_name(&self, number: usize) -> String {
+        format!(
+            "{}_part_{:03}.csv",
+            self.file.trim_end_matches(".csv"),
+            number
+        )
+    }
+
+    fn write_headers(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(headers) = &self.headers {
+            self.wtr.write_record(headers)?;
+        }
+        Ok(())
+    }
+
+    /// Moves on to a new part once the current one is full.
+    fn reserve_row(&mut self) -> Result<(), Box<dyn Error>> {
+        let current = self.parts.last_mut().expect("at least one part");
+        if self.max_rows == 0 || current.rows < self.max_rows {
+            current.rows += 1;
+            return Ok(());
+        }
+
+        self.wtr.flush()?;
+        if self.parts.len() == 1 {
+            // The output no longer fits in one file: the first part gets a part name too.
+            let first = self.part_name(1);
+            fs::rename(
+                self.output_dir.join(self.file),
+                self.output_dir.join(&first),
+            )?;
+            self.parts[0].file = first;
+        }
+        let name = self.part_name(self.parts.len() + 1);
+        self.wtr = Writer::from_path(self.output_dir.join(&name))?;
+        self.parts.push(PartitionEntry {
+            file: name,
+            rows: 1,
+        });
+        self.write_headers()
+    }
+
+    pub fn write_record(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.reserve_row()?;
+        self.wtr.write_record(row)?;
+        Ok(())
+    }
+
+    pub fn serialize<S: Serialize>(&mut self, record: S) -> Result<(), Box<dyn Error>> {
+        self.reserve_row()?;
+        self.wtr.serialize(record)?;
+        Ok(())
+    }
+
+    /// Flushes the rows written so far and, if the output was split, writes the partition
+    /// index of the parts so far.
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.wtr.flush()?;
+        if self.parts.len() > 1 {
+            let index = PartitionIndex {
+                parts: std::mem::take(&mut self.parts),
+            };
+            let written = fs::write(
+                partition_index_path(&self.output_dir, self.file),
+                serde_json::to_string_pretty(&index)?,
+            );
+            self.parts = index.parts;
+            written?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the last part and, if the output was split, writes the partition index.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.flush()
+    }
 }
 
-fn write_oscillation_series(stats: &Statistics, config: &Config) -> Result<(), Box<dyn Error>> {
-    let path = Path::new(&config.output_dir).join("oscillation_series.csv");
-    let mut wtr = Writer::from_path(path)?;
+/// Trailing columns of `oscillation_series.csv`, in the order of `BinRecord::quantiles`.
+pub const QUANTILE_COLUMNS: [&str; 6] = [
+    "gap_p50",
+    "gap_p90",
+    "gap_p99",
+    "merit_p50",
+    "merit_p90",
+    "merit_p99",
+];
+
+/// Success rates of the target gaps by the gap before and the gap after them.
+pub const NEIGHBOR_GAPS_FILE: &str = "neighbor_gaps.csv";
+
+/// Gap spectrum pooled into residue families, written under `--gap-families`.
+pub const GAP_FAMILIES_FILE: &str = "gap_families.csv";
+
+/// Correlation matrix of the per-bin S/P ratio and gap rate series.
+pub const RATE_CORRELATIONS_FILE: &str = "rate_correlations.csv";
 
-    // Dynamically build headers
+/// Columns of `oscillation_series.csv` for `target_gaps`.
+pub fn oscillation_columns(target_gaps: &[Gap]) -> Vec<String> {
     let mut headers: Vec<String> = vec![
         "bin_start".to_string(),
         "bin_end".to_string(),
@@ -158,41 +784,153 @@ fn write_oscillation_series(stats: &Statistics, config: &Config) -> Result<(), B
         "prime_count_s".to_string(),
         "ratio_s_p".to_string(),
     ];
-    for &g in &stats.target_gaps {
+    for &g in target_gaps {
         headers.push(format!("gap_{}_rate", g));
     }
-    wtr.write_record(headers.iter())?;
+    headers.extend(QUANTILE_COLUMNS.iter().map(|c| c.to_string()));
+    headers
+}
 
-    // Dynamically write data rows
-    for bin in &stats.bins {
-        let ratio_s_p = if bin.prime_count_p > 0 {
-            bin.prime_count_s as f64 / bin.prime_count_p as f64
-        } else {
-            0.0
-        };
+/// The fields of `row` at the indices `kept`, all of them if `None`.
+fn project(row: Vec<String>, kept: Option<&[usize]>) -> Vec<String> {
+    match kept {
+        Some(kept) => kept.iter().map(|&i| row[i].clone()).collect(),
+        None => row,
+    }
+}
 
-        let mut record: Vec<String> = vec![
-            bin.bin_start.to_string(),
-            bin.bin_end.to_string(),
-            bin.prime_count_p.to_string(),
-            bin.prime_count_s.to_string(),
-            ratio_s_p.to_string(),
+/// Incremental writer for `oscillation_series.csv` and its long format `gap_bins.csv`, fed
+/// one bin at a time. Finishing also writes `rate_correlations.csv` over the bins that hold
+/// a prime p.
+#[derive(Debug)]
+pub struct OscillationWriter {
+    wtr: PartitionedWriter,
+    gap_wtr: PartitionedWriter,
+    // Indices of the columns kept in each file, `None` for all of them.
+    kept: Option<Vec<usize>>,
+    gap_kept: Option<Vec<usize>>,
+    target_gaps: Vec<Gap>,
+    output_dir: PathBuf,
+    correlations: SeriesCorrelations,
+}
+
+impl OscillationWriter {
+    /// Starts `oscillation_series.csv` and `gap_bins.csv`, each partitioned every `max_rows`
+    /// rows (0 never partitions), with the columns that `selection` keeps.
+    pub fn create(
+        output_dir: &str,
+        target_gaps: &[Gap],
+        max_rows: u64,
+        selection: &ColumnSelection,
+    ) -> Result<Self, Box<dyn Error>> {
+        let headers = oscillation_columns(target_gaps);
+        let kept = selection.kept("oscillation_series.csv", &headers);
+        let wtr = PartitionedWriter::create(
+            output_dir,
+            "oscillation_series.csv",
+            max_rows,
+            Some(project(headers, kept.as_deref())),
+        )?;
+        let gap_headers: Vec<String> = columns_of::<GapBinRecord>()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let gap_kept = selection.kept("gap_bins.csv", &gap_headers);
+        // Without target gaps there are no rows, and the file stays empty.
+        let gap_headers =
+            (!target_gaps.is_empty()).then(|| project(gap_headers, gap_kept.as_deref()));
+        let gap_wtr = PartitionedWriter::create(output_dir, "gap_bins.csv", max_rows, gap_headers)?;
+
+        Ok(Self {
+            wtr,
+            gap_wtr,
+            kept,
+            gap_kept,
+            target_gaps: target_gaps.to_vec(),
+            output_dir: PathBuf::from(output_dir),
+            correlations: SeriesCorrelations::new(target_gaps.len() + 1),
+        })
+    }
+
+    pub fn write_bin(&mut self, bin: &BinStats) -> Result<(), Box<dyn Error>> {
+        let record = BinRecord::new(bin, &self.target_gaps);
+        let mut row: Vec<String> = vec![
+            record.bin_start.to_string(),
+            record.bin_end.to_string(),
+            record.prime_count_p.to_string(),
+            record.prime_count_s.to_string(),
+            record.ratio_s_p.to_string(),
         ];
+        row.extend(record.gap_rates.iter().map(|g| g.rate.to_string()));
+        row.extend(record.quantiles().iter().map(|q| q.to_string()));
+        self.wtr.write_record(&project(row, self.kept.as_deref()))?;
+        let gap_records: Vec<_> = record.gap_records().collect();
+        for cells in Table::new("gap_bins.csv", &gap_records)?.rows {
+            let row = cells.iter().map(Cell::to_csv).collect();
+            self.gap_wtr
+                .write_record(&project(row, self.gap_kept.as_deref()))?;
+        }
+        // Bins above max N have no pairs, only sums, and would pull every series to zero.
+        if record.prime_count_p > 0 {
+            let mut values = vec![record.ratio_s_p];
+            values.extend(record.gap_rates.iter().map(|g| g.rate));
+            self.correlations.add(&values);
+        }
+        Ok(())
+    }
 
-        for &g in &stats.target_gaps {
-            let occurrences = bin.gap_occurrences.get(&g).cloned().unwrap_or(0);
-            let successes = bin.gap_successes.get(&g).cloned().unwrap_or(0);
-            let rate = if occurrences > 0 {
-                successes as f64 / occurrences as f64
-            } else {
-                0.0
-            };
-            record.push(rate.to_string());
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.wtr.flush()?;
+        self.gap_wtr.flush()
+    }
+
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.write_correlations()?;
+        self.wtr.finish()?;
+        self.gap_wtr.finish()
+    }
+
+    /// One row and one column per series; undefined correlations (a constant series) are
+    /// left empty.
+    fn write_correlations(&self) -> Result<(), Box<dyn Error>> {
+        let mut names = vec!["ratio_s_p".to_string()];
+        names.extend(self.target_gaps.iter().map(|g| format!("gap_{}_rate", g)));
+        let mut wtr = Writer::from_path(self.output_dir.join(RATE_CORRELATIONS_FILE))?;
+        wtr.write_record(std::iter::once("series").chain(names.iter().map(String::as_str)))?;
+        for (i, name) in names.iter().enumerate() {
+            let mut row = vec![name.clone()];
+            row.extend((0..names.len()).map(|j| {
+                self.correlations
+                    .correlation(i, j)
+                    .map_or(String::new(), |r| r.to_string())
+            }));
+            wtr.write_record(&row)?;
         }
-        wtr.write_record(record.iter())?;
+        wtr.flush()?;
+        Ok(())
     }
-    wtr.flush()?;
-    Ok(())
+}
+
+fn write_oscillation_series(
+    stats: &Statistics,
+    config: &Config,
+    dir: &str,
+) -> Result<(), Box<dyn Error>> {
+    // Streamed runs write every bin during the analysis.
+    if stats.bins_streamed() || stats.streaming_bins() {
+        return Ok(());
+    }
+
+    let mut writer = OscillationWriter::create(
+        dir,
+        &stats.target_gaps,
+        config.partition_rows,
+        &ColumnSelection::new(config),
+    )?;
+    for bin in &stats.bins {
+        writer.write_bin(bin)?;
+    }
+    writer.finish()
 }
 
 #[cfg(test)]
@@ -200,26 +938,68 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_shielding_logic() {
-        // Test Gap 2: No shields
-        let info_2 = calculate_shielding_info(2);
-        assert_eq!(info_2.shield_score, 0);
-        assert_eq!(info_2.shield_primes, "");
-        assert_eq!(info_2.theoretical_boost, 1.0);
-
-        // Test Gap 4: Shielded by 3 and 5
-        let info_4 = calculate_shielding_info(4);
-        assert_eq!(info_4.shield_score, 2);
-        assert_eq!(info_4.shield_primes, "3,5");
-        assert_eq!(info_4.theoretical_boost, 1.5 * (5.0 / 4.0));
-
-        // Test Gap 34 ("King"): Shielded by 3, 5, 7
-        let info_34 = calculate_shielding_info(34);
-        assert_eq!(info_34.shield_score, 3);
-        assert_eq!(info_34.shield_primes, "3,5,7");
+    fn test_partitioned_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        let headers = Some(vec!["n".to_string()]);
+
+        let mut wtr = PartitionedWriter::create(out, "series.csv", 2, headers.clone()).unwrap();
+        for n in 0..3 {
+            wtr.write_record(&[n.to_string()]).unwrap();
+        }
+        // A mid-run flush leaves the rows so far readable.
+        wtr.flush().unwrap();
+        let rows: Vec<(u64,)> = read_rows(out, "series.csv").unwrap();
+        assert_eq!(rows, [(0,), (1,), (2,)]);
+        for n in 3..5 {
+            wtr.write_record(&[n.to_string()]).unwrap();
+        }
+        wtr.finish().unwrap();
         assert_eq!(
-            info_34.theoretical_boost,
-            (3.0 / 2.0) * (5.0 / 4.0) * (7.0 / 6.0)
+            data_files(out, "series.csv").unwrap(),
+            [
+                "series_part_001.csv",
+                "series_part_002.csv",
+                "series_part_003.csv"
+            ]
         );
+        let rows: Vec<(u64,)> = read_rows(out, "series.csv").unwrap();
+        assert_eq!(rows, [(0,), (1,), (2,), (3,), (4,)]);
+
+        // A smaller rerun replaces the parts with a single file.
+        let mut wtr = PartitionedWriter::create(out, "series.csv", 2, headers).unwrap();
+        wtr.write_record(&["7".to_string()]).unwrap();
+        wtr.finish().unwrap();
+        assert_eq!(data_files(out, "series.csv").unwrap(), ["series.csv"]);
+        assert!(!dir.path().join("series_part_001.csv").exists());
+    }
+
+    #[test]
+    fn test_column_table_reads_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        fs::write(dir.path().join("t.csv"), "added,b,a\nx,2,1\ny,,3\n").unwrap();
+        write_schema(out).unwrap();
+        let schema = read_schema(out).unwrap().unwrap();
+        assert_eq!(schema.schema_version, CSV_SCHEMA_VERSION);
+        assert_eq!(schema.files["t.csv"], ["added", "b", "a"]);
+        assert_eq!(unsplit_name("gap_bins_part_002.csv"), "gap_bins.csv");
+
+        let table = ColumnTable::read(out, "t.csv").unwrap();
+        table.require(&["a", "b"]).unwrap();
+        let err = table.require(&["a", "c", "d"]).unwrap_err();
+        assert_eq!(err.to_string(), "t.csv lacks the column(s) c, d");
+        let rows: Vec<(u64, u64, u64)> = table
+            .rows()
+            .map(|r| {
+                (
+                    r.get("a").unwrap(),
+                    r.get_or("b", 0).unwrap(),
+                    r.get_or("c", 7).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(rows, [(1, 2, 7), (3, 0, 7)]);
+        assert!(table.rows().next().unwrap().get::<u64>("added").is_err());
     }
 }
@@ -0,0 +1,153 @@
+//! The `plan` subcommand: splits an analysis into `--shard`s for a cluster. It lists the
+//! shards with their slices of p and output directories, or with `--slurm` prints a batch
+//! script that runs them as a Slurm job array, one shard per task (`--slurm-array`).
+//!
+//! The script's tasks checkpoint every ten minutes and start with `--resume`, and the job
+//! is requeueable, so a preempted or timed-out task continues from its last checkpoint.
+//! Tasks that cannot resume (streamed bins, prime dumps) start over instead.
+
+use crate::config::{Cli, Config, Formula, PlanConfig, Shard};
+use clap::Parser;
+use std::error::Error;
+use std::path::Path;
+
+/// Seconds between the checkpoints of a planned task, unless the analysis sets its own.
+const TASK_CHECKPOINT_SECS: u64 = 600;
+
+/// Quotes `arg` for a POSIX shell, leaving plain words as they are.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./,:=^+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Parses the analysis options of the plan, which must describe a single sharded-to-be run.
+fn analysis(config: &PlanConfig) -> Result<Config, Box<dyn Error>> {
+    let binary = env!("CARGO_PKG_NAME");
+    // Options the analysis would reject are reported as it would report them.
+    let cli =
+        Cli::try_parse_from(std::iter::once(binary).chain(config.args.iter().map(String::as_str)))
+            .unwrap_or_else(|e| e.exit());
+    let run = match (cli.command, cli.run) {
+        (None, Some(run)) => run,
+        _ => return Err("plan takes the options of an analysis, not a subcommand".into()),
+    };
+    if run.shard.is_some() || run.slurm_array {
+        return Err(
+            "the planned tasks choose their own shards; leave out --shard and --slurm-array".into(),
+        );
+    }
+    if !run.milestone_exponents().is_empty() {
+        return Err("shards take a single --max-exponent".into());
+    }
+    if run.formula == Formula::Triple {
+        return Err("--formula triple cannot be split into shards".into());
+    }
+    Ok(run)
+}
+
+/// The batch script running `args` as `shards` array tasks.
+fn slurm_script(config: &PlanConfig, run: &Config) -> String {
+    let binary = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string());
+    let mut command = vec![shell_quote(&binary)];
+    command.extend(config.args.iter().map(|arg| shell_quote(arg)));
+    command.push("--slurm-array".to_string());
+    // Streamed bins cannot be checkpointed and dumps cannot be resumed; such tasks start
+    // over when requeued.
+    if !run.stream_bins && !run.dump_primes && !run.dump_s_primes {
+        if run.checkpoint_every == 0 {
+            command.push(format!("--checkpoint-every {}", TASK_CHECKPOINT_SECS));
+        }
+        if !run.resume {
+            command.push("--resume".to_string());
+        }
+    }
+
+    let mut script = String::from("#!/bin/bash\n");
+    script += &format!(
+        "# {} shards of 10^{} up to N = {}, one per array task, written to {}/shard_<I>/.\n",
+        config.shards,
+        run.max_exponent(),
+        run.max_n(),
+        run.output_dir
+    );
+    script += &format!("#SBATCH --job-name=prime_shield_e{}\n", run.max_exponent());
+    script += &format!("#SBATCH --array=0-{}\n", config.shards - 1);
+    script += &format!("#SBATCH --time={}\n", config.time);
+    script += &format!("#SBATCH --cpus-per-task={}\n", config.cpus);
+    script += "#SBATCH --requeue\n";
+    script += "#SBATCH --output=slurm-%A_%a.out\n\n";
+    script += &format!("srun {}\n", command.join(" "));
+    script
+}
+
+pub fn run(config: &PlanConfig) -> Result<(), Box<dyn Error>> {
+    let run = analysis(config)?;
+    if config.slurm {
+        print!("{}", slurm_script(config, &run));
+        return Ok(());
+    }
+
+    let max_n = run.max_n();
+    println!("{:<10} {:<44} output directory", "shard", "pairs with p in");
+    for index in 0..config.shards {
+        let shard = Shard {
+            index,
+            count: config.shards,
+        };
+        let (start, end) = shard.range(max_n);
+        let slice = format!("[{}, {})", start, end);
+        let dir = Path::new(&run.output_dir).join(shard.dir_name());
+        println!("{:<10} {:<44} {}", shard.to_string(), slice, dir.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(args: &[&str]) -> String {
+        let config = PlanConfig {
+            shards: 4,
+            slurm: true,
+            time: "1:00:00".to_string(),
+            cpus: 1,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        };
+        slurm_script(&config, &analysis(&config).unwrap())
+    }
+
+    #[test]
+    fn test_tasks_resume_only_runs_that_can() {
+        let resumed = script(&["-E", "6"]);
+        assert!(resumed.contains(" --slurm-array --checkpoint-every 600 --resume\n"));
+        for args in [
+            &["-E", "6", "--dump-primes"][..],
+            &["-E", "6", "--dump-s-primes"],
+            &["-E", "6", "--stream-bins"],
+        ] {
+            let script = script(args);
+            assert!(script.ends_with(" --slurm-array\n"), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_triple_runs_are_not_planned() {
+        let config = PlanConfig {
+            shards: 4,
+            slurm: true,
+            time: "1:00:00".to_string(),
+            cpus: 1,
+            args: ["-E", "6", "--formula", "triple"]
+                .map(String::from)
+                .to_vec(),
+        };
+        assert!(analysis(&config).is_err());
+    }
+}
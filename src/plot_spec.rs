@@ -0,0 +1,197 @@
+//! `--plot-spec`: ready-to-run gnuplot scripts or Vega-Lite specs for the main report
+//! charts, reading the CSV outputs in place (including partitioned sets).
+
+use crate::config::PlotSpec;
+use crate::output;
+use crate::stats::Gap;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub const GNUPLOT_FILE: &str = "plots.gp";
+
+/// Writes the specs for `format` into `output_dir` and returns the file names written.
+pub fn write(
+    format: PlotSpec,
+    output_dir: &str,
+    target_gaps: &[Gap],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let gap_files = output::data_files(output_dir, "gap_spectrum.csv")?;
+    let osc_files = output::data_files(output_dir, "oscillation_series.csv")?;
+
+    let specs = match format {
+        PlotSpec::Gnuplot => vec![(
+            GNUPLOT_FILE.to_string(),
+            gnuplot_script(&gap_files, &osc_files, target_gaps),
+        )],
+        PlotSpec::Vega => vega_lite_specs(&gap_files, &osc_files, target_gaps)
+            .into_iter()
+            .map(|(name, spec)| Ok((name, serde_json::to_string_pretty(&spec)?)))
+            .collect::<Result<_, serde_json::Error>>()?,
+    };
+
+    let mut written = Vec::new();
+    for (name, contents) in specs {
+        fs::write(Path::new(output_dir).join(&name), contents)?;
+        written.push(name);
+    }
+    Ok(written)
+}
+
+fn gnuplot_script(gap_files: &[String], osc_files: &[String], target_gaps: &[Gap]) -> String {
+    let gaps = target_gaps
+        .iter()
+        .map(|g| g.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        r#"# Charts of the prime_shield_analyzer report. Run `gnuplot {script}` in this directory.
+set datafile separator comma
+set terminal pngcairo size 1200,700 noenhanced
+set grid
+set key autotitle columnhead
+
+GAP_FILES = "{gap_files}"
+OSC_FILES = "{osc_files}"
+GAPS = "{gaps}"
+# Partitioned outputs are plotted part by part; only the first part gets a legend entry.
+label(f, text) = (f eq word(OSC_FILES, 1) || f eq word(GAP_FILES, 1)) ? text : ""
+
+set output 'theory_verification.png'
+set title 'Theory Verification'
+set xlabel 'Theoretical boost'
+set ylabel 'Observed success rate'
+plot for [f in GAP_FILES] f using "theoretical_boost":"success_rate" with points pt 7 title label(f, 'theoretical_boost'), \
+     for [f in GAP_FILES] f using "exact_boost":"success_rate" with points pt 6 title label(f, 'exact_boost')
+
+set output 'ratio_oscillation.png'
+set title 'S = p_n + p_(n+1) - 1 Primality Ratio Oscillation'
+set xlabel 'Bin start'
+set ylabel 'S/P ratio'
+plot for [f in OSC_FILES] f using "bin_start":"ratio_s_p" with lines lc 1 title label(f, 'S/P ratio')
+
+set output 'gap_oscillation.png'
+set title 'Per-Gap Rate Oscillation'
+set ylabel 'Success rate'
+plot for [i=1:words(GAPS)] for [f in OSC_FILES] f \
+     using "bin_start":(column("gap_".word(GAPS, i)."_rate")) with lines lc i \
+     title label(f, 'gap '.word(GAPS, i))
+
+set output 'gap_spectrum.png'
+set title 'Gap Success Rate Spectrum'
+set xlabel 'Gap size'
+set ylabel 'Success rate (95% CI)'
+plot for [f in GAP_FILES] f using "gap_size":"success_rate":"success_ci_low":"success_ci_high" \
+     with yerrorbars pt 7 lc 1 title label(f, 'success_rate')
+
+unset output
+"#,
+        script = GNUPLOT_FILE,
+        gap_files = gap_files.join(" "),
+        osc_files = osc_files.join(" "),
+        gaps = gaps,
+    )
+}
+
+/// A layered chart with one layer per file of a (possibly partitioned) CSV output.
+fn layered(title: &str, files: &[String], layer: Value, encoding: Value) -> Value {
+    let layers: Vec<Value> = files
+        .iter()
+        .map(|file| {
+            let mut spec = layer.clone();
+            spec["data"] = json!({ "url": file, "format": { "type": "csv" } });
+            spec
+        })
+        .collect();
+    json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "title": title,
+        "width": 900,
+        "height": 450,
+        "encoding": encoding,
+        "layer": layers,
+    })
+}
+
+fn vega_lite_specs(
+    gap_files: &[String],
+    osc_files: &[String],
+    target_gaps: &[Gap],
+) -> Vec<(String, Value)> {
+    let gap_columns: Vec<String> = target_gaps
+        .iter()
+        .map(|g| format!("gap_{}_rate", g))
+        .collect();
+    vec![
+        (
+            "theory_verification.vl.json".to_string(),
+            layered(
+                "Theory Verification",
+                gap_files,
+                json!({
+                    "transform": [{ "fold": ["theoretical_boost", "exact_boost"], "as": ["model", "boost"] }],
+                    "mark": { "type": "point", "filled": true },
+                }),
+                json!({
+                    "x": { "field": "boost", "type": "quantitative", "title": "Boost" },
+                    "y": { "field": "success_rate", "type": "quantitative", "title": "Observed success rate" },
+                    "color": { "field": "model", "type": "nominal" },
+                    "tooltip": [
+                        { "field": "gap_size", "type": "quantitative" },
+                        { "field": "boost", "type": "quantitative" },
+                        { "field": "success_rate", "type": "quantitative" },
+                        { "field": "shield_primes", "type": "nominal" },
+                    ],
+                }),
+            ),
+        ),
+        (
+            "ratio_oscillation.vl.json".to_string(),
+            layered(
+                "S/P Ratio Oscillation",
+                osc_files,
+                json!({ "mark": "line" }),
+                json!({
+                    "x": { "field": "bin_start", "type": "quantitative", "title": "Bin start" },
+                    "y": { "field": "ratio_s_p", "type": "quantitative", "title": "S/P ratio", "scale": { "zero": false } },
+                }),
+            ),
+        ),
+        (
+            "gap_oscillation.vl.json".to_string(),
+            layered(
+                "Per-Gap Rate Oscillation",
+                osc_files,
+                json!({
+                    "transform": [{ "fold": gap_columns, "as": ["gap", "rate"] }],
+                    "mark": "line",
+                }),
+                json!({
+                    "x": { "field": "bin_start", "type": "quantitative", "title": "Bin start" },
+                    "y": { "field": "rate", "type": "quantitative", "title": "Success rate" },
+                    "color": { "field": "gap", "type": "nominal", "sort": gap_columns },
+                }),
+            ),
+        ),
+        (
+            "gap_spectrum.vl.json".to_string(),
+            layered(
+                "Gap Success Rate Spectrum",
+                gap_files,
+                json!({ "mark": "bar" }),
+                json!({
+                    "x": { "field": "gap_size", "type": "ordinal", "title": "Gap size" },
+                    "y": { "field": "success_rate", "type": "quantitative", "title": "Success rate" },
+                    "tooltip": [
+                        { "field": "gap_size", "type": "quantitative" },
+                        { "field": "count", "type": "quantitative" },
+                        { "field": "success_rate", "type": "quantitative" },
+                        { "field": "success_ci_low", "type": "quantitative" },
+                        { "field": "success_ci_high", "type": "quantitative" },
+                    ],
+                }),
+            ),
+        ),
+    ]
+}
@@ -0,0 +1,224 @@
+//! The `probe` subcommand: estimates the S statistics far beyond exhaustive sieving reach
+//! by analyzing random windows `[x, x + W)` of a range `[A, B]`.
+//!
+//! Each window's primes are sieved with the primes up to `sqrt(B + W)`, and every sum
+//! `S = p_n + p_{n+1} - 1` is tested with deterministic Miller-Rabin, so no sieve ever has
+//! to reach `2 * B`. Windowed results are aggregated both pooled (with Wilson intervals)
+//! and as a mean over windows (with its standard error).
+
+use crate::config::ProbeConfig;
+use crate::counting;
+use crate::sieve::{base_primes, is_prime_u64, primes_in_window};
+use crate::stats::{self, Gap, SplitMix64, TargetGapSet};
+use csv::Writer;
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Pair and success counts for all pairs of one window, then for each target gap.
+struct WindowCounts {
+    start: u64,
+    all: (u64, u64),
+    gaps: Vec<(u64, u64)>,
+}
+
+fn rate((trials, successes): (u64, u64)) -> f64 {
+    if trials > 0 {
+        successes as f64 / trials as f64
+    } else {
+        0.0
+    }
+}
+
+fn analyze_window(start: u64, width: u64, base: &[u32], gaps: &TargetGapSet) -> WindowCounts {
+    let primes = primes_in_window(start, start + width, base);
+    let mut counts = WindowCounts {
+        start,
+        all: (0, 0),
+        gaps: vec![(0, 0); gaps.len()],
+    };
+    for pair in primes.windows(2) {
+        let (p_prev, p_current) = (pair[0], pair[1]);
+        let success = is_prime_u64(p_prev + p_current - 1) as u64;
+        counts.all.0 += 1;
+        counts.all.1 += success;
+        if let Some(slot) = gaps.slot(Gap(p_current - p_prev)) {
+            counts.gaps[slot].0 += 1;
+            counts.gaps[slot].1 += success;
+        }
+    }
+    counts
+}
+
+/// The value range `[from, to]` windows are drawn from, resolving `--from-index` ranges
+/// to the primes at their ends.
+fn value_range(config: &ProbeConfig) -> Result<(u64, u64), Box<dyn Error>> {
+    match (config.from_index, config.count, config.from, config.to) {
+        (Some(index), Some(count), _, _) => {
+            let last = index.checked_add(count).map(|end| end - 1);
+            match last {
+                Some(last) if index >= 1 && count >= 1 && last <= counting::MAX_N => {
+                    Ok((counting::nth_prime(index), counting::nth_prime(last)))
+                }
+                _ => Err(format!(
+                    "--from-index and --count must select primes 1 to {}",
+                    counting::MAX_N
+                )
+                .into()),
+            }
+        }
+        (_, _, Some(from), Some(to)) => Ok((from, to)),
+        _ => Err("either --from and --to or --from-index and --count are required".into()),
+    }
+}
+
+pub fn run(config: &ProbeConfig) -> Result<(), Box<dyn Error>> {
+    if config.width == 0 || config.windows == 0 {
+        return Err("--width and --windows must be greater than 0".into());
+    }
+    let (from, to) = value_range(config)?;
+    if let Some(index) = config.from_index {
+        println!(
+            "Primes {} to {} span [{}, {}]",
+            index,
+            index + config.count.unwrap_or(1) - 1,
+            from,
+            to
+        );
+    }
+    if to < from || to - from < config.width {
+        return Err("the range --from .. --to must be at least one --width wide".into());
+    }
+    let max_end = to
+        .checked_add(config.width)
+        .filter(|&end| end <= u64::MAX / 2)
+        .ok_or("--to is too large: the sums S must fit in 64 bits")?;
+
+    let mut gaps = config.gaps.clone();
+    gaps.sort_unstable();
+    gaps.dedup();
+
+    println!(
+        "Probing {} windows of width {} in [{}, {}] (seed {})",
+        config.windows, config.width, from, to, config.seed
+    );
+    let base = base_primes(max_end);
+    let target_set = TargetGapSet::new(&gaps.iter().map(|&g| Gap(g)).collect::<Vec<_>>());
+
+    let mut rng = SplitMix64::new(config.seed);
+    let span = to - from - config.width + 1;
+    let bar = ProgressBar::new(config.windows as u64);
+    let windows: Vec<WindowCounts> = (0..config.windows)
+        .map(|_| {
+            let start = from + rng.next_u64() % span;
+            let counts = analyze_window(start, config.width, &base, &target_set);
+            bar.inc(1);
+            counts
+        })
+        .collect();
+    bar.finish();
+
+    fs::create_dir_all(&config.output_dir)?;
+    write_windows(&windows, &gaps, config)?;
+    write_summary(&windows, &gaps, config)?;
+    println!(
+        "Probe results written to {}/probe_windows.csv and probe_summary.csv",
+        config.output_dir
+    );
+    Ok(())
+}
+
+fn write_windows(
+    windows: &[WindowCounts],
+    gaps: &[u64],
+    config: &ProbeConfig,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(Path::new(&config.output_dir).join("probe_windows.csv"))?;
+    let mut headers: Vec<String> = vec![
+        "window_start".to_string(),
+        "window_end".to_string(),
+        "pairs".to_string(),
+        "successes".to_string(),
+        "success_rate".to_string(),
+    ];
+    for g in gaps {
+        headers.push(format!("gap_{}_rate", g));
+    }
+    wtr.write_record(headers.iter())?;
+
+    for window in windows {
+        let mut row: Vec<String> = vec![
+            window.start.to_string(),
+            (window.start + config.width).to_string(),
+            window.all.0.to_string(),
+            window.all.1.to_string(),
+            rate(window.all).to_string(),
+        ];
+        row.extend(window.gaps.iter().map(|&c| rate(c).to_string()));
+        wtr.write_record(row.iter())?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_summary(
+    windows: &[WindowCounts],
+    gaps: &[u64],
+    config: &ProbeConfig,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(Path::new(&config.output_dir).join("probe_summary.csv"))?;
+    wtr.write_record([
+        "gap",
+        "occurrences",
+        "successes",
+        "success_rate",
+        "success_ci_low",
+        "success_ci_high",
+        "window_mean_rate",
+        "window_rate_std_error",
+    ])?;
+
+    let rows = std::iter::once(("all".to_string(), None)).chain(
+        gaps.iter()
+            .enumerate()
+            .map(|(i, g)| (g.to_string(), Some(i))),
+    );
+    for (label, slot) in rows {
+        let per_window: Vec<(u64, u64)> = windows
+            .iter()
+            .map(|w| slot.map_or(w.all, |i| w.gaps[i]))
+            .collect();
+        let pooled = per_window
+            .iter()
+            .fold((0, 0), |(t, s), &(wt, ws)| (t + wt, s + ws));
+        let (ci_low, ci_high) = stats::wilson_interval(pooled.1, pooled.0, stats::Z_95);
+
+        // Window-to-window spread of the rate, over windows where the gap occurred.
+        let rates: Vec<f64> = per_window
+            .iter()
+            .filter(|c| c.0 > 0)
+            .map(|&c| rate(c))
+            .collect();
+        let n = rates.len() as f64;
+        let mean = rates.iter().sum::<f64>() / n.max(1.0);
+        let std_error = if rates.len() > 1 {
+            (rates.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt() / n.sqrt()
+        } else {
+            0.0
+        };
+
+        wtr.write_record([
+            label,
+            pooled.0.to_string(),
+            pooled.1.to_string(),
+            rate(pooled).to_string(),
+            ci_low.to_string(),
+            ci_high.to_string(),
+            mean.to_string(),
+            std_error.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Sampled CPU profile of an analysis run (`--profile`, `profile` feature), written as
+//! `flamegraph.svg` next to `perf.json`.
+//!
+//! Samples every thread of the process with `SIGPROF`, so sieve workers and prefetch
+//! threads appear alongside the analysis loop.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+pub const FLAMEGRAPH_FILE: &str = "flamegraph.svg";
+
+/// Samples per second and thread.
+const SAMPLE_FREQUENCY: i32 = 997;
+
+/// A running profile; sampling stops when it is finished or dropped.
+pub struct Profiler {
+    guard: pprof::ProfilerGuard<'static>,
+}
+
+impl fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profiler").finish_non_exhaustive()
+    }
+}
+
+impl Profiler {
+    pub fn start() -> Result<Self, Box<dyn Error>> {
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(SAMPLE_FREQUENCY)
+            // Unwinding through these from a signal handler can deadlock.
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()?;
+        Ok(Self { guard })
+    }
+
+    /// Writes the samples taken so far as a flamegraph into `output_dir`.
+    pub fn finish(self, output_dir: &str) -> Result<(), Box<dyn Error>> {
+        let report = self.guard.report().build()?;
+        let file = File::create(Path::new(output_dir).join(FLAMEGRAPH_FILE))?;
+        report.flamegraph(BufWriter::new(file))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! The `prune` subcommand: deletes the bulky by-products of the runs in a registry once
+//! they are older than a retention window (prime dumps, checkpoints and write-ahead logs),
+//! keeping the CSV summaries, manifests and reports.
+//!
+//! Incomplete runs keep their checkpoints unless asked otherwise, since `--resume` needs
+//! them to pick the run back up. A directory is only pruned while it still holds the run
+//! the registry recorded: its manifest must match the entry, and nothing to delete may be
+//! newer than the manifest, which a later run reusing the directory writes only at its end.
+
+use crate::config::{DumpFormat, PruneConfig};
+use crate::dump;
+use crate::memory::format_bytes;
+use crate::output::{self, MANIFEST_FILE, SNAPSHOT_DIR};
+use crate::recovery::{CHECKPOINT_FILE, WAL_FILE};
+use crate::registry::{Registry, RunEntry};
+use clap::ValueEnum;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Files and directories in a run's output directory that `prune` removes.
+fn prunable(output_dir: &Path) -> Vec<PathBuf> {
+    let mut names = vec![
+        CHECKPOINT_FILE.to_string(),
+        WAL_FILE.to_string(),
+        SNAPSHOT_DIR.to_string(),
+    ];
+    for stem in [dump::PRIME_DUMP, dump::S_PRIME_DUMP] {
+        for &format in DumpFormat::value_variants() {
+            names.push(dump::file_name(stem, format));
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| output_dir.join(name))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// Bytes taken by a file, or by everything below a directory.
+fn disk_usage(path: &Path) -> u64 {
+    if path.is_dir() {
+        fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| disk_usage(&entry.path()))
+            .sum()
+    } else {
+        fs::metadata(path).map_or(0, |m| m.len())
+    }
+}
+
+/// Why the output directory of `run` is left alone though it holds files to prune, if it is.
+fn skip_reason(run: &RunEntry, paths: &[PathBuf]) -> Option<&'static str> {
+    let dir = Path::new(&run.output_dir);
+    let Ok(Some(manifest)) = output::read_manifest(&run.output_dir) else {
+        return Some("no readable manifest.json");
+    };
+    if manifest.max_n != run.max_n
+        || manifest.complete != run.complete
+        || manifest.label != run.label
+        || manifest.meta != run.meta
+    {
+        return Some("manifest.json does not match the registry entry");
+    }
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let manifest_modified = modified(&dir.join(MANIFEST_FILE));
+    if paths.iter().any(|path| modified(path) > manifest_modified) {
+        return Some("reused by a later run that has not finished");
+    }
+    None
+}
+
+pub fn run(config: &PruneConfig) -> Result<(), Box<dyn Error>> {
+    let registry = Registry::load(&config.registry)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let cutoff = now.saturating_sub(config.older_than * SECS_PER_DAY);
+
+    let (mut files, mut bytes, mut runs) = (0, 0, 0);
+    for run in &registry.runs {
+        if run.recorded_at > cutoff || !(run.complete || config.include_incomplete) {
+            continue;
+        }
+        let dir = Path::new(&run.output_dir);
+        if !dir.is_absolute() {
+            eprintln!(
+                "Warning: skipping {}: relative path recorded by an older version",
+                run.output_dir
+            );
+            continue;
+        }
+        let paths = prunable(dir);
+        if paths.is_empty() {
+            continue;
+        }
+        if let Some(reason) = skip_reason(run, &paths) {
+            eprintln!("Warning: skipping {}: {}", run.output_dir, reason);
+            continue;
+        }
+        runs += 1;
+        for path in paths {
+            let size = disk_usage(&path);
+            if !config.dry_run {
+                if path.is_dir() {
+                    fs::remove_dir_all(&path)?;
+                } else {
+                    fs::remove_file(&path)?;
+                }
+            }
+            println!("{} ({})", path.display(), format_bytes(size));
+            files += 1;
+            bytes += size;
+        }
+    }
+    println!(
+        "{} {} files, {} from {} runs older than {} days",
+        if config.dry_run {
+            "Would delete"
+        } else {
+            "Deleted"
+        },
+        files,
+        format_bytes(bytes),
+        runs,
+        config.older_than
+    );
+    Ok(())
+}
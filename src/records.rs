@@ -0,0 +1,915 @@
+//! Typed, fully derived output records.
+//!
+//! These are the rows the CSV writers emit, computed once from `Statistics` so that
+//! library users and alternative sinks don't have to re-derive rates and shielding.
+
+use crate::config::Baseline;
+use crate::factoring::FactorClass;
+use crate::model::ResidueModel;
+use crate::shielding::{self, calculate_shielding_info, ShieldModel};
+use crate::stats::{self, BinStats, Gap, RunLengths, Statistics};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Fewest occurrences of a gap (or successes) for it to enter the tail fit: below that the
+/// logarithm of a Poisson count is too noisy.
+pub const TAIL_MIN_COUNT: u64 = 5;
+
+/// Gaps listed as outliers of each tail fit.
+const TAIL_OUTLIERS: usize = 5;
+
+/// Run-wide totals, one row of `global_stats.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GlobalStats {
+    pub total_primes_p: u64,
+    pub total_primes_s: u64,
+    pub global_ratio_s_p: f64,
+    pub spectrum_max_gap: u64,
+    pub spectrum_overflow_count: u64,
+    pub spectrum_overflow_successes: u64,
+    /// 1 for a full run; k when only 1 in k prime pairs was analyzed.
+    pub sample_every: u64,
+    /// Standard error of `total_primes_s` due to sampling (0 for a full run).
+    pub total_primes_s_std_error: f64,
+    /// Pairs and prime sums weighted by `--pair-weight`; empty without it.
+    pub weighted_pairs: Option<f64>,
+    pub weighted_primes_s: Option<f64>,
+    pub weighted_ratio_s_p: Option<f64>,
+}
+
+/// One gap size of the spectrum, one row of `gap_spectrum.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GapSpectrumRecord {
+    pub gap_size: Gap,
+    pub count: u64,
+    pub successes: u64,
+    pub success_rate: f64,
+    pub success_ci_low: f64,
+    pub success_ci_high: f64,
+    pub p_value: f64,
+    /// Expected success rate under `baseline`.
+    pub expected_rate_heuristic: f64,
+    /// The `--baseline` behind `expected_rate_heuristic`, e.g. `ln-n` or `hl`.
+    pub baseline: String,
+    /// Hardy–Littlewood predicted number of consecutive prime pairs with this gap.
+    pub expected_count_hl: f64,
+    pub shield_score: u32,
+    pub shield_primes: Vec<u32>,
+    /// The largest of `shield_primes`, empty without shields; `shield_score` counts them.
+    pub max_shield_prime: Option<u32>,
+    pub theoretical_boost: f64,
+    /// `theoretical_boost` as an exact fraction, e.g. `15/8`.
+    pub theoretical_boost_fraction: String,
+    /// Terms of the shield model behind `theoretical_boost`, e.g. `first_order+mod5_trap`.
+    pub shield_model: String,
+    /// Boost computed exactly over the residue classes modulo a primorial (see [`crate::model`]).
+    pub exact_boost: f64,
+    /// Pairs whose sum S lay above the primality checker's limit (see `--out-of-range`).
+    pub out_of_range: u64,
+    /// Sums S divisible by the square of no odd prime up to 97.
+    pub square_free: u64,
+    pub square_free_rate: f64,
+    /// The share of `square_free` sums predicted from the residues the gap leaves open.
+    pub expected_square_free_rate: f64,
+    /// `count` and `successes` split at `--split-at`: pairs with p_prev below it and above.
+    pub early_count: u64,
+    pub early_successes: u64,
+    pub early_rate: f64,
+    pub late_count: u64,
+    pub late_successes: u64,
+    pub late_rate: f64,
+    /// `late_rate - early_rate`, with the two-proportion test of the difference.
+    pub rate_change: f64,
+    pub rate_change_z: f64,
+    pub rate_change_p_value: f64,
+    /// `count` and `successes` weighted by `--pair-weight`; empty without it.
+    pub weighted_count: Option<f64>,
+    pub weighted_successes: Option<f64>,
+    pub weighted_success_rate: Option<f64>,
+    /// Position of the gap on the wheels of the primorials 30 and 210.
+    pub wheel_30: u64,
+    pub wheel_210: u64,
+    /// The gap in units of the primorials 6, 30 and 210.
+    pub gap_over_6: f64,
+    pub gap_over_30: f64,
+    pub gap_over_210: f64,
+}
+
+/// One residue class of `p_prev` modulo a shield prime of a target gap, one row of
+/// `conditional_rates.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConditionalRateRecord {
+    pub gap_size: Gap,
+    pub shield_prime: u64,
+    pub residue: u64,
+    pub occurrences: u64,
+    pub successes: u64,
+    pub success_rate: f64,
+    pub success_ci_low: f64,
+    pub success_ci_high: f64,
+}
+
+/// Pairs of a target gap next to one neighbouring gap, with the neighbour before and after
+/// them, one row of `neighbor_gaps.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NeighborGapRecord {
+    pub gap_size: Gap,
+    pub neighbor_gap: u64,
+    /// Pairs whose preceding pair has `neighbor_gap`.
+    pub preceded_count: u64,
+    pub preceded_successes: u64,
+    pub preceded_rate: f64,
+    /// Pairs whose following pair has `neighbor_gap`.
+    pub followed_count: u64,
+    pub followed_successes: u64,
+    pub followed_rate: f64,
+    /// Two-proportion test of the preceded rate against the followed rate.
+    pub z_score: f64,
+    pub p_value: f64,
+}
+
+/// The first prime pair with a given gap, one row of `first_occurrences.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FirstOccurrenceRecord {
+    pub gap_size: Gap,
+    /// Smallest p with `nextprime(p) - p == gap_size`.
+    pub first_p: u64,
+    /// Whether the gap is a maximal gap: larger than every gap before it.
+    pub maximal: bool,
+}
+
+/// A prime pair with a gap of at least `--large-gap-threshold`, one row of
+/// `large_gap_events.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LargeGapEvent {
+    pub p_prev: u64,
+    pub p_curr: u64,
+    pub gap: u64,
+    pub s: u64,
+    pub s_is_prime: bool,
+    /// Smallest prime factor of S; empty when S is prime.
+    pub s_smallest_factor: Option<u64>,
+    /// Gaps of the neighbouring pairs; empty at the ends of the analyzed range.
+    pub prev_gap: Option<u64>,
+    pub next_gap: Option<u64>,
+}
+
+/// The gaps in one residue class modulo a `--gap-families` modulus, one row of
+/// `gap_families.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GapFamilyRecord {
+    pub modulus: u64,
+    pub residue: u64,
+    /// Distinct gap sizes seen in the family.
+    pub gaps: u64,
+    pub count: u64,
+    pub successes: u64,
+    pub success_rate: f64,
+    pub success_ci_low: f64,
+    pub success_ci_high: f64,
+    /// The gaps' `expected_rate_heuristic`, `theoretical_boost` and `exact_boost`, averaged
+    /// over the family's pairs.
+    pub expected_rate_heuristic: f64,
+    pub theoretical_boost: f64,
+    pub exact_boost: f64,
+}
+
+/// One gap size of the cumulative gap distribution, one row of `gap_cdf.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GapCdfRecord {
+    pub gap_size: Gap,
+    pub count: u64,
+    /// Share of all prime pairs with a gap of at most `gap_size`.
+    pub cdf: f64,
+    pub successes: u64,
+    /// Share of all prime sums S whose pair has a gap of at most `gap_size`.
+    pub success_cdf: f64,
+}
+
+/// π(n) at a power of ten, one row of `prime_counts.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrimeCountRecord {
+    pub n: u64,
+    pub prime_count: u64,
+}
+
+/// Counts and rate of one target gap within a bin.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BinGapRate {
+    pub gap: Gap,
+    pub occurrences: u64,
+    pub successes: u64,
+    pub rate: f64,
+}
+
+/// The number of runs of one length, one row of `streaks.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreakRecord {
+    /// `all` for the sequence of every pair, or a target gap for the pairs with that gap.
+    pub gap: String,
+    /// `success` for a run of prime sums S, `failure` for a run of composite ones.
+    pub outcome: String,
+    pub run_length: u64,
+    pub runs: u64,
+    /// Runs of this length expected if the outcomes were independent at the sequence's
+    /// overall success rate r: n (1 - r)² r^k for successes, n r² (1 - r)^k for failures.
+    pub expected_runs: f64,
+}
+
+/// One kind of edge event of a run, one row of `diagnostics.csv`. Every event is listed,
+/// with a count of 0 when it did not occur.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiagnosticRecord {
+    pub event: String,
+    pub count: u64,
+    pub detail: String,
+}
+
+impl DiagnosticRecord {
+    pub fn new(event: &str, count: u64, detail: String) -> Self {
+        Self {
+            event: event.to_string(),
+            count,
+            detail,
+        }
+    }
+}
+
+/// One bucket of the spacings between consecutive prime sums S, one row of
+/// `success_spacing.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpacingRecord {
+    /// `pairs` for the spacing in consecutive prime pairs, `distance` for S_next - S.
+    pub measure: String,
+    pub bucket_start: u64,
+    pub bucket_end: u64,
+    pub count: u64,
+    /// Count expected without clustering: geometric in pairs at the observed success rate,
+    /// exponential in distance at the observed mean distance.
+    pub expected: f64,
+}
+
+/// Exponential fit of one series of the gap spectrum's tail, one row of `tail_fit.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TailFitRecord {
+    /// `count` for the gap frequencies, `successes` for the prime sums among them.
+    pub series: String,
+    /// Smallest gap fitted: the even gap at ln N, the mean gap between primes up to N.
+    pub tail_start: u64,
+    pub gaps_fitted: u64,
+    /// A and λ of `A · 𝔖(g) · exp(-(g - tail_start) / λ)`, where 𝔖 is the
+    /// Hardy–Littlewood singular series; λ is about ln N under the Cramér model.
+    pub amplitude: f64,
+    pub decay_length: f64,
+    /// Of the weighted fit of `ln(count / 𝔖(g))`.
+    pub r_squared: f64,
+    /// Pearson χ² of the fitted gaps' values against the fit, per degree of freedom.
+    pub chi_square_per_dof: f64,
+    /// The gaps deviating most from the fit, as `gap:z` with the standardized residual
+    /// `(observed - fitted) / √fitted`, largest |z| first.
+    pub outliers: String,
+}
+
+/// How the sums S of one gap size factor, one row of `almost_prime_spectrum.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlmostPrimeRecord {
+    pub gap_size: u64,
+    pub count: u64,
+    /// Sums with Ω(S) = 1, 2, 3 and at least 4 prime factors.
+    pub prime: u64,
+    pub semiprime: u64,
+    pub almost_prime_3: u64,
+    pub higher: u64,
+    /// Sums whose cofactor was out of reach of `--factor-bound` (always 0 by default).
+    pub unresolved: u64,
+    pub prime_fraction: f64,
+    pub semiprime_fraction: f64,
+    pub almost_prime_3_fraction: f64,
+    pub shield_score: u32,
+    pub shield_primes: Vec<u32>,
+    /// The largest of `shield_primes`, empty without shields; `shield_score` counts them.
+    pub max_shield_prime: Option<u32>,
+}
+
+/// One target gap within one bin, one row of the long-format `gap_bins.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GapBinRecord {
+    pub bin_start: u64,
+    pub gap: Gap,
+    pub occurrences: u64,
+    pub successes: u64,
+    pub rate: f64,
+}
+
+/// One bin of the oscillation series. `gap_rates` follows the order of `Statistics::target_gaps`.
+/// The gap and merit quantiles come from the bin's sketches, within their relative accuracy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BinRecord {
+    pub bin_start: u64,
+    pub bin_end: u64,
+    pub prime_count_p: u64,
+    pub prime_count_s: u64,
+    pub ratio_s_p: f64,
+    pub gap_rates: Vec<BinGapRate>,
+    pub gap_p50: f64,
+    pub gap_p90: f64,
+    pub gap_p99: f64,
+    pub merit_p50: f64,
+    pub merit_p90: f64,
+    pub merit_p99: f64,
+}
+
+fn weighted_rate(successes: f64, trials: f64) -> f64 {
+    if trials > 0.0 {
+        successes / trials
+    } else {
+        0.0
+    }
+}
+
+fn rate(successes: u64, trials: u64) -> f64 {
+    if trials > 0 {
+        successes as f64 / trials as f64
+    } else {
+        0.0
+    }
+}
+
+impl BinRecord {
+    pub fn new(bin: &BinStats, target_gaps: &[Gap]) -> Self {
+        let gap_rates = target_gaps
+            .iter()
+            .zip(bin.gap_occurrences.iter().zip(&bin.gap_successes))
+            .map(|(&gap, (&occurrences, &successes))| BinGapRate {
+                gap,
+                occurrences,
+                successes,
+                rate: rate(successes, occurrences),
+            })
+            .collect();
+
+        Self {
+            bin_start: bin.bin_start,
+            bin_end: bin.bin_end,
+            prime_count_p: bin.prime_count_p,
+            prime_count_s: bin.prime_count_s,
+            ratio_s_p: rate(bin.prime_count_s, bin.prime_count_p),
+            gap_rates,
+            gap_p50: bin.gap_sketch.quantile(0.5),
+            gap_p90: bin.gap_sketch.quantile(0.9),
+            gap_p99: bin.gap_sketch.quantile(0.99),
+            merit_p50: bin.merit_sketch.quantile(0.5),
+            merit_p90: bin.merit_sketch.quantile(0.9),
+            merit_p99: bin.merit_sketch.quantile(0.99),
+        }
+    }
+
+    /// The gap and merit quantiles, in the order of `output::QUANTILE_COLUMNS`.
+    pub fn quantiles(&self) -> [f64; 6] {
+        [
+            self.gap_p50,
+            self.gap_p90,
+            self.gap_p99,
+            self.merit_p50,
+            self.merit_p90,
+            self.merit_p99,
+        ]
+    }
+
+    /// The bin in long format, one record per target gap.
+    pub fn gap_records(&self) -> impl Iterator<Item = GapBinRecord> + '_ {
+        self.gap_rates.iter().map(|g| GapBinRecord {
+            bin_start: self.bin_start,
+            gap: g.gap,
+            occurrences: g.occurrences,
+            successes: g.successes,
+            rate: g.rate,
+        })
+    }
+}
+
+impl Statistics {
+    pub fn global_stats(&self) -> GlobalStats {
+        let (overflow_count, overflow_successes) = self.gap_spectrum.overflow();
+
+        // Binomial standard error of the scaled S count, with the finite population
+        // correction for drawing 1 in k of the pairs.
+        let k = self.sample_every as f64;
+        let sampled_pairs = self.total_primes.saturating_sub(1) as f64 / k;
+        let sampled_rate = self.total_s_primes as f64 / k / sampled_pairs.max(1.0);
+        let total_primes_s_std_error = k
+            * (sampled_pairs * sampled_rate * (1.0 - sampled_rate) * (1.0 - 1.0 / k))
+                .max(0.0)
+                .sqrt();
+        let weighted = self.pair_weight.map(|_| {
+            self.weighted_spectrum
+                .iter()
+                .fold((0.0, 0.0), |(c, s), &(count, successes)| {
+                    (c + count, s + successes)
+                })
+        });
+
+        GlobalStats {
+            total_primes_p: self.total_primes,
+            total_primes_s: self.total_s_primes,
+            global_ratio_s_p: rate(self.total_s_primes, self.total_primes),
+            spectrum_max_gap: self.gap_spectrum.max_dense_gap(),
+            spectrum_overflow_count: overflow_count,
+            spectrum_overflow_successes: overflow_successes,
+            sample_every: self.sample_every,
+            total_primes_s_std_error,
+            weighted_pairs: weighted.map(|(pairs, _)| pairs),
+            weighted_primes_s: weighted.map(|(_, successes)| successes),
+            weighted_ratio_s_p: weighted.map(|(pairs, successes)| weighted_rate(successes, pairs)),
+        }
+    }
+
+    /// Every observed gap in ascending order, with rates, confidence intervals, shielding
+    /// under `model`, the exact boost from `residues`, and the expected rate under `baseline`.
+    pub fn iter_gap_records<'a>(
+        &'a self,
+        model: ShieldModel,
+        residues: &'a ResidueModel,
+        baseline: Baseline,
+    ) -> impl Iterator<Item = GapSpectrumRecord> + 'a {
+        // Expectations are for the range analyzed, so partial results and snapshots get
+        // their own. Every baseline but `hl` is the same for all gaps.
+        let n = self.analyzed_up_to.min(self.max_n());
+        let local_rate = stats::baseline_rate(baseline, n, 1.0);
+
+        // p-values test each gap against the pooled success rate of all prime pairs.
+        let (total_count, total_successes) = self
+            .gap_spectrum
+            .iter()
+            .fold((0, 0), |(c, s), (_, count, successes)| {
+                (c + count, s + successes)
+            });
+        let pooled_rate = rate(total_successes, total_count);
+
+        self.gap_spectrum
+            .iter()
+            .map(move |(gap_size, count, successes)| {
+                let shielding_info = calculate_shielding_info(gap_size.0, model);
+                let exact_boost = residues.boost(gap_size.0);
+                // The sum S of an odd gap (only 1, between 2 and 3) is even, never prime.
+                let expected_rate = match baseline {
+                    _ if gap_size.0 % 2 == 1 => 0.0,
+                    Baseline::Hl => exact_boost * local_rate,
+                    _ => local_rate,
+                };
+                // Intervals and tests use the number of pairs actually analyzed.
+                let (sampled, sampled_successes) =
+                    (count / self.sample_every, successes / self.sample_every);
+                let (success_ci_low, success_ci_high) =
+                    stats::wilson_interval(sampled_successes, sampled, stats::Z_95);
+                let square_free = self
+                    .square_free
+                    .get(gap_size.0 as usize)
+                    .copied()
+                    .unwrap_or(0);
+                let (early_count, early_successes) = self.early_spectrum.get(gap_size);
+                let (late_count, late_successes) =
+                    (count - early_count, successes - early_successes);
+                let (early_rate, late_rate) = (
+                    rate(early_successes, early_count),
+                    rate(late_successes, late_count),
+                );
+                let halves = [early_successes, early_count, late_successes, late_count]
+                    .map(|n| n / self.sample_every);
+                let weighted = self.pair_weight.map(|_| {
+                    self.weighted_spectrum
+                        .get(gap_size.0 as usize)
+                        .copied()
+                        .unwrap_or((0.0, 0.0))
+                });
+                GapSpectrumRecord {
+                    gap_size,
+                    count,
+                    successes,
+                    success_rate: rate(successes, count),
+                    success_ci_low,
+                    success_ci_high,
+                    p_value: stats::proportion_p_value(sampled_successes, sampled, pooled_rate),
+                    expected_rate_heuristic: expected_rate,
+                    baseline: baseline.label(),
+                    expected_count_hl: stats::hardy_littlewood_gap_count(gap_size.0, n),
+                    shield_score: shielding_info.shield_score,
+                    shield_primes: shielding_info.shield_primes,
+                    max_shield_prime: shielding_info.max_shield_prime,
+                    theoretical_boost: shielding_info.theoretical_boost,
+                    theoretical_boost_fraction: shielding_info.theoretical_boost_exact.to_string(),
+                    shield_model: shielding_info.shield_model,
+                    exact_boost,
+                    out_of_range: self.out_of_range.get(&gap_size.0).copied().unwrap_or(0),
+                    square_free,
+                    square_free_rate: rate(square_free, count),
+                    expected_square_free_rate: shielding::expected_square_free_rate(gap_size.0),
+                    early_count,
+                    early_successes,
+                    early_rate,
+                    late_count,
+                    late_successes,
+                    late_rate,
+                    rate_change: late_rate - early_rate,
+                    rate_change_z: stats::two_proportion_z_score(
+                        halves[2], halves[3], halves[0], halves[1],
+                    ),
+                    rate_change_p_value: stats::two_proportion_p_value(
+                        halves[2], halves[3], halves[0], halves[1],
+                    ),
+                    weighted_count: weighted.map(|(count, _)| count),
+                    weighted_successes: weighted.map(|(_, successes)| successes),
+                    weighted_success_rate: weighted
+                        .map(|(count, successes)| weighted_rate(successes, count)),
+                    wheel_30: gap_size.0 % 30,
+                    wheel_210: gap_size.0 % 210,
+                    gap_over_6: gap_size.0 as f64 / 6.0,
+                    gap_over_30: gap_size.0 as f64 / 30.0,
+                    gap_over_210: gap_size.0 as f64 / 210.0,
+                }
+            })
+    }
+
+    /// The gap spectrum pooled into the residue classes of each modulus in `moduli`, by
+    /// modulus and then residue. Classes without a gap are left out.
+    pub fn gap_family_records(
+        &self,
+        moduli: &[u64],
+        model: ShieldModel,
+        residues: &ResidueModel,
+        baseline: Baseline,
+    ) -> Vec<GapFamilyRecord> {
+        let gaps: Vec<GapSpectrumRecord> =
+            self.iter_gap_records(model, residues, baseline).collect();
+        let mut records = Vec::new();
+        for &modulus in moduli {
+            let mut families: BTreeMap<u64, Vec<&GapSpectrumRecord>> = BTreeMap::new();
+            for gap in &gaps {
+                families
+                    .entry(gap.gap_size.0 % modulus)
+                    .or_default()
+                    .push(gap);
+            }
+            for (residue, members) in families {
+                let count: u64 = members.iter().map(|g| g.count).sum();
+                let successes: u64 = members.iter().map(|g| g.successes).sum();
+                let mean = |value: fn(&GapSpectrumRecord) -> f64| {
+                    let total: f64 = members.iter().map(|g| value(g) * g.count as f64).sum();
+                    if count > 0 {
+                        total / count as f64
+                    } else {
+                        0.0
+                    }
+                };
+                let (success_ci_low, success_ci_high) = stats::wilson_interval(
+                    successes / self.sample_every,
+                    count / self.sample_every,
+                    stats::Z_95,
+                );
+                records.push(GapFamilyRecord {
+                    modulus,
+                    residue,
+                    gaps: members.len() as u64,
+                    count,
+                    successes,
+                    success_rate: rate(successes, count),
+                    success_ci_low,
+                    success_ci_high,
+                    expected_rate_heuristic: mean(|g| g.expected_rate_heuristic),
+                    theoretical_boost: mean(|g| g.theoretical_boost),
+                    exact_boost: mean(|g| g.exact_boost),
+                });
+            }
+        }
+        records
+    }
+
+    /// The cumulative distributions of gap sizes and of the gaps of prime sums, in ascending
+    /// gap order.
+    pub fn gap_cdf_records(&self) -> Vec<GapCdfRecord> {
+        let (total_count, total_successes) = self
+            .gap_spectrum
+            .iter()
+            .fold((0, 0), |(c, s), (_, count, successes)| {
+                (c + count, s + successes)
+            });
+        let (mut count_so_far, mut successes_so_far) = (0, 0);
+        self.gap_spectrum
+            .iter()
+            .map(|(gap_size, count, successes)| {
+                count_so_far += count;
+                successes_so_far += successes;
+                GapCdfRecord {
+                    gap_size,
+                    count,
+                    cdf: rate(count_so_far, total_count),
+                    successes,
+                    success_cdf: rate(successes_so_far, total_successes),
+                }
+            })
+            .collect()
+    }
+
+    /// Every gap seen so far with its first occurrence, in ascending gap order.
+    pub fn first_occurrence_records(&self) -> Vec<FirstOccurrenceRecord> {
+        let mut records: Vec<FirstOccurrenceRecord> = self
+            .first_occurrences
+            .iter()
+            .enumerate()
+            .filter(|&(_, &p)| p > 0)
+            .map(|(gap, &first_p)| FirstOccurrenceRecord {
+                gap_size: Gap(gap as u64),
+                first_p,
+                maximal: false,
+            })
+            .collect();
+        // A gap is maximal when every larger gap first occurs after it.
+        let mut earliest_larger = u64::MAX;
+        for record in records.iter_mut().rev() {
+            record.maximal = record.first_p < earliest_larger;
+            earliest_larger = earliest_larger.min(record.first_p);
+        }
+        records
+    }
+
+    /// π(10^k) for every power of ten the analysis has covered.
+    pub fn iter_prime_count_records(&self) -> impl Iterator<Item = PrimeCountRecord> {
+        self.prime_counts_at_powers_of_ten()
+            .into_iter()
+            .map(|(n, prime_count)| PrimeCountRecord { n, prime_count })
+    }
+
+    /// Success rates of every target gap split by `p_prev mod q`, for each of its shield
+    /// primes `q`, in gap, prime and residue order.
+    pub fn iter_conditional_records(&self) -> impl Iterator<Item = ConditionalRateRecord> + '_ {
+        self.target_gaps
+            .iter()
+            .zip(&self.conditional)
+            .flat_map(move |(&gap_size, tables)| {
+                tables.iter().flat_map(move |table| {
+                    table.counts.iter().enumerate().map(
+                        move |(residue, &(occurrences, successes))| {
+                            let (success_ci_low, success_ci_high) = stats::wilson_interval(
+                                successes / self.sample_every,
+                                occurrences / self.sample_every,
+                                stats::Z_95,
+                            );
+                            ConditionalRateRecord {
+                                gap_size,
+                                shield_prime: table.modulus,
+                                residue: residue as u64,
+                                occurrences,
+                                successes,
+                                success_rate: rate(successes, occurrences),
+                                success_ci_low,
+                                success_ci_high,
+                            }
+                        },
+                    )
+                })
+            })
+    }
+
+    /// Success rates of every target gap by the gap of the pair before and of the pair
+    /// after, in gap and neighbouring gap order.
+    pub fn iter_neighbor_gap_records(&self) -> impl Iterator<Item = NeighborGapRecord> + '_ {
+        self.target_gaps
+            .iter()
+            .zip(&self.neighbor_gaps)
+            .flat_map(move |(&gap_size, neighbors)| {
+                neighbors.iter().map(move |(&neighbor_gap, counts)| {
+                    let (preceded_count, preceded_successes) = counts.preceded;
+                    let (followed_count, followed_successes) = counts.followed;
+                    let sampled = [
+                        preceded_successes,
+                        preceded_count,
+                        followed_successes,
+                        followed_count,
+                    ]
+                    .map(|n| n / self.sample_every);
+                    NeighborGapRecord {
+                        gap_size,
+                        neighbor_gap,
+                        preceded_count,
+                        preceded_successes,
+                        preceded_rate: rate(preceded_successes, preceded_count),
+                        followed_count,
+                        followed_successes,
+                        followed_rate: rate(followed_successes, followed_count),
+                        z_score: stats::two_proportion_z_score(
+                            sampled[0], sampled[1], sampled[2], sampled[3],
+                        ),
+                        p_value: stats::two_proportion_p_value(
+                            sampled[0], sampled[1], sampled[2], sampled[3],
+                        ),
+                    }
+                })
+            })
+    }
+
+    /// Run lengths of successes and failures, over every pair and then per target gap.
+    pub fn streak_records(&self) -> Vec<StreakRecord> {
+        let mut records = streak_records("all".to_string(), &self.streaks);
+        for (gap, runs) in self.target_gaps.iter().zip(&self.gap_streaks) {
+            records.extend(streak_records(gap.to_string(), runs));
+        }
+        records
+    }
+
+    /// Exponential fits of the gap spectrum's tail, of the gap counts and of their successes.
+    /// Gaps seen fewer than [`TAIL_MIN_COUNT`] times are left out of a fit.
+    pub fn tail_fit_records(&self) -> Vec<TailFitRecord> {
+        let n = self.analyzed_up_to.min(self.max_n());
+        let tail_start = ((n.max(2) as f64).ln().round() as u64)
+            .next_multiple_of(2)
+            .max(2);
+        let spectrum: Vec<(u64, [u64; 2])> = self
+            .gap_spectrum
+            .iter()
+            .filter(|(gap, _, _)| gap.0 >= tail_start && gap.0 % 2 == 0)
+            .map(|(gap, count, successes)| (gap.0, [count, successes]))
+            .collect();
+
+        let mut records = Vec::new();
+        for (column, series) in ["count", "successes"].into_iter().enumerate() {
+            let observed: Vec<(u64, f64)> = spectrum
+                .iter()
+                .filter(|(_, values)| values[column] >= TAIL_MIN_COUNT)
+                .map(|(g, values)| (*g, values[column] as f64))
+                .collect();
+            let points: Vec<(f64, f64, f64)> = observed
+                .iter()
+                .map(|&(g, v)| {
+                    let x = (g - tail_start) as f64;
+                    (x, (v / stats::singular_series(g)).ln(), v)
+                })
+                .collect();
+            let Some((intercept, slope, r_squared)) = stats::weighted_linear_fit(&points) else {
+                continue;
+            };
+            let fitted = |g: u64| {
+                stats::singular_series(g) * (intercept + slope * (g - tail_start) as f64).exp()
+            };
+            let mut residuals: Vec<(u64, f64)> = observed
+                .iter()
+                .map(|&(g, v)| (g, (v - fitted(g)) / fitted(g).sqrt()))
+                .collect();
+            let chi_square: f64 = residuals.iter().map(|(_, z)| z * z).sum();
+            residuals.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+            records.push(TailFitRecord {
+                series: series.to_string(),
+                tail_start,
+                gaps_fitted: observed.len() as u64,
+                amplitude: intercept.exp(),
+                decay_length: -1.0 / slope,
+                r_squared,
+                chi_square_per_dof: chi_square / (observed.len() as f64 - 2.0).max(1.0),
+                outliers: residuals
+                    .iter()
+                    .take(TAIL_OUTLIERS)
+                    .map(|(g, z)| format!("{}:{:+.2}", g, z))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            });
+        }
+        records
+    }
+
+    /// The Ω(S) classes of the sums of each gap size seen with `--almost-primes`.
+    pub fn almost_prime_records(&self, model: ShieldModel) -> Vec<AlmostPrimeRecord> {
+        self.almost_primes
+            .iter()
+            .map(|(&gap_size, classes)| {
+                let count = classes.iter().sum();
+                let info = calculate_shielding_info(gap_size, model);
+                AlmostPrimeRecord {
+                    gap_size,
+                    count,
+                    prime: classes[FactorClass::Prime.index()],
+                    semiprime: classes[FactorClass::Semiprime.index()],
+                    almost_prime_3: classes[FactorClass::AlmostPrime3.index()],
+                    higher: classes[FactorClass::Higher.index()],
+                    unresolved: classes[FactorClass::Unresolved.index()],
+                    prime_fraction: rate(classes[FactorClass::Prime.index()], count),
+                    semiprime_fraction: rate(classes[FactorClass::Semiprime.index()], count),
+                    almost_prime_3_fraction: rate(
+                        classes[FactorClass::AlmostPrime3.index()],
+                        count,
+                    ),
+                    shield_score: info.shield_score,
+                    shield_primes: info.shield_primes,
+                    max_shield_prime: info.max_shield_prime,
+                }
+            })
+            .collect()
+    }
+
+    /// The edge events recorded in the statistics: values that fell outside the bins, the
+    /// primality checker or the dense gap spectrum.
+    pub fn diagnostic_records(&self) -> Vec<DiagnosticRecord> {
+        let (overflow_count, _) = self.gap_spectrum.overflow();
+        let overflow_gaps: Vec<u64> = self
+            .gap_spectrum
+            .iter()
+            .map(|(gap, _, _)| gap.0)
+            .filter(|&g| g > self.gap_spectrum.max_dense_gap())
+            .collect();
+        vec![
+            DiagnosticRecord::new(
+                "unbinned_primes",
+                self.unbinned_primes,
+                "primes p above the last bin, missing from oscillation_series.csv".to_string(),
+            ),
+            DiagnosticRecord::new(
+                "unbinned_sums",
+                self.unbinned_sums,
+                "prime sums S above the last bin, missing from oscillation_series.csv".to_string(),
+            ),
+            DiagnosticRecord::new(
+                "s_out_of_range",
+                self.total_out_of_range(),
+                format!(
+                    "sums S above the primality checker's limit, in {} gap sizes",
+                    self.out_of_range.len()
+                ),
+            ),
+            DiagnosticRecord::new(
+                "spectrum_overflow",
+                overflow_count,
+                format!(
+                    "gaps above --spectrum-max-gap {} ({} sizes, largest {})",
+                    self.gap_spectrum.max_dense_gap(),
+                    overflow_gaps.len(),
+                    overflow_gaps.last().copied().unwrap_or(0)
+                ),
+            ),
+        ]
+    }
+
+    /// The spacing histograms between consecutive successes, in pairs and then in distance.
+    pub fn spacing_records(&self) -> Vec<SpacingRecord> {
+        let spacing = &self.success_spacing;
+        let (spacings, total_pairs) =
+            spacing.by_pairs().fold((0, 0), |(n, sum), (pairs, count)| {
+                (n + count, sum + pairs * count)
+            });
+        // Maximum-likelihood success rate of a geometric distribution.
+        let r = rate(spacings, total_pairs);
+        let mut records: Vec<SpacingRecord> = spacing
+            .by_pairs()
+            .map(|(pairs, count)| SpacingRecord {
+                measure: "pairs".to_string(),
+                bucket_start: pairs,
+                bucket_end: pairs,
+                count,
+                expected: spacings as f64 * r * (1.0 - r).powi(pairs as i32 - 1),
+            })
+            .collect();
+        let mean = spacing.mean_distance().unwrap_or(1.0);
+        records.extend(
+            spacing
+                .by_distance()
+                .map(|(bucket_start, bucket_end, count)| SpacingRecord {
+                    measure: "distance".to_string(),
+                    bucket_start,
+                    bucket_end,
+                    count,
+                    expected: spacings as f64
+                        * ((-(bucket_start as f64) / mean).exp()
+                            - (-(bucket_end as f64 + 1.0) / mean).exp()),
+                }),
+        );
+        records
+    }
+
+    /// The bins currently held in memory, in order. With `--stream-bins` this is only
+    /// the live window; flushed bins have already been written out.
+    pub fn iter_bin_records(&self) -> impl Iterator<Item = BinRecord> + '_ {
+        self.bins
+            .iter()
+            .map(|bin| BinRecord::new(bin, &self.target_gaps))
+    }
+}
+
+fn streak_records(gap: String, runs: &RunLengths) -> Vec<StreakRecord> {
+    let (mut trials, mut successes) = (0, 0);
+    for (success, length, count) in runs.iter() {
+        trials += length * count;
+        if success {
+            successes += length * count;
+        }
+    }
+    let r = rate(successes, trials);
+    runs.iter()
+        .map(|(success, run_length, count)| {
+            let (stay, leave) = if success { (r, 1.0 - r) } else { (1.0 - r, r) };
+            StreakRecord {
+                gap: gap.clone(),
+                outcome: if success { "success" } else { "failure" }.to_string(),
+                run_length,
+                runs: count,
+                expected_runs: trials as f64 * leave * leave * stay.powi(run_length as i32),
+            }
+        })
+        .collect()
+}
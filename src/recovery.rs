@@ -0,0 +1,292 @@
+//! Crash safety for long runs: periodic checkpoints of the full statistics plus an
+//! append-only write-ahead log (WAL) of per-segment records written in between.
+//!
+//! A checkpoint is a regular state file (see [`Statistics::save`]) replaced atomically.
+//! The WAL starts with a header naming the `analyzed_up_to` of the checkpoint it extends;
+//! each record is `len: u32 | checksum: u64 | payload` and is synced before the analysis
+//! continues, so after a crash at most the segment in flight is lost. A record carries only
+//! what changed since the previous one, and replay folds the records in order; a torn or
+//! corrupt trailing record is simply ignored.
+
+use crate::stats::Statistics;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const CHECKPOINT_FILE: &str = "checkpoint.state";
+pub const WAL_FILE: &str = "analysis.wal";
+
+const WAL_MAGIC: &[u8; 8] = b"PSAWAL\0\0";
+const WAL_FORMAT_VERSION: u32 = 2;
+const WAL_HEADER_LEN: usize = WAL_MAGIC.len() + 4 + 8;
+const RECORD_HEADER_LEN: usize = 4 + 8;
+
+fn checkpoint_path(output_dir: &str) -> PathBuf {
+    Path::new(output_dir).join(CHECKPOINT_FILE)
+}
+
+fn wal_path(output_dir: &str) -> PathBuf {
+    Path::new(output_dir).join(WAL_FILE)
+}
+
+/// 64-bit FNV-1a, enough to detect a torn or partially flushed record.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Atomically replaces the checkpoint in `output_dir` with the current statistics.
+pub fn write_checkpoint(stats: &Statistics, output_dir: &str) -> Result<(), Box<dyn Error>> {
+    let path = checkpoint_path(output_dir);
+    let tmp = path.with_extension("state.tmp");
+    stats.save(&tmp)?;
+    File::open(&tmp)?.sync_all()?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Appends WAL records for the segments analyzed since the last checkpoint.
+pub struct WalWriter {
+    file: File,
+}
+
+impl WalWriter {
+    /// Starts a new, empty log on top of a checkpoint (or fresh state) at `stats.analyzed_up_to`.
+    pub fn create(output_dir: &str, stats: &mut Statistics) -> Result<Self, Box<dyn Error>> {
+        stats.start_wal();
+        let mut file = File::create(wal_path(output_dir))?;
+        file.write_all(WAL_MAGIC)?;
+        file.write_all(&WAL_FORMAT_VERSION.to_le_bytes())?;
+        file.write_all(&stats.analyzed_up_to.to_le_bytes())?;
+        file.sync_data()?;
+        Ok(Self { file })
+    }
+
+    /// Logs everything that changed since the previous record and syncs it to disk.
+    pub fn append(&mut self, stats: &mut Statistics) -> Result<(), Box<dyn Error>> {
+        let payload = stats.encode_wal_record()?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&u32::try_from(payload.len())?.to_le_bytes());
+        record.extend_from_slice(&checksum(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Outcome of [`recover`].
+pub struct Recovered {
+    pub stats: Statistics,
+    pub from_checkpoint: bool,
+    pub wal_records: usize,
+}
+
+/// Rebuilds the statistics of an interrupted run in `output_dir`: the checkpoint if
+/// there is one (otherwise `fresh`), followed by every intact WAL record logged on top
+/// of it. The checkpoint must describe the same analysis as `fresh`.
+pub fn recover(output_dir: &str, fresh: Statistics) -> Result<Recovered, Box<dyn Error>> {
+    let path = checkpoint_path(output_dir);
+    let from_checkpoint = path.exists();
+    let mut stats = if from_checkpoint {
+        let stats = Statistics::load(&path)?;
+        if stats.max_n() != fresh.max_n()
+            || stats.num_bins() != fresh.num_bins()
+            || stats.target_gaps != fresh.target_gaps
+            || stats.sample_every != fresh.sample_every
+            || stats.sample_seed != fresh.sample_seed
+            || stats.counts_almost_primes != fresh.counts_almost_primes
+            || stats.shield_model != fresh.shield_model
+            || stats.pair_weight != fresh.pair_weight
+            || stats.large_gap_threshold != fresh.large_gap_threshold
+            || stats.split_at != fresh.split_at
+            || stats.shard != fresh.shard
+            || stats.attribute_by != fresh.attribute_by
+            || stats.bin_domain != fresh.bin_domain
+            || stats.gap_spectrum.max_dense_gap() != fresh.gap_spectrum.max_dense_gap()
+        {
+            return Err(format!(
+                "{} was written for a different analysis (max N, bins, gaps, --spectrum-max-gap, --sample, --sample-seed, --almost-primes, the shield model, --pair-weight, --large-gap-threshold, --split-at, --shard, --attribute-by or --bin-domain differ)",
+                path.display()
+            )
+            .into());
+        }
+        stats
+    } else {
+        fresh
+    };
+
+    let mut wal_records = 0;
+    let wal = wal_path(output_dir);
+    if wal.exists() {
+        let bytes = fs::read(&wal)?;
+        let valid_header = bytes.len() >= WAL_HEADER_LEN
+            && &bytes[..WAL_MAGIC.len()] == WAL_MAGIC
+            && bytes[WAL_MAGIC.len()..WAL_MAGIC.len() + 4] == WAL_FORMAT_VERSION.to_le_bytes();
+        let base = bytes
+            .get(WAL_MAGIC.len() + 4..WAL_HEADER_LEN)
+            .filter(|_| valid_header)
+            .map(|b| u64::from_le_bytes(b.try_into().expect("8-byte slice")));
+
+        // A log based on an older state predates the checkpoint and is already contained in it.
+        if base == Some(stats.analyzed_up_to) {
+            let mut pos = WAL_HEADER_LEN;
+            while let Some(header) = bytes.get(pos..pos + RECORD_HEADER_LEN) {
+                let len = u32::from_le_bytes(header[..4].try_into()?) as usize;
+                let sum = u64::from_le_bytes(header[4..].try_into()?);
+                let start = pos + RECORD_HEADER_LEN;
+                let Some(payload) = bytes.get(start..start + len) else {
+                    break;
+                };
+                if checksum(payload) != sum {
+                    break;
+                }
+                stats = stats.apply_wal_record(payload)?;
+                wal_records += 1;
+                pos = start + len;
+            }
+        }
+    }
+
+    Ok(Recovered {
+        stats,
+        from_checkpoint,
+        wal_records,
+    })
+}
+
+/// Removes the checkpoint and WAL once a run has completed.
+pub fn clear(output_dir: &str) -> Result<(), Box<dyn Error>> {
+    for path in [checkpoint_path(output_dir), wal_path(output_dir)] {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::{is_prime_u64, PrimeIterator};
+    use crate::stats::{Gap, Prime};
+
+    const MAX_N: u64 = 20_000;
+
+    fn fresh() -> Statistics {
+        Statistics::new(MAX_N, 20, &[Gap(2), Gap(4), Gap(6)], 64)
+    }
+
+    /// Records the pairs of consecutive primes whose first prime lies in `[from, to)`.
+    fn record_pairs(stats: &mut Statistics, primes: &[u64], from: u64, to: u64) {
+        for w in primes.windows(2).filter(|w| (from..to).contains(&w[0])) {
+            let s = w[0] + w[1] - 1;
+            stats.record_pair(Prime(w[0]), Prime(w[1]), s, is_prime_u64(s));
+        }
+    }
+
+    fn state_bytes(stats: &Statistics, dir: &Path) -> Vec<u8> {
+        let path = dir.join("compare.state");
+        stats.save(&path).unwrap();
+        fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn test_resume_after_a_torn_wal_record_matches_an_uninterrupted_run() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let primes: Vec<u64> = PrimeIterator::new(MAX_N, 1024).collect();
+        let quarters: Vec<u64> = (0..4).map(|i| primes[primes.len() * i / 4]).collect();
+        let bounds = [quarters[1], quarters[2], quarters[3], MAX_N];
+
+        let mut uninterrupted = fresh();
+        uninterrupted.record_first_prime();
+        record_pairs(&mut uninterrupted, &primes, 0, MAX_N);
+        uninterrupted.analyzed_up_to = MAX_N;
+
+        // Checkpoint after the first quarter and log one record per further quarter, the
+        // last of them torn by a crash.
+        let mut stats = fresh();
+        stats.record_first_prime();
+        record_pairs(&mut stats, &primes, 0, bounds[0]);
+        write_checkpoint(&stats, dir).unwrap();
+        let mut wal = WalWriter::create(dir, &mut stats).unwrap();
+        for w in bounds.windows(2) {
+            record_pairs(&mut stats, &primes, w[0], w[1]);
+            wal.append(&mut stats).unwrap();
+        }
+        let log = wal_path(dir);
+        let torn = fs::metadata(&log).unwrap().len() - 5;
+        let file = File::options().write(true).open(&log).unwrap();
+        file.set_len(torn).unwrap();
+
+        let recovered = recover(dir, fresh()).unwrap();
+        assert!(recovered.from_checkpoint);
+        assert_eq!(recovered.wal_records, 2);
+        let mut resumed = recovered.stats;
+        assert_eq!(resumed.analyzed_up_to, bounds[2]);
+        record_pairs(&mut resumed, &primes, bounds[2], MAX_N);
+        resumed.analyzed_up_to = MAX_N;
+
+        assert_eq!(
+            state_bytes(&resumed, temp_dir.path()),
+            state_bytes(&uninterrupted, temp_dir.path())
+        );
+    }
+
+    #[test]
+    fn test_wal_records_carry_only_what_changed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let primes: Vec<u64> = PrimeIterator::new(MAX_N, 1024).collect();
+        let wide = || {
+            let mut stats = Statistics::new(MAX_N, 20, &[Gap(2), Gap(4)], 4096);
+            stats.record_first_prime();
+            stats
+        };
+        let mut uninterrupted = wide();
+        record_pairs(&mut uninterrupted, &primes, 0, MAX_N);
+
+        let mut stats = wide();
+        record_pairs(&mut stats, &primes, 0, MAX_N / 2);
+        write_checkpoint(&stats, dir).unwrap();
+        let mut wal = WalWriter::create(dir, &mut stats).unwrap();
+        let logged = || fs::metadata(wal_path(dir)).unwrap().len();
+
+        // A segment without pairs changes no entry of the 4097-gap spectrum.
+        let before = logged();
+        wal.append(&mut stats).unwrap();
+        assert!(logged() - before < 1024, "{} bytes", logged() - before);
+        record_pairs(&mut stats, &primes, MAX_N / 2, MAX_N);
+        wal.append(&mut stats).unwrap();
+
+        let recovered = recover(dir, wide()).unwrap();
+        assert_eq!(recovered.wal_records, 2);
+        assert_eq!(
+            state_bytes(&recovered.stats, temp_dir.path()),
+            state_bytes(&uninterrupted, temp_dir.path())
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_of_another_analysis_is_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        write_checkpoint(&fresh(), dir).unwrap();
+        assert!(recover(dir, fresh()).is_ok());
+
+        let changes: [fn(&mut Statistics); 3] = [
+            |stats| stats.counts_almost_primes = true,
+            |stats| stats.shield_model = crate::shielding::ShieldModel::FULL,
+            |stats| stats.sample_seed = Some(7),
+        ];
+        for change in changes {
+            let mut other = fresh();
+            change(&mut other);
+            let err = recover(dir, other).err().expect("a different analysis");
+            assert!(err.to_string().contains("different analysis"));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! A lightweight registry of completed runs (`runs.json`), used by the `trends`
+//! subcommand to show how the statistics evolve as max N grows.
+
+use crate::config::{Config, Exponent};
+use crate::stats::Statistics;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Summary of one run as recorded in the registry.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunEntry {
+    /// Seconds since the Unix epoch when the run was registered.
+    pub recorded_at: u64,
+    /// Canonical path of the output directory; older registries may hold the path as given
+    /// on the command line.
+    pub output_dir: String,
+    pub max_exponent: Exponent,
+    pub max_n: u64,
+    pub complete: bool,
+    pub sample_every: u64,
+    pub total_primes_p: u64,
+    pub total_primes_s: u64,
+    pub global_ratio_s_p: f64,
+    /// `--label` and `--meta` of the run; registries from before the flags lack them.
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+    /// Success rate of each tracked gap, keyed by gap size.
+    pub gap_rates: BTreeMap<u64, f64>,
+}
+
+impl RunEntry {
+    pub fn new(stats: &Statistics, config: &Config) -> Self {
+        let global = stats.global_stats();
+        let gap_rates = stats
+            .gap_spectrum
+            .iter()
+            .filter(|(gap, count, _)| *count > 0 && stats.target_gaps.contains(gap))
+            .map(|(gap, count, successes)| (gap.0, successes as f64 / count as f64))
+            .collect();
+        Self {
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            output_dir: fs::canonicalize(&config.output_dir).map_or_else(
+                |_| config.output_dir.clone(),
+                |path| path.to_string_lossy().into_owned(),
+            ),
+            max_exponent: config.max_exponent(),
+            max_n: stats.max_n(),
+            complete: stats.is_complete(),
+            sample_every: stats.sample_every,
+            total_primes_p: global.total_primes_p,
+            total_primes_s: global.total_primes_s,
+            global_ratio_s_p: global.global_ratio_s_p,
+            label: config.label.clone(),
+            meta: config.meta.iter().cloned().collect(),
+            gap_rates,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Registry {
+    pub runs: Vec<RunEntry>,
+}
+
+impl Registry {
+    /// Reads the registry at `path`; a missing file is an empty registry.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Adds `entry` to the registry at `path`, replacing an earlier run of the same output directory.
+    ///
+    /// Runs sharing a registry may finish at the same time: the update holds an exclusive
+    /// lock on `<path>.lock`, so none of them loses the entry of another, and the new
+    /// registry is written beside the old one and renamed over it, so a reader never sees
+    /// half of it.
+    pub fn record<P: AsRef<Path>>(path: P, entry: RunEntry) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(with_suffix(path, ".lock"))?;
+        fs4::FileExt::lock(&lock)?;
+        let mut registry = Self::load(path)?;
+        registry.runs.retain(|r| r.output_dir != entry.output_dir);
+        registry.runs.push(entry);
+        let staged = with_suffix(path, ".tmp");
+        fs::write(&staged, serde_json::to_string_pretty(&registry)?)?;
+        fs::rename(staged, path)?;
+        Ok(())
+    }
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `runs.json.lock`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_records_keep_every_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("runs.json");
+        let entry = |i: u64| RunEntry {
+            recorded_at: i,
+            output_dir: format!("/runs/{}", i),
+            max_exponent: Exponent(6.0),
+            max_n: 1_000_000,
+            complete: true,
+            sample_every: 1,
+            total_primes_p: 78_498,
+            total_primes_s: 0,
+            global_ratio_s_p: 0.0,
+            label: None,
+            meta: BTreeMap::new(),
+            gap_rates: BTreeMap::new(),
+        };
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = &path;
+                scope.spawn(move || Registry::record(path, entry(i)).unwrap());
+            }
+        });
+        let mut dirs: Vec<String> = Registry::load(&path)
+            .unwrap()
+            .runs
+            .into_iter()
+            .map(|run| run.output_dir)
+            .collect();
+        dirs.sort();
+        assert_eq!(
+            dirs,
+            (0..8).map(|i| format!("/runs/{}", i)).collect::<Vec<_>>()
+        );
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+}
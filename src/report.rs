@@ -1,77 +1,869 @@
-use crate::config::Config;
+use crate::config::{Baseline, Config, Exponent, SpectrumChartMax, Theme, SPECTRUM_CHART_COVERAGE};
+use crate::output::{self, RATE_CORRELATIONS_FILE};
+use crate::records::GapBinRecord;
+use crate::shielding::ShieldModel;
+use crate::stats;
+use crate::throughput::{ThroughputRecord, THROUGHPUT_FILE};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Debug)]
 struct GapSpectrumData {
     gap_size: u64,
+    count: u64,
+    successes: u64,
     success_rate: f64,
+    success_ci_low: f64,
+    success_ci_high: f64,
+    p_value: f64,
+    expected_rate_heuristic: f64,
+    baseline: String,
+    expected_count_hl: f64,
     theoretical_boost: f64,
     shield_score: u32,
-    shield_primes: String,
+    /// Parsed from the comma-joined column, so the JSON holds an array.
+    shield_primes: Vec<u32>,
+    shield_model: String,
+    exact_boost: f64,
+    /// Radius of the gap's point in the verification chart, see [`point_radius`].
+    point_radius: f64,
+}
+
+impl GapSpectrumData {
+    /// Columns the findings and the convergence chart cannot do without.
+    const REQUIRED: [&str; 6] = [
+        "gap_size",
+        "count",
+        "successes",
+        "success_rate",
+        "expected_rate_heuristic",
+        "theoretical_boost",
+    ];
+
+    /// Columns missing from older output directories, or left out with `--columns`, take
+    /// the value the run would have implied.
+    fn from_row(row: output::ColumnRow) -> Result<Self, Box<dyn Error>> {
+        Ok(GapSpectrumData {
+            gap_size: row.get("gap_size")?,
+            count: row.get("count")?,
+            successes: row.get("successes")?,
+            success_rate: row.get("success_rate")?,
+            success_ci_low: row.get_or("success_ci_low", f64::NAN)?,
+            success_ci_high: row.get_or("success_ci_high", f64::NAN)?,
+            p_value: row.get_or("p_value", f64::NAN)?,
+            expected_rate_heuristic: row.get("expected_rate_heuristic")?,
+            baseline: row.get_or("baseline", Baseline::LnN.label())?,
+            expected_count_hl: row.get_or("expected_count_hl", 0.0)?,
+            theoretical_boost: row.get("theoretical_boost")?,
+            shield_score: row.get_or("shield_score", 0)?,
+            shield_primes: row
+                .get_or("shield_primes", String::new())?
+                .split(',')
+                .filter(|q| !q.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            // Written as the first-order model before the column existed.
+            shield_model: row.get_or("shield_model", ShieldModel::FIRST_ORDER.label())?,
+            exact_boost: row.get_or("exact_boost", 0.0)?,
+            point_radius: 0.0,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct GlobalStatsData {
+    total_primes_p: u64,
+    total_primes_s: u64,
+    global_ratio_s_p: f64,
+}
+
+impl GlobalStatsData {
+    const REQUIRED: [&str; 3] = ["total_primes_p", "total_primes_s", "global_ratio_s_p"];
+
+    fn from_row(row: output::ColumnRow) -> Result<Self, Box<dyn Error>> {
+        Ok(GlobalStatsData {
+            total_primes_p: row.get("total_primes_p")?,
+            total_primes_s: row.get("total_primes_s")?,
+            global_ratio_s_p: row.get("global_ratio_s_p")?,
+        })
+    }
+}
+
+/// One row of `gap_families.csv` for the family chart.
+#[derive(Serialize, Debug)]
+struct GapFamilyData {
+    modulus: u64,
+    residue: u64,
+    gaps: u64,
+    count: u64,
+    successes: u64,
+    success_rate: f64,
+    success_ci_low: f64,
+    success_ci_high: f64,
+    theoretical_boost: f64,
+}
+
+impl GapFamilyData {
+    const REQUIRED: [&str; 6] = [
+        "modulus",
+        "residue",
+        "gaps",
+        "count",
+        "successes",
+        "success_rate",
+    ];
+
+    /// Columns left out with `--columns` or `--slim` are left out of the chart.
+    fn from_row(row: output::ColumnRow) -> Result<Self, Box<dyn Error>> {
+        Ok(GapFamilyData {
+            modulus: row.get("modulus")?,
+            residue: row.get("residue")?,
+            gaps: row.get("gaps")?,
+            count: row.get("count")?,
+            successes: row.get("successes")?,
+            success_rate: row.get("success_rate")?,
+            success_ci_low: row.get_or("success_ci_low", f64::NAN)?,
+            success_ci_high: row.get_or("success_ci_high", f64::NAN)?,
+            theoretical_boost: row.get_or("theoretical_boost", f64::NAN)?,
+        })
+    }
+}
+
+/// Errors if `output_dir` was written with a newer CSV schema than this build reads.
+/// Directories from before the schema sidecar are read as the current schema.
+fn check_schema(output_dir: &str) -> Result<(), Box<dyn Error>> {
+    match output::read_schema(output_dir)? {
+        Some(schema) if schema.schema_version > output::CSV_SCHEMA_VERSION => Err(format!(
+            "{} was written with CSV schema version {}, newer than version {} this build reads",
+            output_dir,
+            schema.schema_version,
+            output::CSV_SCHEMA_VERSION
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// `rate_correlations.csv` for the heatmap: `matrix[i][j]` correlates `series[i]` and
+/// `series[j]`, null where undefined.
+#[derive(Serialize, Default, Debug)]
+struct RateCorrelations {
+    series: Vec<String>,
+    matrix: Vec<Vec<Option<f64>>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ReportMetadata {
-    max_exponent: u32,
+    max_exponent: Exponent,
     bins: usize,
     max_n: u64,
     target_gaps: Vec<u64>,
+    osc_total_points: usize,
+    osc_points: usize,
+    theme: Theme,
+    findings: Vec<String>,
+    highlight_gaps: Vec<u64>,
+    spectrum_chart_max: u64,
+    scatter_min_count: u64,
+    locale: String,
+    data_files: Vec<String>,
+    analyzed_up_to: u64,
+    complete: bool,
+    label: Option<String>,
+    meta: BTreeMap<String, String>,
+}
+
+/// Largest gap of the spectrum chart: the fixed one, or the smallest gap up to which the
+/// gaps (in ascending order in `gap_data`) hold [`SPECTRUM_CHART_COVERAGE`] of the pairs.
+fn spectrum_chart_max(gap_data: &[GapSpectrumData], setting: SpectrumChartMax) -> u64 {
+    let SpectrumChartMax::Gap(max) = setting else {
+        let total: u64 = gap_data.iter().map(|d| d.count).sum();
+        let mut covered = 0;
+        for d in gap_data {
+            covered += d.count;
+            if covered as f64 >= SPECTRUM_CHART_COVERAGE * total as f64 {
+                return d.gap_size;
+            }
+        }
+        return gap_data.last().map_or(0, |d| d.gap_size);
+    };
+    max
+}
+
+/// Smallest and largest point radius of the verification chart.
+const POINT_RADIUS_RANGE: (f64, f64) = (2.0, 10.0);
+
+/// Radius encoding log(count) in the verification chart: the rarest gap (count 1) gets the
+/// smallest radius and the most frequent one, with `max_count` occurrences, the largest.
+fn point_radius(count: u64, max_count: u64) -> f64 {
+    let (min, max) = POINT_RADIUS_RANGE;
+    if max_count <= 1 {
+        return max;
+    }
+    let share = (count.max(1) as f64).ln() / (max_count as f64).ln();
+    min + (max - min) * share
+}
+
+/// Largest-Triangle-Three-Buckets downsampling. Returns the indices of the points to keep,
+/// always including the first and last point.
+fn lttb_indices(xs: &[f64], ys: &[f64], threshold: usize) -> Vec<usize> {
+    let n = xs.len();
+    if threshold >= n || threshold < 3 {
+        return (0..n).collect();
+    }
+
+    let mut sampled = Vec::with_capacity(threshold);
+    sampled.push(0);
+
+    // The first and last points are fixed, the rest is split into equal buckets.
+    let bucket_size = (n - 2) as f64 / (threshold - 2) as f64;
+    let mut a = 0;
+
+    for i in 0..threshold - 2 {
+        let bucket_start = (i as f64 * bucket_size) as usize + 1;
+        let bucket_end = (((i + 1) as f64 * bucket_size) as usize + 1).min(n - 1);
+
+        // Average of the next bucket is the third vertex of the triangle.
+        let next_start = bucket_end;
+        let next_end = (((i + 2) as f64 * bucket_size) as usize + 1).clamp(next_start + 1, n);
+        let next_len = (next_end - next_start) as f64;
+        let (sum_x, sum_y) =
+            (next_start..next_end).fold((0.0, 0.0), |(sx, sy), j| (sx + xs[j], sy + ys[j]));
+        let (avg_x, avg_y) = (sum_x / next_len, sum_y / next_len);
+
+        let mut max_area = -1.0;
+        let mut max_index = bucket_start;
+        for j in bucket_start..bucket_end {
+            let area =
+                ((xs[a] - avg_x) * (ys[j] - ys[a]) - (xs[a] - xs[j]) * (avg_y - ys[a])).abs();
+            if area > max_area {
+                max_area = area;
+                max_index = j;
+            }
+        }
+
+        sampled.push(max_index);
+        a = max_index;
+    }
+
+    sampled.push(n - 1);
+    sampled
+}
+
+/// The rows to keep so that several series sharing them are LTTB-downsampled together to
+/// at most `max_points` rows. Each series gets an equal share of the budget, the largest
+/// whose union of selections still fits; a budget below three points per series keeps
+/// evenly spaced rows instead.
+fn lttb_union(xs: &[f64], series: &[Vec<f64>], max_points: usize) -> Vec<bool> {
+    let n = xs.len();
+    let union = |share: usize| {
+        let mut keep = vec![false; n];
+        for ys in series {
+            for i in lttb_indices(xs, ys, share) {
+                keep[i] = true;
+            }
+        }
+        keep
+    };
+    let kept = |keep: &[bool]| keep.iter().filter(|&&k| k).count();
+
+    let share = max_points / series.len().max(1);
+    if share < 3 {
+        let step = (n - 1) as f64 / max_points.saturating_sub(1).max(1) as f64;
+        let mut keep = vec![false; n];
+        for i in 0..max_points {
+            keep[((i as f64 * step).round() as usize).min(n - 1)] = true;
+        }
+        return keep;
+    }
+    // Selections overlap, so shares above the equal split usually fit too.
+    let (mut lo, mut hi) = (share, max_points);
+    let mut best = union(lo);
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        let keep = union(mid);
+        if kept(&keep) <= max_points {
+            (lo, best) = (mid, keep);
+        } else {
+            hi = mid - 1;
+        }
+    }
+    best
+}
+
+/// Downsamples the oscillation rows to at most `max_points`, LTTB-downsampling the S/P
+/// ratio and every rate series together (see [`lttb_union`]).
+fn downsample_oscillation(
+    osc_data: Vec<BTreeMap<String, serde_json::Value>>,
+    max_points: usize,
+) -> Vec<BTreeMap<String, serde_json::Value>> {
+    if max_points == 0 || osc_data.len() <= max_points {
+        return osc_data;
+    }
+
+    let value_of = |row: &BTreeMap<String, serde_json::Value>, key: &str| {
+        row.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0)
+    };
+
+    let xs: Vec<f64> = osc_data
+        .iter()
+        .map(|row| value_of(row, "bin_start"))
+        .collect();
+    let series_keys: Vec<String> = osc_data[0]
+        .keys()
+        .filter(|k| *k == "ratio_s_p" || k.ends_with("_rate"))
+        .cloned()
+        .collect();
+
+    let series: Vec<Vec<f64>> = series_keys
+        .iter()
+        .map(|key| osc_data.iter().map(|row| value_of(row, key)).collect())
+        .collect();
+    let keep = lttb_union(&xs, &series, max_points);
+
+    osc_data
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(row, k)| k.then_some(row))
+        .collect()
+}
+
+/// Thins the throughput rows to at most `max_points`, LTTB-downsampling both rates together
+/// (see [`lttb_union`]).
+fn downsample_throughput(rows: Vec<ThroughputRecord>, max_points: usize) -> Vec<ThroughputRecord> {
+    if max_points == 0 || rows.len() <= max_points {
+        return rows;
+    }
+    let xs: Vec<f64> = rows.iter().map(|r| r.segment_end as f64).collect();
+    let series = [
+        rows.iter().map(|r| r.primes_per_sec).collect(),
+        rows.iter().map(|r| r.s_checks_per_sec).collect(),
+    ];
+    let keep = lttb_union(&xs, &series, max_points);
+    rows.into_iter()
+        .zip(keep)
+        .filter_map(|(row, k)| k.then_some(row))
+        .collect()
+}
+
+/// A target gap's success rate over every pair up to `n`, and the heuristic `2B / ln S`
+/// averaged over the same pairs.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct ConvergencePoint {
+    gap: u64,
+    n: u64,
+    rate: f64,
+    expected: f64,
+}
+
+/// The convergence series of every target gap in `gap_bins.csv`, one point per bin up to
+/// `max_n`, each thinned to an equal share of `max_points`.
+fn convergence_series(
+    gap_bins: &[GapBinRecord],
+    gap_data: &[GapSpectrumData],
+    max_n: u64,
+    max_points: usize,
+) -> Vec<ConvergencePoint> {
+    let mut bin_starts: Vec<u64> = gap_bins.iter().map(|r| r.bin_start).collect();
+    bin_starts.sort_unstable();
+    bin_starts.dedup();
+    let bin_end = |start: u64| {
+        let next = bin_starts.partition_point(|&s| s <= start);
+        bin_starts.get(next).map_or(max_n, |&s| s - 1).min(max_n)
+    };
+
+    let mut gaps: Vec<u64> = gap_bins.iter().map(|r| r.gap.0).collect();
+    gaps.sort_unstable();
+    gaps.dedup();
+    let budget = (max_points / gaps.len().max(1)).max(2);
+
+    let mut series = Vec::new();
+    for gap in gaps {
+        let boost = gap_data
+            .iter()
+            .find(|d| d.gap_size == gap)
+            .map_or(1.0, |d| d.theoretical_boost);
+        let (mut occurrences, mut successes, mut expected) = (0u64, 0u64, 0.0);
+        let mut points = Vec::new();
+        for row in gap_bins
+            .iter()
+            .filter(|r| r.gap.0 == gap && r.bin_start <= max_n)
+        {
+            let end = bin_end(row.bin_start);
+            let s = (row.bin_start + end) as f64 + gap as f64 - 1.0;
+            occurrences += row.occurrences;
+            successes += row.successes;
+            if s > 1.0 {
+                expected += row.occurrences as f64 * 2.0 * boost / s.ln();
+            }
+            if occurrences > 0 {
+                points.push(ConvergencePoint {
+                    gap,
+                    n: end,
+                    rate: successes as f64 / occurrences as f64,
+                    expected: expected / occurrences as f64,
+                });
+            }
+        }
+        if max_points > 0 && points.len() > budget {
+            let xs: Vec<f64> = points.iter().map(|p| (p.n.max(1) as f64).ln()).collect();
+            let ys: Vec<f64> = points.iter().map(|p| p.rate).collect();
+            let keep = lttb_indices(&xs, &ys, budget);
+            points = keep.into_iter().map(|i| points[i].clone()).collect();
+        }
+        series.extend(points);
+    }
+    series
+}
+
+/// Resamples per bin of the bootstrap confidence band of the S/P ratio.
+const BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// Bins with more pairs than this draw each resample's success count from the normal
+/// approximation of the binomial instead of pair by pair.
+const BOOTSTRAP_EXACT_MAX_PAIRS: u64 = 256;
+
+/// Uniform in `[0, 1)`.
+fn unit_f64(rng: &mut stats::SplitMix64) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 95% percentile-bootstrap interval of `successes / trials`. Resampling a bin's pairs with
+/// replacement makes each resample's success count Binomial(trials, successes / trials).
+fn bootstrap_ratio_interval(
+    successes: u64,
+    trials: u64,
+    rng: &mut stats::SplitMix64,
+) -> (f64, f64) {
+    let n = trials as f64;
+    let rate = successes as f64 / n;
+    let mut ratios: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            let count = if trials <= BOOTSTRAP_EXACT_MAX_PAIRS {
+                (0..trials).filter(|_| unit_f64(rng) < rate).count() as f64
+            } else {
+                // Box–Muller; 1 - u keeps the logarithm finite.
+                let radius = (-2.0 * (1.0 - unit_f64(rng)).ln()).sqrt();
+                let normal = radius * (std::f64::consts::TAU * unit_f64(rng)).cos();
+                (n * rate + normal * (n * rate * (1.0 - rate)).sqrt()).clamp(0.0, n)
+            };
+            count / n
+        })
+        .collect();
+    ratios.sort_by(f64::total_cmp);
+    let low = BOOTSTRAP_RESAMPLES * 25 / 1000;
+    let high = BOOTSTRAP_RESAMPLES * 975 / 1000 - 1;
+    (ratios[low], ratios[high])
+}
+
+/// Adds `ratio_ci_low` and `ratio_ci_high`, the bootstrap band of `ratio_s_p`, to every
+/// bin the prime stream reached.
+fn add_ratio_bands(osc_data: &mut [BTreeMap<String, serde_json::Value>], seed: u64) {
+    let mut rng = stats::SplitMix64::new(seed);
+    for row in osc_data {
+        let count = |key: &str| row.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let (successes, trials) = (count("prime_count_s"), count("prime_count_p"));
+        if trials == 0 {
+            continue;
+        }
+        let (low, high) = bootstrap_ratio_interval(successes, trials, &mut rng);
+        row.insert("ratio_ci_low".to_string(), low.into());
+        row.insert("ratio_ci_high".to_string(), high.into());
+    }
+}
+
+/// CSV outputs offered for download in the report.
+const DATA_FILES: &[&str] = &[
+    "global_stats.csv",
+    "gap_spectrum.csv",
+    "oscillation_series.csv",
+    "gap_bins.csv",
+    "tail_fit.csv",
+];
+
+/// Stems of the JSON files the charts load, embedded with the CSVs by `--embed-data`.
+const CHART_DATA: &[&str] = &[
+    "oscillation_series",
+    "gap_spectrum",
+    "report_metadata",
+    "convergence",
+    "gap_families",
+    "throughput",
+    "rate_correlations",
+];
+
+/// Minimum occurrences for a gap to be quoted in the findings; rarer gaps are too noisy.
+const FINDINGS_MIN_COUNT: u64 = 100;
+
+/// Writes the plain-language findings shown at the top of the report.
+fn build_findings(
+    global: &GlobalStatsData,
+    gap_data: &[GapSpectrumData],
+    target_gaps: &[u64],
+    max_exponent: Exponent,
+) -> Vec<String> {
+    let mut findings = Vec::new();
+
+    findings.push(format!(
+        "Up to 10^{}, {} of the sums S built from {} primes were prime, a global S/P ratio of {:.4}.",
+        max_exponent, global.total_primes_s, global.total_primes_p, global.global_ratio_s_p
+    ));
+
+    let (total_count, total_successes) = gap_data
+        .iter()
+        .fold((0, 0), |(c, s), d| (c + d.count, s + d.successes));
+    if total_count == 0 {
+        return findings;
+    }
+    let pooled_rate = total_successes as f64 / total_count as f64;
+
+    let mut sorted_targets = target_gaps.to_vec();
+    sorted_targets.sort_unstable();
+    for &gap in &sorted_targets {
+        let Some(d) = gap_data.iter().find(|d| d.gap_size == gap) else {
+            findings.push(format!("Gap {} never occurred in the analyzed range.", gap));
+            continue;
+        };
+        if gap % 2 == 1 {
+            findings.push(format!(
+                "Gap {} occurs only between 2 and 3; its sum S = 4 is even, never prime.",
+                gap
+            ));
+            continue;
+        }
+        let relative = (d.success_rate / pooled_rate - 1.0) * 100.0;
+        let z = stats::proportion_z_score(d.successes, d.count, pooled_rate);
+        findings.push(format!(
+            "Gap {} {} the pooled baseline rate of {:.4} by {:.1}% (rate {:.4}, z = {:.2}, n = {}); the {} baseline expects {:.4} ({:+.1}%).",
+            gap,
+            if relative >= 0.0 { "outperformed" } else { "underperformed" },
+            pooled_rate,
+            relative.abs(),
+            d.success_rate,
+            z,
+            d.count,
+            d.baseline,
+            d.expected_rate_heuristic,
+            (d.success_rate / d.expected_rate_heuristic - 1.0) * 100.0
+        ));
+    }
+
+    let reliable: Vec<&GapSpectrumData> = gap_data
+        .iter()
+        .filter(|d| d.count >= FINDINGS_MIN_COUNT)
+        .collect();
+    if let Some(best) = reliable
+        .iter()
+        .max_by(|a, b| a.success_rate.total_cmp(&b.success_rate))
+    {
+        findings.push(format!(
+            "Among gaps with at least {} occurrences, gap {} had the highest success rate ({:.4}, theoretical boost {:.3}).",
+            FINDINGS_MIN_COUNT, best.gap_size, best.success_rate, best.theoretical_boost
+        ));
+    }
+
+    if reliable.len() >= 3 {
+        let points: Vec<(f64, f64)> = reliable
+            .iter()
+            .map(|d| (d.theoretical_boost, d.success_rate))
+            .collect();
+        let r = pearson_correlation(&points);
+        findings.push(format!(
+            "Across those {} gaps, the shield boost explains R² = {:.3} of the variation in success rate.",
+            reliable.len(),
+            r * r
+        ));
+    }
+
+    findings
+}
+
+fn pearson_correlation(points: &[(f64, f64)]) -> f64 {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for &(x, y) in points {
+        sxy += (x - mean_x) * (y - mean_y);
+        sxx += (x - mean_x) * (x - mean_x);
+        syy += (y - mean_y) * (y - mean_y);
+    }
+    if sxx == 0.0 || syy == 0.0 {
+        0.0
+    } else {
+        sxy / (sxx * syy).sqrt()
+    }
 }
 
 pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>> {
-    let output_dir = &config.output_dir;
+    write_report(config, max_n, config.max_exponent(), "")
+}
+
+/// Writes a snapshot of the report for the results so far as `index_e<k>.html` (with its
+/// data as `*_e<k>.json`), once the analysis has passed 10^k, and refreshes the
+/// `snapshots.html` page linking every snapshot.
+pub fn generate_snapshot(config: &Config, exponent: Exponent) -> Result<(), Box<dyn Error>> {
+    write_report(
+        config,
+        exponent.max_n(),
+        exponent,
+        &format!("_e{}", exponent),
+    )?;
+    write_snapshot_index(&config.output_dir)
+}
 
-    // Read oscillation_series.csv dynamically
-    let osc_path = Path::new(output_dir).join("oscillation_series.csv");
-    let mut osc_reader = csv::Reader::from_path(osc_path)?;
-    let mut osc_data: Vec<BTreeMap<String, serde_json::Value>> = Vec::new();
+/// Lists the report snapshots in `output_dir`, by exponent, and the final report.
+fn write_snapshot_index(output_dir: &str) -> Result<(), Box<dyn Error>> {
+    let mut exponents: Vec<f64> = fs::read_dir(output_dir)?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_prefix("index_e")?
+                .strip_suffix(".html")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    exponents.sort_by(f64::total_cmp);
 
-    for result in osc_reader.deserialize() {
-        let record: BTreeMap<String, serde_json::Value> = result?;
-        osc_data.push(record);
+    let mut items: Vec<String> = exponents
+        .iter()
+        .map(|k| {
+            format!(
+                r#"<li><a href="index_e{k}.html">Up to 10<sup>{k}</sup></a></li>"#,
+                k = k
+            )
+        })
+        .collect();
+    if Path::new(output_dir).join("index.html").exists() {
+        items.push(r#"<li><a href="index.html">Latest report</a></li>"#.to_string());
     }
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>Prime Sum Analysis Snapshots</title>
+    <style>body {{ font-family: sans-serif; margin: 2em; }} li {{ margin: 0.4em 0; }}</style>
+</head>
+<body>
+    <h1>Report Snapshots</h1>
+    <ul>
+        {}
+    </ul>
+</body>
+</html>
+"#,
+        items.join("\n        ")
+    );
+    fs::write(Path::new(output_dir).join(SNAPSHOT_INDEX), html)?;
+    Ok(())
+}
+
+/// Landing page of the report snapshots.
+pub const SNAPSHOT_INDEX: &str = "snapshots.html";
+
+/// Writes the report as `index<suffix>.html`, reading its data from `*<suffix>.json`.
+fn write_report(
+    config: &Config,
+    max_n: u64,
+    max_exponent: Exponent,
+    suffix: &str,
+) -> Result<(), Box<dyn Error>> {
+    let output_dir = &config.output_dir;
+    let json_path = |stem: &str| Path::new(output_dir).join(format!("{}{}.json", stem, suffix));
+    check_schema(output_dir)?;
+
+    // Read oscillation_series.csv (or its parts) dynamically
+    let osc_data: Vec<BTreeMap<String, serde_json::Value>> =
+        output::read_rows(output_dir, "oscillation_series.csv")?;
+
+    // Large bin counts would make the report unusable, so thin the series for display.
+    let osc_total_points = osc_data.len();
+    let mut osc_data = downsample_oscillation(osc_data, config.report_max_points);
+    let osc_points = osc_data.len();
+    add_ratio_bands(
+        &mut osc_data,
+        stats::derive_seed(config.seed.unwrap_or(0), "bootstrap"),
+    );
 
     let osc_json = serde_json::to_string(&osc_data)?;
     // Write oscillation_series.json
-    let osc_json_path = Path::new(output_dir).join("oscillation_series.json");
+    let osc_json_path = json_path("oscillation_series");
     fs::write(osc_json_path, &osc_json)?;
 
-    // Read gap_spectrum.csv, now including all fields for the new chart
-    let gap_path = Path::new(output_dir).join("gap_spectrum.csv");
-    let mut gap_reader = csv::Reader::from_path(gap_path)?;
-    let mut gap_data: Vec<GapSpectrumData> = Vec::new();
-
-    for result in gap_reader.deserialize() {
-        let record: GapSpectrumData = result?;
-        if record.success_rate > 0.0 {
-            // Only include gaps with data
-            gap_data.push(record);
-        }
+    // Read gap_spectrum.csv (or its parts), now including all fields for the new chart
+    let gap_table = output::ColumnTable::read(output_dir, "gap_spectrum.csv")?;
+    gap_table.require(&GapSpectrumData::REQUIRED)?;
+    let mut gap_data = gap_table
+        .rows()
+        .map(GapSpectrumData::from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+    let max_count = gap_data.iter().map(|d| d.count).max().unwrap_or(0);
+    for d in &mut gap_data {
+        d.point_radius = point_radius(d.count, max_count);
     }
 
+    // Output directories from before gap_bins.csv get no convergence chart.
+    let gap_bin_files = output::data_files(output_dir, "gap_bins.csv")?;
+    let convergence = if gap_bin_files
+        .iter()
+        .all(|f| Path::new(output_dir).join(f).exists())
+    {
+        let gap_bins: Vec<GapBinRecord> = output::read_rows(output_dir, "gap_bins.csv")?;
+        convergence_series(&gap_bins, &gap_data, max_n, config.report_max_points)
+    } else {
+        Vec::new()
+    };
+    fs::write(
+        json_path("convergence"),
+        serde_json::to_string(&convergence)?,
+    )?;
+
     let gap_json = serde_json::to_string(&gap_data)?;
     // Write gap_spectrum.json
-    let gap_json_path = Path::new(output_dir).join("gap_spectrum.json");
+    let gap_json_path = json_path("gap_spectrum");
     fs::write(gap_json_path, &gap_json)?;
 
+    // Deterministic runs and runs from before the file existed have no throughput rows.
+    let throughput_files = output::data_files(output_dir, THROUGHPUT_FILE)?;
+    let has_throughput = throughput_files
+        .iter()
+        .all(|f| Path::new(output_dir).join(f).exists());
+    let throughput: Vec<ThroughputRecord> = if has_throughput {
+        output::read_rows(output_dir, THROUGHPUT_FILE)?
+    } else {
+        Vec::new()
+    };
+    let throughput = downsample_throughput(throughput, config.report_max_points);
+    fs::write(json_path("throughput"), serde_json::to_string(&throughput)?)?;
+
+    // Runs without --gap-families get no family chart.
+    let has_families = Path::new(output_dir)
+        .join(output::GAP_FAMILIES_FILE)
+        .exists();
+    let families = if has_families {
+        let table = output::ColumnTable::read(output_dir, output::GAP_FAMILIES_FILE)?;
+        table.require(&GapFamilyData::REQUIRED)?;
+        table
+            .rows()
+            .map(GapFamilyData::from_row)
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+    fs::write(json_path("gap_families"), serde_json::to_string(&families)?)?;
+
+    // Output directories from before rate_correlations.csv get no heatmap.
+    let correlations_path = Path::new(output_dir).join(RATE_CORRELATIONS_FILE);
+    let has_correlations = correlations_path.exists();
+    let mut correlations = RateCorrelations::default();
+    if has_correlations {
+        let mut reader = csv::Reader::from_path(&correlations_path)?;
+        for row in reader.records() {
+            let row = row?;
+            correlations.series.push(row[0].to_string());
+            correlations
+                .matrix
+                .push(row.iter().skip(1).map(|r| r.parse().ok()).collect());
+        }
+    }
+    fs::write(
+        json_path("rate_correlations"),
+        serde_json::to_string(&correlations)?,
+    )?;
+
+    let global_table = output::ColumnTable::read(output_dir, "global_stats.csv")?;
+    global_table.require(&GlobalStatsData::REQUIRED)?;
+    let global = GlobalStatsData::from_row(
+        global_table
+            .rows()
+            .next()
+            .ok_or("global_stats.csv is empty")?,
+    )?;
+    let findings = build_findings(&global, &gap_data, &config.target_gaps(), max_exponent);
+
+    // Older output directories have no manifest; their runs always completed.
+    // With --bin-align the run has more bins than --bins asked for.
+    let (bins, analyzed_up_to, complete, label, meta) = match output::read_manifest(output_dir)? {
+        Some(manifest) => (
+            manifest.bins,
+            manifest.analyzed_up_to,
+            manifest.complete,
+            manifest.label,
+            manifest.meta,
+        ),
+        None => (config.bins, max_n, true, None, BTreeMap::new()),
+    };
+
     // Write report_metadata.json
+    // Partitioned outputs are offered part by part.
+    let mut data_files = Vec::new();
+    for name in DATA_FILES {
+        data_files.extend(output::data_files(output_dir, name)?);
+    }
+    if has_throughput {
+        data_files.extend(throughput_files);
+    }
+    if has_correlations {
+        data_files.push(RATE_CORRELATIONS_FILE.to_string());
+    }
+    if has_families {
+        data_files.push(output::GAP_FAMILIES_FILE.to_string());
+    }
+
     let metadata = ReportMetadata {
-        max_exponent: config.max_exponent,
-        bins: config.bins,
+        max_exponent,
+        bins,
         max_n,
-        target_gaps: config.gaps.clone(),
+        target_gaps: config.target_gaps(),
+        osc_total_points,
+        osc_points,
+        theme: config.theme,
+        findings,
+        highlight_gaps: config.highlight_gaps.clone(),
+        spectrum_chart_max: spectrum_chart_max(&gap_data, config.spectrum_chart_max),
+        scatter_min_count: FINDINGS_MIN_COUNT,
+        locale: config.locale.clone(),
+        data_files: data_files.clone(),
+        analyzed_up_to,
+        complete,
+        label,
+        meta,
     };
     let metadata_json = serde_json::to_string(&metadata)?;
-    let metadata_path = Path::new(output_dir).join("report_metadata.json");
+    let metadata_path = json_path("report_metadata");
     fs::write(metadata_path, &metadata_json)?;
 
+    // Copies of the CSVs and of the charts' JSON files carried inside the page, keyed by
+    // file name, so the page renders and downloads without the files next to it.
+    let mut embedded: BTreeMap<String, String> = BTreeMap::new();
+    if config.embed_data {
+        for name in &data_files {
+            embedded.insert(
+                name.clone(),
+                fs::read_to_string(Path::new(output_dir).join(name))?,
+            );
+        }
+        for stem in CHART_DATA {
+            let path = json_path(stem);
+            if path.exists() {
+                embedded.insert(
+                    format!("{}{}.json", stem, suffix),
+                    fs::read_to_string(path)?,
+                );
+            }
+        }
+    }
+    // A literal "</script>" inside the CSV text would end the JSON block early.
+    let embedded_json = serde_json::to_string(&embedded)?.replace("</", "<\\/");
+
     let html_content = r#"
 <!DOCTYPE html>
-<html lang="en">
+<html lang="en" data-theme="light">
 <head>
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
@@ -85,24 +877,68 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
     <script id="MathJax-script" async src="https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-chtml.js"></script>
 
     <style>
-        body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, Helvetica, Arial, sans-serif; margin: 0; background-color: #f8f9fa; color: #212529; }
-        .container { max-width: 1200px; margin: 2rem auto; padding: 2rem; background-color: #fff; border-radius: 8px; box-shadow: 0 4px 6px rgba(0,0,0,0.1); }
-        h1, h2 { text-align: center; color: #343a40; }
-        .summary { text-align: center; margin-bottom: 2rem; color: #6c757d; font-size: 1.1em; }
+        :root, :root[data-theme="light"] {
+            --page-bg: #f8f9fa; --card-bg: #fff; --text: #212529; --heading: #343a40; --muted: #6c757d;
+            --accent: #007bff; --border: #dee2e6; --table-head: #e9ecef; --tooltip-bg: #343a40; --shadow: rgba(0,0,0,0.1);
+            --chart-text: #495057; --chart-grid: rgba(0,0,0,0.1);
+        }
+        :root[data-theme="dark"] {
+            --page-bg: #121417; --card-bg: #1e2126; --text: #dee2e6; --heading: #f1f3f5; --muted: #adb5bd;
+            --accent: #4dabf7; --border: #343a40; --table-head: #2b3036; --tooltip-bg: #495057; --shadow: rgba(0,0,0,0.5);
+            --chart-text: #ced4da; --chart-grid: rgba(255,255,255,0.12);
+        }
+        :root[data-theme="high-contrast"] {
+            --page-bg: #fff; --card-bg: #fff; --text: #000; --heading: #000; --muted: #000;
+            --accent: #0000c8; --border: #000; --table-head: #fff; --tooltip-bg: #000; --shadow: rgba(0,0,0,0);
+            --chart-text: #000; --chart-grid: rgba(0,0,0,0.6);
+        }
+        :root[data-theme="high-contrast"] .container, :root[data-theme="high-contrast"] .findings { border: 2px solid #000; }
+        :focus-visible { outline: 3px solid var(--accent); outline-offset: 2px; }
+        .visually-hidden { position: absolute; width: 1px; height: 1px; overflow: hidden; clip: rect(0 0 0 0); white-space: nowrap; }
+        .dataset-toggles { display: flex; flex-wrap: wrap; justify-content: center; gap: 0.4rem; margin-top: 0.5rem; }
+        .dataset-toggles button { background: none; border: 1px solid var(--border); border-radius: 6px; color: var(--text); cursor: pointer; padding: 0.15rem 0.5rem; font-size: 0.85em; }
+        .dataset-toggles button[aria-pressed="false"] { text-decoration: line-through; color: var(--muted); }
+        body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, Helvetica, Arial, sans-serif; margin: 0; background-color: var(--page-bg); color: var(--text); }
+        a { color: var(--accent); }
+        .container { max-width: 1200px; margin: 2rem auto; padding: 2rem; background-color: var(--card-bg); border-radius: 8px; box-shadow: 0 4px 6px var(--shadow); position: relative; }
+        h1, h2 { text-align: center; color: var(--heading); }
+        .summary { text-align: center; margin-bottom: 2rem; color: var(--muted); font-size: 1.1em; }
+        .run-label { text-align: center; margin: -0.5rem 0 0.5rem 0; font-size: 1.3em; color: var(--heading); }
+        .run-meta { text-align: center; margin-bottom: 1rem; color: var(--muted); }
+        .run-meta span { margin: 0 0.5rem; }
+        .theme-toggle { position: absolute; top: 1rem; right: 1rem; background: none; border: 1px solid var(--border); border-radius: 6px; color: var(--text); cursor: pointer; padding: 0.3rem 0.6rem; }
+        .partial-banner { margin: 0 auto 1.5rem auto; max-width: 900px; padding: 0.75rem 1rem; border: 1px solid #f0ad4e; background-color: rgba(240, 173, 78, 0.15); border-radius: 4px; text-align: center; font-weight: bold; }
+        .findings { margin: 0 auto 2rem auto; max-width: 900px; padding: 1rem 1.5rem; border-left: 4px solid var(--accent); background-color: var(--table-head); border-radius: 4px; }
+        .findings h2 { text-align: left; margin-top: 0; font-size: 1.2em; }
+        .findings li { margin: 0.3rem 0; }
+        .downloads { display: flex; flex-wrap: wrap; justify-content: center; gap: 0.75rem; }
+        .download-button { border: 1px solid var(--border); border-radius: 6px; padding: 0.4rem 0.8rem; text-decoration: none; background-color: var(--table-head); }
+        .chart-note { text-align: center; color: var(--muted); font-size: 0.9em; }
+        .table-controls { text-align: center; margin: 1rem 0; }
+        .table-controls input { width: 6em; margin: 0 0.5em; }
+        .table-scroll { max-height: 480px; overflow-y: auto; }
+        table.gap-table { width: 100%; border-collapse: collapse; font-size: 0.9em; }
+        .gap-table th, .gap-table td { padding: 0.3rem 0.6rem; border-bottom: 1px solid var(--border); text-align: right; }
+        .gap-table th { position: sticky; top: 0; background-color: var(--table-head); cursor: pointer; user-select: none; }
+        .gap-table th.sorted-asc::after { content: " ▲"; }
+        .gap-table th.sorted-desc::after { content: " ▼"; }
         .chart-container { margin-top: 2rem; }
+        .correlation-table td, .correlation-table th { text-align: center; cursor: default; }
+        .small-multiples { display: grid; grid-template-columns: repeat(auto-fill, minmax(320px, 1fr)); gap: 1.5rem; margin-top: 1rem; }
+        .small-multiples h3 { text-align: center; margin: 0 0 0.5rem 0; font-size: 1em; color: var(--heading); }
         .info-tooltip {
             position: relative;
             display: inline-block;
-            border-bottom: 1px dotted black;
+            border-bottom: 1px dotted var(--text);
             cursor: help;
             font-weight: bold;
             font-size: 0.8em;
-            color: #007bff;
+            color: var(--accent);
         }
         .info-tooltip .tooltip-text {
             visibility: hidden;
             width: 250px;
-            background-color: #343a40;
+            background-color: var(--tooltip-bg);
             color: #fff;
             text-align: center;
             border-radius: 6px;
@@ -117,50 +953,266 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
             font-weight: normal;
             font-size: 0.95em;
         }
-        .info-tooltip:hover .tooltip-text {
+        .info-tooltip:hover .tooltip-text, .info-tooltip:focus .tooltip-text {
             visibility: visible;
             opacity: 1;
         }
+        /* Paper: one chart per page with its explanation as the caption, no controls. */
+        @media print {
+            body, .container { background: #fff; color: #000; box-shadow: none; margin: 0; padding: 0; max-width: none; }
+            .theme-toggle, .table-controls, .dataset-toggles, #downloads, .chart-container:has(#downloads) { display: none; }
+            .chart-container { break-before: page; break-inside: avoid; }
+            .chart-container canvas { max-height: 70vh; }
+            .small-multiples { grid-template-columns: repeat(2, 1fr); }
+            .small-multiples > div { break-inside: avoid; }
+            .table-scroll { max-height: none; overflow: visible; }
+            .gap-table th { position: static; }
+            .info-tooltip { border: none; cursor: auto; color: #000; font-size: 1em; }
+            .info-tooltip .tooltip-text { display: none; }
+            .chart-container h2 .tooltip-caption-mark { display: none; }
+            .chart-container h2 .info-tooltip .tooltip-text { visibility: visible; opacity: 1; position: static; display: block; width: auto; margin: 0.5rem 0 1rem 0; padding: 0; background: none; color: #000; text-align: left; font-size: 0.75em; font-style: italic; }
+        }
     </style>
 </head>
 <body>
     <div class="container">
+        <button class="theme-toggle" id="themeToggle" title="Cycle light, dark and high-contrast themes" aria-label="Change theme">◐ Theme</button>
         <h1>Prime Sum Analysis Report</h1>
+        <div class="run-label" id="runLabel" hidden></div>
+        <div class="run-meta" id="runMeta" hidden></div>
+        <div class="partial-banner" id="partialBanner" hidden></div>
         <div class="summary">
-            <span><strong>Max N:</strong> <span id="displayMaxExponent">...</span> <span class="info-tooltip">ⓘ<span class="tooltip-text">The upper bound ($p_n$) for the prime analysis.</span></span></span> |
-            <span><strong>Analysis Bins:</strong> <span id="displayBins">...</span> <span class="info-tooltip">ⓘ<span class="tooltip-text">The number of windows the analysis range is divided into. Higher numbers provide more detail but can be noisier.</span></span></span>
+            <span><strong>Max N:</strong> <span id="displayMaxExponent">...</span> <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">The upper bound ($p_n$) for the prime analysis.</span></span></span> |
+            <span><strong>Analysis Bins:</strong> <span id="displayBins">...</span> <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">The number of windows the analysis range is divided into. Higher numbers provide more detail but can be noisier.</span></span></span>
+        </div>
+
+        <div class="findings">
+            <h2>Key Findings</h2>
+            <ul id="findingsList"></ul>
+        </div>
+
+        <div class="chart-container">
+            <h2>Theory Verification <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">This chart plots the theoretical model (our 'boost' score) against the observed success rate to verify our hypothesis. The trendline is an ordinary least squares regression (best fit) line; points on this line behave exactly as predicted by the aggregate model. Point size grows with the logarithm of the gap's count; hollow points have fewer than the minimum count of the findings and are left out of the trendline.</span></span></h2>
+            <canvas id="verificationChart" role="img" aria-label="Scatter plot of each gap's observed success rate against its theoretical boost, with a least squares trendline"></canvas>
+        </div>
+
+        <div class="chart-container">
+            <h2>$S = p_n + p_{n+1} - 1$ Primality Ratio Oscillation <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Shows how the success rate changes across the number line, revealing density oscillations.</span></span></h2>
+            <canvas id="oscillationChart" role="img" aria-label="Line chart of the ratio of prime sums to primes in each bin, with its 95% bootstrap band"></canvas>
+            <p class="chart-note" id="downsampleNote" hidden></p>
+        </div>
+
+        <div class="chart-container">
+            <h2>Per-Gap Rate Oscillation <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">One chart per tracked gap. The shaded band is the shield-adjusted heuristic $2B / \ln(S)$ evaluated across each bin, where $B$ is the gap's theoretical boost.</span></span></h2>
+            <div id="gapGrid" class="small-multiples" role="group" aria-label="One rate chart per tracked gap"></div>
+        </div>
+
+        <div class="chart-container" id="convergenceSection" hidden>
+            <h2>Convergence of Gap Rates <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Each tracked gap's success rate over every pair up to N, on a logarithmic N axis. The dashed line of the same color is the heuristic $2B / \ln(S)$ averaged over the same pairs, where $B$ is the gap's theoretical boost: a gap converging to the model follows its dashed line, one diverging from it drifts away.</span></span></h2>
+            <canvas id="convergenceChart" role="img" aria-label="Line chart of each tracked gap's cumulative success rate against N, with its heuristic as a dashed line"></canvas>
+        </div>
+
+        <div class="chart-container">
+            <h2>Gap Success Rate Spectrum <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">A bar chart showing the overall success rate for each prime gap size, up to the largest gap set with --spectrum-chart-max (by default, the gaps that make up 99.9% of the prime pairs).</span></span></h2>
+            <canvas id="gapChart" role="img" aria-label="Bar chart of the success rate of each gap size"></canvas>
         </div>
 
         <div class="chart-container">
-            <h2>Theory Verification <span class="info-tooltip">ⓘ<span class="tooltip-text">This chart plots the theoretical model (our 'boost' score) against the observed success rate to verify our hypothesis. The trendline is an ordinary least squares regression (best fit) line; points on this line behave exactly as predicted by the aggregate model.</span></span></h2>
-            <canvas id="verificationChart"></canvas>
+            <h2>Gap Counts vs. Hardy–Littlewood <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Observed count of each gap divided by the Hardy–Littlewood prediction $2C_2 \prod_{p \mid g} \frac{p-1}{p-2} \int_2^N e^{-g/\ln t} \frac{dt}{\ln^2 t}$. Points above the dashed 1.0 line are over-represented relative to the prediction.</span></span></h2>
+            <canvas id="hlChart" role="img" aria-label="Chart of each gap's observed count divided by the Hardy–Littlewood prediction"></canvas>
+        </div>
+
+        <div class="chart-container" id="familySection" hidden>
+            <h2>Gap Families <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Prime pairs pooled by the residue of their gap modulo each --gap-families modulus. The bars are each family's success rate relative to the pooled rate of all pairs, the outlines its theoretical boost (averaged over the family's pairs) relative to the pooled boost. Where the shield model holds, the two match.</span></span></h2>
+            <canvas id="familyChart" role="img" aria-label="Bar chart of the relative success rate and relative boost of each residue family of gaps"></canvas>
+        </div>
+
+        <div class="chart-container" id="throughputSection" hidden>
+            <h2>Throughput <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Primes and S primality checks processed per second in each sieve segment, with the share of each segment's time spent sieving. A falling rate with a rising sieve share marks the move from cache-friendly to memory-bound work as N grows.</span></span></h2>
+            <canvas id="throughputChart" role="img" aria-label="Line chart of primes and sum checks processed per second in each sieve segment"></canvas>
+        </div>
+
+        <div class="chart-container" id="correlationSection" hidden>
+            <h2>Rate Correlations <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Pearson correlation between the per-bin series of the S/P ratio and of each tracked gap's rate, over the bins holding a prime p. Red cells oscillate together, blue ones in opposition, pale ones independently. The rates all fall with N, which alone correlates them positively.</span></span></h2>
+            <div class="table-scroll">
+                <table class="gap-table correlation-table" id="correlationTable" aria-label="Rate correlations"></table>
+            </div>
         </div>
 
         <div class="chart-container">
-            <h2>$S = p_n + p_{n+1} - 1$ Primality Ratio Oscillation <span class="info-tooltip">ⓘ<span class="tooltip-text">Shows how the success rate changes across the number line, revealing density oscillations.</span></span></h2>
-            <canvas id="oscillationChart"></canvas>
+            <h2>Gap Table <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">Every observed gap. Click a column header to sort. The CI is a 95% Wilson interval; the p-value tests the gap's rate against the pooled rate of all prime pairs.</span></span></h2>
+            <div class="table-controls">
+                <label for="gapFilterMin">Gap size from</label> <input type="number" id="gapFilterMin" min="0" step="2"> <label for="gapFilterMax">to</label> <input type="number" id="gapFilterMax" min="0" step="2">
+            </div>
+            <div class="table-scroll">
+                <table class="gap-table" id="gapTable" aria-label="Every observed gap">
+                    <thead>
+                        <tr>
+                            <th data-key="gap_size">Gap</th>
+                            <th data-key="count">Count</th>
+                            <th data-key="successes">Successes</th>
+                            <th data-key="success_rate">Rate</th>
+                            <th data-key="success_ci_low">95% CI</th>
+                            <th data-key="expected_rate_heuristic">Expected</th>
+                            <th data-key="theoretical_boost">Boost</th>
+                            <th data-key="exact_boost">Exact Boost</th>
+                            <th data-key="shield_primes">Shield Primes</th>
+                            <th data-key="p_value">p-value</th>
+                        </tr>
+                    </thead>
+                    <tbody></tbody>
+                </table>
+            </div>
         </div>
 
         <div class="chart-container">
-            <h2>Gap Success Rate Spectrum <span class="info-tooltip">ⓘ<span class="tooltip-text">A bar chart showing the overall success rate for each prime gap size.</span></span></h2>
-            <canvas id="gapChart"></canvas>
+            <h2>Data Downloads <span class="info-tooltip" tabindex="0"><span class="tooltip-caption-mark" aria-hidden="true">ⓘ</span><span class="tooltip-text" role="tooltip">The CSV files this report was built from. Reports generated with --embed-data carry a copy of them and of the chart data inside this page, so the charts and downloads work even when the page is shared on its own.</span></span></h2>
+            <nav class="downloads" id="downloads" aria-label="Data downloads"></nav>
         </div>
     </div>
 
+    <script id="embeddedData" type="application/json">__EMBEDDED_DATA__</script>
+
     <script>
+        // Resolves 'auto' against the OS preference and syncs Chart.js defaults with the CSS palette.
+        function applyTheme(theme) {
+            if (theme === 'auto' && window.matchMedia('(prefers-contrast: more)').matches) {
+                theme = 'high-contrast';
+            } else if (theme === 'auto') {
+                theme = window.matchMedia('(prefers-color-scheme: dark)').matches ? 'dark' : 'light';
+            }
+            document.documentElement.dataset.theme = theme;
+            const css = getComputedStyle(document.documentElement);
+            Chart.defaults.color = css.getPropertyValue('--chart-text').trim();
+            Chart.defaults.borderColor = css.getPropertyValue('--chart-grid').trim();
+            const highContrast = theme === 'high-contrast';
+            Chart.defaults.font.size = highContrast ? 14 : 12;
+            Chart.defaults.elements.line.borderWidth = highContrast ? 3 : 2;
+            Chart.defaults.elements.point.borderWidth = highContrast ? 2 : 1;
+        }
+
+        // Buttons under a chart that show and hide its datasets, as clicking the legend does,
+        // but reachable with the keyboard and announced by screen readers.
+        function addDatasetToggles(chart) {
+            const datasets = chart.data.datasets;
+            if (datasets.length < 2) return;
+            const toggles = document.createElement('div');
+            toggles.className = 'dataset-toggles';
+            toggles.setAttribute('role', 'group');
+            toggles.setAttribute('aria-label', 'Show or hide series');
+            datasets.forEach((dataset, i) => {
+                const button = document.createElement('button');
+                button.type = 'button';
+                button.textContent = dataset.label;
+                button.setAttribute('aria-pressed', String(chart.isDatasetVisible(i)));
+                button.addEventListener('click', () => {
+                    const visible = !chart.isDatasetVisible(i);
+                    chart.setDatasetVisibility(i, visible);
+                    chart.update();
+                    button.setAttribute('aria-pressed', String(visible));
+                });
+                toggles.appendChild(button);
+            });
+            chart.canvas.after(toggles);
+        }
+
+        // Chart data embedded by --embed-data, or else the JSON file next to the page.
+        const embeddedData = JSON.parse(document.getElementById('embeddedData').textContent);
+        async function loadJson(name) {
+            if (embeddedData[name] !== undefined) return JSON.parse(embeddedData[name]);
+            return (await fetch(name)).json();
+        }
+
         async function loadDataAndRenderCharts() {
-            const oscResponse = await fetch('oscillation_series.json');
-            const oscData = await oscResponse.json();
+            const oscData = await loadJson('oscillation_series__SUFFIX__.json');
 
-            const gapResponse = await fetch('gap_spectrum.json');
-            const gapData = await gapResponse.json();
+            const allGapData = await loadJson('gap_spectrum__SUFFIX__.json');
+            // Only gaps with at least one success are charted; the table lists every gap.
+            const gapData = allGapData.filter(d => d.success_rate > 0);
 
-            const metadataResponse = await fetch('report_metadata.json');
-            const metadata = await metadataResponse.json();
+            const metadata = await loadJson('report_metadata__SUFFIX__.json');
+
+            // --- Locale-aware number formatting ---
+            let locale = metadata.locale;
+            try {
+                new Intl.NumberFormat(locale);
+            } catch (e) {
+                console.warn(`Unsupported locale '${locale}', falling back to en-US.`);
+                locale = 'en-US';
+            }
+            Chart.defaults.locale = locale;
+            const integerFormat = new Intl.NumberFormat(locale, { maximumFractionDigits: 0 });
+            const fixedFormats = {};
+            const fmtInt = v => integerFormat.format(v);
+            const fmtFixed = (v, digits) => {
+                if (!fixedFormats[digits]) {
+                    fixedFormats[digits] = new Intl.NumberFormat(locale, { minimumFractionDigits: digits, maximumFractionDigits: digits });
+                }
+                return fixedFormats[digits].format(v);
+            };
+            const fmtSci = v => new Intl.NumberFormat(locale, { notation: 'scientific', maximumFractionDigits: 2 }).format(v);
 
             // Update summary DOM elements
-            document.getElementById('displayMaxExponent').textContent = `1E${metadata.max_exponent}`;
-            document.getElementById('displayBins').textContent = metadata.bins;
+            document.getElementById('displayMaxExponent').textContent = `10^${metadata.max_exponent} (${fmtInt(metadata.max_n)})`;
+            document.getElementById('displayBins').textContent = fmtInt(metadata.bins);
+            if (metadata.label) {
+                const runLabel = document.getElementById('runLabel');
+                runLabel.textContent = metadata.label;
+                runLabel.hidden = false;
+                document.title = `${metadata.label} - ${document.title}`;
+            }
+            const metaEntries = Object.entries(metadata.meta || {});
+            if (metaEntries.length > 0) {
+                const runMeta = document.getElementById('runMeta');
+                for (const [key, value] of metaEntries) {
+                    const item = document.createElement('span');
+                    const name = document.createElement('strong');
+                    name.textContent = `${key}:`;
+                    item.append(name, ` ${value}`);
+                    runMeta.append(item);
+                }
+                runMeta.hidden = false;
+            }
+
+            if (metadata.osc_points < metadata.osc_total_points) {
+                const note = document.getElementById('downsampleNote');
+                note.innerHTML = `Showing ${fmtInt(metadata.osc_points)} of ${fmtInt(metadata.osc_total_points)} bins (LTTB downsampled). Exact values: <a href="oscillation_series.csv">oscillation_series.csv</a>`;
+                note.hidden = false;
+            }
+
+            const findingsList = document.getElementById('findingsList');
+            metadata.findings.forEach(text => {
+                const item = document.createElement('li');
+                item.textContent = text;
+                findingsList.appendChild(item);
+            });
+
+            applyTheme(localStorage.getItem('reportTheme') || metadata.theme);
+            const themeCycle = ['light', 'dark', 'high-contrast'];
+            document.getElementById('themeToggle').addEventListener('click', () => {
+                const current = themeCycle.indexOf(document.documentElement.dataset.theme);
+                const next = themeCycle[(current + 1) % themeCycle.length];
+                localStorage.setItem('reportTheme', next);
+                applyTheme(next);
+                Object.values(Chart.instances).forEach(chart => chart.update());
+            });
+
+            // --- Partial Runs ---
+            // Bins beyond the analyzed range hold no data yet and are drawn in grey.
+            const partialLimit = metadata.complete ? null : metadata.analyzed_up_to;
+            const unreachedColor = 'rgba(128, 128, 128, 0.4)';
+            const isUnreached = binStart => partialLimit !== null && binStart > partialLimit;
+            const unreachedSegment = labels => ({
+                borderColor: ctx => isUnreached(labels[ctx.p1DataIndex]) ? unreachedColor : undefined,
+                borderDash: ctx => isUnreached(labels[ctx.p1DataIndex]) ? [4, 4] : undefined,
+            });
+            if (partialLimit !== null) {
+                const banner = document.getElementById('partialBanner');
+                banner.textContent = `Partial results: analyzed up to N = ${fmtInt(partialLimit)} of ${fmtInt(metadata.max_n)} (${fmtFixed(100 * partialLimit / metadata.max_n, 1)}%). Totals and rates cover the analyzed range only.`;
+                banner.hidden = false;
+                Chart.defaults.plugins.title.display = true;
+                Chart.defaults.plugins.title.text = `Partial up to N = ${fmtInt(partialLimit)}`;
+            }
 
             const targetGaps = metadata.target_gaps;
             const max_n = metadata.max_n;
@@ -168,11 +1220,10 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
             // --- Explanations for Tooltips ---
             const legendExplanations = {
                 'Ratio S_p / p': 'How often the sum is prime compared to a regular number of the same size ($S_p / p$). A value > 1 suggests a bias.',
+                '95% band (low)': 'Bootstrap 95% interval of the ratio: the bin\'s pairs resampled with replacement 1000 times.',
+                '95% band (high)': 'Bootstrap 95% interval of the ratio: the bin\'s pairs resampled with replacement 1000 times.',
                 'Default': 'The observed success rate for this specific prime gap within this bin.'
             };
-            targetGaps.forEach(gap => {
-                legendExplanations[`Gap ${gap} Rate`] = `The success rate for gaps of size ${gap} in this specific bin.`;
-            });
 
             // --- Verification Chart (New) ---
             function calculateLinearRegression(data) {
@@ -191,34 +1242,45 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
                 x: d.theoretical_boost,
                 y: d.success_rate,
                 gap: d.gap_size,
+                count: d.count,
+                radius: d.point_radius,
                 score: d.shield_score,
-                primes: d.shield_primes
+                primes: d.shield_primes.join(',')
             }));
+            // Gaps below the minimum count are drawn hollow so their noise does not dominate.
+            const isReliable = p => p.count >= metadata.scatter_min_count;
+            const pointStyle = color => ({
+                backgroundColor: ctx => ctx.raw && isReliable(ctx.raw) ? color : 'rgba(0, 0, 0, 0)',
+                borderColor: color,
+                borderWidth: 1,
+                pointRadius: ctx => ctx.raw ? ctx.raw.radius : 0,
+                pointHoverRadius: ctx => ctx.raw ? ctx.raw.radius + 2 : 0,
+            });
+
+            const highlightGaps = metadata.highlight_gaps;
+            const highlightColors = [
+                'rgba(255, 99, 132, 1)', 'rgba(54, 162, 235, 1)', 'rgba(255, 159, 64, 1)',
+                'rgba(75, 192, 75, 1)', 'rgba(153, 102, 255, 1)', 'rgba(255, 206, 86, 1)'
+            ];
 
-            const regression = calculateLinearRegression(verificationData);
+            // The trendline is fitted to the reliable gaps, or to all of them in short runs.
+            const reliableData = verificationData.filter(isReliable);
+            const regression = calculateLinearRegression(reliableData.length >= 2 ? reliableData : verificationData);
             const trendlineData = verificationData.map(p => ({ x: p.x, y: regression.m * p.x + regression.b }));
 
             new Chart(document.getElementById('verificationChart'), {
                 type: 'scatter',
                 data: {
                     datasets: [
-                        {
-                            label: 'Gap 4 (Red)',
-                            data: verificationData.filter(p => p.gap === 4),
-                            backgroundColor: 'rgba(255, 99, 132, 1)', // Red for Gap 4
-                            pointRadius: 7,
-                        },
-                        {
-                            label: 'Gap 34 (Blue)',
-                            data: verificationData.filter(p => p.gap === 34),
-                            backgroundColor: 'rgba(54, 162, 235, 1)', // Blue for Gap 34
-                            pointRadius: 7,
-                        },
+                        ...highlightGaps.map((gap, i) => ({
+                            label: `Gap ${gap}`,
+                            data: verificationData.filter(p => p.gap === gap),
+                            ...pointStyle(highlightColors[i % highlightColors.length]),
+                        })),
                         {
                             label: 'Other Gaps (Grey)',
-                            data: verificationData.filter(p => p.gap !== 4 && p.gap !== 34),
-                            backgroundColor: 'rgba(0, 0, 0, 0.3)', // Default
-                            pointRadius: 4,
+                            data: verificationData.filter(p => !highlightGaps.includes(p.gap)),
+                            ...pointStyle('rgba(128, 128, 128, 0.5)'), // Default, readable on both themes
                         },
                         {
                             label: 'Trendline',
@@ -237,13 +1299,14 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
                             callbacks: {
                                 label: function(context) {
                                     const d = context.raw;
-                                    return `Gap: ${d.gap} | Boost: ${d.x.toFixed(2)} | Rate: ${d.y.toFixed(3)} | Score: ${d.score} | Primes: ${d.primes || 'none'}`;
+                                    const label = `Gap: ${d.gap} | Boost: ${fmtFixed(d.x, 2)} | Rate: ${fmtFixed(d.y, 3)} | Count: ${d.count} | Score: ${d.score} | Primes: ${d.primes || 'none'}`;
+                                    return isReliable(d) ? label : `${label} (below ${metadata.scatter_min_count}, hollow)`;
                                 }
                             }
                         }
                     },
                     scales: {
-                        x: { title: { display: true, text: 'Theoretical Boost' } },
+                        x: { title: { display: true, text: `Theoretical Boost (${gapData.length ? gapData[0].shield_model : 'first_order'})` } },
                         y: { title: { display: true, text: 'Observed Success Rate' } }
                     }
                 }
@@ -252,27 +1315,15 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
             // --- Oscillation Chart ---
             // Filter out the last data point which is always zero
             const filteredOscData = oscData.slice(0, oscData.length - 1);
-            const oscillationDatasets = [ { label: 'Ratio S_p / p', data: filteredOscData.map(d => d.ratio_s_p), borderColor: 'rgba(75, 192, 192, 1)', tension: 0.1 } ];
-            const colors = [
-                'rgba(255, 99, 132, 0.5)', 'rgba(54, 162, 235, 0.5)', 'rgba(255, 206, 86, 0.5)',
-                'rgba(75, 192, 192, 0.5)', 'rgba(153, 102, 255, 0.5)', 'rgba(255, 159, 64, 0.5)'
+            const oscLabels = filteredOscData.map(d => d.bin_start);
+            const oscillationDatasets = [
+                { label: 'Ratio S_p / p', data: filteredOscData.map(d => d.ratio_s_p), borderColor: 'rgba(75, 192, 192, 1)', tension: 0.1, segment: unreachedSegment(oscLabels) },
+                { label: '95% band (low)', data: filteredOscData.map(d => d.ratio_ci_low ?? null), borderColor: 'rgba(0, 0, 0, 0)', pointRadius: 0, tension: 0.1 },
+                { label: '95% band (high)', data: filteredOscData.map(d => d.ratio_ci_high ?? null), borderColor: 'rgba(0, 0, 0, 0)', backgroundColor: 'rgba(75, 192, 192, 0.2)', pointRadius: 0, tension: 0.1, fill: '-1' }
             ];
-            let colorIndex = 0;
-            targetGaps.forEach(gap => {
-                const gapKey = `gap_${gap}_rate`;
-                if (filteredOscData.length > 0 && filteredOscData[0][gapKey] !== undefined) {
-                    oscillationDatasets.push({
-                        label: `Gap ${gap} Rate`,
-                        data: filteredOscData.map(d => d[gapKey]),
-                        borderColor: colors[colorIndex % colors.length],
-                        hidden: true,
-                    });
-                    colorIndex++;
-                }
-            });
             new Chart(document.getElementById('oscillationChart'), {
                 type: 'line',
-                data: { labels: filteredOscData.map(d => d.bin_start), datasets: oscillationDatasets },
+                data: { labels: oscLabels, datasets: oscillationDatasets },
                 options: {
                     plugins: {
                         tooltip: {
@@ -294,19 +1345,359 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
                 }
             });
 
+            // --- Per-Gap Small Multiples ---
+            // Only bins reached by the prime stream carry gap data.
+            const reachedOscData = filteredOscData.filter(d => d.prime_count_p > 0);
+            const boostByGap = {};
+            gapData.forEach(d => { boostByGap[d.gap_size] = d.theoretical_boost; });
+            const gapGrid = document.getElementById('gapGrid');
+            targetGaps.forEach(gap => {
+                const gapKey = `gap_${gap}_rate`;
+                if (reachedOscData.length === 0 || reachedOscData[0][gapKey] === undefined) {
+                    return;
+                }
+                const boost = boostByGap[gap] !== undefined ? boostByGap[gap] : 1.0;
+                // Expected rate for S = p_prev + p_curr - 1 at a given p (S is odd, hence the factor 2).
+                const expectedAt = p => {
+                    const s = 2 * p + gap - 1;
+                    return s > 1 ? 2 * boost / Math.log(s) : null;
+                };
+
+                const cell = document.createElement('div');
+                const title = document.createElement('h3');
+                title.textContent = `Gap ${gap} (boost ${fmtFixed(boost, 2)})`;
+                const canvas = document.createElement('canvas');
+                canvas.setAttribute('role', 'img');
+                canvas.setAttribute('aria-label', `Line chart of the success rate of gap ${gap} in each bin, with its heuristic band`);
+                cell.appendChild(title);
+                cell.appendChild(canvas);
+                gapGrid.appendChild(cell);
+
+                new Chart(canvas, {
+                    type: 'line',
+                    data: {
+                        labels: reachedOscData.map(d => d.bin_start),
+                        datasets: [
+                            {
+                                label: 'Expected (bin end)',
+                                data: reachedOscData.map(d => expectedAt(d.bin_end)),
+                                borderColor: 'rgba(0, 0, 0, 0)',
+                                pointRadius: 0,
+                            },
+                            {
+                                label: 'Expected (bin start)',
+                                data: reachedOscData.map(d => expectedAt(d.bin_start)),
+                                borderColor: 'rgba(0, 0, 0, 0)',
+                                backgroundColor: 'rgba(75, 192, 192, 0.25)',
+                                pointRadius: 0,
+                                fill: '-1',
+                            },
+                            {
+                                label: `Gap ${gap} Rate`,
+                                data: reachedOscData.map(d => d[gapKey]),
+                                borderColor: 'rgba(153, 102, 255, 1)',
+                                borderWidth: 1,
+                                pointRadius: 0,
+                            }
+                        ]
+                    },
+                    options: {
+                        animation: false,
+                        plugins: {
+                            legend: { display: false },
+                            tooltip: {
+                                callbacks: {
+                                    label: function(context) {
+                                        const v = context.parsed.y;
+                                        return v === null ? '' : `${context.dataset.label}: ${fmtFixed(v, 4)}`;
+                                    }
+                                }
+                            }
+                        },
+                        scales: {
+                            y: { beginAtZero: true, title: { display: true, text: 'Relative to all pairs' } },
+                            x: { ticks: { maxTicksLimit: 5 } }
+                        }
+                    }
+                });
+            });
+
+            // --- Convergence Chart ---
+            const convergenceData = await loadJson('convergence__SUFFIX__.json');
+            if (convergenceData.length > 0) {
+                document.getElementById('convergenceSection').hidden = false;
+                const convergenceColors = ['rgba(54, 162, 235, 1)', 'rgba(255, 99, 132, 1)', 'rgba(75, 192, 192, 1)', 'rgba(255, 159, 64, 1)', 'rgba(153, 102, 255, 1)', 'rgba(108, 117, 125, 1)'];
+                const convergenceDatasets = [];
+                [...new Set(convergenceData.map(d => d.gap))].forEach((gap, i) => {
+                    const points = convergenceData.filter(d => d.gap === gap);
+                    const color = convergenceColors[i % convergenceColors.length];
+                    convergenceDatasets.push({ label: `Gap ${gap}`, data: points.map(d => ({ x: d.n, y: d.rate })), borderColor: color, borderWidth: 1.5, pointRadius: 0 });
+                    convergenceDatasets.push({ label: `Gap ${gap} (model)`, data: points.map(d => ({ x: d.n, y: d.expected })), borderColor: color, borderWidth: 1, borderDash: [6, 4], pointRadius: 0 });
+                });
+                new Chart(document.getElementById('convergenceChart'), {
+                    type: 'line',
+                    data: { datasets: convergenceDatasets },
+                    options: {
+                        plugins: {
+                            tooltip: { callbacks: { label: ctx => `${ctx.dataset.label}: ${fmtFixed(ctx.raw.y, 4)} up to N = ${fmtInt(ctx.raw.x)}` } }
+                        },
+                        scales: {
+                            x: { type: 'logarithmic', title: { display: true, text: 'N' } },
+                            y: { beginAtZero: true, title: { display: true, text: 'Cumulative success rate' } }
+                        }
+                    }
+                });
+            }
+
             // --- Gap Spectrum Chart ---
+            const chartedGaps = gapData.filter(d => d.gap_size <= metadata.spectrum_chart_max);
             new Chart(document.getElementById('gapChart'), {
                 type: 'bar',
                 data: {
-                    labels: gapData.filter(d=>d.gap_size <= 60).map(d => d.gap_size),
+                    labels: chartedGaps.map(d => d.gap_size),
                     datasets: [{
                         label: 'Success Rate',
-                        data: gapData.filter(d=>d.gap_size <= 60).map(d => d.success_rate),
+                        data: chartedGaps.map(d => d.success_rate),
                         backgroundColor: 'rgba(153, 102, 255, 0.6)'
                     }]
                 },
                 options: { scales: { y: { beginAtZero: true, title: { display: true, text: 'Success Rate' } }, x: { title: { display: true, text: 'Gap Size' } } } }
             });
+
+            // --- Observed / Hardy–Littlewood Chart ---
+            const hlPoints = allGapData
+                .filter(d => d.expected_count_hl > 0 && d.count > 0 && d.gap_size > 1)
+                .map(d => ({ x: d.gap_size, y: d.count / d.expected_count_hl }));
+            const hlMaxGap = hlPoints.length > 0 ? hlPoints[hlPoints.length - 1].x : 2;
+            new Chart(document.getElementById('hlChart'), {
+                type: 'scatter',
+                data: {
+                    datasets: [{
+                        label: 'Observed / predicted',
+                        data: hlPoints,
+                        backgroundColor: 'rgba(255, 159, 64, 0.8)',
+                    }, {
+                        label: 'Prediction (1.0)',
+                        data: [{ x: 0, y: 1 }, { x: hlMaxGap, y: 1 }],
+                        showLine: true,
+                        pointRadius: 0,
+                        borderColor: 'rgba(108, 117, 125, 0.9)',
+                        borderDash: [6, 4],
+                    }]
+                },
+                options: {
+                    plugins: {
+                        tooltip: { callbacks: { label: ctx => ctx.datasetIndex === 0 ? `Gap ${ctx.raw.x}: ${fmtFixed(ctx.raw.y, 3)}×` : '' } }
+                    },
+                    scales: {
+                        y: { title: { display: true, text: 'Observed / predicted count' } },
+                        x: { title: { display: true, text: 'Gap Size' } }
+                    }
+                }
+            });
+
+            // --- Gap Family Chart ---
+            const familyData = await loadJson('gap_families__SUFFIX__.json');
+            if (familyData.length > 0) {
+                document.getElementById('familySection').hidden = false;
+                const familyLabels = familyData.map(d => `g ≡ ${d.residue} (mod ${d.modulus})`);
+                // Rates and boosts relative to the pooled values, which do not depend on the modulus.
+                const pooled = familyData.filter(d => d.modulus === familyData[0].modulus).reduce(
+                    (sum, d) => ({ count: sum.count + d.count, successes: sum.successes + d.successes, boost: sum.boost + d.theoretical_boost * d.count }),
+                    { count: 0, successes: 0, boost: 0 });
+                const pooledRate = pooled.successes / pooled.count;
+                const pooledBoost = pooled.boost / pooled.count;
+                new Chart(document.getElementById('familyChart'), {
+                    type: 'bar',
+                    data: {
+                        labels: familyLabels,
+                        datasets: [{
+                            label: 'Rate / pooled rate',
+                            data: familyData.map(d => d.success_rate / pooledRate),
+                            backgroundColor: 'rgba(54, 162, 235, 0.6)',
+                        }, {
+                            label: 'Boost / pooled boost',
+                            data: familyData.map(d => d.theoretical_boost / pooledBoost),
+                            backgroundColor: 'rgba(0, 0, 0, 0)',
+                            borderColor: 'rgba(255, 99, 132, 1)',
+                            borderWidth: 2,
+                        }]
+                    },
+                    options: {
+                        plugins: {
+                            tooltip: {
+                                callbacks: {
+                                    label: ctx => `${ctx.dataset.label}: ${fmtFixed(ctx.raw, 3)}`,
+                                    afterBody: context => {
+                                        const d = familyData[context[0].dataIndex];
+                                        return `${fmtInt(d.count)} pairs over ${d.gaps} gap sizes | Rate ${fmtFixed(d.success_rate, 4)} (95% CI ${fmtFixed(d.success_ci_low, 4)} – ${fmtFixed(d.success_ci_high, 4)}) | Boost ${fmtFixed(d.theoretical_boost, 3)}`;
+                                    }
+                                }
+                            }
+                        },
+                        scales: {
+                            y: { beginAtZero: true, title: { display: true, text: 'Relative to all pairs' } }
+                        }
+                    }
+                });
+            }
+
+            // --- Throughput Chart ---
+            const throughputData = await loadJson('throughput__SUFFIX__.json');
+            if (throughputData.length > 0) {
+                document.getElementById('throughputSection').hidden = false;
+                const point = (d, y) => ({ x: d.segment_end, y });
+                new Chart(document.getElementById('throughputChart'), {
+                    type: 'line',
+                    data: {
+                        datasets: [{
+                            label: 'Primes/sec',
+                            data: throughputData.map(d => point(d, d.primes_per_sec)),
+                            borderColor: 'rgba(54, 162, 235, 1)',
+                            pointRadius: 0,
+                            yAxisID: 'rate',
+                        }, {
+                            label: 'S checks/sec',
+                            data: throughputData.map(d => point(d, d.s_checks_per_sec)),
+                            borderColor: 'rgba(75, 192, 192, 1)',
+                            pointRadius: 0,
+                            yAxisID: 'rate',
+                        }, {
+                            label: 'Sieve time share',
+                            data: throughputData.map(d => point(d, d.elapsed_secs > 0 ? d.sieve_secs / d.elapsed_secs : 0)),
+                            borderColor: 'rgba(255, 159, 64, 1)',
+                            borderDash: [6, 4],
+                            pointRadius: 0,
+                            yAxisID: 'share',
+                        }]
+                    },
+                    options: {
+                        plugins: {
+                            tooltip: { callbacks: { label: ctx => ctx.dataset.yAxisID === 'share' ? `${ctx.dataset.label}: ${fmtFixed(100 * ctx.raw.y, 1)}%` : `${ctx.dataset.label}: ${fmtInt(ctx.raw.y)}` } }
+                        },
+                        scales: {
+                            x: { type: 'linear', title: { display: true, text: 'p (segment end)' } },
+                            rate: { position: 'left', beginAtZero: true, title: { display: true, text: 'Per second' } },
+                            share: { position: 'right', min: 0, max: 1, grid: { drawOnChartArea: false }, title: { display: true, text: 'Sieve time share' } }
+                        }
+                    }
+                });
+            }
+
+            // --- Rate Correlation Heatmap ---
+            const correlations = await loadJson('rate_correlations__SUFFIX__.json');
+            if (correlations.series.length > 0) {
+                document.getElementById('correlationSection').hidden = false;
+                const label = name => name === 'ratio_s_p' ? 'S/P' : name.replace(/^gap_(\d+)_rate$/, (_, g) => `Gap ${g}`);
+                const cell = r => {
+                    if (r === null) return '<td>–</td>';
+                    const color = r >= 0 ? `rgba(220, 53, 69, ${Math.abs(r)})` : `rgba(0, 123, 255, ${Math.abs(r)})`;
+                    return `<td style="background-color: ${color}">${fmtFixed(r, 2)}</td>`;
+                };
+                document.getElementById('correlationTable').innerHTML =
+                    `<thead><tr><th></th>${correlations.series.map(s => `<th>${label(s)}</th>`).join('')}</tr></thead>` +
+                    '<tbody>' + correlations.series.map((s, i) =>
+                        `<tr><th>${label(s)}</th>${correlations.matrix[i].map(cell).join('')}</tr>`).join('') + '</tbody>';
+            }
+
+            // --- Gap Table ---
+            const gapTable = document.getElementById('gapTable');
+            const gapTableBody = gapTable.querySelector('tbody');
+            const gapFilterMin = document.getElementById('gapFilterMin');
+            const gapFilterMax = document.getElementById('gapFilterMax');
+            let sortKey = 'gap_size';
+            let sortAscending = true;
+
+            function renderGapTable() {
+                const minGap = gapFilterMin.value === '' ? -Infinity : Number(gapFilterMin.value);
+                const maxGap = gapFilterMax.value === '' ? Infinity : Number(gapFilterMax.value);
+                const rows = allGapData
+                    .filter(d => d.gap_size >= minGap && d.gap_size <= maxGap)
+                    .sort((a, b) => {
+                        // Shield prime lists sort as their joined text, numbers in it by value.
+                        const value = v => Array.isArray(v) ? v.join(',') : v;
+                        const x = value(a[sortKey]), y = value(b[sortKey]);
+                        const cmp = typeof x === 'string' ? x.localeCompare(y, undefined, { numeric: true }) : x - y;
+                        return sortAscending ? cmp : -cmp;
+                    });
+                gapTableBody.innerHTML = rows.map(d => `<tr>
+                    <td>${d.gap_size}</td>
+                    <td>${fmtInt(d.count)}</td>
+                    <td>${fmtInt(d.successes)}</td>
+                    <td>${fmtFixed(d.success_rate, 4)}</td>
+                    <td>${fmtFixed(d.success_ci_low, 4)} – ${fmtFixed(d.success_ci_high, 4)}</td>
+                    <td title="${d.baseline}">${fmtFixed(d.expected_rate_heuristic, 4)}</td>
+                    <td>${fmtFixed(d.theoretical_boost, 3)}</td>
+                    <td>${fmtFixed(d.exact_boost, 3)}</td>
+                    <td>${d.shield_primes.join(',') || '-'}</td>
+                    <td>${fmtSci(d.p_value)}</td>
+                </tr>`).join('');
+                gapTable.querySelectorAll('th').forEach(th => {
+                    th.classList.toggle('sorted-asc', th.dataset.key === sortKey && sortAscending);
+                    th.classList.toggle('sorted-desc', th.dataset.key === sortKey && !sortAscending);
+                    th.setAttribute('aria-sort', th.dataset.key !== sortKey ? 'none' : sortAscending ? 'ascending' : 'descending');
+                });
+            }
+
+            gapTable.querySelectorAll('th').forEach(th => {
+                th.tabIndex = 0;
+                const sortBy = () => {
+                    if (sortKey === th.dataset.key) {
+                        sortAscending = !sortAscending;
+                    } else {
+                        sortKey = th.dataset.key;
+                        sortAscending = true;
+                    }
+                    renderGapTable();
+                };
+                th.addEventListener('click', sortBy);
+                th.addEventListener('keydown', event => {
+                    if (event.key === 'Enter' || event.key === ' ') {
+                        event.preventDefault();
+                        sortBy();
+                    }
+                });
+            });
+            gapFilterMin.addEventListener('input', renderGapTable);
+            gapFilterMax.addEventListener('input', renderGapTable);
+            renderGapTable();
+
+            // --- Data Downloads ---
+            const downloads = document.getElementById('downloads');
+            metadata.data_files.forEach(name => {
+                const link = document.createElement('a');
+                link.className = 'download-button';
+                link.textContent = `⬇ ${name}`;
+                link.download = name;
+                if (embeddedData[name] !== undefined) {
+                    link.href = URL.createObjectURL(new Blob([embeddedData[name]], { type: 'text/csv' }));
+                    link.title = 'Embedded copy';
+                } else {
+                    link.href = name;
+                }
+                downloads.appendChild(link);
+            });
+
+            // --- Accessibility and Printing ---
+            // Screen readers describe each chart and table with its section's explanation.
+            document.querySelectorAll('.chart-container').forEach((section, i) => {
+                const caption = section.querySelector('h2 .tooltip-text');
+                if (!caption) return;
+                caption.id = `sectionCaption${i}`;
+                section.querySelectorAll('canvas, table').forEach(el => el.setAttribute('aria-describedby', caption.id));
+            });
+            Object.values(Chart.instances).forEach(addDatasetToggles);
+            // Print in the light palette at the paper's width, then restore the viewer's theme.
+            let themeBeforePrint = null;
+            window.addEventListener('beforeprint', () => {
+                themeBeforePrint = document.documentElement.dataset.theme;
+                applyTheme('light');
+                Object.values(Chart.instances).forEach(chart => { chart.resize(); chart.update('none'); });
+            });
+            window.addEventListener('afterprint', () => {
+                applyTheme(themeBeforePrint);
+                Object.values(Chart.instances).forEach(chart => { chart.resize(); chart.update('none'); });
+            });
         }
         loadDataAndRenderCharts();
     </script>
@@ -314,8 +1705,168 @@ pub fn generate_report(config: &Config, max_n: u64) -> Result<(), Box<dyn Error>
 </html>
 "#;
 
-    let report_path = Path::new(output_dir).join("index.html");
+    let html_content = html_content
+        .replace("__EMBEDDED_DATA__", &embedded_json)
+        .replace("__SUFFIX__", suffix);
+
+    let report_path = Path::new(output_dir).join(format!("index{}.html", suffix));
     fs::write(report_path, html_content)?;
 
     Ok(())
 }
+
+/// Browser executables tried, in order, when `CHROME_PATH` is not set.
+const BROWSER_CANDIDATES: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "microsoft-edge",
+];
+
+/// Prints the generated `index.html` to `report.pdf` using a headless Chromium-based browser.
+pub fn export_pdf(config: &Config) -> Result<PathBuf, Box<dyn Error>> {
+    let output_dir = fs::canonicalize(&config.output_dir)?;
+    let html_path = output_dir.join("index.html");
+    let pdf_path = output_dir.join("report.pdf");
+
+    let candidates: Vec<String> = match std::env::var("CHROME_PATH") {
+        Ok(path) => vec![path],
+        Err(_) => BROWSER_CANDIDATES.iter().map(|s| s.to_string()).collect(),
+    };
+
+    for browser in &candidates {
+        let status = Command::new(browser)
+            .arg("--headless")
+            .arg("--disable-gpu")
+            .arg("--no-pdf-header-footer")
+            // The report fetches its JSON files, which file:// pages may only do with this flag.
+            .arg("--allow-file-access-from-files")
+            // Give Chart.js and MathJax time to render before printing.
+            .arg("--virtual-time-budget=15000")
+            .arg(format!("--print-to-pdf={}", pdf_path.display()))
+            .arg(format!("file://{}", html_path.display()))
+            .output();
+        match status {
+            Ok(output) if output.status.success() && pdf_path.exists() => return Ok(pdf_path),
+            Ok(output) => {
+                return Err(format!(
+                    "{} failed to print the report: {}",
+                    browser,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )
+                .into())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(format!(
+        "No headless browser found for PDF export (tried {}). Set CHROME_PATH to a Chromium-based browser.",
+        candidates.join(", ")
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_radius_spans_the_range_on_a_log_scale() {
+        let (min, max) = POINT_RADIUS_RANGE;
+        assert_eq!(point_radius(1, 10_000), min);
+        assert_eq!(point_radius(10_000, 10_000), max);
+        assert!((point_radius(100, 10_000) - (min + max) / 2.0).abs() < 1e-12);
+        assert_eq!(point_radius(1, 1), max);
+    }
+
+    #[test]
+    fn test_lttb_keeps_endpoints_and_budget() {
+        let xs: Vec<f64> = (0..1000).map(|i| i as f64).collect();
+        let ys: Vec<f64> = xs.iter().map(|x| (x / 10.0).sin()).collect();
+
+        let indices = lttb_indices(&xs, &ys, 100);
+        assert_eq!(indices.len(), 100);
+        assert_eq!(indices[0], 0);
+        assert_eq!(*indices.last().unwrap(), 999);
+        assert!(indices.windows(2).all(|w| w[0] < w[1]));
+
+        // Short series are returned untouched.
+        assert_eq!(lttb_indices(&xs[..10], &ys[..10], 100).len(), 10);
+    }
+
+    #[test]
+    fn test_downsampled_rows_stay_within_the_budget() {
+        // Series peaking in different places pick mostly different rows.
+        let row = |i: usize| {
+            let x = i as f64;
+            let mut row = BTreeMap::new();
+            row.insert("bin_start".to_string(), serde_json::json!(x));
+            row.insert("ratio_s_p".to_string(), serde_json::json!((x / 7.0).sin()));
+            for gap in [2, 4, 6, 12, 30] {
+                let rate = (x / gap as f64).cos() * (x / 100.0 + gap as f64).sin();
+                row.insert(format!("gap_{}_rate", gap), serde_json::json!(rate));
+            }
+            row
+        };
+        let rows: Vec<_> = (0..2000).map(row).collect();
+        for max_points in [5, 20, 100, 500] {
+            let kept = downsample_oscillation(rows.clone(), max_points);
+            assert!(kept.len() <= max_points, "{} > {}", kept.len(), max_points);
+            assert!(kept.len() >= max_points / 2);
+            assert_eq!(kept[0]["bin_start"], rows[0]["bin_start"]);
+            assert_eq!(kept[kept.len() - 1]["bin_start"], rows[1999]["bin_start"]);
+        }
+
+        let segment = |i: u64| ThroughputRecord {
+            segment_start: i * 100,
+            segment_end: i * 100 + 99,
+            primes: 10,
+            s_checks: 10,
+            elapsed_secs: 1.0,
+            sieve_secs: 0.5,
+            primes_per_sec: (i as f64 / 3.0).sin(),
+            s_checks_per_sec: (i as f64 / 11.0).cos(),
+        };
+        let segments: Vec<_> = (0..1000).map(segment).collect();
+        assert!(downsample_throughput(segments, 50).len() <= 50);
+    }
+
+    #[test]
+    fn test_convergence_series_is_cumulative() {
+        let row = |bin_start, occurrences, successes| GapBinRecord {
+            bin_start,
+            gap: stats::Gap(2),
+            occurrences,
+            successes,
+            rate: 0.0,
+        };
+        let gap_bins = [
+            row(0, 10, 5),
+            row(100, 0, 0),
+            row(200, 30, 5),
+            row(300, 40, 0),
+        ];
+        let points = convergence_series(&gap_bins, &[], 250, 0);
+        let rates: Vec<(u64, f64)> = points.iter().map(|p| (p.n, p.rate)).collect();
+        assert_eq!(rates, vec![(99, 0.5), (199, 0.5), (250, 0.25)]);
+        assert!(points.windows(2).all(|w| w[1].expected <= w[0].expected));
+    }
+
+    #[test]
+    fn test_bootstrap_band_brackets_the_ratio() {
+        let mut rng = stats::SplitMix64::new(1);
+        for (successes, trials) in [(30, 200), (1_500, 10_000), (0, 50), (40, 40)] {
+            let rate = successes as f64 / trials as f64;
+            let (low, high) = bootstrap_ratio_interval(successes, trials, &mut rng);
+            assert!(low <= rate && rate <= high, "{}/{}", successes, trials);
+            // About two standard errors either side.
+            let se = (rate * (1.0 - rate) / trials as f64).sqrt();
+            assert!(high - low <= 5.0 * se + 1e-12, "{}/{}", successes, trials);
+        }
+        let (low, high) = bootstrap_ratio_interval(1_500, 10_000, &mut rng);
+        assert!(low > 0.14 && high < 0.16);
+    }
+}
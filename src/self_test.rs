@@ -0,0 +1,248 @@
+//! The `self-test` subcommand: a tiny built-in analysis (N = 10^6) whose outputs are
+//! compared value by value against golden fixtures embedded in the binary, so a build on
+//! an unfamiliar platform can be checked before it is trusted with a long run.
+//!
+//! Integers and strings must match exactly; floating-point values may differ by a relative
+//! [`FLOAT_TOLERANCE`], since `ln`, `exp` and friends are not bit-identical across libms.
+//! After an intentional change to the outputs, regenerate the fixtures with
+//! `prime_shield_analyzer self-test --keep fixtures/self_test` and review the diff.
+
+use crate::output;
+use std::error::Error;
+use std::path::Path;
+
+/// Relative difference tolerated between a floating-point value and its fixture.
+pub const FLOAT_TOLERANCE: f64 = 1e-9;
+
+/// Golden outputs of [`analysis_args`], by file name.
+const FIXTURES: &[(&str, &str)] = &[
+    (
+        "global_stats.csv",
+        include_str!("../fixtures/self_test/global_stats.csv"),
+    ),
+    (
+        "gap_spectrum.csv",
+        include_str!("../fixtures/self_test/gap_spectrum.csv"),
+    ),
+    (
+        "oscillation_series.csv",
+        include_str!("../fixtures/self_test/oscillation_series.csv"),
+    ),
+    (
+        "gap_bins.csv",
+        include_str!("../fixtures/self_test/gap_bins.csv"),
+    ),
+    (
+        "conditional_rates.csv",
+        include_str!("../fixtures/self_test/conditional_rates.csv"),
+    ),
+    (
+        "neighbor_gaps.csv",
+        include_str!("../fixtures/self_test/neighbor_gaps.csv"),
+    ),
+    (
+        "first_occurrences.csv",
+        include_str!("../fixtures/self_test/first_occurrences.csv"),
+    ),
+    (
+        "prime_counts.csv",
+        include_str!("../fixtures/self_test/prime_counts.csv"),
+    ),
+    (
+        "streaks.csv",
+        include_str!("../fixtures/self_test/streaks.csv"),
+    ),
+    (
+        "success_spacing.csv",
+        include_str!("../fixtures/self_test/success_spacing.csv"),
+    ),
+    (
+        "tail_fit.csv",
+        include_str!("../fixtures/self_test/tail_fit.csv"),
+    ),
+    (
+        "gap_cdf.csv",
+        include_str!("../fixtures/self_test/gap_cdf.csv"),
+    ),
+    (
+        "rate_correlations.csv",
+        include_str!("../fixtures/self_test/rate_correlations.csv"),
+    ),
+];
+
+/// Command line of the analysis the fixtures were generated with. Every option that shapes
+/// the outputs is spelled out so changing a default does not invalidate the fixtures.
+pub fn analysis_args(output_dir: &str) -> Vec<String> {
+    [
+        "prime_shield_analyzer",
+        "--max-exponent",
+        "6",
+        "--bins",
+        "100",
+        "--gaps",
+        "2,4,6,12,30",
+        "--spectrum-max-gap",
+        "2048",
+        "--residue-prime-limit",
+        "13",
+        "--baseline",
+        "ln-n",
+        "--output-dir",
+        output_dir,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+/// One value that differs from its fixture.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    /// Data row (1-based, after the header), or 0 for the header and row count.
+    pub row: usize,
+    pub column: String,
+    pub expected: String,
+    pub observed: String,
+}
+
+/// Outcome of comparing one output file.
+#[derive(Debug)]
+pub struct FileResult {
+    pub file: &'static str,
+    pub values: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+fn values_match(expected: &str, observed: &str) -> bool {
+    if expected == observed {
+        return true;
+    }
+    // Integers parse as f64 too, but only floats get the tolerance.
+    if expected.parse::<i128>().is_ok() || observed.parse::<i128>().is_ok() {
+        return false;
+    }
+    match (expected.parse::<f64>(), observed.parse::<f64>()) {
+        (Ok(e), Ok(o)) => {
+            (e.is_nan() && o.is_nan())
+                || e == o
+                || (e - o).abs() <= FLOAT_TOLERANCE * e.abs().max(o.abs())
+        }
+        _ => false,
+    }
+}
+
+fn compare_file(
+    file: &'static str,
+    fixture: &str,
+    dir: &str,
+) -> Result<FileResult, Box<dyn Error>> {
+    let mut golden = csv::Reader::from_reader(fixture.as_bytes());
+    let headers = golden.headers()?.clone();
+    let expected: Vec<csv::StringRecord> = golden.records().collect::<Result<_, _>>()?;
+    let mut observed_headers = None;
+    let mut observed = Vec::new();
+    for part in output::data_files(dir, file)? {
+        let path = Path::new(dir).join(part);
+        let mut reader =
+            csv::Reader::from_path(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        observed_headers.get_or_insert(reader.headers()?.clone());
+        for row in reader.records() {
+            observed.push(row?);
+        }
+    }
+
+    let mut result = FileResult {
+        file,
+        values: 0,
+        mismatches: Vec::new(),
+    };
+    let observed_headers = observed_headers.unwrap_or_default();
+    if observed_headers != headers {
+        result.mismatches.push(Mismatch {
+            row: 0,
+            column: "header".to_string(),
+            expected: headers.iter().collect::<Vec<_>>().join(","),
+            observed: observed_headers.iter().collect::<Vec<_>>().join(","),
+        });
+    }
+    if expected.len() != observed.len() {
+        result.mismatches.push(Mismatch {
+            row: 0,
+            column: "row count".to_string(),
+            expected: expected.len().to_string(),
+            observed: observed.len().to_string(),
+        });
+    }
+    for (row, (e, o)) in expected.iter().zip(&observed).enumerate() {
+        for (i, column) in headers.iter().enumerate() {
+            let (e, o) = (e.get(i).unwrap_or(""), o.get(i).unwrap_or("missing"));
+            result.values += 1;
+            if !values_match(e, o) {
+                result.mismatches.push(Mismatch {
+                    row: row + 1,
+                    column: column.to_string(),
+                    expected: e.to_string(),
+                    observed: o.to_string(),
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Compares the outputs in `dir` against every fixture.
+pub fn check(dir: &str) -> Result<Vec<FileResult>, Box<dyn Error>> {
+    FIXTURES
+        .iter()
+        .map(|(file, fixture)| compare_file(file, fixture, dir))
+        .collect()
+}
+
+/// Prints the pass/fail table for `dir` and errors if any value differs.
+pub fn run(dir: &str) -> Result<(), Box<dyn Error>> {
+    let results = check(dir)?;
+    println!();
+    println!("{:<26} {:>8}  result", "file", "values");
+    for r in &results {
+        println!(
+            "{:<26} {:>8}  {}",
+            r.file,
+            r.values,
+            if r.mismatches.is_empty() {
+                "PASS".to_string()
+            } else {
+                format!("FAIL ({} differ)", r.mismatches.len())
+            }
+        );
+    }
+
+    let failed: usize = results.iter().map(|r| r.mismatches.len()).sum();
+    if failed == 0 {
+        return Ok(());
+    }
+    println!();
+    // Enough to diagnose a platform problem without flooding the terminal.
+    for r in &results {
+        for m in r.mismatches.iter().take(10) {
+            println!(
+                "{} row {} {}: expected {}, observed {}",
+                r.file, m.row, m.column, m.expected, m.observed
+            );
+        }
+    }
+    Err(format!("{} values differ from the golden fixtures", failed).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerance_applies_only_to_floats() {
+        assert!(values_match("0.18504930061912406", "0.18504930061912410"));
+        assert!(!values_match("0.185", "0.186"));
+        assert!(!values_match("78498", "78499"));
+        assert!(!values_match("true", "false"));
+        assert!(values_match("NaN", "NaN"));
+    }
+}
@@ -0,0 +1,455 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+
+/// How strongly the Shielding Theory predicts a gap to be protected from small prime divisors.
+#[derive(Debug)]
+pub struct ShieldingInfo {
+    pub shield_score: u32,
+    pub shield_primes: Vec<u32>,
+    /// The largest of `shield_primes`, if any.
+    pub max_shield_prime: Option<u32>,
+    /// The boost as an exact fraction, and rounded once to the nearest `f64`.
+    pub theoretical_boost_exact: BigRational,
+    pub theoretical_boost: f64,
+    /// Label of the [`ShieldModel`] the boost was computed with.
+    pub shield_model: String,
+}
+
+/// Which terms beyond the first-order shields enter the theoretical boost. The default is
+/// the first-order model; the corrections are opt-in.
+///
+/// Both corrections follow from counting the residues `p mod q` left open to a prime pair
+/// `(p, p + g)`: `p` rules out `0` and `p + g` rules out `-g`, and `q | S` exactly when
+/// `p ≡ (1 - g) / 2`. That residue is ruled out when `g ≡ 1` or `g ≡ -1 (mod q)`. The
+/// Shielding Theory counts only one of the two classes as a shield, `g ≡ -1 (mod q)` for
+/// `q ≥ 5` and `g ≡ 1 (mod 3)`, and this model keeps to its shields, so gaps in the other
+/// class (e.g. gap 2, with `2 ≡ -1 (mod 3)`) get no boost here. `exact_boost` counts every
+/// residue instead, which is why the two differ (1 against about 1.12 for gap 2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShieldModel {
+    /// The mod-5 trap: for `g ≡ 2, 3 (mod 5)` only three residues stay open and one of them
+    /// makes `5 | S`, so `S` is divisible by 5 a third of the time instead of a fifth.
+    pub mod5_trap: bool,
+    /// Divisor interactions: when a small prime `q` divides `g`, `p + g` rules out no
+    /// residue beyond `p` itself, leaving `q - 1` open, one of which makes `q | S`.
+    pub divisor_interactions: bool,
+    /// Whether 3 enters the model through its own terms: the Mod 3 Rule shield of gaps
+    /// `g ≡ 1 (mod 3)` (a factor 3/2) and the largest divisor interaction (a factor 3/4 for
+    /// every gap divisible by 3).
+    pub mod3_penalty: bool,
+    /// Count 2 as a shield of every even gap: S is always odd, which doubles the boost.
+    pub include_two: bool,
+    /// Primes of [`SMALL_PRIMES`] left out of the model entirely, one bit per index.
+    pub excluded: u32,
+}
+
+impl ShieldModel {
+    /// The original model: shield boosts only.
+    pub const FIRST_ORDER: Self = Self {
+        mod5_trap: false,
+        divisor_interactions: false,
+        mod3_penalty: true,
+        include_two: false,
+        excluded: 0,
+    };
+
+    /// Every correction term enabled.
+    pub const FULL: Self = Self {
+        mod5_trap: true,
+        divisor_interactions: true,
+        mod3_penalty: true,
+        include_two: false,
+        excluded: 0,
+    };
+
+    /// The model without the primes in `primes`, which must be in [`SMALL_PRIMES`].
+    pub fn excluding(mut self, primes: &[u32]) -> Self {
+        for q in primes {
+            if let Some(i) = SMALL_PRIMES.iter().position(|p| p == q) {
+                self.excluded |= 1 << i;
+            }
+        }
+        self
+    }
+
+    /// Whether the odd prime `q` takes part in the model.
+    fn includes(&self, q: u32) -> bool {
+        SMALL_PRIMES
+            .iter()
+            .position(|&p| p == q)
+            .is_none_or(|i| self.excluded & (1 << i) == 0)
+    }
+
+    /// The shields of gap `g` in this model, in ascending order.
+    pub fn shield_primes(&self, g: u64) -> Vec<u32> {
+        if g % 2 == 1 {
+            return Vec::new();
+        }
+        let two = self.include_two.then_some(2);
+        let counted = |&q: &u32| self.includes(q) && (q != 3 || self.mod3_penalty);
+        two.into_iter()
+            .chain(shield_primes(g).into_iter().filter(counted))
+            .collect()
+    }
+
+    /// Short name of the active terms, e.g. `first_order+mod5_trap`, followed by the
+    /// changes to the shield primes, e.g. `+two` or `+excluding_5_7`.
+    pub fn label(&self) -> String {
+        let mut label = String::from("first_order");
+        if self.mod5_trap && self.includes(5) {
+            label.push_str("+mod5_trap");
+        }
+        if self.divisor_interactions {
+            label.push_str("+divisor_interactions");
+        }
+        if !self.mod3_penalty && self.includes(3) {
+            label.push_str("-mod3_penalty");
+        }
+        if self.include_two {
+            label.push_str("+two");
+        }
+        let excluded: Vec<String> = SMALL_PRIMES
+            .iter()
+            .filter(|&&q| !self.includes(q))
+            .map(|q| q.to_string())
+            .collect();
+        if !excluded.is_empty() {
+            label.push_str("+excluding_");
+            label.push_str(&excluded.join("_"));
+        }
+        label
+    }
+}
+
+impl Default for ShieldModel {
+    fn default() -> Self {
+        Self::FIRST_ORDER
+    }
+}
+
+// Pre-compute primes up to 100 for the shielding calculation.
+pub(crate) const SMALL_PRIMES: &[u32] = &[
+    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+/// The small primes that shield gap `g`, in ascending order. [`ShieldModel::shield_primes`]
+/// applies the model's changes to this set.
+pub fn shield_primes(g: u64) -> Vec<u32> {
+    if g % 2 == 1 {
+        return Vec::new();
+    }
+    SMALL_PRIMES
+        .iter()
+        .copied()
+        .filter(|&q| {
+            let q = q as u64;
+            if q == 3 {
+                // The Mod 3 Rule
+                g % 3 == 1
+            } else {
+                // The General Rule (q >= 5)
+                g % q == q - 1
+            }
+        })
+        .collect()
+}
+
+/// The fraction `numerator / denominator`.
+fn ratio(numerator: u64, denominator: u64) -> BigRational {
+    BigRational::new(BigInt::from(numerator), BigInt::from(denominator))
+}
+
+/// Fills in the float of an exact boost.
+fn with_boost(info: ShieldingInfo, boost: BigRational) -> ShieldingInfo {
+    ShieldingInfo {
+        theoretical_boost: boost.to_f64().unwrap_or(f64::NAN),
+        theoretical_boost_exact: boost,
+        ..info
+    }
+}
+
+pub fn calculate_shielding_info(g: u64, model: ShieldModel) -> ShieldingInfo {
+    let info = ShieldingInfo {
+        shield_score: 0,
+        shield_primes: Vec::new(),
+        max_shield_prime: None,
+        theoretical_boost_exact: BigRational::zero(),
+        theoretical_boost: 0.0,
+        shield_model: model.label(),
+    };
+    // For an odd gap (only 1, between 2 and 3) S is even, never prime.
+    if g % 2 == 1 {
+        return info;
+    }
+    let shield_primes_vec = model.shield_primes(g);
+    let shield_score = shield_primes_vec.len() as u32;
+    // Kept exact: a product of many factors each rounded to f64 drifts in the last bits.
+    let mut boost = BigRational::one();
+    for &q in &shield_primes_vec {
+        boost *= ratio(q as u64, q as u64 - 1);
+    }
+
+    // The Mod 5 Trap: P(5 ∤ S) = 2/3 against 4/5 for a random S.
+    if model.mod5_trap && model.includes(5) && matches!(g % 5, 2 | 3) {
+        boost *= ratio(5, 6);
+    }
+
+    // Divisor Interactions: P(q ∤ S) = (q - 2) / (q - 1) against (q - 1) / q.
+    if model.divisor_interactions {
+        for &q in SMALL_PRIMES {
+            if q == 3 && !model.mod3_penalty {
+                continue;
+            }
+            if model.includes(q) && g.is_multiple_of(q as u64) {
+                let q = q as u64;
+                boost *= ratio(q * (q - 2), (q - 1) * (q - 1));
+            }
+        }
+    }
+
+    with_boost(
+        ShieldingInfo {
+            shield_score,
+            max_shield_prime: shield_primes_vec.last().copied(),
+            shield_primes: shield_primes_vec,
+            ..info
+        },
+        boost,
+    )
+}
+
+/// Whether `s` is divisible by the square of none of the odd primes up to 97. Sums S are
+/// odd, and a larger square divides fewer than 0.2% of integers.
+#[inline]
+pub fn small_square_free(s: u64) -> bool {
+    SMALL_PRIMES.iter().all(|&q| {
+        let q = q as u64;
+        !s.is_multiple_of(q * q)
+    })
+}
+
+/// The expected share of the sums S of gap `g` that pass [`small_square_free`]: for each
+/// small prime `q`, one residue of `p mod q²` makes `q² | S = 2p + g - 1`, and it counts
+/// only if `p` and `p + g` are both prime to `q` there.
+pub fn expected_square_free_rate(g: u64) -> f64 {
+    SMALL_PRIMES
+        .iter()
+        .map(|&q| {
+            let q = q as u64;
+            let prime_to_q = |r: u64| !r.is_multiple_of(q) && !(r + g).is_multiple_of(q);
+            let open = (1..q).filter(|&r| prime_to_q(r)).count() as f64;
+            // 2 is invertible mod q², so `2p ≡ 1 - g` has the one solution p = (1 - g) / 2.
+            let m = q * q;
+            let half = m.div_ceil(2);
+            let r = ((1 + m - g % m) % m * half) % m;
+            let hit = prime_to_q(r);
+            if hit {
+                1.0 - 1.0 / (q as f64 * open)
+            } else {
+                1.0
+            }
+        })
+        .product()
+}
+
+/// Shielding of the sum `S_k = p + (p + o_1) + … + (p + o_{k-1}) - (k - 1)` of a window of
+/// `k` consecutive primes, given the offsets `o_i` of each prime from the first (`o_0 = 0`).
+///
+/// For every small prime `q` the residues `p mod q` left open by the window (none of
+/// `p + o_i` divisible by `q`) are counted exactly, along with those among them that make
+/// `q | S_k`; the boost is the product of `P(q ∤ S_k) / (1 - 1/q)`. This covers the shields
+/// and both second-order terms of [`ShieldModel`] at once. A shield is a `q` that never
+/// divides `S_k`; for `q | k` the sum's residue does not depend on `p`, so `q` either never
+/// or always divides it, and in the latter case the boost is 0 and no shields are listed.
+pub fn window_shielding_info(offsets: &[u64]) -> ShieldingInfo {
+    let never_prime = ShieldingInfo {
+        shield_score: 0,
+        shield_primes: Vec::new(),
+        max_shield_prime: None,
+        theoretical_boost_exact: BigRational::zero(),
+        theoretical_boost: 0.0,
+        shield_model: String::from("window_exact"),
+    };
+    // Only windows starting at 2 have an odd offset, and their sums are even.
+    if offsets.iter().any(|o| o % 2 == 1) {
+        return never_prime;
+    }
+    let k = offsets.len() as u64;
+    let offset_sum: u64 = offsets.iter().sum();
+    let mut shields = Vec::new();
+    let mut boost = BigRational::one();
+    for &q in SMALL_PRIMES {
+        let q = q as u64;
+        let constant = (offset_sum % q + q - (k - 1) % q) % q;
+        let open: Vec<u64> = (0..q)
+            .filter(|&r| offsets.iter().all(|o| (r + o) % q != 0))
+            .collect();
+        if open.is_empty() {
+            // Only the window of the primes up to q itself.
+            continue;
+        }
+        let divisible = open
+            .iter()
+            .filter(|&&r| (k % q * r + constant).is_multiple_of(q))
+            .count();
+        if divisible == open.len() {
+            return never_prime;
+        }
+        if divisible == 0 {
+            shields.push(q);
+        }
+        let open = open.len() as u64;
+        boost *= ratio((open - divisible as u64) * q, open * (q - 1));
+    }
+    with_boost(
+        ShieldingInfo {
+            shield_score: shields.len() as u32,
+            max_shield_prime: shields.last().map(|&q| q as u32),
+            shield_primes: shields.iter().map(|&q| q as u32).collect(),
+            ..never_prime
+        },
+        boost,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shielding_logic() {
+        // Test Gap 2: No shields
+        let info_2 = calculate_shielding_info(2, ShieldModel::FIRST_ORDER);
+        assert_eq!(info_2.shield_score, 0);
+        assert!(info_2.shield_primes.is_empty());
+        assert_eq!(info_2.theoretical_boost_exact, ratio(1, 1));
+
+        // Test Gap 4: Shielded by 3 and 5
+        let info_4 = calculate_shielding_info(4, ShieldModel::FIRST_ORDER);
+        assert_eq!(info_4.shield_score, 2);
+        assert_eq!(info_4.shield_primes, [3, 5]);
+        assert_eq!(info_4.theoretical_boost_exact, ratio(15, 8));
+        assert_eq!(info_4.theoretical_boost, 1.875);
+
+        // Test Gap 34 ("King"): Shielded by 3, 5, 7
+        let info_34 = calculate_shielding_info(34, ShieldModel::FIRST_ORDER);
+        assert_eq!(info_34.shield_score, 3);
+        assert_eq!(info_34.shield_primes, [3, 5, 7]);
+        assert_eq!(info_34.theoretical_boost_exact, ratio(35, 16));
+        assert_eq!(info_34.theoretical_boost_exact.to_string(), "35/16");
+    }
+
+    #[test]
+    fn test_boost_is_rounded_once() {
+        // Gap 1924 is shielded by 3, 5, 7 and 11 and divisible by 13 and 37.
+        let info = calculate_shielding_info(1924, ShieldModel::FULL);
+        let exact = ratio(3 * 5 * 7 * 11, 2 * 4 * 6 * 10)
+            * ratio(13 * 11, 12 * 12)
+            * ratio(37 * 35, 36 * 36);
+        assert_eq!(info.theoretical_boost_exact, exact);
+        assert_eq!(
+            info.theoretical_boost,
+            (3 * 5 * 7 * 11 * 13 * 11 * 37 * 35) as f64
+                / (2 * 4 * 6 * 10 * 12 * 12 * 36 * 36) as f64
+        );
+    }
+
+    #[test]
+    fn test_gap_one_has_no_shields_or_boost() {
+        let info_1 = calculate_shielding_info(1, ShieldModel::FULL);
+        assert_eq!(info_1.shield_score, 0);
+        assert!(info_1.shield_primes.is_empty());
+        assert_eq!(info_1.theoretical_boost, 0.0);
+    }
+
+    #[test]
+    fn test_second_order_terms() {
+        // Gap 2 falls into the mod-5 trap.
+        let info_2 = calculate_shielding_info(2, ShieldModel::FULL);
+        assert_eq!(info_2.theoretical_boost_exact, ratio(5, 6));
+        assert_eq!(
+            info_2.shield_model,
+            "first_order+mod5_trap+divisor_interactions"
+        );
+
+        // Gap 6 is shielded by 7 but divisible by 3, and escapes the trap (6 ≡ 1 mod 5).
+        let info_6 = calculate_shielding_info(6, ShieldModel::FULL);
+        assert_eq!(info_6.theoretical_boost_exact, ratio(7, 8));
+
+        // Each term can be switched off on its own.
+        let no_trap = ShieldModel {
+            mod5_trap: false,
+            ..ShieldModel::FULL
+        };
+        assert!(calculate_shielding_info(2, no_trap)
+            .theoretical_boost_exact
+            .is_one());
+        assert_eq!(no_trap.label(), "first_order+divisor_interactions");
+    }
+
+    #[test]
+    fn test_shield_prime_changes() {
+        // Gap 34 without 5: shields 3 and 7, and no mod-5 trap to apply.
+        let info_34 = calculate_shielding_info(34, ShieldModel::FIRST_ORDER.excluding(&[5]));
+        assert_eq!(info_34.shield_primes, [3, 7]);
+        assert_eq!(info_34.theoretical_boost_exact, ratio(7, 4));
+        assert_eq!(
+            ShieldModel::FULL.excluding(&[5, 7]).label(),
+            "first_order+divisor_interactions+excluding_5_7"
+        );
+
+        // 2 doubles every boost; without the mod-3 penalty gap 6 keeps only 7's shield.
+        let model = ShieldModel {
+            include_two: true,
+            mod3_penalty: false,
+            ..ShieldModel::FULL
+        };
+        let info_6 = calculate_shielding_info(6, model);
+        assert_eq!(info_6.shield_primes, [2, 7]);
+        assert_eq!(info_6.theoretical_boost_exact, ratio(7, 3));
+        assert_eq!(
+            model.label(),
+            "first_order+mod5_trap+divisor_interactions-mod3_penalty+two"
+        );
+        assert_eq!(calculate_shielding_info(1, model).theoretical_boost, 0.0);
+
+        // The penalty also covers the Mod 3 Rule: gap 4 keeps only 5's shield.
+        let model = ShieldModel {
+            mod3_penalty: false,
+            ..ShieldModel::FIRST_ORDER
+        };
+        let info_4 = calculate_shielding_info(4, model);
+        assert_eq!(info_4.shield_primes, [5]);
+        assert_eq!(info_4.theoretical_boost_exact, ratio(5, 4));
+        assert_eq!(model.label(), "first_order-mod3_penalty");
+    }
+
+    #[test]
+    fn test_square_free() {
+        assert!(small_square_free(4 * 3 * 5 * 101 * 101));
+        assert!(!small_square_free(7 * 9));
+        // S = 2p + 3 for gap 4: 9 | S needs p ≡ 3 (mod 9) and 25 | S needs p ≡ 11 (mod 25),
+        // ruled out by p and p + 4. Gap 2 leaves p ≡ 12 (mod 25), one of the 5 · 3
+        // classes open to twin primes.
+        assert!(expected_square_free_rate(4) > expected_square_free_rate(2));
+        assert!(expected_square_free_rate(2) < 1.0 - 1.0 / 15.0);
+    }
+
+    #[test]
+    fn test_window_shielding() {
+        // A two-prime window is the pair sum: q shields g when q | g - 1 or q | g + 1.
+        assert_eq!(window_shielding_info(&[0, 4]).shield_primes, [3, 5]);
+        assert_eq!(window_shielding_info(&[0, 34]).shield_primes, [3, 5, 7, 11]);
+        assert_eq!(window_shielding_info(&[0, 6]).shield_primes, [5, 7]);
+
+        // S_3 = 3p + 2 g_1 + g_2 - 2: 3 divides it for every p when 2 g_1 + g_2 ≡ 2 (mod 3)
+        // (gaps 2, 4), and never for gaps 2, 18, which 5 and 7 shield as well.
+        assert_eq!(window_shielding_info(&[0, 2, 6]).theoretical_boost, 0.0);
+        let shielded = window_shielding_info(&[0, 2, 20]);
+        assert_eq!(shielded.shield_primes, [3, 5, 7]);
+        assert!(shielded.theoretical_boost > 1.9);
+        assert_eq!(window_shielding_info(&[0, 1, 3]).theoretical_boost, 0.0);
+    }
+}
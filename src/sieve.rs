@@ -1,7 +1,141 @@
+use crate::sieve_core;
 use bitvec::prelude::*;
+use memmap2::MmapMut;
 use rayon::prelude::*;
-use std::collections::VecDeque;
-use std::sync::atomic::{AtomicU64, Ordering};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Plain sieve of Eratosthenes over `[0, sqrt_limit]` (1 means prime); its primes are the
+/// base primes of the segmented sieves.
+pub(crate) fn base_sieve(sqrt_limit: u64) -> BitVec<u8, Lsb0> {
+    let mut base_sieve = bitvec![u8, Lsb0; 1; (sqrt_limit + 1) as usize];
+    base_sieve.set(0, false);
+    base_sieve.set(1, false);
+
+    for i in 2..=(sqrt_limit as f64).sqrt() as u64 {
+        if base_sieve[i as usize] {
+            for j in (i * i..=sqrt_limit).step_by(i as usize) {
+                base_sieve.set(j as usize, false);
+            }
+        }
+    }
+    base_sieve
+}
+
+/// Sieves the primes of `[start, end)` for windows far from the origin, given the primes
+/// up to `sqrt(end)` (see [`base_primes`]).
+pub fn primes_in_window(start: u64, end: u64, base_primes: &[u32]) -> Vec<u64> {
+    let segment = PrimeIterator::sieve_segment(start, end, base_primes, None);
+    segment
+        .iter_zeros()
+        .map(|i| start + i as u64)
+        .filter(|&p| p >= 2)
+        .collect()
+}
+
+/// Every prime up to `sqrt(limit)`.
+pub fn base_primes(limit: u64) -> Vec<u32> {
+    let sqrt_limit = (limit as f64).sqrt() as u64;
+    base_sieve(sqrt_limit)
+        .iter_ones()
+        .map(|i| i as u32)
+        .collect()
+}
+
+/// Deterministic Miller-Rabin test, exact for every `u64`.
+pub fn is_prime_u64(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for &p in &BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    let mul_mod = |a: u64, b: u64| ((a as u128 * b as u128) % n as u128) as u64;
+    let pow_mod = |mut base: u64, mut exp: u64| {
+        let mut result = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = mul_mod(result, base);
+            }
+            base = mul_mod(base, base);
+            exp >>= 1;
+        }
+        result
+    };
+
+    let d = (n - 1) >> (n - 1).trailing_zeros();
+    let r = (n - 1).trailing_zeros();
+    'witness: for &a in &BASES {
+        let mut x = pow_mod(a, d);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..r {
+            x = mul_mod(x, x);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Set by [`pin_threads`]: mark segments in fixed per-thread chunks instead of
+/// distributing base primes through work stealing.
+static CHUNKED_MARKING: AtomicBool = AtomicBool::new(false);
+
+/// Pins each rayon worker thread to its own core and switches segment marking to fixed
+/// per-thread chunk ranges, so that on NUMA machines a chunk stays on the node whose
+/// thread first touched it. Must be called before any other rayon work.
+pub fn pin_threads() -> Result<(), Box<dyn Error>> {
+    let cores = core_affinity::get_core_ids().ok_or("could not query the CPU cores")?;
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(cores.len())
+        .start_handler(move |index| {
+            core_affinity::set_for_current(cores[index]);
+        })
+        .build_global()?;
+    CHUNKED_MARKING.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Set by [`use_fixed_partitioning`]: mark segments in [`FIXED_MARKING_CHUNKS`] ranges.
+static FIXED_MARKING: AtomicBool = AtomicBool::new(false);
+
+/// Word-aligned ranges each segment is split into under [`use_fixed_partitioning`].
+pub const FIXED_MARKING_CHUNKS: usize = 64;
+
+/// Splits segment marking into the same [`FIXED_MARKING_CHUNKS`] ranges on every run,
+/// whatever the thread count or work-stealing schedule (`--deterministic`). Takes
+/// precedence over the per-thread chunks of [`pin_threads`].
+pub fn use_fixed_partitioning() {
+    FIXED_MARKING.store(true, Ordering::Relaxed);
+}
+
+/// Set by [`use_nested_parallel_marking`]: keep marking in parallel inside rayon workers.
+static NESTED_PARALLEL_MARKING: AtomicBool = AtomicBool::new(false);
+
+/// Lets segments sieved from inside a rayon worker (e.g. the segments of
+/// [`SegmentStore::ensure`], which are sieved in parallel with each other) still split their
+/// marking across the pool. By default such segments are marked on the worker alone, since
+/// nesting one parallel loop in another oversubscribes the pool without speeding it up.
+pub fn use_nested_parallel_marking() {
+    NESTED_PARALLEL_MARKING.store(true, Ordering::Relaxed);
+}
 
 /// An iterator that generates primes up to a given limit using a segmented sieve.
 pub struct PrimeIterator {
@@ -10,13 +144,137 @@ pub struct PrimeIterator {
     base_primes: Vec<u32>,
     sieve_state: SieveState,
     segment_size_bits: u64,
+    tuner: Option<SegmentTuner>,
+    mmap_dir: Option<PathBuf>,
+    sieve_time: Duration,
+}
+
+/// The bits of one sieved segment (1 means composite), held either on the heap or in a
+/// memory-mapped scratch file so that very large segments can page to disk.
+pub enum SegmentBits {
+    Heap(BitVec<u64, Lsb0>),
+    Mapped { map: MmapMut, len: usize },
+}
+
+impl SegmentBits {
+    fn zeroed(len: usize, mmap_dir: Option<&Path>) -> Self {
+        match mmap_dir {
+            Some(dir) if len > 0 => Self::mapped(dir, len).unwrap_or_else(|e| {
+                panic!("failed to map a sieve segment in {}: {}", dir.display(), e)
+            }),
+            _ => Self::Heap(bitvec![u64, Lsb0; 0; len]),
+        }
+    }
+
+    fn mapped(dir: &Path, len: usize) -> io::Result<Self> {
+        static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+        let path = dir.join(format!(
+            ".segment-{}-{}.bits",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // The mapping keeps the space alive, and nothing is left behind if the process dies.
+        fs::remove_file(&path)?;
+        file.set_len((len.div_ceil(64) * 8) as u64)?;
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Self::Mapped { map, len })
+    }
+
+    /// Bytes of bit storage, whether on the heap or mapped.
+    pub fn memory_bytes(&self) -> u64 {
+        self.words().len() as u64 * 8
+    }
+
+    fn words(&self) -> &[u64] {
+        match self {
+            Self::Heap(bits) => bits.as_raw_slice(),
+            // Mappings are page aligned, so viewing the bytes as u64 words is sound.
+            Self::Mapped { map, .. } => unsafe {
+                std::slice::from_raw_parts(map.as_ptr() as *const u64, map.len() / 8)
+            },
+        }
+    }
+}
+
+impl Deref for SegmentBits {
+    type Target = BitSlice<u64, Lsb0>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Heap(bits) => bits,
+            Self::Mapped { len, .. } => &self.words().view_bits::<Lsb0>()[..*len],
+        }
+    }
+}
+
+impl DerefMut for SegmentBits {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::Heap(bits) => bits,
+            Self::Mapped { map, len } => {
+                let words = unsafe {
+                    std::slice::from_raw_parts_mut(map.as_mut_ptr() as *mut u64, map.len() / 8)
+                };
+                &mut words.view_bits_mut::<Lsb0>()[..*len]
+            }
+        }
+    }
+}
+
+/// Hill-climbing controller for the segment size: after every segment it compares the
+/// measured throughput (primes yielded per second, including the consumer's work between
+/// calls) with the previous segment's, keeps doubling or halving while that helps, and
+/// reverses direction when it does not.
+struct SegmentTuner {
+    segment_started: Instant,
+    primes_in_segment: u64,
+    last_rate: f64,
+    growing: bool,
+}
+
+impl SegmentTuner {
+    const MIN_SEGMENT_BITS: u64 = 16 * 1024 * 8;
+    const MAX_SEGMENT_BITS: u64 = 16 * 1024 * 1024 * 8;
+
+    fn new() -> Self {
+        Self {
+            segment_started: Instant::now(),
+            primes_in_segment: 0,
+            last_rate: 0.0,
+            growing: true,
+        }
+    }
+
+    /// Returns the size to use for the next segment.
+    fn next_size(&mut self, current_bits: u64) -> u64 {
+        let elapsed = self.segment_started.elapsed().as_secs_f64();
+        let rate = self.primes_in_segment as f64 / elapsed.max(1e-9);
+        if rate < self.last_rate {
+            self.growing = !self.growing;
+        }
+        self.last_rate = rate;
+        self.primes_in_segment = 0;
+        self.segment_started = Instant::now();
+
+        let next = if self.growing {
+            current_bits * 2
+        } else {
+            current_bits / 2
+        };
+        next.clamp(Self::MIN_SEGMENT_BITS, Self::MAX_SEGMENT_BITS)
+    }
 }
 
 enum SieveState {
     Base(usize), // Index into base_primes
     Segmented {
         segment_start: u64,
-        segment: BitVec<u64, Lsb0>,
+        segment: SegmentBits,
         segment_index: usize,
     },
 }
@@ -25,18 +283,7 @@ impl PrimeIterator {
     pub fn new(limit: u64, segment_size_bytes: usize) -> Self {
         let sqrt_limit = (limit as f64).sqrt() as u64;
 
-        let mut base_sieve = bitvec![u8, Lsb0; 1; (sqrt_limit + 1) as usize];
-        base_sieve.set(0, false);
-        base_sieve.set(1, false);
-
-        for i in 2..=(sqrt_limit as f64).sqrt() as u64 {
-            if base_sieve[i as usize] {
-                for j in (i * i..=sqrt_limit).step_by(i as usize) {
-                    base_sieve.set(j as usize, false);
-                }
-            }
-        }
-
+        let base_sieve = base_sieve(sqrt_limit);
         let base_primes: Vec<u32> = base_sieve.iter_ones().map(|i| i as u32).collect();
 
         Self {
@@ -45,38 +292,116 @@ impl PrimeIterator {
             base_primes,
             sieve_state: SieveState::Base(0),
             segment_size_bits: (segment_size_bytes * 8) as u64,
+            tuner: None,
+            mmap_dir: None,
+            sieve_time: Duration::ZERO,
+        }
+    }
+
+    /// Backs segments with memory-mapped scratch files in `dir` instead of heap memory.
+    pub fn with_mmap_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.mmap_dir = Some(dir.into());
+        self
+    }
+
+    /// Lets the segment size adapt between segments to maximize measured primes/sec,
+    /// starting from the size given to [`PrimeIterator::new`].
+    pub fn with_adaptive_segment_size(mut self) -> Self {
+        self.tuner = Some(SegmentTuner::new());
+        self
+    }
+
+    /// Total time spent sieving segments of p so far.
+    pub fn sieve_time(&self) -> Duration {
+        self.sieve_time
+    }
+
+    /// Bytes held by the base primes and the current segment.
+    pub fn memory_bytes(&self) -> u64 {
+        let segment = match &self.sieve_state {
+            SieveState::Base(_) => 0,
+            SieveState::Segmented { segment, .. } => segment.memory_bytes(),
+        };
+        self.base_primes.len() as u64 * 4 + segment
+    }
+
+    /// Skips ahead so that the next prime yielded is the first one `>= start`.
+    /// Used to resume an analysis from a checkpoint.
+    pub fn starting_at(mut self, start: u64) -> Self {
+        if start <= self.sqrt_limit {
+            let index = self.base_primes.partition_point(|&p| (p as u64) < start);
+            self.sieve_state = SieveState::Base(index);
+        } else {
+            let segment_end = (start + self.segment_size_bits).min(self.limit + 1);
+            let segment = if start < segment_end {
+                Self::sieve_segment(
+                    start,
+                    segment_end,
+                    &self.base_primes,
+                    self.mmap_dir.as_deref(),
+                )
+            } else {
+                SegmentBits::Heap(BitVec::new())
+            };
+            self.sieve_state = SieveState::Segmented {
+                segment_start: start,
+                segment,
+                segment_index: 0,
+            };
         }
+        self
     }
 
-    fn sieve_segment(start: u64, end: u64, base_primes: &[u32]) -> BitVec<u64, Lsb0> {
-        let mut segment = bitvec![u64, Lsb0; 0; (end - start) as usize]; // 0 means prime
+    /// Marks the multiples of `p` (from `p * p` on) in `[from, to)` of the segment at `start`.
+    pub(crate) fn sieve_segment(
+        start: u64,
+        end: u64,
+        base_primes: &[u32],
+        mmap_dir: Option<&Path>,
+    ) -> SegmentBits {
+        let mut segment = SegmentBits::zeroed((end - start) as usize, mmap_dir); // 0 means prime
 
         // Unsafe cast to atomic slice. This is safe because u64 and AtomicU64 have the
         // same memory representation, and we are only performing atomic operations.
         let atomic_segment: &[AtomicU64] = unsafe {
             std::slice::from_raw_parts(
-                segment.as_raw_slice().as_ptr() as *const AtomicU64,
-                segment.as_raw_slice().len(),
+                segment.words().as_ptr() as *const AtomicU64,
+                segment.words().len(),
             )
         };
 
-        // Parallelize the marking of composites
-        base_primes.par_iter().for_each(|&p_u32| {
-            let p = p_u32 as u64;
-            let mut mark_start = start.div_ceil(p) * p;
-            if p * p > start {
-                mark_start = p * p;
-            }
-
-            for i in (mark_start..end).step_by(p as usize) {
-                let idx = (i - start) as usize;
-                let word_idx = idx / 64;
-                let bit_in_word = idx % 64;
-                if word_idx < atomic_segment.len() {
-                    atomic_segment[word_idx].fetch_or(1 << bit_in_word, Ordering::Relaxed);
+        // Parallelize the marking of composites, unless the caller already runs in parallel
+        let nested = rayon::current_thread_index().is_some()
+            && !NESTED_PARALLEL_MARKING.load(Ordering::Relaxed);
+        if nested {
+            sieve_core::mark_segment_serial(start, end, base_primes, atomic_segment);
+        } else if FIXED_MARKING.load(Ordering::Relaxed) {
+            let words = atomic_segment.len();
+            let per_chunk = words.div_ceil(FIXED_MARKING_CHUNKS);
+            (0..FIXED_MARKING_CHUNKS).into_par_iter().for_each(|chunk| {
+                let lo = (chunk * per_chunk).min(words) as u64 * 64;
+                let hi = ((chunk + 1) * per_chunk).min(words) as u64 * 64;
+                let (from, to) = (start + lo, (start + hi).min(end));
+                for &p in base_primes {
+                    sieve_core::mark_multiples(p as u64, from, to, start, atomic_segment);
                 }
-            }
-        });
+            });
+        } else if CHUNKED_MARKING.load(Ordering::Relaxed) {
+            // Every worker marks one fixed, word-aligned range with all base primes, so
+            // each range is only ever touched (and first faulted in) by the same thread.
+            let words = atomic_segment.len();
+            rayon::broadcast(|ctx| {
+                let per_thread = words.div_ceil(ctx.num_threads());
+                let lo = (ctx.index() * per_thread).min(words) as u64 * 64;
+                let hi = ((ctx.index() + 1) * per_thread).min(words) as u64 * 64;
+                let (from, to) = (start + lo, (start + hi).min(end));
+                for &p in base_primes {
+                    sieve_core::mark_multiples(p as u64, from, to, start, atomic_segment);
+                }
+            });
+        } else {
+            sieve_core::mark_segment(start, end, base_primes, atomic_segment);
+        }
 
         if start == 0 {
             if !segment.is_empty() {
@@ -109,8 +434,14 @@ impl Iterator for PrimeIterator {
                         let segment_start = self.sqrt_limit + 1;
                         let segment_end =
                             (segment_start + self.segment_size_bits).min(self.limit + 1);
-                        let segment =
-                            Self::sieve_segment(segment_start, segment_end, &self.base_primes);
+                        let sieve_started = Instant::now();
+                        let segment = Self::sieve_segment(
+                            segment_start,
+                            segment_end,
+                            &self.base_primes,
+                            self.mmap_dir.as_deref(),
+                        );
+                        self.sieve_time += sieve_started.elapsed();
                         self.sieve_state = SieveState::Segmented {
                             segment_start,
                             segment,
@@ -130,17 +461,30 @@ impl Iterator for PrimeIterator {
                             if prime > self.limit {
                                 return None;
                             }
+                            if let Some(tuner) = self.tuner.as_mut() {
+                                tuner.primes_in_segment += 1;
+                            }
                             return Some(prime);
                         }
                         *segment_index += 1;
                     }
 
-                    *segment_start += self.segment_size_bits;
+                    *segment_start += segment.len() as u64;
+                    if let Some(tuner) = self.tuner.as_mut() {
+                        self.segment_size_bits = tuner.next_size(self.segment_size_bits);
+                    }
                     if *segment_start > self.limit {
                         return None;
                     }
                     let segment_end = (*segment_start + self.segment_size_bits).min(self.limit + 1);
-                    *segment = Self::sieve_segment(*segment_start, segment_end, &self.base_primes);
+                    let sieve_started = Instant::now();
+                    *segment = Self::sieve_segment(
+                        *segment_start,
+                        segment_end,
+                        &self.base_primes,
+                        self.mmap_dir.as_deref(),
+                    );
+                    self.sieve_time += sieve_started.elapsed();
                     *segment_index = 0;
                 }
             }
@@ -148,33 +492,26 @@ impl Iterator for PrimeIterator {
     }
 }
 
-pub struct PrimalityChecker {
+/// A sieved segment keyed by its start.
+type CachedSegment = (u64, Arc<SegmentBits>);
+
+/// Read-mostly store of sieved segments shared by every [`PrimalityChecker`] built on it.
+/// Segments are added by range prefetches and by checkers on a miss, and read by all of them.
+pub struct SegmentStore {
     limit: u64,
     sqrt_limit: u64,
     base_primes: Vec<u32>,
     known_primes_under_sqrt: BitVec<u8, Lsb0>,
-
-    cached_segments: VecDeque<(u64, BitVec<u64, Lsb0>)>,
-    cache_size: usize,
     segment_size_bits: u64,
+    mmap_dir: Option<PathBuf>,
+    segments: RwLock<BTreeMap<u64, Arc<SegmentBits>>>,
 }
 
-impl PrimalityChecker {
+impl SegmentStore {
     pub fn new(limit: u64, segment_size_bytes: usize) -> Self {
         let sqrt_limit = (limit as f64).sqrt() as u64;
 
-        let mut base_sieve = bitvec![u8, Lsb0; 1; (sqrt_limit + 1) as usize];
-        base_sieve.set(0, false);
-        base_sieve.set(1, false);
-
-        for i in 2..=(sqrt_limit as f64).sqrt() as u64 {
-            if base_sieve[i as usize] {
-                for j in (i * i..=sqrt_limit).step_by(i as usize) {
-                    base_sieve.set(j as usize, false);
-                }
-            }
-        }
-
+        let base_sieve = base_sieve(sqrt_limit);
         let base_primes: Vec<u32> = base_sieve.iter_ones().map(|i| i as u32).collect();
 
         Self {
@@ -182,40 +519,258 @@ impl PrimalityChecker {
             sqrt_limit,
             base_primes,
             known_primes_under_sqrt: base_sieve,
-            cached_segments: VecDeque::with_capacity(4),
-            cache_size: 4,
             segment_size_bits: (segment_size_bytes * 8) as u64,
+            mmap_dir: None,
+            segments: RwLock::new(BTreeMap::new()),
         }
     }
 
+    /// Backs segments with memory-mapped scratch files in `dir` instead of heap memory.
+    pub fn with_mmap_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.mmap_dir = Some(dir.into());
+        self
+    }
+
+    fn segment_start(&self, n: u64) -> u64 {
+        (n / self.segment_size_bits) * self.segment_size_bits
+    }
+
+    fn sieve(&self, start: u64) -> Arc<SegmentBits> {
+        Arc::new(PrimeIterator::sieve_segment(
+            start,
+            start + self.segment_size_bits,
+            &self.base_primes,
+            self.mmap_dir.as_deref(),
+        ))
+    }
+
+    /// Bytes held by the base primes, the small-prime table and every stored segment.
+    pub fn memory_bytes(&self) -> u64 {
+        let segments: u64 = self
+            .segments
+            .read()
+            .unwrap()
+            .values()
+            .map(|s| s.memory_bytes())
+            .sum();
+        self.base_primes.len() as u64 * 4
+            + self.known_primes_under_sqrt.as_raw_slice().len() as u64
+            + segments
+    }
+
+    fn get(&self, start: u64) -> Option<Arc<SegmentBits>> {
+        self.segments.read().unwrap().get(&start).cloned()
+    }
+
+    fn insert(&self, start: u64, segment: Arc<SegmentBits>) -> Arc<SegmentBits> {
+        Arc::clone(
+            self.segments
+                .write()
+                .unwrap()
+                .entry(start)
+                .or_insert(segment),
+        )
+    }
+
+    /// Sieves every missing segment covering `[min, max]` in parallel.
+    pub fn ensure(&self, min: u64, max: u64) {
+        let max = max.min(self.limit);
+        let first = self.segment_start(min.max(self.sqrt_limit + 1));
+        if min > max || first > max {
+            return;
+        }
+        let missing: Vec<u64> = {
+            let segments = self.segments.read().unwrap();
+            (first..=max)
+                .step_by(self.segment_size_bits as usize)
+                .filter(|start| !segments.contains_key(start))
+                .collect()
+        };
+        let sieved: Vec<CachedSegment> = missing
+            .into_par_iter()
+            .map(|start| (start, self.sieve(start)))
+            .collect();
+
+        self.segments.write().unwrap().extend(sieved);
+    }
+
+    /// Drops the segments wholly below `min`, which the analysis has moved past.
+    pub fn evict_below(&self, min: u64) {
+        let mut segments = self.segments.write().unwrap();
+        *segments = segments.split_off(&self.segment_start(min));
+    }
+}
+
+/// Where `is_prime` lookups were answered, for tuning the cache layout.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct CacheStats {
+    /// Capacity of the checker's own cache, in segments (`--checker-cache-segments`).
+    pub cache_segments: usize,
+    /// Found in the checker's own small cache.
+    pub local_hits: u64,
+    /// Found in the shared [`SegmentStore`] (e.g. prefetched).
+    pub store_hits: u64,
+    /// Sieved on demand.
+    pub misses: u64,
+    /// Segments pushed out of the checker's own cache to make room for another. Many
+    /// evictions next to few local hits mean lookups cycle through more segments than the
+    /// cache holds.
+    pub evictions: u64,
+}
+
+/// Segments a [`PrimalityChecker`] keeps in its own cache unless told otherwise.
+pub const DEFAULT_CHECKER_CACHE_SEGMENTS: usize = 4;
+
+/// Primality lookups for the sums S. Each worker thread should own one checker; they
+/// keep small local caches in front of a shared [`SegmentStore`].
+pub struct PrimalityChecker {
+    store: Arc<SegmentStore>,
+    cached_segments: VecDeque<CachedSegment>,
+    cache_size: usize,
+    cache_stats: CacheStats,
+    prefetch: Option<JoinHandle<()>>,
+    synchronous: bool,
+}
+
+impl PrimalityChecker {
+    pub fn new(limit: u64, segment_size_bytes: usize) -> Self {
+        Self::with_store(Arc::new(SegmentStore::new(limit, segment_size_bytes)))
+    }
+
+    /// A checker with its own local cache over an existing store, e.g. for another thread.
+    pub fn with_store(store: Arc<SegmentStore>) -> Self {
+        Self {
+            store,
+            cached_segments: VecDeque::with_capacity(DEFAULT_CHECKER_CACHE_SEGMENTS),
+            cache_size: DEFAULT_CHECKER_CACHE_SEGMENTS,
+            cache_stats: CacheStats::default(),
+            prefetch: None,
+            synchronous: false,
+        }
+    }
+
+    /// Runs [`PrimalityChecker::prefetch_range`] on the calling thread, so which lookups
+    /// hit the store no longer depends on how fast the background sieve was.
+    pub fn with_synchronous_prefetch(mut self) -> Self {
+        self.synchronous = true;
+        self
+    }
+
+    /// Keeps up to `segments` (at least one) segments in the checker's own cache.
+    pub fn with_cache_size(mut self, segments: usize) -> Self {
+        self.cache_size = segments.max(1);
+        self.cached_segments = VecDeque::with_capacity(self.cache_size);
+        self
+    }
+
+    pub fn store(&self) -> &Arc<SegmentStore> {
+        &self.store
+    }
+
+    /// Bytes held by the store plus local cache segments the store has already dropped.
+    pub fn memory_bytes(&self) -> u64 {
+        let evicted: u64 = self
+            .cached_segments
+            .iter()
+            .filter(|(start, _)| self.store.get(*start).is_none())
+            .map(|(_, s)| s.memory_bytes())
+            .sum();
+        self.store.memory_bytes() + evicted
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            cache_segments: self.cache_size,
+            ..self.cache_stats
+        }
+    }
+
+    /// Largest n that [`PrimalityChecker::is_prime`] can answer; above it, it returns false.
+    pub fn limit(&self) -> u64 {
+        self.store.limit
+    }
+
+    fn wait_for_prefetch(&mut self) {
+        if let Some(handle) = self.prefetch.take() {
+            handle.join().expect("prefetch thread panicked");
+        }
+    }
+
+    /// Sieves every segment covering `[min, max]` in parallel, so that subsequent
+    /// `is_prime` calls in that range do not sieve on demand. Segments below `min` are
+    /// dropped: lookups only move upward.
+    pub fn ensure_range(&mut self, min: u64, max: u64) {
+        self.wait_for_prefetch();
+        self.store.evict_below(min);
+        self.store.ensure(min, max);
+    }
+
+    /// Like [`PrimalityChecker::ensure_range`], but sieves on a background thread while the
+    /// caller keeps working. It never drops segments, so the range being read stays put.
+    pub fn prefetch_range(&mut self, min: u64, max: u64) {
+        self.wait_for_prefetch();
+        if self.synchronous {
+            self.store.ensure(min, max);
+            return;
+        }
+        let store = Arc::clone(&self.store);
+        self.prefetch = Some(std::thread::spawn(move || store.ensure(min, max)));
+    }
+
     pub fn is_prime(&mut self, n: u64) -> bool {
-        if n > self.limit {
+        if n > self.store.limit {
             return false;
         }
-        if n <= self.sqrt_limit {
-            return self.known_primes_under_sqrt[n as usize];
+        if n <= self.store.sqrt_limit {
+            return self.store.known_primes_under_sqrt[n as usize];
         }
 
-        let segment_start = (n / self.segment_size_bits) * self.segment_size_bits;
+        let segment_start = self.store.segment_start(n);
+        let index = (n - segment_start) as usize;
 
-        for (start, segment) in &self.cached_segments {
-            if *start == segment_start {
-                let index = (n - segment_start) as usize;
-                return !segment[index];
-            }
+        if let Some((_, segment)) = self
+            .cached_segments
+            .iter()
+            .find(|(start, _)| *start == segment_start)
+        {
+            self.cache_stats.local_hits += 1;
+            return !segment[index];
         }
 
-        let segment_end = segment_start + self.segment_size_bits;
-        let new_segment =
-            PrimeIterator::sieve_segment(segment_start, segment_end, &self.base_primes);
-
-        let is_p = !new_segment[(n - segment_start) as usize];
+        let segment = match self.store.get(segment_start) {
+            Some(segment) => {
+                self.cache_stats.store_hits += 1;
+                segment
+            }
+            None => {
+                self.cache_stats.misses += 1;
+                self.store
+                    .insert(segment_start, self.store.sieve(segment_start))
+            }
+        };
+        let is_p = !segment[index];
 
         if self.cached_segments.len() >= self.cache_size {
             self.cached_segments.pop_front();
+            self.cache_stats.evictions += 1;
         }
-        self.cached_segments.push_back((segment_start, new_segment));
+        self.cached_segments.push_back((segment_start, segment));
 
         is_p
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_miller_rabin_matches_sieve() {
+        let sieved: Vec<u64> = PrimeIterator::new(100_000, 4096).collect();
+        let tested: Vec<u64> = (0..=100_000).filter(|&n| is_prime_u64(n)).collect();
+        assert_eq!(sieved, tested);
+
+        assert!(is_prime_u64(18_446_744_073_709_551_557)); // Largest 64-bit prime
+        assert!(!is_prime_u64(3_215_031_751)); // Strong pseudoprime to bases 2, 3, 5, 7
+    }
+}
@@ -0,0 +1,219 @@
+//! The segmented sieve of Eratosthenes on its own, usable without `std`: it needs only
+//! `alloc` and has no dependencies, so it builds for embedded and WASM targets with
+//! `--no-default-features`. The `rayon` feature marks segments in parallel.
+//!
+//! Segments are bit arrays over `[start, end)` stored in `u64` words, where a set bit
+//! means composite. [`crate::sieve`] builds its heap and memory-mapped segments, prefetching
+//! and caching on top of the marking routines here.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The primes up to `limit`, by a plain sieve; enough base primes for any segment ending at
+/// or below `limit²`.
+pub fn base_primes(limit: u64) -> Vec<u32> {
+    let limit = limit as usize;
+    let mut composite = vec![false; limit + 1];
+    let mut primes = Vec::new();
+    for n in 2..=limit {
+        if composite[n] {
+            continue;
+        }
+        primes.push(n as u32);
+        for multiple in (n * n..=limit).step_by(n) {
+            composite[multiple] = true;
+        }
+    }
+    primes
+}
+
+/// Marks the multiples of `p` in `[from, to)` in the segment starting at `start`, starting
+/// no lower than `p²`. Safe to call for disjoint or overlapping ranges from many threads.
+#[inline]
+pub fn mark_multiples(p: u64, from: u64, to: u64, start: u64, words: &[AtomicU64]) {
+    let mark_start = (from.div_ceil(p) * p).max(p * p);
+
+    for i in (mark_start..to).step_by(p as usize) {
+        let idx = (i - start) as usize;
+        let word_idx = idx / 64;
+        let bit_in_word = idx % 64;
+        if word_idx < words.len() {
+            words[word_idx].fetch_or(1 << bit_in_word, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Views a word buffer as atomics for [`mark_multiples`].
+pub fn as_atomic(words: &mut [u64]) -> &[AtomicU64] {
+    // SAFETY: AtomicU64 has the same size and alignment as u64, and the exclusive borrow
+    // guarantees no non-atomic access while the atomic view lives.
+    unsafe { core::slice::from_raw_parts(words.as_ptr() as *const AtomicU64, words.len()) }
+}
+
+/// Marks every composite of `[start, end)` with the base primes, one prime at a time (in
+/// parallel over the primes with the `rayon` feature).
+pub fn mark_segment(start: u64, end: u64, base_primes: &[u32], words: &[AtomicU64]) {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        base_primes.par_iter().for_each(|&p| {
+            mark_multiples(p as u64, start, end, start, words);
+        });
+    }
+    #[cfg(not(feature = "rayon"))]
+    mark_segment_serial(start, end, base_primes, words);
+}
+
+/// [`mark_segment`] on the calling thread only, for callers that are already one of
+/// several parallel tasks.
+pub fn mark_segment_serial(start: u64, end: u64, base_primes: &[u32], words: &[AtomicU64]) {
+    for &p in base_primes {
+        mark_multiples(p as u64, start, end, start, words);
+    }
+}
+
+/// A sieved range `[start, end)`.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    start: u64,
+    end: u64,
+    words: Vec<u64>,
+}
+
+impl Segment {
+    /// Sieves `[start, end)`; `base_primes` must include every prime up to `sqrt(end)`.
+    pub fn sieve(start: u64, end: u64, base_primes: &[u32]) -> Self {
+        let len = (end - start) as usize;
+        let mut words = vec![0u64; len.div_ceil(64)];
+        mark_segment(start, end, base_primes, as_atomic(&mut words));
+        // 0 and 1 are not prime.
+        for n in start..end.min(2) {
+            words[0] |= 1 << (n - start);
+        }
+        Self { start, end, words }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Whether `n`, which must lie in the segment, is prime.
+    #[inline]
+    pub fn is_prime(&self, n: u64) -> bool {
+        debug_assert!((self.start..self.end).contains(&n));
+        let idx = (n - self.start) as usize;
+        self.words[idx / 64] & (1 << (idx % 64)) == 0
+    }
+
+    /// The primes of the segment in ascending order.
+    pub fn primes(&self) -> impl Iterator<Item = u64> + '_ {
+        (self.start..self.end).filter(|&n| self.is_prime(n))
+    }
+}
+
+/// The segments of `[0, limit]`, in order.
+#[derive(Debug, Clone)]
+pub struct SegmentedSieve {
+    limit: u64,
+    segment_bits: u64,
+    base_primes: Vec<u32>,
+    next_start: u64,
+}
+
+impl SegmentedSieve {
+    pub fn new(limit: u64, segment_size_bytes: usize) -> Self {
+        Self {
+            limit,
+            segment_bits: (segment_size_bytes as u64 * 8).max(64),
+            base_primes: base_primes(limit.isqrt()),
+            next_start: 0,
+        }
+    }
+
+    /// Every prime up to `limit` in ascending order.
+    pub fn primes(self) -> impl Iterator<Item = u64> {
+        self.flat_map(|segment| segment.primes().collect::<Vec<_>>())
+    }
+}
+
+impl Iterator for SegmentedSieve {
+    type Item = Segment;
+
+    fn next(&mut self) -> Option<Segment> {
+        if self.next_start > self.limit {
+            return None;
+        }
+        let start = self.next_start;
+        let end = (start + self.segment_bits).min(self.limit + 1);
+        self.next_start = end;
+        Some(Segment::sieve(start, end, &self.base_primes))
+    }
+}
+
+/// Primality of arbitrary numbers up to a limit, sieving the segment around each lookup
+/// and keeping the most recent one, which suits ascending or clustered lookups.
+#[derive(Debug, Clone)]
+pub struct SegmentChecker {
+    limit: u64,
+    segment_bits: u64,
+    base_primes: Vec<u32>,
+    cached: Option<Segment>,
+}
+
+impl SegmentChecker {
+    pub fn new(limit: u64, segment_size_bytes: usize) -> Self {
+        Self {
+            limit,
+            segment_bits: (segment_size_bytes as u64 * 8).max(64),
+            base_primes: base_primes(limit.isqrt()),
+            cached: None,
+        }
+    }
+
+    /// Whether `n` is prime; numbers above the limit are reported as not prime.
+    pub fn is_prime(&mut self, n: u64) -> bool {
+        if n > self.limit {
+            return false;
+        }
+        match &self.cached {
+            Some(segment) if (segment.start..segment.end).contains(&n) => segment.is_prime(n),
+            _ => {
+                let start = n / self.segment_bits * self.segment_bits;
+                let end = (start + self.segment_bits).min(self.limit + 1);
+                let segment = Segment::sieve(start, end, &self.base_primes);
+                let is_prime = segment.is_prime(n);
+                self.cached = Some(segment);
+                is_prime
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_agree_with_base_primes() {
+        let expected = base_primes(100_000);
+        // A small segment size so the range spans many segments, including a partial last one.
+        let primes: Vec<u64> = SegmentedSieve::new(100_000, 128).primes().collect();
+        assert_eq!(primes.len(), expected.len());
+        assert!(primes.iter().zip(&expected).all(|(&a, &b)| a == b as u64));
+
+        let mut checker = SegmentChecker::new(100_000, 128);
+        for n in [0, 1, 2, 3, 4, 97, 1024, 99_991, 99_999, 100_001] {
+            assert_eq!(
+                checker.is_prime(n),
+                expected.binary_search(&(n as u32)).is_ok(),
+                "n = {}",
+                n
+            );
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Destinations of the result tables (`--sink`).
+//!
+//! Every table of a run is built once as typed records and handed to each active sink. The
+//! CSV files are always among them, since the report, `diff`, `migrate` and `--fail-if`
+//! read them; `--sink` adds NDJSON files, an NDJSON stream on stdout, a SQLite database or
+//! Parquet files. The per-bin series (`oscillation_series.csv`, `gap_bins.csv`) are written
+//! bin by bin, possibly while the run goes on, and stay CSV only.
+
+use crate::config::{Config, SinkKind};
+use crate::output::{ColumnSelection, PartitionedWriter, PARTITIONED_FILES};
+use crate::table::{Cell, JsonRow, Table};
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// Database of `--sink sqlite`, one table per result table.
+pub const SQLITE_FILE: &str = "results.sqlite";
+
+/// A destination for the result tables of a run.
+pub trait OutputSink {
+    /// Writes one table in full; sinks other than CSV name it after [`Table::name`].
+    fn write_table(&mut self, table: &Table) -> Result<(), Box<dyn Error>>;
+}
+
+/// The CSV files; those in [`PARTITIONED_FILES`] are split every `--partition-rows` rows.
+/// A table without rows is an empty file.
+pub struct CsvSink {
+    dir: String,
+    partition_rows: u64,
+}
+
+impl CsvSink {
+    pub fn new(dir: &str, partition_rows: u64) -> Self {
+        Self {
+            dir: dir.to_string(),
+            partition_rows,
+        }
+    }
+}
+
+impl OutputSink for CsvSink {
+    fn write_table(&mut self, table: &Table) -> Result<(), Box<dyn Error>> {
+        let max_rows = if PARTITIONED_FILES.contains(&table.file) {
+            self.partition_rows
+        } else {
+            0
+        };
+        let headers =
+            (!table.rows.is_empty()).then(|| table.columns.iter().map(|c| c.to_string()).collect());
+        let mut wtr = PartitionedWriter::create(&self.dir, table.file, max_rows, headers)?;
+        for row in &table.rows {
+            wtr.write_record(&row.iter().map(Cell::to_csv).collect::<Vec<_>>())?;
+        }
+        wtr.finish()
+    }
+}
+
+/// One `<table>.ndjson` file per table, a JSON object per row (`--sink json`).
+pub struct JsonSink {
+    dir: PathBuf,
+}
+
+impl OutputSink for JsonSink {
+    fn write_table(&mut self, table: &Table) -> Result<(), Box<dyn Error>> {
+        let path = self.dir.join(format!("{}.ndjson", table.name()));
+        let mut out = BufWriter::new(File::create(path)?);
+        for row in table.json_rows() {
+            serde_json::to_writer(&mut out, &row)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// A row of the stream, tagged with its table and the max N of its run.
+#[derive(Serialize)]
+struct StreamRow<'a> {
+    table: &'a str,
+    max_n: u64,
+    #[serde(flatten)]
+    row: JsonRow<'a>,
+}
+
+/// Every table as NDJSON on stdout, each row tagged with its table (`--sink stream`).
+pub struct StreamSink {
+    max_n: u64,
+}
+
+impl OutputSink for StreamSink {
+    fn write_table(&mut self, table: &Table) -> Result<(), Box<dyn Error>> {
+        let mut out = std::io::stdout().lock();
+        for row in table.json_rows() {
+            let row = StreamRow {
+                table: table.name(),
+                max_n: self.max_n,
+                row,
+            };
+            serde_json::to_writer(&mut out, &row)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Every table in [`SQLITE_FILE`], replacing the table of an earlier run (`--sink sqlite`,
+/// `sqlite` feature). Columns take the types of their values; empty cells are NULL and
+/// lists are text, as in the CSV.
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl OutputSink for SqliteSink {
+    fn write_table(&mut self, table: &Table) -> Result<(), Box<dyn Error>> {
+        use rusqlite::types::Value;
+
+        let name = table.name();
+        let tx = self.conn.transaction()?;
+        tx.execute(&format!("DROP TABLE IF EXISTS \"{}\"", name), [])?;
+        if !table.rows.is_empty() {
+            let quoted: Vec<String> = table.columns.iter().map(|c| format!("\"{}\"", c)).collect();
+            tx.execute(
+                &format!("CREATE TABLE \"{}\" ({})", name, quoted.join(", ")),
+                [],
+            )?;
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO \"{}\" VALUES ({})",
+                name,
+                vec!["?"; table.columns.len()].join(", ")
+            ))?;
+            for row in &table.rows {
+                let values = row.iter().map(|cell| match cell {
+                    Cell::Empty => Value::Null,
+                    Cell::Bool(b) => Value::Integer(*b as i64),
+                    Cell::Int(i) => Value::Integer(*i),
+                    Cell::UInt(u) => {
+                        i64::try_from(*u).map_or(Value::Real(*u as f64), Value::Integer)
+                    }
+                    Cell::Float(x) => Value::Real(*x),
+                    Cell::Text(_) | Cell::List(_) => Value::Text(cell.to_csv()),
+                });
+                insert.execute(rusqlite::params_from_iter(values))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// One `<table>.parquet` file per table (`--sink parquet`, `parquet` feature). Columns take
+/// the Arrow types of their values, nullable, with lists as lists of `UInt64`; a table
+/// without rows has no file.
+#[cfg(feature = "parquet")]
+pub struct ParquetSink {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "parquet")]
+impl OutputSink for ParquetSink {
+    fn write_table(&mut self, table: &Table) -> Result<(), Box<dyn Error>> {
+        use arrow_array::types::UInt64Type;
+        use arrow_array::{
+            ArrayRef, BooleanArray, Float64Array, Int64Array, ListArray, RecordBatch, StringArray,
+            UInt64Array,
+        };
+        use arrow_schema::{Field, Schema};
+        use std::sync::Arc;
+
+        let path = self.dir.join(format!("{}.parquet", table.name()));
+        if table.rows.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let mut fields = Vec::with_capacity(table.columns.len());
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
+        for (i, column) in table.columns.iter().enumerate() {
+            let cells = || table.rows.iter().map(move |row| &row[i]);
+            // The first value decides the type; a column without values is text.
+            let array: ArrayRef = match cells().find(|cell| **cell != Cell::Empty) {
+                Some(Cell::Bool(_)) => {
+                    Arc::new(BooleanArray::from_iter(cells().map(|cell| match cell {
+                        Cell::Bool(b) => Some(*b),
+                        _ => None,
+                    })))
+                }
+                Some(Cell::Int(_)) => {
+                    Arc::new(Int64Array::from_iter(cells().map(|cell| match cell {
+                        Cell::Int(i) => Some(*i),
+                        _ => None,
+                    })))
+                }
+                Some(Cell::UInt(_)) => {
+                    Arc::new(UInt64Array::from_iter(cells().map(|cell| match cell {
+                        Cell::UInt(u) => Some(*u),
+                        _ => None,
+                    })))
+                }
+                Some(Cell::Float(_)) => {
+                    Arc::new(Float64Array::from_iter(cells().map(|cell| match cell {
+                        Cell::Float(x) => Some(*x),
+                        _ => None,
+                    })))
+                }
+                Some(Cell::List(_)) => {
+                    Arc::new(ListArray::from_iter_primitive::<UInt64Type, _, _>(
+                        cells().map(|cell| match cell {
+                            Cell::List(values) => {
+                                Some(values.iter().map(|&v| Some(v)).collect::<Vec<_>>())
+                            }
+                            _ => None,
+                        }),
+                    ))
+                }
+                _ => Arc::new(StringArray::from_iter(cells().map(|cell| match cell {
+                    Cell::Empty => None,
+                    cell => Some(cell.to_csv()),
+                }))),
+            };
+            fields.push(Field::new(*column, array.data_type().clone(), true));
+            arrays.push(array);
+        }
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?;
+        let mut writer =
+            parquet::arrow::ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+enum Sink {
+    Csv(CsvSink),
+    Json(JsonSink),
+    Stream(StreamSink),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteSink),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetSink),
+}
+
+/// The sinks of one output directory: the CSV files and those of `--sink`, all given the
+/// columns that `--columns` and `--slim` keep.
+pub struct Sinks {
+    sinks: Vec<Sink>,
+    selection: ColumnSelection,
+}
+
+impl Sinks {
+    /// Opens the sinks of `config` for `dir`. The stream is left out unless `stream`, so
+    /// snapshots written during a run do not repeat their tables on stdout.
+    pub fn open(
+        config: &Config,
+        dir: &str,
+        max_n: u64,
+        stream: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut sinks = vec![Sink::Csv(CsvSink::new(dir, config.partition_rows))];
+        let mut kinds = config.sinks.clone();
+        kinds.sort_unstable();
+        kinds.dedup();
+        for kind in kinds {
+            match kind {
+                SinkKind::Csv => {}
+                SinkKind::Json => sinks.push(Sink::Json(JsonSink {
+                    dir: PathBuf::from(dir),
+                })),
+                SinkKind::Stream if stream => sinks.push(Sink::Stream(StreamSink { max_n })),
+                SinkKind::Stream => {}
+                #[cfg(feature = "sqlite")]
+                SinkKind::Sqlite => sinks.push(Sink::Sqlite(SqliteSink {
+                    conn: rusqlite::Connection::open(std::path::Path::new(dir).join(SQLITE_FILE))?,
+                })),
+                #[cfg(not(feature = "sqlite"))]
+                SinkKind::Sqlite => {
+                    return Err("--sink sqlite requires a build with `--features sqlite`".into())
+                }
+                #[cfg(feature = "parquet")]
+                SinkKind::Parquet => sinks.push(Sink::Parquet(ParquetSink {
+                    dir: PathBuf::from(dir),
+                })),
+                #[cfg(not(feature = "parquet"))]
+                SinkKind::Parquet => {
+                    return Err("--sink parquet requires a build with `--features parquet`".into())
+                }
+            }
+        }
+        Ok(Self {
+            sinks,
+            selection: ColumnSelection::new(config),
+        })
+    }
+}
+
+impl Sinks {
+    /// Writes the table of `records`, all of one record type, to every sink. `file` is its
+    /// CSV file name, e.g. `gap_spectrum.csv`.
+    pub fn write_records<R: Serialize>(
+        &mut self,
+        file: &'static str,
+        records: &[R],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut table = Table::new(file, records)?;
+        table.retain_columns(|column| self.selection.keeps(file, column));
+        for sink in &mut self.sinks {
+            match sink {
+                Sink::Csv(sink) => sink.write_table(&table)?,
+                Sink::Json(sink) => sink.write_table(&table)?,
+                Sink::Stream(sink) => sink.write_table(&table)?,
+                #[cfg(feature = "sqlite")]
+                Sink::Sqlite(sink) => sink.write_table(&table)?,
+                #[cfg(feature = "parquet")]
+                Sink::Parquet(sink) => sink.write_table(&table)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "parquet"))]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, UInt64Type};
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[derive(Serialize)]
+    struct Row {
+        gap: u64,
+        rate: Option<f64>,
+        shield_primes: Vec<u32>,
+    }
+
+    #[test]
+    fn test_parquet_keeps_the_cell_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let rows = [
+            Row {
+                gap: 4,
+                rate: Some(0.25),
+                shield_primes: vec![3, 5],
+            },
+            Row {
+                gap: 6,
+                rate: None,
+                shield_primes: vec![],
+            },
+        ];
+        let mut sink = ParquetSink {
+            dir: dir.path().to_path_buf(),
+        };
+        sink.write_table(&Table::new("t.csv", &rows).unwrap())
+            .unwrap();
+
+        let file = File::open(dir.path().join("t.parquet")).unwrap();
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let gaps = batch.column(0).as_primitive::<UInt64Type>();
+        assert_eq!(gaps.values(), &[4, 6]);
+        let rates = batch.column(1).as_primitive::<Float64Type>();
+        assert_eq!((rates.value(0), rates.is_null(1)), (0.25, true));
+        let shields = batch.column(2).as_list::<i32>();
+        assert_eq!(
+            shields.value(0).as_primitive::<UInt64Type>().values(),
+            &[3, 5]
+        );
+        assert!(shields.value(1).is_empty());
+
+        // A table without rows leaves no file behind.
+        sink.write_table(&Table::new::<Row>("t.csv", &[]).unwrap())
+            .unwrap();
+        assert!(!dir.path().join("t.parquet").exists());
+    }
+}
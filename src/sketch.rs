@@ -0,0 +1,111 @@
+//! DDSketch: streaming quantiles of positive values with a bounded relative error.
+//!
+//! Values are counted in logarithmic buckets `(γ^(i-1), γ^i]` with `γ = (1 + α) / (1 - α)`,
+//! so any quantile is returned within a relative error α of the true one, whatever the
+//! distribution. Gaps and merits span a few hundred buckets at most, held densely between
+//! the smallest and largest bucket seen.
+
+use serde::{Deserialize, Serialize};
+
+/// Relative accuracy α of every quantile.
+pub const RELATIVE_ACCURACY: f64 = 0.01;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DdSketch {
+    /// Bucket index of `counts[0]`.
+    offset: i32,
+    counts: Vec<u64>,
+    count: u64,
+}
+
+fn ln_gamma() -> f64 {
+    ((1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)).ln()
+}
+
+impl DdSketch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of values added.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Adds a positive value; zero and negative values are ignored.
+    pub fn add(&mut self, value: f64) {
+        if value <= 0.0 {
+            return;
+        }
+        let index = (value.ln() / ln_gamma()).ceil() as i32;
+        if self.counts.is_empty() {
+            self.offset = index;
+        } else if index < self.offset {
+            let grow = (self.offset - index) as usize;
+            self.counts.splice(0..0, std::iter::repeat_n(0, grow));
+            self.offset = index;
+        }
+        let slot = (index - self.offset) as usize;
+        if slot >= self.counts.len() {
+            self.counts.resize(slot + 1, 0);
+        }
+        self.counts[slot] += 1;
+        self.count += 1;
+    }
+
+    /// The q-quantile (`q` in `[0, 1]`) within the relative accuracy, or 0 if empty.
+    pub fn quantile(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64) as u64;
+        let mut seen = 0;
+        for (slot, &n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen > rank {
+                let gamma = ln_gamma().exp();
+                let index = self.offset + slot as i32;
+                return 2.0 * gamma.powi(index) / (gamma + 1.0);
+            }
+        }
+        unreachable!("rank {} is below the count {}", rank, self.count)
+    }
+
+    /// Bytes held by the buckets.
+    pub fn memory_bytes(&self) -> u64 {
+        (self.counts.len() * std::mem::size_of::<u64>()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles_within_relative_accuracy() {
+        let mut sketch = DdSketch::new();
+        assert_eq!(sketch.quantile(0.5), 0.0);
+        // Added out of order, so the buckets grow in both directions.
+        for v in (1..=1000).rev().chain(1..=1000) {
+            sketch.add(v as f64 / 10.0);
+        }
+        sketch.add(0.0);
+        assert_eq!(sketch.count(), 2000);
+        for (q, exact) in [
+            (0.0, 0.1),
+            (0.5, 50.0),
+            (0.9, 90.0),
+            (0.99, 99.0),
+            (1.0, 100.0),
+        ] {
+            let estimate = sketch.quantile(q);
+            assert!(
+                (estimate - exact).abs() <= RELATIVE_ACCURACY * exact + 0.1,
+                "q = {}: {} against {}",
+                q,
+                estimate,
+                exact
+            );
+        }
+    }
+}
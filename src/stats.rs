@@ -1,81 +1,1943 @@
-use serde::Serialize;
-use std::collections::HashMap;
+#[cfg(feature = "arrow")]
+use crate::arrow_ipc::ArrowBinWriter;
+use crate::config::{AttributeBy, Baseline, BinAlign, BinDomain, PairWeight, Shard};
+use crate::factoring::{self, FactorClass};
+use crate::output::OscillationWriter;
+use crate::records::LargeGapEvent;
+use crate::shielding::{self, ShieldModel};
+use crate::sieve::is_prime_u64;
+use crate::sketch::DdSketch;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
 
-#[derive(Debug, Serialize)]
+/// A prime gap size, `p_{n+1} - p_n`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct Gap(pub u64);
+
+/// A prime value produced by the sieve.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct Prime(pub u64);
+
+/// Global index of a bin, as returned by `Statistics::get_bin_index`. Bins are never
+/// indexed by raw numbers, so a prime or S value can't be mistaken for a bin index.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct BinIndex(pub usize);
+
+impl Gap {
+    /// The gap between two consecutive primes.
+    #[inline]
+    pub fn between(prev: Prime, curr: Prime) -> Self {
+        Gap(curr.0 - prev.0)
+    }
+}
+
+impl std::fmt::Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::fmt::Display for Prime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// z-score of the two-sided 95% confidence level.
+pub const Z_95: f64 = 1.959963984540054;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct BinStats {
     pub bin_start: u64,
     pub bin_end: u64,
     pub prime_count_p: u64,
     pub prime_count_s: u64,
-    #[serde(skip)]
-    pub gap_successes: HashMap<u64, u64>, // Map<GapSize, Successes>
-    #[serde(skip)]
-    pub gap_occurrences: HashMap<u64, u64>, // Map<GapSize, Occurrences>
+    pub gap_successes: Vec<u64>, // Indexed by the gap's slot in `Statistics::target_gaps`
+    pub gap_occurrences: Vec<u64>, // Indexed by the gap's slot in `Statistics::target_gaps`
+    /// Gaps ending in the bin, sampled or not.
+    pub gap_sketch: DdSketch,
+    /// Merits `g / ln p_prev` of the same gaps.
+    pub merit_sketch: DdSketch,
 }
 
 impl BinStats {
-    fn new(start: u64, end: u64, target_gaps: &[u64]) -> Self {
-        let mut gap_successes = HashMap::new();
-        let mut gap_occurrences = HashMap::new();
-        for &g in target_gaps {
-            gap_successes.insert(g, 0);
-            gap_occurrences.insert(g, 0);
-        }
+    fn new(start: u64, end: u64, num_target_gaps: usize) -> Self {
         Self {
             bin_start: start,
             bin_end: end,
             prime_count_p: 0,
             prime_count_s: 0,
-            gap_successes,
-            gap_occurrences,
+            gap_successes: vec![0; num_target_gaps],
+            gap_occurrences: vec![0; num_target_gaps],
+            gap_sketch: DdSketch::new(),
+            merit_sketch: DdSketch::new(),
+        }
+    }
+
+    fn record_gap(&mut self, p_prev: Prime, p_current: Prime) {
+        let gap = (p_current.0 - p_prev.0) as f64;
+        self.gap_sketch.add(gap);
+        self.merit_sketch.add(gap / (p_prev.0 as f64).ln());
+    }
+}
+
+/// Occurrence and success counts of one target gap, split by `p_prev mod modulus`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResidueCounts {
+    pub modulus: u64,
+    pub counts: Vec<(u64, u64)>, // Indexed by residue: (Occurrences, Successes)
+}
+
+impl ResidueCounts {
+    fn new(modulus: u64) -> Self {
+        Self {
+            modulus,
+            counts: vec![(0, 0); modulus as usize],
+        }
+    }
+}
+
+/// The target gaps of an analysis and the slot of each, its position in ascending order,
+/// looked up through a bitset over gap sizes with the count of set bits before each word:
+/// about 1.5 bits per gap size up to the largest target, so even gaps in the millions cost
+/// little and every lookup is two loads and a popcount.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetGapSet {
+    words: Vec<u64>,
+    ranks: Vec<u32>,
+}
+
+impl TargetGapSet {
+    /// `gaps` in any order, with duplicates.
+    pub fn new(gaps: &[Gap]) -> Self {
+        let max_gap = gaps.iter().map(|g| g.0).max();
+        let mut words = vec![0u64; max_gap.map_or(0, |g| g as usize / 64 + 1)];
+        for g in gaps {
+            words[g.0 as usize / 64] |= 1 << (g.0 % 64);
+        }
+        let ranks = words
+            .iter()
+            .scan(0, |before, word| {
+                let rank = *before;
+                *before += word.count_ones();
+                Some(rank)
+            })
+            .collect();
+        Self { words, ranks }
+    }
+
+    /// Number of target gaps.
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The slot of `gap`, if it is a target.
+    #[inline]
+    pub fn slot(&self, gap: Gap) -> Option<usize> {
+        let index = gap.0 as usize / 64;
+        let word = *self.words.get(index)?;
+        let bit = 1u64 << (gap.0 % 64);
+        (word & bit != 0)
+            .then(|| self.ranks[index] as usize + (word & (bit - 1)).count_ones() as usize)
+    }
+}
+
+/// Occurrence and success counts per gap size.
+///
+/// Gaps up to `max_dense_gap` live in a dense array; larger ones are summed into an
+/// overflow bucket and also kept exactly in a small sorted map. Gaps beyond any sane
+/// bound are rare, so memory stays predictable and the common path is a plain index.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GapSpectrum {
+    dense: Vec<(u64, u64)>, // Indexed by gap size: (Occurrences, Successes)
+    overflow: (u64, u64),   // Totals over every gap > max_dense_gap
+    overflow_gaps: BTreeMap<u64, (u64, u64)>,
+}
+
+impl GapSpectrum {
+    pub fn new(max_dense_gap: u64) -> Self {
+        Self {
+            dense: vec![(0, 0); max_dense_gap as usize + 1],
+            overflow: (0, 0),
+            overflow_gaps: BTreeMap::new(),
+        }
+    }
+
+    /// Largest gap counted in the dense array.
+    pub fn max_dense_gap(&self) -> u64 {
+        self.dense.len() as u64 - 1
+    }
+
+    #[inline]
+    fn counts_mut(&mut self, gap: Gap) -> &mut (u64, u64) {
+        match self.dense.get_mut(gap.0 as usize) {
+            Some(counts) => counts,
+            None => self.overflow_gaps.entry(gap.0).or_insert((0, 0)),
         }
     }
+
+    #[inline]
+    pub fn record_occurrence(&mut self, gap: Gap) {
+        self.add_occurrences(gap, 1);
+    }
+
+    #[inline]
+    pub fn record_success(&mut self, gap: Gap) {
+        self.add_successes(gap, 1);
+    }
+
+    /// Weighted form of [`GapSpectrum::record_occurrence`], used when sampling.
+    #[inline]
+    pub fn add_occurrences(&mut self, gap: Gap, weight: u64) {
+        self.counts_mut(gap).0 += weight;
+        if gap.0 > self.max_dense_gap() {
+            self.overflow.0 += weight;
+        }
+    }
+
+    /// Weighted form of [`GapSpectrum::record_success`], used when sampling.
+    #[inline]
+    pub fn add_successes(&mut self, gap: Gap, weight: u64) {
+        self.counts_mut(gap).1 += weight;
+        if gap.0 > self.max_dense_gap() {
+            self.overflow.1 += weight;
+        }
+    }
+
+    /// (Occurrences, Successes) for a single gap size.
+    pub fn get(&self, gap: Gap) -> (u64, u64) {
+        match self.dense.get(gap.0 as usize) {
+            Some(&counts) => counts,
+            None => self.overflow_gaps.get(&gap.0).copied().unwrap_or((0, 0)),
+        }
+    }
+
+    /// All observed gaps in ascending order as (gap, occurrences, successes).
+    pub fn iter(&self) -> impl Iterator<Item = (Gap, u64, u64)> + '_ {
+        self.dense
+            .iter()
+            .enumerate()
+            .filter(|(_, &(count, _))| count > 0)
+            .map(|(gap, &(count, successes))| (Gap(gap as u64), count, successes))
+            .chain(
+                self.overflow_gaps
+                    .iter()
+                    .map(|(&gap, &(count, successes))| (Gap(gap), count, successes)),
+            )
+    }
+
+    /// (Occurrences, Successes) summed over every gap above `max_dense_gap`.
+    pub fn overflow(&self) -> (u64, u64) {
+        self.overflow
+    }
+
+    /// Number of distinct gap sizes that landed in the overflow bucket.
+    pub fn overflow_gap_count(&self) -> usize {
+        self.overflow_gaps.len()
+    }
+
+    /// Bytes of the dense counters plus the overflow entries (keys and counters only).
+    pub fn memory_bytes(&self) -> u64 {
+        let entry = std::mem::size_of::<(u64, (u64, u64))>();
+        (self.dense.len() * std::mem::size_of::<(u64, u64)>() + self.overflow_gaps.len() * entry)
+            as u64
+    }
+}
+
+/// Pairs of a target gap next to one neighbouring gap, as `(occurrences, successes)` of
+/// their sums S: with the neighbour as the pair before and as the pair after.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NeighborCounts {
+    pub preceded: (u64, u64),
+    pub followed: (u64, u64),
+}
+
+fn tally(cell: &mut (u64, u64), weight: u64, success: bool) {
+    cell.0 += weight;
+    if success {
+        cell.1 += weight;
+    }
 }
 
-#[derive(Debug)]
+/// Lengths of the maximal runs of consecutive successes and of consecutive failures in a
+/// sequence of sums S, in pair order. Runs are counted once they end; the open run at the
+/// end of the sequence is kept apart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunLengths {
+    successes: BTreeMap<u64, u64>, // Run length -> number of runs
+    failures: BTreeMap<u64, u64>,
+    open: Option<(bool, u64)>, // Outcome and length of the run still in progress
+}
+
+impl RunLengths {
+    #[inline]
+    pub fn record(&mut self, success: bool) {
+        match &mut self.open {
+            Some((outcome, length)) if *outcome == success => *length += 1,
+            open => {
+                if let Some((outcome, length)) = open.replace((success, 1)) {
+                    let runs = if outcome {
+                        &mut self.successes
+                    } else {
+                        &mut self.failures
+                    };
+                    *runs.entry(length).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    /// Every run as `(success, length, count)`, failures first and by ascending length,
+    /// counting the open run as if the sequence ended here.
+    pub fn iter(&self) -> impl Iterator<Item = (bool, u64, u64)> {
+        let mut failures = self.failures.clone();
+        let mut successes = self.successes.clone();
+        if let Some((outcome, length)) = self.open {
+            let runs = if outcome {
+                &mut successes
+            } else {
+                &mut failures
+            };
+            *runs.entry(length).or_insert(0) += 1;
+        }
+        failures
+            .into_iter()
+            .map(|(length, count)| (false, length, count))
+            .chain(
+                successes
+                    .into_iter()
+                    .map(|(length, count)| (true, length, count)),
+            )
+    }
+}
+
+/// Spacings between consecutive successes (prime sums S) in pair order: in pairs, exactly,
+/// and in the numeric distance between the two sums, by power-of-two bucket.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SuccessSpacing {
+    by_pairs: BTreeMap<u64, u64>, // Pairs from one success to the next -> count
+    by_distance: Vec<u64>,        // Indexed by ⌊log2(distance)⌋
+    pairs_since: u64,
+    first_s: Option<u64>,
+    last_s: Option<u64>,
+}
+
+impl SuccessSpacing {
+    #[inline]
+    pub fn record(&mut self, success: bool, s: u64) {
+        self.pairs_since += 1;
+        if !success {
+            return;
+        }
+        if let Some(last) = self.last_s {
+            *self.by_pairs.entry(self.pairs_since).or_insert(0) += 1;
+            let bucket = (s - last).ilog2() as usize;
+            if bucket >= self.by_distance.len() {
+                self.by_distance.resize(bucket + 1, 0);
+            }
+            self.by_distance[bucket] += 1;
+        } else {
+            self.first_s = Some(s);
+        }
+        self.last_s = Some(s);
+        self.pairs_since = 0;
+    }
+
+    /// `(pairs, count)` in ascending order of pairs.
+    pub fn by_pairs(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.by_pairs.iter().map(|(&pairs, &count)| (pairs, count))
+    }
+
+    /// `(bucket_start, bucket_end, count)` for the distance buckets `[2^k, 2^(k+1))`.
+    pub fn by_distance(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.by_distance
+            .iter()
+            .enumerate()
+            .map(|(k, &count)| (1 << k, (1 << k) * 2 - 1, count))
+    }
+
+    /// Mean numeric distance between consecutive successes, if there were two.
+    pub fn mean_distance(&self) -> Option<f64> {
+        let spacings: u64 = self.by_pairs.values().sum();
+        match (self.first_s, self.last_s) {
+            (Some(first), Some(last)) if spacings > 0 => {
+                Some((last - first) as f64 / spacings as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Small, fast, seedable PRNG (SplitMix64) for reproducible sampling.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Seed of one feature's random stream, derived from the global `--seed` and the
+/// stream's name so that features don't share (or shift) each other's numbers.
+pub fn derive_seed(seed: u64, stream: &str) -> u64 {
+    // FNV-1a of the name, then one SplitMix64 step to spread it.
+    let name = stream.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    SplitMix64::new(seed ^ name).next_u64()
+}
+
+/// Chooses which prime pairs a sampled run analyzes: every `every`-th pair, or each pair
+/// independently with probability `1 / every` when seeded.
+pub struct PairSampler {
+    every: u64,
+    counter: u64,
+    rng: Option<SplitMix64>,
+}
+
+impl PairSampler {
+    pub fn new(every: u64, seed: Option<u64>) -> Self {
+        Self {
+            every,
+            counter: 0,
+            rng: seed.map(SplitMix64::new),
+        }
+    }
+
+    /// Whether the next pair should be analyzed.
+    #[inline]
+    pub fn take(&mut self) -> bool {
+        if self.every <= 1 {
+            return true;
+        }
+        match self.rng.as_mut() {
+            Some(rng) => rng.next_u64() % self.every == 0,
+            None => {
+                self.counter += 1;
+                if self.counter == self.every {
+                    self.counter = 0;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Default file name for saved statistics state inside an output directory.
+pub const STATE_FILE: &str = "statistics.state";
+
+/// Leading bytes of every state file.
+const STATE_MAGIC: &[u8; 8] = b"PSASTATE";
+
+/// Version of the state payload layout. Bump this whenever `Statistics` or `BinStats`
+/// change shape, so that old files are rejected instead of being misparsed.
+pub const STATE_FORMAT_VERSION: u32 = 24;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Statistics {
     pub total_primes: u64,
     pub total_s_primes: u64,
-    pub gap_spectrum: HashMap<u64, (u64, u64)>, // Map<GapSize, (Occurrences, Successes)>
-    pub bins: Vec<BinStats>,
+    pub gap_spectrum: GapSpectrum,
+    pub early_spectrum: GapSpectrum, // The pairs with p_prev below `split_at`
+    pub split_at: u64,
+    pub shard: Option<Shard>, // The `--shard` whose slice of p these statistics cover, if any
+    pub bins: VecDeque<BinStats>, // Live bins; `bins[0]` is global bin `first_bin`
+    first_bin: usize,
+    num_bins: usize,
     bin_size: u64,
+    bin_starts: Vec<u64>, // First number of each bin when aligned to landmarks; empty for uniform bins
     max_n_analysis_range: u64,
-    pub target_gaps: Vec<u64>, // Store this for output.rs
+    pub target_gaps: Vec<Gap>, // Sorted and deduplicated; a gap's position is its slot
+    target_slots: TargetGapSet,
+    pub analyzed_up_to: u64, // Highest p whose pair has been accounted for
+    pub sample_every: u64,   // Pairs are sampled 1 in `sample_every`; pair counts are scaled by it
+    pub sample_seed: Option<u64>, // Seed of a random sample, `None` for every `sample_every`-th pair
+    pub counts_almost_primes: bool, // Whether the Ω(S) classes of `almost_primes` are counted
+    pub shield_model: ShieldModel, // The model the outputs compute shield boosts with
+    pub pair_weight: Option<PairWeight>, // Weight of the `weighted_spectrum` counts, if any
+    pub weighted_spectrum: Vec<(f64, f64)>, // Indexed by gap size: weighted (Occurrences, Successes)
+    pub attribute_by: AttributeBy,          // Which number places a pair's gap counts in the bins
+    pub bin_domain: BinDomain,              // Under `S`, every pair count goes to the bin of S
+    pub conditional: Vec<Vec<ResidueCounts>>, // By target slot, then by the gap's shield primes
+    pub first_occurrences: Vec<u64>, // Indexed by gap size: smallest p_prev with that gap, 0 if unseen
+    pub prime_counts_at_powers_of_ten: Vec<u64>, // π(10^k) for k = 1, 2, … as the stream passes 10^k
+    pub streaks: RunLengths,                     // Over every analyzed pair
+    pub gap_streaks: Vec<RunLengths>,            // By target slot, over the pairs with that gap
+    pub neighbor_gaps: Vec<BTreeMap<u64, NeighborCounts>>, // By target slot, then neighbouring gap
+    awaiting_next_gap: Option<(usize, bool)>, // Target slot and outcome of the latest pair, if any
+    pub success_spacing: SuccessSpacing,
+    pub out_of_range: BTreeMap<u64, u64>, // Gap size -> sums S above the checker's limit
+    pub almost_primes: BTreeMap<u64, [u64; FactorClass::COUNT]>, // Gap size -> sums by Ω(S) class
+    pub square_free: Vec<u64>,            // Indexed by gap size: sums S with no small square factor
+    pub large_gap_threshold: Option<u64>, // Pairs with at least this gap go to `large_gap_events`
+    pub large_gap_events: Vec<LargeGapEvent>,
+    last_gap: u64, // Gap of the latest pair in the prime stream, 0 before the first
+    pub unbinned_primes: u64, // Primes p beyond the last bin, left out of the oscillation series
+    pub unbinned_sums: u64, // Prime sums S beyond the last bin, likewise
+    next_power_of_ten: u64,
+    primes_before: u64, // π of the prime a `--shard` starts at, which an earlier shard counts
+    #[serde(skip)]
+    bin_stream: Option<OscillationWriter>,
+    #[serde(skip)]
+    bins_streamed: bool,
+    #[serde(skip)]
+    dirty_bins: Option<Vec<usize>>, // Bins touched since the last WAL record, when tracked
+    #[serde(skip)]
+    wal_base: Option<Box<WalBase>>, // The delta fields as of the last WAL record, when tracked
+    #[cfg(feature = "arrow")]
+    #[serde(skip)]
+    arrow_stream: Option<(ArrowBinWriter, usize)>, // With the next bin index to write
+}
+
+/// A write-ahead log record as stored on disk: the small fields in full, the changes to
+/// the fields indexed by gap size since the previous record, and the full contents of the
+/// bins touched since then.
+#[derive(Serialize)]
+struct WalRecordRef<'a> {
+    core: &'a Statistics,
+    deltas: WalDeltas,
+    bins: Vec<(usize, &'a BinStats)>,
+}
+
+#[derive(Deserialize)]
+struct WalRecord {
+    core: Statistics,
+    deltas: WalDeltas,
+    bins: Vec<(usize, BinStats)>,
+}
+
+/// The fields of [`Statistics`] indexed by gap size, which a WAL record carries as the
+/// entries that changed rather than in full.
+#[derive(Debug, Clone)]
+struct GapSizeFields {
+    gap_spectrum: Vec<(u64, u64)>,
+    early_spectrum: Vec<(u64, u64)>,
+    weighted_spectrum: Vec<(f64, f64)>,
+    first_occurrences: Vec<u64>,
+    square_free: Vec<u64>,
+}
+
+/// The state of the previous WAL record that the next one is a delta against.
+#[derive(Debug, Clone)]
+struct WalBase {
+    fields: GapSizeFields,
+    large_gap_events: usize, // Events are only ever appended
+}
+
+impl WalBase {
+    fn of(stats: &Statistics) -> Self {
+        Self {
+            fields: GapSizeFields {
+                gap_spectrum: stats.gap_spectrum.dense.clone(),
+                early_spectrum: stats.early_spectrum.dense.clone(),
+                weighted_spectrum: stats.weighted_spectrum.clone(),
+                first_occurrences: stats.first_occurrences.clone(),
+                square_free: stats.square_free.clone(),
+            },
+            large_gap_events: stats.large_gap_events.len(),
+        }
+    }
+}
+
+/// The changes of one WAL record to the fields in [`GapSizeFields`] and the large gap events.
+#[derive(Serialize, Deserialize, Debug)]
+struct WalDeltas {
+    gap_spectrum: VecDelta<(u64, u64)>,
+    early_spectrum: VecDelta<(u64, u64)>,
+    weighted_spectrum: VecDelta<(f64, f64)>,
+    first_occurrences: VecDelta<u64>,
+    square_free: VecDelta<u64>,
+    large_gap_events: Vec<LargeGapEvent>,
+}
+
+/// The entries of a vector that differ from an earlier state of it, and its new length.
+#[derive(Serialize, Deserialize, Debug)]
+struct VecDelta<T> {
+    len: usize,
+    changed: Vec<(usize, T)>,
+}
+
+impl<T: Clone + Default + PartialEq> VecDelta<T> {
+    fn between(base: &[T], current: &[T]) -> Self {
+        Self {
+            len: current.len(),
+            changed: current
+                .iter()
+                .enumerate()
+                .filter(|&(i, value)| base.get(i) != Some(value))
+                .map(|(i, value)| (i, value.clone()))
+                .collect(),
+        }
+    }
+
+    fn apply(self, values: &mut Vec<T>) {
+        values.resize(self.len, T::default());
+        for (i, value) in self.changed {
+            values[i] = value;
+        }
+    }
 }
 
 impl Statistics {
-    pub fn new(max_n: u64, num_bins: usize, target_gaps: &[u64]) -> Self {
+    pub fn new(max_n: u64, num_bins: usize, target_gaps: &[Gap], max_spectrum_gap: u64) -> Self {
+        let mut target_gaps = target_gaps.to_vec();
+        target_gaps.sort_unstable();
+        target_gaps.dedup();
+
+        let target_slots = TargetGapSet::new(&target_gaps);
+
         let max_n_analysis_range = max_n * 2;
+        // Tiny runs (`--max-exponent 0` or 1) get one bin per number rather than empty ones.
+        let num_bins = num_bins.min(max_n_analysis_range as usize);
         let bin_size = (max_n_analysis_range as f64 / num_bins as f64).ceil() as u64;
 
-        let bins = (0..num_bins)
-            .map(|i| {
-                let start = (i as u64) * bin_size;
-                let end = start + bin_size - 1;
-                BinStats::new(start, end.min(max_n_analysis_range), target_gaps)
+        let conditional = target_gaps
+            .iter()
+            .map(|g| {
+                shielding::shield_primes(g.0)
+                    .into_iter()
+                    .map(|q| ResidueCounts::new(q as u64))
+                    .collect()
             })
             .collect();
 
-        Self {
+        let mut stats = Self {
             total_primes: 0,
             total_s_primes: 0,
-            gap_spectrum: HashMap::new(),
-            bins,
+            gap_spectrum: GapSpectrum::new(max_spectrum_gap),
+            early_spectrum: GapSpectrum::new(max_spectrum_gap),
+            split_at: max_n / 2,
+            shard: None,
+            bins: VecDeque::with_capacity(num_bins),
+            first_bin: 0,
+            num_bins,
             bin_size,
+            bin_starts: Vec::new(),
             max_n_analysis_range,
-            target_gaps: target_gaps.to_vec(),
+            target_slots,
+            analyzed_up_to: 0,
+            sample_every: 1,
+            sample_seed: None,
+            counts_almost_primes: false,
+            shield_model: ShieldModel::FIRST_ORDER,
+            pair_weight: None,
+            weighted_spectrum: Vec::new(),
+            attribute_by: AttributeBy::Curr,
+            bin_domain: BinDomain::P,
+            conditional,
+            first_occurrences: Vec::new(),
+            prime_counts_at_powers_of_ten: Vec::new(),
+            next_power_of_ten: 10,
+            primes_before: 0,
+            streaks: RunLengths::default(),
+            success_spacing: SuccessSpacing::default(),
+            out_of_range: BTreeMap::new(),
+            almost_primes: BTreeMap::new(),
+            square_free: Vec::new(),
+            large_gap_threshold: None,
+            large_gap_events: Vec::new(),
+            last_gap: 0,
+            unbinned_primes: 0,
+            unbinned_sums: 0,
+            gap_streaks: target_gaps.iter().map(|_| RunLengths::default()).collect(),
+            neighbor_gaps: target_gaps.iter().map(|_| BTreeMap::new()).collect(),
+            awaiting_next_gap: None,
+            target_gaps,
+            bin_stream: None,
+            bins_streamed: false,
+            dirty_bins: None,
+            wal_base: None,
+            #[cfg(feature = "arrow")]
+            arrow_stream: None,
+        };
+        stats.bins = (0..num_bins).map(|i| stats.make_bin(i)).collect();
+        stats
+    }
+
+    /// Moves bin boundaries onto the landmarks of `align`: each landmark replaces the
+    /// nearest uniform boundary, or becomes an extra boundary where landmarks lie closer
+    /// together than the bins (at the low end, and for powers of two everywhere with few bins).
+    pub fn with_bin_align(mut self, align: BinAlign) -> Self {
+        let mut starts: Vec<u64> = (0..self.num_bins).map(|i| self.bin_start(i)).collect();
+        let mut snapped = vec![false; starts.len()];
+        let mut extra = Vec::new();
+        for landmark in landmarks(align, self.max_n_analysis_range) {
+            let nearest = ((landmark + self.bin_size / 2) / self.bin_size) as usize;
+            if (1..starts.len()).contains(&nearest) && !snapped[nearest] {
+                starts[nearest] = landmark;
+                snapped[nearest] = true;
+            } else {
+                extra.push(landmark);
+            }
+        }
+        starts.extend(extra);
+        starts.sort_unstable();
+        starts.dedup();
+
+        self.num_bins = starts.len();
+        self.bin_starts = starts;
+        self.bins = (0..self.num_bins).map(|i| self.make_bin(i)).collect();
+        self
+    }
+
+    /// First number of bin `index`; for `index == num_bins`, one past the last bin.
+    fn bin_start(&self, index: usize) -> u64 {
+        if self.bin_starts.is_empty() {
+            index as u64 * self.bin_size
+        } else {
+            self.bin_starts
+                .get(index)
+                .copied()
+                .unwrap_or(self.max_n_analysis_range + 1)
+        }
+    }
+
+    fn make_bin(&self, index: usize) -> BinStats {
+        BinStats::new(
+            self.bin_start(index),
+            self.make_bin_end(index),
+            self.target_gaps.len(),
+        )
+    }
+
+    pub fn num_bins(&self) -> usize {
+        self.num_bins
+    }
+
+    /// Bytes of the bins currently held in memory, including their per-gap counters and
+    /// gap sketches.
+    pub fn bins_memory_bytes(&self) -> u64 {
+        let per_bin = std::mem::size_of::<BinStats>()
+            + 2 * self.target_gaps.len() * std::mem::size_of::<u64>();
+        (self.bins.len() * per_bin) as u64 + self.sketches_memory_bytes()
+    }
+
+    /// Bytes held by the buckets of the bins' gap and merit sketches.
+    pub fn sketches_memory_bytes(&self) -> u64 {
+        self.bins
+            .iter()
+            .map(|bin| bin.gap_sketch.memory_bytes() + bin.merit_sketch.memory_bytes())
+            .sum()
+    }
+
+    /// Mutable access to a bin by its global index (as returned by `get_bin_index`).
+    /// When bins are streamed, bins are created on first touch; touching a bin that
+    /// was already flushed is a logic error.
+    #[inline]
+    pub fn bin_mut(&mut self, BinIndex(index): BinIndex) -> &mut BinStats {
+        if let Some(dirty) = self.dirty_bins.as_mut() {
+            if dirty.last() != Some(&index) {
+                dirty.push(index);
+            }
+        }
+        while self.first_bin + self.bins.len() <= index {
+            let bin = self.make_bin(self.first_bin + self.bins.len());
+            self.bins.push_back(bin);
+        }
+        &mut self.bins[index - self.first_bin]
+    }
+
+    /// Accounts for the first prime, 2, which has no predecessor (and so no pair to bin by S).
+    pub fn record_first_prime(&mut self) {
+        self.total_primes += 1;
+        if let (BinDomain::P, Some(bin_index)) = (self.bin_domain, self.get_bin_index(2)) {
+            self.bin_mut(bin_index).prime_count_p += 1;
+        }
+        self.analyzed_up_to = 2;
+    }
+
+    /// Starts the statistics of a `--shard` at the prime `p`, as if every pair before it
+    /// had been accounted for elsewhere: the pairs from `p` on are this shard's, and π(10^k)
+    /// below `p` comes from `pi`.
+    pub fn start_at(&mut self, p: Prime, pi: impl Fn(u64) -> u64) {
+        while p.0 > self.next_power_of_ten {
+            self.prime_counts_at_powers_of_ten
+                .push(pi(self.next_power_of_ten));
+            self.next_power_of_ten = self.next_power_of_ten.saturating_mul(10);
+        }
+        self.primes_before = pi(p.0);
+        self.analyzed_up_to = p.0;
+    }
+
+    /// Exact bookkeeping for every prime, sampled or not: π(10^k) as the stream passes each
+    /// power of ten, and the first occurrence of each gap. Runs before `total_primes` counts
+    /// `p_current`.
+    ///
+    /// Returns the gap of the pair before, 0 for the first pair.
+    #[inline]
+    fn record_prime_stream(&mut self, p_prev: Prime, p_current: Prime) -> u64 {
+        while p_current.0 > self.next_power_of_ten {
+            self.prime_counts_at_powers_of_ten
+                .push(self.primes_before + self.total_primes);
+            self.next_power_of_ten = self.next_power_of_ten.saturating_mul(10);
+        }
+        let gap = (p_current.0 - p_prev.0) as usize;
+        if gap >= self.first_occurrences.len() {
+            self.first_occurrences.resize(gap + 1, 0);
+        }
+        if self.first_occurrences[gap] == 0 {
+            self.first_occurrences[gap] = p_prev.0;
+        }
+        if let Some(event) = self.large_gap_events.last_mut() {
+            if event.p_curr == p_prev.0 {
+                event.next_gap = Some(gap as u64);
+            }
+        }
+        // The pair before was one of a target gap: its following gap is now known.
+        if let Some((slot, success)) = self.awaiting_next_gap.take() {
+            let counts = self.neighbor_gaps[slot].entry(gap as u64).or_default();
+            tally(&mut counts.followed, self.sample_every, success);
+        }
+        std::mem::replace(&mut self.last_gap, gap as u64)
+    }
+
+    /// π(10^k) for every power of ten the analysis has fully covered, as `(10^k, π(10^k))`.
+    pub fn prime_counts_at_powers_of_ten(&self) -> Vec<(u64, u64)> {
+        let mut counts: Vec<(u64, u64)> = self
+            .prime_counts_at_powers_of_ten
+            .iter()
+            .enumerate()
+            .map(|(k, &count)| (10u64.pow(k as u32 + 1), count))
+            .collect();
+        // A complete run has counted every prime up to max N, including any power of ten
+        // the stream never passed.
+        if self.is_complete() && self.next_power_of_ten <= self.max_n() {
+            counts.push((
+                self.next_power_of_ten,
+                self.primes_before + self.total_primes,
+            ));
+        }
+        counts
+    }
+
+    /// Accounts for a prime whose pair was skipped by sampling: only the prime counts, first
+    /// occurrences and bin gap sketches, which are always exact, are updated.
+    pub fn record_unsampled_prime(&mut self, p_prev: Prime, p_current: Prime) {
+        self.record_prime_stream(p_prev, p_current);
+        self.total_primes += 1;
+        let pair_bin = self.pair_bin_index(p_prev, p_current);
+        match self.count_bin_index(p_current, pair_bin) {
+            Some(bin_index) => self.bin_mut(bin_index).prime_count_p += 1,
+            None => self.unbinned_primes += 1,
+        }
+        if let Some(bin_index) = pair_bin {
+            self.bin_mut(bin_index).record_gap(p_prev, p_current);
+        }
+        self.analyzed_up_to = p_current.0;
+    }
+
+    /// Accounts for one consecutive prime pair and its sum `s = p_prev + p_current - 1`.
+    /// When sampling, every pair-derived count is weighted by `sample_every`, so the
+    /// counters hold estimates for the full population.
+    pub fn record_pair(&mut self, p_prev: Prime, p_current: Prime, s: u64, s_is_prime: bool) {
+        let weight = self.sample_every;
+        let prev_gap = self.record_prime_stream(p_prev, p_current);
+        self.total_primes += 1;
+
+        // Update stats for p_current
+        let pair_bin = self.pair_bin_index(p_prev, p_current);
+        match self.count_bin_index(p_current, pair_bin) {
+            Some(bin_index) => self.bin_mut(bin_index).prime_count_p += 1,
+            None => self.unbinned_primes += 1,
+        }
+        if let Some(bin_index) = pair_bin {
+            self.bin_mut(bin_index).record_gap(p_prev, p_current);
+        }
+
+        // Update gap spectrum (occurrences)
+        let gap = Gap::between(p_prev, p_current);
+        self.gap_spectrum.add_occurrences(gap, weight);
+        let early = p_prev.0 < self.split_at;
+        if early {
+            self.early_spectrum.add_occurrences(gap, weight);
         }
+        if self.large_gap_threshold.is_some_and(|t| gap.0 >= t) {
+            self.large_gap_events.push(LargeGapEvent {
+                p_prev: p_prev.0,
+                p_curr: p_current.0,
+                gap: gap.0,
+                s,
+                s_is_prime,
+                s_smallest_factor: (!s_is_prime).then(|| factoring::smallest_factor(s)),
+                prev_gap: (prev_gap > 0).then_some(prev_gap),
+                next_gap: None,
+            });
+        }
+
+        // Update high-interest gap occurrences in the bin `attribute_by` places the pair in
+        let target_slot = self.target_slot(gap);
+        if let (Some(slot), Some(bin_index)) = (target_slot, pair_bin) {
+            self.bin_mut(bin_index).gap_occurrences[slot] += weight;
+        }
+
+        self.streaks.record(s_is_prime);
+        self.success_spacing.record(s_is_prime, s);
+        if shielding::small_square_free(s) {
+            let slot = gap.0 as usize;
+            if slot >= self.square_free.len() {
+                self.square_free.resize(slot + 1, 0);
+            }
+            self.square_free[slot] += weight;
+        }
+        if let Some(pair_weight) = self.pair_weight {
+            let slot = gap.0 as usize;
+            if slot >= self.weighted_spectrum.len() {
+                self.weighted_spectrum.resize(slot + 1, (0.0, 0.0));
+            }
+            let w = weight as f64 * pair_weight.weight(p_prev.0);
+            self.weighted_spectrum[slot].0 += w;
+            if s_is_prime {
+                self.weighted_spectrum[slot].1 += w;
+            }
+        }
+        if let Some(slot) = target_slot {
+            self.gap_streaks[slot].record(s_is_prime);
+            if prev_gap > 0 {
+                let counts = self.neighbor_gaps[slot].entry(prev_gap).or_default();
+                tally(&mut counts.preceded, weight, s_is_prime);
+            }
+            self.awaiting_next_gap = Some((slot, s_is_prime));
+            for table in &mut self.conditional[slot] {
+                let cell = &mut table.counts[(p_prev.0 % table.modulus) as usize];
+                tally(cell, weight, s_is_prime);
+            }
+        }
+
+        if s_is_prime {
+            self.total_s_primes += weight;
+
+            // Update gap spectrum (successes)
+            self.gap_spectrum.add_successes(gap, weight);
+            if early {
+                self.early_spectrum.add_successes(gap, weight);
+            }
+
+            // Update bin stats for S
+            match self.get_bin_index(s) {
+                Some(bin_index) => self.bin_mut(bin_index).prime_count_s += weight,
+                None => self.unbinned_sums += weight,
+            }
+
+            // Update high-interest gap successes in the same bin as the occurrence
+            if let (Some(slot), Some(bin_index)) = (target_slot, pair_bin) {
+                self.bin_mut(bin_index).gap_successes[slot] += weight;
+            }
+        }
+
+        self.analyzed_up_to = p_current.0;
+    }
+
+    /// The bin of a pair's gap counts under `attribute_by`, or of its S when binning by S.
+    #[inline]
+    fn pair_bin_index(&self, p_prev: Prime, p_current: Prime) -> Option<BinIndex> {
+        let attribute_by = match self.bin_domain {
+            BinDomain::P => self.attribute_by,
+            BinDomain::S => AttributeBy::S,
+        };
+        self.get_bin_index(attribute_by.position(p_prev.0, p_current.0))
+    }
+
+    /// The bin whose `prime_count_p` counts `p_current`: its own, or its pair's when
+    /// binning by S.
+    #[inline]
+    fn count_bin_index(&self, p_current: Prime, pair_bin: Option<BinIndex>) -> Option<BinIndex> {
+        match self.bin_domain {
+            BinDomain::P => self.get_bin_index(p_current.0),
+            BinDomain::S => pair_bin,
+        }
+    }
+
+    /// Counts a pair whose sum S lay above the primality checker's limit. Runs before the
+    /// pair itself is recorded.
+    pub fn record_out_of_range(&mut self, gap: Gap) {
+        *self.out_of_range.entry(gap.0).or_insert(0) += self.sample_every;
+    }
+
+    /// Counts the Ω(S) class of a pair's sum under `--almost-primes`.
+    pub fn record_factor_class(&mut self, gap: Gap, class: FactorClass) {
+        self.almost_primes.entry(gap.0).or_default()[class.index()] += self.sample_every;
+    }
+
+    /// Sums S above the primality checker's limit, over every gap.
+    pub fn total_out_of_range(&self) -> u64 {
+        self.out_of_range.values().sum()
+    }
+
+    /// Starts a WAL on top of the current state: from now on the bins and gap-size entries
+    /// that change are tracked, so that each record only carries the changes since the
+    /// previous one.
+    pub fn start_wal(&mut self) {
+        self.dirty_bins = Some(Vec::new());
+        self.wal_base = Some(Box::new(WalBase::of(self)));
+    }
+
+    /// Moves the fields indexed by gap size out of `self`, leaving them empty.
+    fn take_gap_size_fields(&mut self) -> GapSizeFields {
+        GapSizeFields {
+            gap_spectrum: std::mem::take(&mut self.gap_spectrum.dense),
+            early_spectrum: std::mem::take(&mut self.early_spectrum.dense),
+            weighted_spectrum: std::mem::take(&mut self.weighted_spectrum),
+            first_occurrences: std::mem::take(&mut self.first_occurrences),
+            square_free: std::mem::take(&mut self.square_free),
+        }
+    }
+
+    fn restore_gap_size_fields(&mut self, fields: GapSizeFields) {
+        self.gap_spectrum.dense = fields.gap_spectrum;
+        self.early_spectrum.dense = fields.early_spectrum;
+        self.weighted_spectrum = fields.weighted_spectrum;
+        self.first_occurrences = fields.first_occurrences;
+        self.square_free = fields.square_free;
+    }
+
+    /// Encodes a WAL record of the segments analyzed since the previous one (or since
+    /// [`Statistics::start_wal`]): the small fields in full, the entries indexed by gap size
+    /// that changed, the new large gap events and the bins touched. Replaying the records in
+    /// order on top of the state the log started from reproduces the current state exactly.
+    pub fn encode_wal_record(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut dirty = self.dirty_bins.replace(Vec::new()).unwrap_or_default();
+        dirty.sort_unstable();
+        dirty.dedup();
+        let base = self
+            .wal_base
+            .replace(Box::new(WalBase::of(self)))
+            .ok_or("the WAL was not started")?;
+        let old = &base.fields;
+        let deltas = WalDeltas {
+            gap_spectrum: VecDelta::between(&old.gap_spectrum, &self.gap_spectrum.dense),
+            early_spectrum: VecDelta::between(&old.early_spectrum, &self.early_spectrum.dense),
+            weighted_spectrum: VecDelta::between(&old.weighted_spectrum, &self.weighted_spectrum),
+            first_occurrences: VecDelta::between(&old.first_occurrences, &self.first_occurrences),
+            square_free: VecDelta::between(&old.square_free, &self.square_free),
+            large_gap_events: self.large_gap_events[base.large_gap_events..].to_vec(),
+        };
+
+        let bins = std::mem::take(&mut self.bins);
+        let fields = self.take_gap_size_fields();
+        let events = std::mem::take(&mut self.large_gap_events);
+        let record = WalRecordRef {
+            core: self,
+            deltas,
+            bins: dirty
+                .iter()
+                .map(|&i| (i, &bins[i - self.first_bin]))
+                .collect(),
+        };
+        let bytes = bincode::serde::encode_to_vec(&record, bincode::config::standard());
+        self.bins = bins;
+        self.restore_gap_size_fields(fields);
+        self.large_gap_events = events;
+        Ok(bytes?)
+    }
+
+    /// Applies a record produced by [`Statistics::encode_wal_record`], folding its deltas
+    /// into the state so far.
+    pub fn apply_wal_record(mut self, bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let (record, _): (WalRecord, usize) =
+            bincode::serde::decode_from_slice(bytes, bincode::config::standard())?;
+        let mut next = record.core;
+        next.bins = std::mem::take(&mut self.bins);
+        next.dirty_bins = self.dirty_bins.take();
+        next.wal_base = self.wal_base.take();
+        next.restore_gap_size_fields(self.take_gap_size_fields());
+        next.large_gap_events = std::mem::take(&mut self.large_gap_events);
+
+        let deltas = record.deltas;
+        deltas.gap_spectrum.apply(&mut next.gap_spectrum.dense);
+        deltas.early_spectrum.apply(&mut next.early_spectrum.dense);
+        deltas.weighted_spectrum.apply(&mut next.weighted_spectrum);
+        deltas.first_occurrences.apply(&mut next.first_occurrences);
+        deltas.square_free.apply(&mut next.square_free);
+        next.large_gap_events.extend(deltas.large_gap_events);
+        for (index, bin) in record.bins {
+            *next.bin_mut(BinIndex(index)) = bin;
+        }
+        Ok(next)
+    }
+
+    /// Switches to streaming mode: bins are written to `writer` as soon as the prime
+    /// stream passes their end, and dropped from memory. Must be called before any
+    /// bin is updated.
+    pub fn stream_bins_to(&mut self, writer: OscillationWriter) {
+        self.bins.clear();
+        self.first_bin = 0;
+        self.bin_stream = Some(writer);
+    }
+
+    /// Also streams every bin to an Arrow IPC writer once the prime stream passes it, in
+    /// addition to the regular CSV output.
+    #[cfg(feature = "arrow")]
+    pub fn stream_bins_to_arrow(&mut self, writer: ArrowBinWriter) {
+        self.arrow_stream = Some((writer, 0));
+    }
+
+    /// Writes the bins ending below `p` that the Arrow stream hasn't seen yet. Runs before
+    /// the CSV stream flushes them, so they are still in memory (or were never touched).
+    #[cfg(feature = "arrow")]
+    fn write_arrow_bins_below(&mut self, p: u64) -> Result<(), Box<dyn Error>> {
+        let Some((mut writer, mut next)) = self.arrow_stream.take() else {
+            return Ok(());
+        };
+        while next < self.num_bins && self.make_bin_end(next) < p {
+            match self.bins.get(next - self.first_bin) {
+                Some(bin) => writer.write_bin(bin)?,
+                None => writer.write_bin(&self.make_bin(next))?,
+            }
+            next += 1;
+        }
+        self.arrow_stream = Some((writer, next));
+        Ok(())
+    }
+
+    /// Whether the oscillation series was already written incrementally.
+    pub fn bins_streamed(&self) -> bool {
+        self.bins_streamed
+    }
+
+    /// Whether the oscillation series is being written incrementally right now.
+    pub fn streaming_bins(&self) -> bool {
+        self.bin_stream.is_some()
+    }
+
+    /// Flushes the bins streamed so far to disk, without closing the stream.
+    pub fn flush_bin_stream(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.bin_stream.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flushes every bin whose end lies below `p` to the bin stream, if one is active.
+    /// A pair ending at `p` has p_current = p and S > p, so such bins are final, unless the
+    /// pair is attributed by p_prev or the midpoint: then only bins below p_prev are.
+    #[inline]
+    pub fn flush_completed_bins(&mut self, p: u64) -> Result<(), Box<dyn Error>> {
+        let p = match self.attribute_by {
+            AttributeBy::Curr | AttributeBy::S => p,
+            AttributeBy::Prev | AttributeBy::Midpoint => p.min(self.analyzed_up_to),
+        };
+        #[cfg(feature = "arrow")]
+        self.write_arrow_bins_below(p)?;
+        if self.bin_stream.is_none() {
+            return Ok(());
+        }
+        while self.first_bin < self.num_bins && self.make_bin_end(self.first_bin) < p {
+            self.flush_front_bin()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes all remaining bins and closes the bin streams, if any are active.
+    pub fn finish_bin_stream(&mut self) -> Result<(), Box<dyn Error>> {
+        #[cfg(feature = "arrow")]
+        {
+            self.write_arrow_bins_below(u64::MAX)?;
+            if let Some((writer, _)) = self.arrow_stream.take() {
+                writer.finish()?;
+            }
+        }
+        if self.bin_stream.is_none() {
+            return Ok(());
+        }
+        while self.first_bin < self.num_bins {
+            self.flush_front_bin()?;
+        }
+        if let Some(writer) = self.bin_stream.take() {
+            writer.finish()?;
+        }
+        self.bins_streamed = true;
+        Ok(())
+    }
+
+    fn make_bin_end(&self, index: usize) -> u64 {
+        (self.bin_start(index + 1) - 1).min(self.max_n_analysis_range)
     }
 
-    pub fn get_bin_index(&self, n: u64) -> Option<usize> {
+    fn flush_front_bin(&mut self) -> Result<(), Box<dyn Error>> {
+        let bin = match self.bins.pop_front() {
+            Some(bin) => bin,
+            None => self.make_bin(self.first_bin),
+        };
+        if let Some(writer) = self.bin_stream.as_mut() {
+            writer.write_bin(&bin)?;
+        }
+        self.first_bin += 1;
+        Ok(())
+    }
+
+    /// The upper bound of p this run was configured to reach.
+    pub fn max_n(&self) -> u64 {
+        self.max_n_analysis_range / 2
+    }
+
+    /// Whether the prime stream reached `max_n`.
+    pub fn is_complete(&self) -> bool {
+        self.analyzed_up_to >= self.max_n()
+    }
+
+    /// Writes the full state as `STATE_MAGIC`, the little-endian `STATE_FORMAT_VERSION`,
+    /// and a bincode payload.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut bytes = Vec::with_capacity(STATE_MAGIC.len() + 4);
+        bytes.extend_from_slice(STATE_MAGIC);
+        bytes.extend_from_slice(&STATE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend(bincode::serde::encode_to_vec(
+            self,
+            bincode::config::standard(),
+        )?);
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Reads a state file written by [`Statistics::save`], refusing files from other format versions.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let header_len = STATE_MAGIC.len() + 4;
+        if bytes.len() < header_len || &bytes[..STATE_MAGIC.len()] != STATE_MAGIC {
+            return Err(format!("{} is not a statistics state file", path.display()).into());
+        }
+        let version = u32::from_le_bytes(bytes[STATE_MAGIC.len()..header_len].try_into()?);
+        if version != STATE_FORMAT_VERSION {
+            return Err(format!(
+                "{} uses state format version {}, but this build reads version {}",
+                path.display(),
+                version,
+                STATE_FORMAT_VERSION
+            )
+            .into());
+        }
+        let (stats, read) =
+            bincode::serde::decode_from_slice(&bytes[header_len..], bincode::config::standard())?;
+        if read != bytes.len() - header_len {
+            return Err(format!(
+                "{} has trailing data after the state payload",
+                path.display()
+            )
+            .into());
+        }
+        Ok(stats)
+    }
+
+    /// Position of `gap` in `target_gaps` (and in each bin's per-gap counters), if it is tracked.
+    #[inline]
+    pub fn target_slot(&self, gap: Gap) -> Option<usize> {
+        self.target_slots.slot(gap)
+    }
+
+    /// The bin containing the number `n` (a prime or an S value).
+    pub fn get_bin_index(&self, n: u64) -> Option<BinIndex> {
         if n > self.max_n_analysis_range {
             return None;
         }
+        if !self.bin_starts.is_empty() {
+            let index = self.bin_starts.partition_point(|&start| start <= n) - 1;
+            return Some(BinIndex(index));
+        }
         let index = (n / self.bin_size) as usize;
-        if index < self.bins.len() {
-            Some(index)
-        } else {
-            Some(self.bins.len() - 1)
+        Some(BinIndex(index.min(self.num_bins - 1)))
+    }
+}
+
+/// The landmarks of `align` below `limit`, in ascending order.
+fn landmarks(align: BinAlign, limit: u64) -> Vec<u64> {
+    let (first, mut factors): (u64, Box<dyn Iterator<Item = u64>>) = match align {
+        BinAlign::Pow10 => (10, Box::new(std::iter::repeat(10))),
+        BinAlign::Pow2 => (2, Box::new(std::iter::repeat(2))),
+        BinAlign::Primorial => (2, Box::new((3..).filter(|&n| is_prime_u64(n)))),
+    };
+    let mut landmarks = Vec::new();
+    let mut landmark = Some(first);
+    while let Some(l) = landmark.filter(|&l| l < limit) {
+        landmarks.push(l);
+        landmark = factors.next().and_then(|factor| l.checked_mul(factor));
+    }
+    landmarks
+}
+
+/// Wilson score interval for a binomial proportion of `successes` out of `trials`.
+pub fn wilson_interval(successes: u64, trials: u64, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 0.0);
+    }
+    let n = trials as f64;
+    let p_hat = successes as f64 / n;
+    let z2 = z * z;
+    let denominator = 1.0 + z2 / n;
+    let center = (p_hat + z2 / (2.0 * n)) / denominator;
+    let half_width = z * (p_hat * (1.0 - p_hat) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+    (
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    )
+}
+
+/// z-score of `successes / trials` against the null proportion `p0`.
+pub fn proportion_z_score(successes: u64, trials: u64, p0: f64) -> f64 {
+    if trials == 0 || p0 <= 0.0 || p0 >= 1.0 {
+        return 0.0;
+    }
+    let n = trials as f64;
+    (successes as f64 / n - p0) / (p0 * (1.0 - p0) / n).sqrt()
+}
+
+/// Two-sided p-value of a one-proportion z-test of `successes / trials` against `p0`.
+pub fn proportion_p_value(successes: u64, trials: u64, p0: f64) -> f64 {
+    if trials == 0 || p0 <= 0.0 || p0 >= 1.0 {
+        return 1.0;
+    }
+    let z = proportion_z_score(successes, trials, p0);
+    erfc(z.abs() / std::f64::consts::SQRT_2)
+}
+
+/// Granville's 2e^(-γ), the conjectured limsup of maximal gaps over ln² p; every record
+/// gap found so far stays below 0.93 ln² p.
+const GRANVILLE_CONSTANT: f64 = 1.122_918_967_133_770_3;
+
+/// An even bound that the gaps between primes up to `n` are not expected to exceed,
+/// `2e^(-γ) ln² n`: 1930 at 10^18, against the largest known gap below 2^64 of 1550.
+pub fn max_gap_bound(n: u64) -> u64 {
+    let ln = (n.max(2) as f64).ln();
+    ((GRANVILLE_CONSTANT * ln * ln).ceil() as u64)
+        .next_multiple_of(2)
+        .max(2)
+}
+
+/// Twin prime constant C2 = prod over odd primes p of (1 - 1/(p-1)^2).
+const TWIN_PRIME_CONSTANT: f64 = 0.660_161_815_846_869_6;
+
+/// The Hardy–Littlewood singular series of an even gap `g`: `2 C2 prod_{p | g, p > 2} (p-1)/(p-2)`.
+pub fn singular_series(g: u64) -> f64 {
+    let mut singular_series = 2.0 * TWIN_PRIME_CONSTANT;
+    let mut rest = g;
+    while rest > 0 && rest.is_multiple_of(2) {
+        rest /= 2;
+    }
+    let mut p = 3;
+    while p * p <= rest {
+        if rest.is_multiple_of(p) {
+            singular_series *= (p - 1) as f64 / (p - 2) as f64;
+            while rest.is_multiple_of(p) {
+                rest /= p;
+            }
         }
+        p += 2;
+    }
+    if rest > 1 {
+        singular_series *= (rest - 1) as f64 / (rest - 2) as f64;
+    }
+    singular_series
+}
+
+/// Pairwise Pearson correlations between several series observed together, accumulated
+/// one observation at a time with Welford's co-moment updates.
+#[derive(Debug, Clone)]
+pub struct SeriesCorrelations {
+    observations: u64,
+    means: Vec<f64>,
+    comoments: Vec<f64>, // k × k, row-major
+}
+
+impl SeriesCorrelations {
+    pub fn new(series: usize) -> Self {
+        Self {
+            observations: 0,
+            means: vec![0.0; series],
+            comoments: vec![0.0; series * series],
+        }
+    }
+
+    pub fn observations(&self) -> u64 {
+        self.observations
+    }
+
+    /// Adds one observation, a value for every series.
+    pub fn add(&mut self, values: &[f64]) {
+        let k = self.means.len();
+        self.observations += 1;
+        let n = self.observations as f64;
+        let deltas: Vec<f64> = values.iter().zip(&self.means).map(|(x, m)| x - m).collect();
+        for (mean, delta) in self.means.iter_mut().zip(&deltas) {
+            *mean += delta / n;
+        }
+        for (row, delta) in self.comoments.chunks_mut(k).zip(&deltas) {
+            for ((comoment, x), mean) in row.iter_mut().zip(values).zip(&self.means) {
+                *comoment += delta * (x - mean);
+            }
+        }
+    }
+
+    /// Correlation of series `i` and `j`; `None` while either is constant.
+    pub fn correlation(&self, i: usize, j: usize) -> Option<f64> {
+        let k = self.means.len();
+        let denominator = (self.comoments[i * k + i] * self.comoments[j * k + j]).sqrt();
+        // The upper triangle only, so that the matrix comes out exactly symmetric.
+        let comoment = self.comoments[i.min(j) * k + i.max(j)];
+        (denominator > 0.0).then(|| (comoment / denominator).clamp(-1.0, 1.0))
+    }
+}
+
+/// Weighted least-squares line through `(x, y, weight)` points, as
+/// `(intercept, slope, r_squared)`; `None` for fewer than two distinct x.
+pub fn weighted_linear_fit(points: &[(f64, f64, f64)]) -> Option<(f64, f64, f64)> {
+    let total: f64 = points.iter().map(|p| p.2).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mean_x = points.iter().map(|p| p.2 * p.0).sum::<f64>() / total;
+    let mean_y = points.iter().map(|p| p.2 * p.1).sum::<f64>() / total;
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for &(x, y, w) in points {
+        sxy += w * (x - mean_x) * (y - mean_y);
+        sxx += w * (x - mean_x) * (x - mean_x);
+        syy += w * (y - mean_y) * (y - mean_y);
+    }
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    let r_squared = if syy == 0.0 {
+        1.0
+    } else {
+        sxy * sxy / (sxx * syy)
+    };
+    Some((mean_y - slope * mean_x, slope, r_squared))
+}
+
+/// Hardy–Littlewood prediction for the number of consecutive prime pairs up to `n` with
+/// gap `g`: the singular series `2 C2 prod_{p | g, p > 2} (p-1)/(p-2)` times
+/// `integral_2^n exp(-g / ln t) / ln^2 t dt`, where the exponential is the Poisson factor
+/// for no prime falling between the pair. Odd gaps other than 1 never occur.
+pub fn hardy_littlewood_gap_count(g: u64, n: u64) -> f64 {
+    if g == 1 {
+        return if n >= 3 { 1.0 } else { 0.0 };
+    }
+    if g == 0 || g % 2 == 1 || n < 3 {
+        return 0.0;
+    }
+    let singular_series = singular_series(g);
+
+    // Simpson's rule in u = ln t, where the integrand becomes exp(u - g/u) / u^2.
+    const STEPS: usize = 1000;
+    let (a, b) = (2f64.ln(), (n as f64).ln());
+    let h = (b - a) / STEPS as f64;
+    let f = |u: f64| (u - g as f64 / u).exp() / (u * u);
+    let interior: f64 = (1..STEPS)
+        .map(|i| f(a + i as f64 * h) * if i % 2 == 1 { 4.0 } else { 2.0 })
+        .sum();
+    singular_series * (f(a) + interior + f(b)) * h / 3.0
+}
+
+/// Mean of 2 / ln S over the pairs of consecutive primes up to `n`, with S ≈ 2p: the
+/// integral of 2 / (ln 2t ln t) over that of 1 / ln t, both from 2 to `n`.
+pub fn mean_local_rate(n: u64) -> f64 {
+    if n < 3 {
+        return 2.0 / 4f64.ln();
+    }
+    // Simpson's rule in u = ln t, where dt / ln t becomes exp(u) / u du.
+    const STEPS: usize = 1000;
+    let (a, b) = (2f64.ln(), (n as f64).ln());
+    let h = (b - a) / STEPS as f64;
+    let density = |u: f64| u.exp() / u;
+    let simpson = |f: &dyn Fn(f64) -> f64| {
+        let interior: f64 = (1..STEPS)
+            .map(|i| f(a + i as f64 * h) * if i % 2 == 1 { 4.0 } else { 2.0 })
+            .sum();
+        (f(a) + interior + f(b)) * h / 3.0
+    };
+    let weighted = simpson(&|u| density(u) * 2.0 / (2f64.ln() + u));
+    weighted / simpson(&density)
+}
+
+/// Expected success rate of a gap under `baseline`, for a run up to `n`; `exact_boost` is
+/// the gap's residue-class boost (see [`crate::model`]).
+pub fn baseline_rate(baseline: Baseline, n: u64, exact_boost: f64) -> f64 {
+    let ln_n = (n as f64).ln();
+    match baseline {
+        Baseline::LnN => 1.0 / ln_n,
+        Baseline::TwoLnN => 2.0 / ln_n,
+        Baseline::LocalLnS => mean_local_rate(n),
+        Baseline::Hl => exact_boost * mean_local_rate(n),
+    }
+}
+
+/// z statistic of a two-proportion test of `s1 / n1` against `s2 / n2` (pooled variance).
+pub fn two_proportion_z_score(s1: u64, n1: u64, s2: u64, n2: u64) -> f64 {
+    if n1 == 0 || n2 == 0 {
+        return 0.0;
+    }
+    let (n1, n2) = (n1 as f64, n2 as f64);
+    let pooled = (s1 + s2) as f64 / (n1 + n2);
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if se == 0.0 {
+        return 0.0;
+    }
+    (s1 as f64 / n1 - s2 as f64 / n2) / se
+}
+
+/// Two-sided p-value of a two-proportion z-test.
+pub fn two_proportion_p_value(s1: u64, n1: u64, s2: u64, n2: u64) -> f64 {
+    let z = two_proportion_z_score(s1, n1, s2, n2);
+    erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0)
+}
+
+/// Complementary error function (Chebyshev fit, fractional error below 1.2e-7),
+/// which keeps relative precision in the far tail where tiny p-values live.
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let ans = t * poly.exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shielding::{calculate_shielding_info, ShieldModel};
+
+    #[test]
+    fn test_target_gap_set_slots() {
+        let gaps = [Gap(1_000_000), Gap(2), Gap(64), Gap(4), Gap(63), Gap(2)];
+        let set = TargetGapSet::new(&gaps);
+        assert_eq!(set.slot(Gap(2)), Some(0));
+        assert_eq!(set.slot(Gap(4)), Some(1));
+        assert_eq!(set.slot(Gap(63)), Some(2));
+        assert_eq!(set.slot(Gap(64)), Some(3));
+        assert_eq!(set.slot(Gap(1_000_000)), Some(4));
+        assert_eq!(set.slot(Gap(6)), None);
+        assert_eq!(set.slot(Gap(2_000_000)), None);
+        assert_eq!(TargetGapSet::new(&[]).slot(Gap(2)), None);
+    }
+
+    #[test]
+    fn test_max_gap_bound_covers_the_record_gaps() {
+        // Maximal gaps: 114 after 492113, 1132 after 1693182318746371, 1550 below 2^64.
+        assert_eq!(max_gap_bound(1_000_000), 216);
+        assert!(max_gap_bound(492_113) > 114);
+        assert!(max_gap_bound(1_693_182_318_746_371) > 1132);
+        assert!(max_gap_bound(u64::MAX) > 1550);
+        assert_eq!(max_gap_bound(1), 2);
+    }
+
+    #[test]
+    fn test_attribute_by_places_a_straddling_pair() {
+        // 99991 and 100003 straddle the boundary of bins 0 and 1 (100000 numbers each).
+        let bin_of_gap = |attribute_by| {
+            let mut stats = Statistics::new(1_000_000, 20, &[Gap(12)], 64);
+            stats.attribute_by = attribute_by;
+            stats.record_pair(Prime(99_991), Prime(100_003), 199_993, false);
+            let bins: Vec<usize> = (0..stats.bins.len())
+                .filter(|&i| stats.bins[i].gap_occurrences[0] > 0)
+                .collect();
+            assert_eq!(stats.bins[1].prime_count_p, 1);
+            bins
+        };
+        assert_eq!(bin_of_gap(AttributeBy::Prev), [0]);
+        assert_eq!(bin_of_gap(AttributeBy::Midpoint), [0]);
+        assert_eq!(bin_of_gap(AttributeBy::Curr), [1]);
+        assert_eq!(bin_of_gap(AttributeBy::S), [1]);
+
+        // Binning by S moves the pair's P count along: 89 + 97 - 1 = 185 lies in bin 1.
+        let mut stats = Statistics::new(1000, 20, &[Gap(8)], 64);
+        stats.bin_domain = BinDomain::S;
+        stats.record_first_prime();
+        stats.record_pair(Prime(89), Prime(97), 185, false);
+        assert_eq!(stats.bins[0].prime_count_p, 0);
+        assert_eq!(stats.bins[1].prime_count_p, 1);
+        assert_eq!(stats.bins[1].gap_occurrences[0], 1);
+    }
+
+    #[test]
+    fn test_large_gap_events_record_their_context() {
+        let mut stats = Statistics::new(10_000, 10, &[Gap(2)], 64);
+        stats.large_gap_threshold = Some(20);
+        stats.record_pair(Prime(883), Prime(887), 1769, false);
+        stats.record_pair(Prime(887), Prime(907), 1793, false);
+        stats.record_pair(Prime(907), Prime(911), 1817, false);
+        stats.record_pair(Prime(1129), Prime(1151), 2279, false);
+        assert_eq!(
+            stats.large_gap_events,
+            [
+                LargeGapEvent {
+                    p_prev: 887,
+                    p_curr: 907,
+                    gap: 20,
+                    s: 1793,
+                    s_is_prime: false,
+                    s_smallest_factor: Some(11),
+                    prev_gap: Some(4),
+                    next_gap: Some(4),
+                },
+                // The last pair analyzed has no known successor.
+                LargeGapEvent {
+                    p_prev: 1129,
+                    p_curr: 1151,
+                    gap: 22,
+                    s: 2279,
+                    s_is_prime: false,
+                    s_smallest_factor: Some(43),
+                    prev_gap: Some(4),
+                    next_gap: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_early_spectrum_holds_the_pairs_below_the_split() {
+        let mut stats = Statistics::new(100, 10, &[Gap(2)], 64);
+        assert_eq!(stats.split_at, 50);
+        stats.record_pair(Prime(41), Prime(43), 83, true);
+        stats.record_pair(Prime(47), Prime(53), 99, false);
+        stats.record_pair(Prime(59), Prime(61), 119, false);
+        assert_eq!(stats.early_spectrum.get(Gap(2)), (1, 1));
+        assert_eq!(stats.early_spectrum.get(Gap(6)), (1, 0));
+        assert_eq!(stats.gap_spectrum.get(Gap(2)), (2, 1));
+    }
+
+    #[test]
+    fn test_neighbor_gaps_look_both_ways() {
+        let mut stats = Statistics::new(100, 10, &[Gap(2)], 64);
+        for (p_prev, p_curr) in [(3, 5), (5, 7), (7, 11), (11, 13), (13, 17), (17, 19)] {
+            let s = p_prev + p_curr - 1;
+            stats.record_pair(Prime(p_prev), Prime(p_curr), s, s != 35);
+        }
+        // The gap after the last analyzed pair still counts, sampled or not.
+        stats.record_unsampled_prime(Prime(19), Prime(23));
+        let counts = |preceded, followed| NeighborCounts { preceded, followed };
+        assert_eq!(
+            stats.neighbor_gaps[0],
+            BTreeMap::from([(2, counts((1, 1), (1, 1))), (4, counts((2, 1), (3, 2)))])
+        );
+    }
+
+    #[test]
+    fn test_gap_families_pool_the_gaps_of_each_residue() {
+        let mut stats = Statistics::new(1000, 10, &[Gap(2)], 64);
+        stats.record_pair(Prime(5), Prime(7), 11, true);
+        stats.record_pair(Prime(7), Prime(11), 17, true);
+        stats.record_pair(Prime(13), Prime(17), 29, true);
+        stats.record_pair(Prime(23), Prime(29), 51, false);
+        stats.record_pair(Prime(31), Prime(37), 67, true);
+        stats.record_pair(Prime(89), Prime(97), 185, false);
+
+        let residues = crate::model::ResidueModel::new(crate::model::DEFAULT_PRIME_LIMIT);
+        let families =
+            stats.gap_family_records(&[6, 30], ShieldModel::default(), &residues, Baseline::LnN);
+        let summary: Vec<_> = families
+            .iter()
+            .map(|f| (f.modulus, f.residue, f.gaps, f.count, f.successes))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (6, 0, 1, 2, 1),
+                (6, 2, 2, 2, 1),
+                (6, 4, 1, 2, 2),
+                (30, 2, 1, 1, 1),
+                (30, 4, 1, 2, 2),
+                (30, 6, 1, 2, 1),
+                (30, 8, 1, 1, 0),
+            ]
+        );
+        // The boost of a family is its gaps' boosts weighted by their counts.
+        let boost = |g| calculate_shielding_info(g, ShieldModel::default());
+        let expected = (boost(2).theoretical_boost + boost(8).theoretical_boost) / 2.0;
+        assert!((families[1].theoretical_boost - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_series_correlations_match_a_direct_computation() {
+        let xs = [1.0, 2.0, 4.0, 3.0, 7.0];
+        let ys = [0.5, 0.1, 0.9, 0.4, 1.3];
+        let mut correlations = SeriesCorrelations::new(3);
+        for (&x, &y) in xs.iter().zip(&ys) {
+            correlations.add(&[x, y, 2.0]);
+        }
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        let (mx, my) = (mean(&xs), mean(&ys));
+        let sxy: f64 = xs.iter().zip(&ys).map(|(x, y)| (x - mx) * (y - my)).sum();
+        let sxx: f64 = xs.iter().map(|x| (x - mx) * (x - mx)).sum();
+        let syy: f64 = ys.iter().map(|y| (y - my) * (y - my)).sum();
+        let r = correlations.correlation(0, 1).unwrap();
+        assert!((r - sxy / (sxx * syy).sqrt()).abs() < 1e-12);
+        assert_eq!(correlations.correlation(1, 0), Some(r));
+        assert!((correlations.correlation(1, 1).unwrap() - 1.0).abs() < 1e-12);
+        // The third series is constant.
+        assert_eq!(correlations.correlation(0, 2), None);
+        assert_eq!(correlations.observations(), 5);
+    }
+
+    #[test]
+    fn test_weighted_linear_fit_recovers_a_line() {
+        let points: Vec<(f64, f64, f64)> = (0..10)
+            .map(|x| (x as f64, 3.0 - 0.5 * x as f64, (x + 1) as f64))
+            .collect();
+        let (intercept, slope, r_squared) = weighted_linear_fit(&points).unwrap();
+        assert!((intercept - 3.0).abs() < 1e-12 && (slope + 0.5).abs() < 1e-12);
+        assert!((r_squared - 1.0).abs() < 1e-12);
+        assert_eq!(weighted_linear_fit(&points[..1]), None);
+        // 𝔖(6) = 2 C2 · 2, 𝔖(30) = 2 C2 · 2 · 4/3.
+        assert!((singular_series(30) / singular_series(6) - 4.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_state_round_trip_and_version_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATE_FILE);
+
+        let mut stats = Statistics::new(1000, 10, &[Gap(2), Gap(4)], 16);
+        stats.total_primes = 168;
+        stats.gap_spectrum.record_occurrence(Gap(4));
+        stats.gap_spectrum.record_success(Gap(4));
+        stats.gap_spectrum.record_occurrence(Gap(20));
+        stats.bins[3].prime_count_p = 12;
+        stats.bins[3].gap_successes[1] = 2;
+        stats.save(&path).unwrap();
+
+        let loaded = Statistics::load(&path).unwrap();
+        assert_eq!(loaded.total_primes, 168);
+        assert_eq!(loaded.gap_spectrum.get(Gap(4)), (1, 1));
+        assert_eq!(loaded.gap_spectrum.get(Gap(20)), (1, 0));
+        assert_eq!(loaded.gap_spectrum.overflow(), (1, 0));
+        assert_eq!(loaded.bins[3].prime_count_p, 12);
+        assert_eq!(
+            loaded.bins[3].gap_successes[loaded.target_slot(Gap(4)).unwrap()],
+            2
+        );
+        assert_eq!(loaded.get_bin_index(999), stats.get_bin_index(999));
+
+        // A file from a different format version must be rejected.
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[STATE_MAGIC.len()] = bytes[STATE_MAGIC.len()].wrapping_add(1);
+        fs::write(&path, bytes).unwrap();
+        let err = Statistics::load(&path).unwrap_err().to_string();
+        assert!(err.contains("format version"), "{}", err);
+    }
+
+    #[test]
+    fn test_bin_align_puts_every_landmark_on_a_boundary() {
+        let stats = Statistics::new(1_000_000, 20, &[Gap(2)], 64).with_bin_align(BinAlign::Pow10);
+        let starts: Vec<u64> = stats.bins.iter().map(|b| b.bin_start).collect();
+        for landmark in [10, 100, 1_000, 10_000, 100_000, 1_000_000] {
+            assert!(starts.contains(&landmark), "no bin starts at {}", landmark);
+        }
+        // Bins tile [0, 2N] and get_bin_index agrees with their bounds.
+        assert_eq!(stats.bins.back().unwrap().bin_end, 2_000_000);
+        for (index, bin) in stats.bins.iter().enumerate() {
+            if index > 0 {
+                assert_eq!(bin.bin_start, stats.bins[index - 1].bin_end + 1);
+            }
+            for n in [bin.bin_start, bin.bin_end] {
+                assert_eq!(stats.get_bin_index(n).unwrap().0, index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_lengths() {
+        let mut runs = RunLengths::default();
+        for success in [true, true, false, true, false, false, false, true, true] {
+            runs.record(success);
+        }
+        let all: Vec<_> = runs.iter().collect();
+        assert_eq!(
+            all,
+            [(false, 1, 1), (false, 3, 1), (true, 1, 1), (true, 2, 2)]
+        );
+    }
+
+    #[test]
+    fn test_success_spacing() {
+        let mut spacing = SuccessSpacing::default();
+        for (success, s) in [(false, 4), (true, 7), (false, 11), (true, 17), (true, 19)] {
+            spacing.record(success, s);
+        }
+        assert_eq!(spacing.by_pairs().collect::<Vec<_>>(), [(1, 1), (2, 1)]);
+        assert_eq!(
+            spacing.by_distance().collect::<Vec<_>>(),
+            [(1, 1, 0), (2, 3, 1), (4, 7, 0), (8, 15, 1)]
+        );
+        assert_eq!(spacing.mean_distance(), Some(6.0));
+    }
+
+    #[test]
+    fn test_overflow_matches_a_spectrum_without_overflow() {
+        let is_prime = |n: u64| {
+            n >= 2
+                && (2..)
+                    .take_while(|d| d * d <= n)
+                    .all(|d| !n.is_multiple_of(d))
+        };
+        let primes: Vec<u64> = (3..5000).filter(|&n| is_prime(n)).collect();
+        let mut narrow = Statistics::new(5000, 10, &[Gap(2)], 8);
+        let mut wide = Statistics::new(5000, 10, &[Gap(2)], 64);
+        for pair in primes.windows(2) {
+            let s = pair[0] + pair[1] - 1;
+            narrow.record_pair(Prime(pair[0]), Prime(pair[1]), s, is_prime(s));
+            wide.record_pair(Prime(pair[0]), Prime(pair[1]), s, is_prime(s));
+        }
+        let narrow_rows: Vec<_> = narrow.gap_spectrum.iter().collect();
+        let wide_rows: Vec<_> = wide.gap_spectrum.iter().collect();
+        assert_eq!(narrow_rows, wide_rows);
+        assert_eq!(wide.gap_spectrum.overflow(), (0, 0));
+        let above: Vec<_> = wide_rows.iter().filter(|(gap, _, _)| gap.0 > 8).collect();
+        let totals = above
+            .iter()
+            .fold((0, 0), |acc, (_, n, k)| (acc.0 + n, acc.1 + k));
+        assert!(totals.0 > 0);
+        assert_eq!(narrow.gap_spectrum.overflow(), totals);
+        assert_eq!(narrow.gap_spectrum.overflow_gap_count(), above.len());
+        for (gap, n, k) in &wide_rows {
+            assert_eq!(narrow.gap_spectrum.get(*gap), (*n, *k));
+        }
+    }
+
+    #[test]
+    fn test_baselines_set_the_expected_rates() {
+        let mut stats = Statistics::new(1000, 10, &[Gap(2)], 64);
+        stats.record_pair(Prime(2), Prime(3), 4, false);
+        stats.record_pair(Prime(5), Prime(7), 11, true);
+        stats.record_pair(Prime(7), Prime(11), 17, true);
+        stats.record_pair(Prime(23), Prime(29), 51, false);
+        stats.analyzed_up_to = 1000;
+
+        let residues = crate::model::ResidueModel::new(crate::model::DEFAULT_PRIME_LIMIT);
+        let expected = |baseline| -> Vec<(u64, f64, String)> {
+            stats
+                .iter_gap_records(ShieldModel::default(), &residues, baseline)
+                .map(|r| (r.gap_size.0, r.expected_rate_heuristic, r.baseline))
+                .collect()
+        };
+        let ln_n = 1000f64.ln();
+        let local = mean_local_rate(1000);
+        // S stays below 2N, and the small sums of the early pairs weigh in.
+        assert!(local > 2.0 / 2000f64.ln());
+        for baseline in [
+            Baseline::LnN,
+            Baseline::TwoLnN,
+            Baseline::LocalLnS,
+            Baseline::Hl,
+        ] {
+            let rows = expected(baseline);
+            assert_eq!(rows.len(), 4);
+            for (gap, rate, label) in rows {
+                // The odd gap between 2 and 3 never gives a prime sum.
+                let want = match baseline {
+                    _ if gap == 1 => 0.0,
+                    Baseline::LnN => 1.0 / ln_n,
+                    Baseline::TwoLnN => 2.0 / ln_n,
+                    Baseline::LocalLnS => local,
+                    Baseline::Hl => residues.boost(gap) * local,
+                };
+                assert!((rate - want).abs() < 1e-12, "{:?} gap {}", baseline, gap);
+                assert_eq!(label, baseline.label());
+            }
+        }
+        // Only `hl` tells the gaps apart: S = 2p + 3 of gap 4 is never divisible by 3.
+        let hl = expected(Baseline::Hl);
+        assert!(hl[2].1 > hl[3].1);
+    }
+
+    #[test]
+    fn test_gap_cdf_rises_to_one() {
+        let is_prime = |n: u64| {
+            n >= 2
+                && (2..)
+                    .take_while(|d| d * d <= n)
+                    .all(|d| !n.is_multiple_of(d))
+        };
+        let primes: Vec<u64> = (2..5000).filter(|&n| is_prime(n)).collect();
+        // Gaps above 8 land in the overflow, which the distribution must still cover.
+        let mut stats = Statistics::new(5000, 10, &[Gap(2)], 8);
+        for pair in primes.windows(2) {
+            let s = pair[0] + pair[1] - 1;
+            stats.record_pair(Prime(pair[0]), Prime(pair[1]), s, is_prime(s));
+        }
+
+        let cdf = stats.gap_cdf_records();
+        assert_eq!(cdf.len(), stats.gap_spectrum.iter().count());
+        assert_eq!(
+            cdf.iter().map(|r| r.count).sum::<u64>(),
+            primes.len() as u64 - 1
+        );
+        for pair in cdf.windows(2) {
+            assert!(pair[0].gap_size < pair[1].gap_size);
+            assert!(pair[0].cdf <= pair[1].cdf);
+            assert!(pair[0].success_cdf <= pair[1].success_cdf);
+        }
+        let last = cdf.last().unwrap();
+        assert_eq!((last.cdf, last.success_cdf), (1.0, 1.0));
+        // The odd gap 1 never gives a prime sum.
+        assert_eq!((cdf[0].gap_size, cdf[0].success_cdf), (Gap(1), 0.0));
+    }
+
+    #[test]
+    fn test_weighted_columns_by_hand() {
+        let mut stats = Statistics::new(100, 10, &[Gap(2)], 64);
+        stats.pair_weight = Some(PairWeight::InverseLog);
+        stats.record_pair(Prime(5), Prime(7), 11, true);
+        stats.record_pair(Prime(7), Prime(11), 17, true);
+        stats.record_pair(Prime(11), Prime(13), 23, true);
+        stats.record_pair(Prime(17), Prime(19), 35, false);
+        stats.analyzed_up_to = 19;
+
+        let w = |p: f64| 1.0 / p.ln();
+        let close = |got: Option<f64>, want: f64| (got.unwrap() - want).abs() < 1e-12;
+        let residues = crate::model::ResidueModel::new(crate::model::DEFAULT_PRIME_LIMIT);
+        let rows: Vec<_> = stats
+            .iter_gap_records(ShieldModel::default(), &residues, Baseline::LnN)
+            .collect();
+        let (twins, fours) = (&rows[0], &rows[1]);
+        assert_eq!((twins.gap_size, fours.gap_size), (Gap(2), Gap(4)));
+        assert!(close(twins.weighted_count, w(5.0) + w(11.0) + w(17.0)));
+        assert!(close(twins.weighted_successes, w(5.0) + w(11.0)));
+        assert!(close(
+            twins.weighted_success_rate,
+            (w(5.0) + w(11.0)) / (w(5.0) + w(11.0) + w(17.0))
+        ));
+        assert!(close(fours.weighted_count, w(7.0)));
+        assert!(close(fours.weighted_success_rate, 1.0));
+
+        let global = stats.global_stats();
+        let pairs = w(5.0) + w(7.0) + w(11.0) + w(17.0);
+        assert!(close(global.weighted_pairs, pairs));
+        assert!(close(global.weighted_primes_s, pairs - w(17.0)));
+
+        // Without --pair-weight the columns stay empty.
+        stats.pair_weight = None;
+        assert_eq!(stats.global_stats().weighted_pairs, None);
     }
 }
@@ -0,0 +1,501 @@
+//! Result tables as typed rows, the form in which every sink receives them.
+//!
+//! A record serializes into one [`Cell`] per field, in declaration order, keeping the
+//! field's type, so each sink writes the same values its own way. `--columns` and `--slim`
+//! pick the kept cells once, before any sink sees the table.
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize, SerializeSeq, SerializeStruct, Serializer};
+use std::fmt;
+
+/// One field of a record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    /// `None` or a unit value.
+    Empty,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+    /// A list of numbers, e.g. the shield primes of a gap.
+    List(Vec<u64>),
+}
+
+impl Cell {
+    /// The cell as a CSV field, formatted as the `csv` crate formats a serialized record;
+    /// a list is joined with commas.
+    pub fn to_csv(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Bool(b) => b.to_string(),
+            Cell::Int(i) => i.to_string(),
+            Cell::UInt(u) => u.to_string(),
+            Cell::Float(x) => ryu::Buffer::new().format(*x).to_string(),
+            Cell::Text(s) => s.clone(),
+            Cell::List(values) => values
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
+
+/// A JSON value: `null` when empty, an array for a list.
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Cell::Empty => serializer.serialize_none(),
+            Cell::Bool(b) => serializer.serialize_bool(*b),
+            Cell::Int(i) => serializer.serialize_i64(*i),
+            Cell::UInt(u) => serializer.serialize_u64(*u),
+            Cell::Float(x) => serializer.serialize_f64(*x),
+            Cell::Text(s) => serializer.serialize_str(s),
+            Cell::List(values) => values.serialize(serializer),
+        }
+    }
+}
+
+/// The rows of one result table, each with a cell per column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    /// CSV file name of the table, e.g. `gap_spectrum.csv`.
+    pub file: &'static str,
+    /// Empty when the table has no rows.
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    /// The table of `records`, all of one record type.
+    pub fn new<R: Serialize>(file: &'static str, records: &[R]) -> Result<Self, RowError> {
+        let mut table = Table {
+            file,
+            ..Table::default()
+        };
+        for record in records {
+            let fields = record.serialize(RowSerializer)?;
+            if table.columns.is_empty() {
+                table.columns = fields.iter().map(|(column, _)| *column).collect();
+            }
+            table
+                .rows
+                .push(fields.into_iter().map(|(_, cell)| cell).collect());
+        }
+        Ok(table)
+    }
+
+    /// Name of the table for sinks other than CSV, the file name without `.csv`.
+    pub fn name(&self) -> &str {
+        self.file.trim_end_matches(".csv")
+    }
+
+    /// Keeps only the columns for which `keep` holds, in their order.
+    pub fn retain_columns(&mut self, keep: impl Fn(&str) -> bool) {
+        let kept: Vec<usize> = (0..self.columns.len())
+            .filter(|&i| keep(self.columns[i]))
+            .collect();
+        if kept.len() == self.columns.len() {
+            return;
+        }
+        self.columns = kept.iter().map(|&i| self.columns[i]).collect();
+        for row in &mut self.rows {
+            let mut cells = std::mem::take(row)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>();
+            *row = kept.iter().filter_map(|&i| cells[i].take()).collect();
+        }
+    }
+
+    /// The rows as JSON objects, their fields in column order.
+    pub fn json_rows(&self) -> impl Iterator<Item = JsonRow<'_>> {
+        self.rows.iter().map(|cells| JsonRow {
+            columns: &self.columns,
+            cells,
+        })
+    }
+}
+
+/// One row of a [`Table`], serialized as an object of its columns.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonRow<'a> {
+    columns: &'a [&'static str],
+    cells: &'a [Cell],
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.columns.iter().zip(self.cells))
+    }
+}
+
+/// A record that does not flatten into a row of cells.
+#[derive(Debug)]
+pub struct RowError(String);
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RowError {}
+
+impl ser::Error for RowError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RowError(msg.to_string())
+    }
+}
+
+impl de::Error for RowError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RowError(msg.to_string())
+    }
+}
+
+fn unsupported<T>(what: &str) -> Result<T, RowError> {
+    Err(RowError(format!("a result table cannot hold {}", what)))
+}
+
+/// Serializes a record, a struct of plain fields, into its named cells.
+struct RowSerializer;
+
+/// Serializes one field into a [`Cell`].
+struct CellSerializer;
+
+/// Collects the elements of a list cell.
+struct ListSerializer(Vec<u64>);
+
+/// Collects the fields of a record.
+struct FieldsSerializer(Vec<(&'static str, Cell)>);
+
+impl SerializeStruct for FieldsSerializer {
+    type Ok = Vec<(&'static str, Cell)>;
+    type Error = RowError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), RowError> {
+        self.0.push((key, value.serialize(CellSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, RowError> {
+        Ok(self.0)
+    }
+}
+
+impl SerializeSeq for ListSerializer {
+    type Ok = Cell;
+    type Error = RowError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), RowError> {
+        match value.serialize(CellSerializer)? {
+            Cell::UInt(u) => self.0.push(u),
+            _ => return unsupported("a list of anything but unsigned integers"),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Cell, RowError> {
+        Ok(Cell::List(self.0))
+    }
+}
+
+/// The methods of a [`Serializer`] that only [`CellSerializer`] and [`RowSerializer`]
+/// implement differently: every other kind of value is rejected.
+macro_rules! reject_compound {
+    () => {
+        type SerializeTuple = Impossible<Self::Ok, RowError>;
+        type SerializeTupleStruct = Impossible<Self::Ok, RowError>;
+        type SerializeTupleVariant = Impossible<Self::Ok, RowError>;
+        type SerializeMap = Impossible<Self::Ok, RowError>;
+        type SerializeStructVariant = Impossible<Self::Ok, RowError>;
+
+        fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, RowError> {
+            unsupported("bytes")
+        }
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: &T,
+        ) -> Result<Self::Ok, RowError> {
+            unsupported("an enum with data")
+        }
+        fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, RowError> {
+            unsupported("a tuple")
+        }
+        fn serialize_tuple_struct(
+            self,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleStruct, RowError> {
+            unsupported("a tuple struct")
+        }
+        fn serialize_tuple_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleVariant, RowError> {
+            unsupported("an enum with data")
+        }
+        fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, RowError> {
+            unsupported("a map")
+        }
+        fn serialize_struct_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStructVariant, RowError> {
+            unsupported("an enum with data")
+        }
+    };
+}
+
+impl Serializer for CellSerializer {
+    type Ok = Cell;
+    type Error = RowError;
+    type SerializeSeq = ListSerializer;
+    type SerializeStruct = Impossible<Cell, RowError>;
+    reject_compound!();
+
+    fn serialize_bool(self, v: bool) -> Result<Cell, RowError> {
+        Ok(Cell::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Cell, RowError> {
+        Ok(Cell::Int(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Cell, RowError> {
+        Ok(Cell::Int(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Cell, RowError> {
+        Ok(Cell::Int(v.into()))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Cell, RowError> {
+        Ok(Cell::Int(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Cell, RowError> {
+        Ok(Cell::UInt(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Cell, RowError> {
+        Ok(Cell::UInt(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Cell, RowError> {
+        Ok(Cell::UInt(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Cell, RowError> {
+        Ok(Cell::UInt(v))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Cell, RowError> {
+        Ok(Cell::Float(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Cell, RowError> {
+        Ok(Cell::Float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Cell, RowError> {
+        Ok(Cell::Text(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Cell, RowError> {
+        Ok(Cell::Text(v.to_string()))
+    }
+    fn serialize_none(self) -> Result<Cell, RowError> {
+        Ok(Cell::Empty)
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Cell, RowError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Cell, RowError> {
+        Ok(Cell::Empty)
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Cell, RowError> {
+        Ok(Cell::Empty)
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Cell, RowError> {
+        Ok(Cell::Text(variant.to_string()))
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Cell, RowError> {
+        value.serialize(self)
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, RowError> {
+        Ok(ListSerializer(Vec::with_capacity(len.unwrap_or(0))))
+    }
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, RowError> {
+        unsupported("a nested struct")
+    }
+}
+
+/// Rejects every value but a struct.
+macro_rules! reject_scalars {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, _: $ty) -> Result<Self::Ok, RowError> {
+            unsupported("a value outside a record")
+        })*
+    };
+}
+
+impl Serializer for RowSerializer {
+    type Ok = Vec<(&'static str, Cell)>;
+    type Error = RowError;
+    type SerializeSeq = Impossible<Self::Ok, RowError>;
+    type SerializeStruct = FieldsSerializer;
+    reject_compound!();
+    reject_scalars!(
+        serialize_bool: bool, serialize_i8: i8, serialize_i16: i16, serialize_i32: i32,
+        serialize_i64: i64, serialize_u8: u8, serialize_u16: u16, serialize_u32: u32,
+        serialize_u64: u64, serialize_f32: f32, serialize_f64: f64, serialize_char: char,
+        serialize_str: &str, serialize_unit_struct: &'static str
+    );
+
+    fn serialize_none(self) -> Result<Self::Ok, RowError> {
+        unsupported("a value outside a record")
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<Self::Ok, RowError> {
+        unsupported("a value outside a record")
+    }
+    fn serialize_unit(self) -> Result<Self::Ok, RowError> {
+        unsupported("a value outside a record")
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Self::Ok, RowError> {
+        unsupported("a value outside a record")
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, RowError> {
+        value.serialize(self)
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, RowError> {
+        unsupported("a value outside a record")
+    }
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<FieldsSerializer, RowError> {
+        Ok(FieldsSerializer(Vec::with_capacity(len)))
+    }
+}
+
+/// The field names of a record type, in order, without a record at hand: the columns its
+/// table has once it has rows.
+pub fn columns_of<'de, R: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = R::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// A deserializer that only records the field names it is asked for.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, RowError> {
+        unsupported("a record that is not a struct")
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, RowError> {
+        *self.0 = fields;
+        Err(RowError("only the field names are read".to_string()))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Record {
+        gap: u64,
+        rate: f64,
+        label: Option<String>,
+        primes: Vec<u32>,
+    }
+
+    #[test]
+    fn test_records_become_typed_cells() {
+        let records = [
+            Record {
+                gap: 4,
+                rate: 1e-7,
+                label: None,
+                primes: vec![3, 5],
+            },
+            Record {
+                gap: 6,
+                rate: 0.5,
+                label: Some("a,b".to_string()),
+                primes: vec![],
+            },
+        ];
+        let mut table = Table::new("t.csv", &records).unwrap();
+        assert_eq!(table.columns, columns_of::<Record>());
+        assert_eq!(table.columns, ["gap", "rate", "label", "primes"]);
+        assert_eq!(
+            table.rows[0],
+            [
+                Cell::UInt(4),
+                Cell::Float(1e-7),
+                Cell::Empty,
+                Cell::List(vec![3, 5])
+            ]
+        );
+
+        let fields: Vec<String> = table.rows[0].iter().map(Cell::to_csv).collect();
+        assert_eq!(fields, ["4", "1e-7", "", "3,5"]);
+        // Floats are formatted as the csv crate formats a serialized record.
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+        wtr.serialize((1e-7f64,)).unwrap();
+        assert_eq!(wtr.into_inner().unwrap(), b"1e-7\n");
+
+        table.retain_columns(|column| column != "rate");
+        let json: Vec<String> = table
+            .json_rows()
+            .map(|row| serde_json::to_string(&row).unwrap())
+            .collect();
+        assert_eq!(
+            json,
+            [
+                r#"{"gap":4,"label":null,"primes":[3,5]}"#,
+                r#"{"gap":6,"label":"a,b","primes":[]}"#
+            ]
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! `--fail-if`: conditions on the final statistics that fail the run with
+//! [`FAIL_IF_EXIT_CODE`], so a pipeline can tell "hypothesis not supported" from an error.
+//!
+//! A condition compares two arithmetic expressions (`+ - * /`, parentheses, numbers) over
+//! the metrics of the written outputs:
+//!
+//! * `gap<G>.<column>`: a numeric column of gap G's row of `gap_spectrum.csv`, with the
+//!   shorthands `rate`, `expected` and `boost` for `success_rate`, `expected_rate_heuristic`
+//!   and `theoretical_boost`;
+//! * a numeric column of `global_stats.csv`, or `ratio` for `global_ratio_s_p`;
+//! * `baseline`, the pooled success rate of all prime pairs.
+//!
+//! A condition that cannot be evaluated (a gap that never occurred) fails the run too; one
+//! reading a column that `--slim` or `--columns` leaves out is rejected with the arguments.
+
+use crate::config::Config;
+use crate::output::{self, ColumnTable};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Exit code of a run one of whose `--fail-if` conditions held.
+pub const FAIL_IF_EXIT_CODE: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Metric(String),
+    Neg(Box<Expr>),
+    /// `+`, `-`, `*` or `/`.
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+impl Expr {
+    /// Names of the metrics the expression reads.
+    fn metrics<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Metric(name) => names.push(name),
+            Expr::Neg(e) => e.metrics(names),
+            Expr::Binary(a, _, b) => {
+                a.metrics(names);
+                b.metrics(names);
+            }
+        }
+    }
+
+    fn value(&self, metrics: &RunMetrics) -> Result<f64, String> {
+        Ok(match self {
+            Expr::Number(x) => *x,
+            Expr::Metric(name) => metrics.get(name)?,
+            Expr::Neg(e) => -e.value(metrics)?,
+            Expr::Binary(a, op, b) => {
+                let (a, b) = (a.value(metrics)?, b.value(metrics)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ => a / b,
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 12] = [
+    "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "(", ")",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() || c == '.' {
+            let mut end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            // An exponent, as in 1e-3.
+            if let Some(exp) = rest[end..].strip_prefix(['e', 'E']) {
+                let digits = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+                let n = digits.len()
+                    - digits
+                        .trim_start_matches(|c: char| c.is_ascii_digit())
+                        .len();
+                if n > 0 {
+                    end = rest.len() - digits.len() + n;
+                }
+            }
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("invalid number {:?}", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(**s))
+                .ok_or_else(|| format!("unexpected {:?}", c))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens of one condition.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_symbol(&self, symbols: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(s)) if symbols.contains(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        while let Some(op) = self.peek_symbol(&["+", "-"]) {
+            self.pos += 1;
+            let rhs = self.product()?;
+            expr = Expr::Binary(Box::new(expr), op.chars().next().unwrap(), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while let Some(op) = self.peek_symbol(&["*", "/"]) {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            expr = Expr::Binary(Box::new(expr), op.chars().next().unwrap(), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_symbol(&["-"]).is_some() {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(x)) => Ok(Expr::Number(x)),
+            Some(Token::Name(name)) => Ok(Expr::Metric(name)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.sum()?;
+                if self.peek_symbol(&[")"]).is_none() {
+                    return Err("missing )".to_string());
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(Token::Symbol(s)) => Err(format!("unexpected {:?}", s)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+/// One `--fail-if` condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailIf {
+    source: String,
+    lhs: Expr,
+    comparison: Comparison,
+    rhs: Expr,
+}
+
+/// Parses a `--fail-if` condition such as `gap4.rate < 1.1*baseline`.
+pub fn parse_fail_if(s: &str) -> Result<FailIf, String> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+    let lhs = parser.sum()?;
+    let comparison = match parser.peek_symbol(&["<=", ">=", "==", "!=", "<", ">"]) {
+        Some("<") => Comparison::Lt,
+        Some("<=") => Comparison::Le,
+        Some(">") => Comparison::Gt,
+        Some(">=") => Comparison::Ge,
+        Some("==") => Comparison::Eq,
+        Some(_) => Comparison::Ne,
+        None => return Err("expected a comparison (<, <=, >, >=, ==, !=)".to_string()),
+    };
+    parser.pos += 1;
+    let rhs = parser.sum()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!(
+            "unexpected {:?} after the condition",
+            parser.tokens[parser.pos]
+        ));
+    }
+    Ok(FailIf {
+        source: s.trim().to_string(),
+        lhs,
+        comparison,
+        rhs,
+    })
+}
+
+impl FailIf {
+    /// The CSV outputs and columns the condition reads, by file stem.
+    fn columns(&self) -> Vec<(&'static str, String)> {
+        let mut names = Vec::new();
+        self.lhs.metrics(&mut names);
+        self.rhs.metrics(&mut names);
+        // Every condition reads the gap spectrum's counts (see `RunMetrics::read`).
+        let mut columns: Vec<_> = ["gap_size", "count", "successes"]
+            .into_iter()
+            .map(|column| ("gap_spectrum", column.to_string()))
+            .collect();
+        for name in names {
+            match Metric::parse(name) {
+                Metric::Baseline => columns.extend(
+                    ["spectrum_overflow_count", "spectrum_overflow_successes"]
+                        .into_iter()
+                        .map(|column| ("global_stats", column.to_string())),
+                ),
+                Metric::Gap(_, column) => columns.push(("gap_spectrum", column.to_string())),
+                Metric::Global(column) => columns.push(("global_stats", column.to_string())),
+            }
+        }
+        columns
+    }
+
+    /// Both sides and whether the condition holds.
+    fn evaluate(&self, metrics: &RunMetrics) -> Result<(f64, f64, bool), String> {
+        let (a, b) = (self.lhs.value(metrics)?, self.rhs.value(metrics)?);
+        Ok((a, b, self.comparison.holds(a, b)))
+    }
+}
+
+/// The numeric columns of `gap_spectrum.csv` and `global_stats.csv` of a run.
+#[derive(Debug, Default)]
+pub struct RunMetrics {
+    gaps: BTreeMap<u64, BTreeMap<String, f64>>,
+    global: BTreeMap<String, f64>,
+    /// Pooled success rate of every prime pair, overflow included.
+    baseline: f64,
+}
+
+fn numeric_columns(table: &ColumnTable, row: output::ColumnRow) -> BTreeMap<String, f64> {
+    table
+        .column_names()
+        .filter_map(|name| Some((name.to_string(), row.get(name).ok()?)))
+        .collect()
+}
+
+/// What a metric name of a condition refers to.
+enum Metric<'a> {
+    Baseline,
+    /// A column of a gap's row of `gap_spectrum.csv`.
+    Gap(u64, &'a str),
+    /// A column of `global_stats.csv`.
+    Global(&'a str),
+}
+
+impl<'a> Metric<'a> {
+    fn parse(name: &'a str) -> Self {
+        if name == "baseline" {
+            return Metric::Baseline;
+        }
+        if let Some((gap, column)) = name.strip_prefix("gap").and_then(|n| n.split_once('.')) {
+            if let Ok(gap) = gap.parse::<u64>() {
+                let column = match column {
+                    "rate" => "success_rate",
+                    "expected" => "expected_rate_heuristic",
+                    "boost" => "theoretical_boost",
+                    column => column,
+                };
+                return Metric::Gap(gap, column);
+            }
+        }
+        Metric::Global(if name == "ratio" {
+            "global_ratio_s_p"
+        } else {
+            name
+        })
+    }
+}
+
+impl RunMetrics {
+    pub fn read(output_dir: &str) -> Result<Self, Box<dyn Error>> {
+        let mut metrics = RunMetrics::default();
+        let gaps = ColumnTable::read(output_dir, "gap_spectrum.csv")?;
+        gaps.require(&["gap_size", "count", "successes"])?;
+        let (mut count, mut successes) = (0.0, 0.0);
+        for row in gaps.rows() {
+            let columns = numeric_columns(&gaps, row);
+            count += columns["count"];
+            successes += columns["successes"];
+            metrics.gaps.insert(row.get("gap_size")?, columns);
+        }
+        let global = ColumnTable::read(output_dir, "global_stats.csv")?;
+        if let Some(row) = global.rows().next() {
+            metrics.global = numeric_columns(&global, row);
+        }
+        count += metrics
+            .global
+            .get("spectrum_overflow_count")
+            .unwrap_or(&0.0);
+        successes += metrics
+            .global
+            .get("spectrum_overflow_successes")
+            .unwrap_or(&0.0);
+        metrics.baseline = successes / count;
+        Ok(metrics)
+    }
+
+    fn get(&self, name: &str) -> Result<f64, String> {
+        match Metric::parse(name) {
+            Metric::Baseline => Ok(self.baseline),
+            Metric::Gap(gap, column) => self
+                .gaps
+                .get(&gap)
+                .ok_or_else(|| format!("gap {} never occurred", gap))?
+                .get(column)
+                .copied()
+                .ok_or_else(|| format!("gap_spectrum.csv has no numeric column {}", column)),
+            Metric::Global(column) => self
+                .global
+                .get(column)
+                .copied()
+                .ok_or_else(|| format!("unknown metric {}", name)),
+        }
+    }
+}
+
+/// The `--fail-if` conditions that held, or could not be evaluated.
+#[derive(Debug)]
+pub struct ThresholdsFailed {
+    pub failed: usize,
+}
+
+impl std::fmt::Display for ThresholdsFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} --fail-if condition(s) failed the run", self.failed)
+    }
+}
+
+impl Error for ThresholdsFailed {}
+
+/// Checks that no condition reads a column that `--slim` or `--columns` leaves out of the
+/// outputs of `config`.
+pub fn check_columns(conditions: &[FailIf], config: &Config) -> Result<(), String> {
+    let selection = output::ColumnSelection::new(config);
+    for condition in conditions {
+        for (stem, column) in condition.columns() {
+            if !selection.keeps(&format!("{}.csv", stem), &column) {
+                return Err(format!(
+                    "--fail-if '{}' reads column {} of {}.csv, which {} leaves out",
+                    condition.source,
+                    column,
+                    stem,
+                    if config.slim { "--slim" } else { "--columns" }
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates `conditions` against the outputs in `output_dir`, printing each one that holds
+/// and erroring with [`ThresholdsFailed`] if any does.
+pub fn check(conditions: &[FailIf], output_dir: &str) -> Result<(), Box<dyn Error>> {
+    if conditions.is_empty() {
+        return Ok(());
+    }
+    let metrics = RunMetrics::read(output_dir)?;
+    let mut failed = 0;
+    for condition in conditions {
+        match condition.evaluate(&metrics) {
+            Ok((a, b, true)) => {
+                eprintln!("Failed: {} ({} vs {})", condition.source, a, b);
+                failed += 1;
+            }
+            Ok((a, b, false)) => crate::info!("Passed: not {} ({} vs {})", condition.source, a, b),
+            Err(e) => {
+                eprintln!("Failed: {} cannot be evaluated: {}", condition.source, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Box::new(ThresholdsFailed { failed }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fail_if_parses_and_evaluates() {
+        let metrics = RunMetrics {
+            gaps: BTreeMap::from([(4, BTreeMap::from([("success_rate".to_string(), 0.24)]))]),
+            global: BTreeMap::from([("global_ratio_s_p".to_string(), 0.18)]),
+            baseline: 0.2,
+        };
+        let eval = |s: &str| parse_fail_if(s).unwrap().evaluate(&metrics);
+        assert!(!eval("gap4.rate < 1.1*baseline").unwrap().2);
+        assert!(eval("gap4.rate >= (1 + 0.1) * baseline").unwrap().2);
+        assert!(eval("-ratio + 2e-1 > 1.5E-2").unwrap().2);
+        assert!(eval("gap6.rate > 0")
+            .unwrap_err()
+            .contains("never occurred"));
+        assert!(eval("gap4.p_value > 0").is_err());
+        for bad in ["gap4.rate", "gap4.rate < ", "(1 < 2", "1 < 2 3", "1 ~ 2"] {
+            assert!(parse_fail_if(bad).is_err(), "{}", bad);
+        }
+    }
+}
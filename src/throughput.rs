@@ -0,0 +1,108 @@
+//! Per-segment throughput of the analysis loop, written to `throughput.csv`.
+//!
+//! Each row covers the primes p between two segment boundaries of the loop. Its sieve
+//! time is the time spent sieving those p plus the time spent sieving (or waiting for the
+//! prefetch of) their sums S, so a rising share of sieve time marks the point where the
+//! run stops fitting in cache.
+
+use crate::output::PartitionedWriter;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+pub const THROUGHPUT_FILE: &str = "throughput.csv";
+
+/// One segment of the loop, one row of `throughput.csv`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThroughputRecord {
+    /// First prime p of the segment.
+    pub segment_start: u64,
+    /// Last prime p of the segment.
+    pub segment_end: u64,
+    pub primes: u64,
+    /// Sums S tested for primality (fewer than `primes` when sampling).
+    pub s_checks: u64,
+    pub elapsed_secs: f64,
+    pub sieve_secs: f64,
+    pub primes_per_sec: f64,
+    pub s_checks_per_sec: f64,
+}
+
+/// Counts the loop's work per segment and writes a record at every boundary.
+#[derive(Debug)]
+pub struct ThroughputTracker {
+    wtr: PartitionedWriter,
+    started: Instant,
+    segment_start: Option<u64>,
+    primes: u64,
+    s_checks: u64,
+    sum_sieve_time: Duration,
+    /// The prime iterator's cumulative sieve time at the start of the segment.
+    prime_sieve_time: Duration,
+}
+
+impl ThroughputTracker {
+    pub fn create(
+        output_dir: &str,
+        max_rows: u64,
+        prime_sieve_time: Duration,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            wtr: PartitionedWriter::create(output_dir, THROUGHPUT_FILE, max_rows, None)?,
+            started: Instant::now(),
+            segment_start: None,
+            primes: 0,
+            s_checks: 0,
+            sum_sieve_time: Duration::ZERO,
+            prime_sieve_time,
+        })
+    }
+
+    /// Counts the prime `p`, and its sum S when it was tested.
+    #[inline]
+    pub fn record_prime(&mut self, p: u64, s_checked: bool) {
+        self.segment_start.get_or_insert(p);
+        self.primes += 1;
+        self.s_checks += s_checked as u64;
+    }
+
+    /// Adds time spent sieving sums S for the current segment.
+    pub fn add_sum_sieve_time(&mut self, time: Duration) {
+        self.sum_sieve_time += time;
+    }
+
+    /// Closes the segment ending at the prime `last_p`, given the prime iterator's
+    /// cumulative sieve time.
+    pub fn end_segment(
+        &mut self,
+        last_p: u64,
+        prime_sieve_time: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(segment_start) = self.segment_start.take() else {
+            return Ok(());
+        };
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let sieve = prime_sieve_time.saturating_sub(self.prime_sieve_time) + self.sum_sieve_time;
+        self.wtr.serialize(ThroughputRecord {
+            segment_start,
+            segment_end: last_p,
+            primes: self.primes,
+            s_checks: self.s_checks,
+            elapsed_secs: elapsed,
+            sieve_secs: sieve.as_secs_f64(),
+            primes_per_sec: self.primes as f64 / elapsed.max(1e-9),
+            s_checks_per_sec: self.s_checks as f64 / elapsed.max(1e-9),
+        })?;
+
+        self.started = Instant::now();
+        self.primes = 0;
+        self.s_checks = 0;
+        self.sum_sieve_time = Duration::ZERO;
+        self.prime_sieve_time = prime_sieve_time;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.wtr.finish()
+    }
+}
@@ -0,0 +1,182 @@
+//! The `trends` subcommand: how the global S/P ratio and gap rates scale with max N
+//! across the runs recorded in a registry.
+
+use crate::config::TrendsConfig;
+use crate::registry::{Registry, RunEntry};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub fn run(config: &TrendsConfig) -> Result<(), Box<dyn Error>> {
+    let registry = Registry::load(&config.registry)?;
+
+    // One point per max N: the most recently registered complete run.
+    let mut by_max_n: BTreeMap<u64, RunEntry> = BTreeMap::new();
+    for run in registry.runs.into_iter().filter(|r| r.complete) {
+        match by_max_n.get(&run.max_n) {
+            Some(existing) if existing.recorded_at > run.recorded_at => {}
+            _ => {
+                by_max_n.insert(run.max_n, run);
+            }
+        }
+    }
+    if by_max_n.is_empty() {
+        return Err(format!("{} has no complete runs registered", config.registry).into());
+    }
+    let runs: Vec<RunEntry> = by_max_n.into_values().collect();
+
+    let mut gaps = config.gaps.clone();
+    if gaps.is_empty() {
+        gaps = runs
+            .iter()
+            .flat_map(|r| r.gap_rates.keys().copied())
+            .collect();
+    }
+    gaps.sort_unstable();
+    gaps.dedup();
+
+    fs::create_dir_all(&config.output_dir)?;
+    let mut wtr = csv::Writer::from_path(Path::new(&config.output_dir).join("trends.csv"))?;
+    let mut headers: Vec<String> = vec![
+        "max_exponent".to_string(),
+        "max_n".to_string(),
+        "output_dir".to_string(),
+        "label".to_string(),
+        "sample_every".to_string(),
+        "global_ratio_s_p".to_string(),
+    ];
+    for g in &gaps {
+        headers.push(format!("gap_{}_rate", g));
+    }
+    wtr.write_record(headers.iter())?;
+    for run in &runs {
+        let mut row: Vec<String> = vec![
+            run.max_exponent.to_string(),
+            run.max_n.to_string(),
+            run.output_dir.clone(),
+            run.label.clone().unwrap_or_default(),
+            run.sample_every.to_string(),
+            run.global_ratio_s_p.to_string(),
+        ];
+        // Gaps a run did not track are left empty.
+        row.extend(gaps.iter().map(|g| {
+            run.gap_rates
+                .get(g)
+                .map_or(String::new(), |r| r.to_string())
+        }));
+        wtr.write_record(row.iter())?;
+    }
+    wtr.flush()?;
+
+    #[cfg(feature = "report")]
+    write_html(config, &gaps, &runs)?;
+    println!(
+        "Trends for {} runs written to {}/trends.csv{}",
+        runs.len(),
+        config.output_dir,
+        if cfg!(feature = "report") {
+            " and trends.html"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+#[cfg(feature = "report")]
+fn write_html(
+    config: &TrendsConfig,
+    gaps: &[u64],
+    runs: &[RunEntry],
+) -> Result<(), Box<dyn Error>> {
+    let data = serde_json::json!({ "gaps": gaps, "runs": runs });
+    let data_json = serde_json::to_string(&data)?.replace("</", "<\\/");
+    let html_content = r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Prime Sum Scaling Trends</title>
+    <script src="https://cdn.jsdelivr.net/npm/chart.js"></script>
+    <style>
+        :root {
+            --page-bg: #f8f9fa; --card-bg: #fff; --text: #212529; --heading: #343a40; --muted: #6c757d;
+            --shadow: rgba(0,0,0,0.1);
+        }
+        body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, Helvetica, Arial, sans-serif; margin: 0; background-color: var(--page-bg); color: var(--text); }
+        .container { max-width: 1200px; margin: 2rem auto; padding: 2rem; background-color: var(--card-bg); border-radius: 8px; box-shadow: 0 4px 6px var(--shadow); }
+        h1, h2 { text-align: center; color: var(--heading); }
+        .summary { text-align: center; margin-bottom: 2rem; color: var(--muted); font-size: 1.1em; }
+        .chart-container { margin-top: 2rem; }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>Scaling Trends</h1>
+        <div class="summary" id="summary"></div>
+
+        <div class="chart-container">
+            <h2>Global S/P Ratio</h2>
+            <canvas id="globalChart"></canvas>
+        </div>
+
+        <div class="chart-container">
+            <h2>Gap Success Rates</h2>
+            <canvas id="gapChart"></canvas>
+        </div>
+    </div>
+
+    <script id="trendsData" type="application/json">__TRENDS_DATA__</script>
+
+    <script>
+        const data = JSON.parse(document.getElementById('trendsData').textContent);
+        document.getElementById('summary').innerText =
+            `${data.runs.length} registered runs, N = 10^${data.runs[0].max_exponent} … 10^${data.runs[data.runs.length - 1].max_exponent}`;
+
+        const logX = {
+            type: 'logarithmic',
+            title: { display: true, text: 'Max N' },
+            ticks: { callback: v => Number.isInteger(Math.log10(v)) ? `10^${Math.log10(v)}` : '' }
+        };
+
+        new Chart(document.getElementById('globalChart'), {
+            type: 'line',
+            data: {
+                datasets: [{
+                    label: 'S/P ratio',
+                    data: data.runs.map(r => ({ x: r.max_n, y: r.global_ratio_s_p })),
+                    borderColor: 'rgb(0, 123, 255)',
+                    tension: 0.1,
+                }]
+            },
+            options: { scales: { x: logX, y: { title: { display: true, text: 'Ratio' } } } }
+        });
+
+        const colors = ['#dc3545', '#28a745', '#6f42c1', '#fd7e14', '#17a2b8', '#e83e8c', '#20c997', '#6c757d'];
+        new Chart(document.getElementById('gapChart'), {
+            type: 'line',
+            data: {
+                datasets: data.gaps.map((g, i) => ({
+                    label: `Gap ${g}`,
+                    data: data.runs
+                        .filter(r => r.gap_rates[g] !== undefined)
+                        .map(r => ({ x: r.max_n, y: r.gap_rates[g] })),
+                    borderColor: colors[i % colors.length],
+                    tension: 0.1,
+                }))
+            },
+            options: { scales: { x: logX, y: { title: { display: true, text: 'Success rate' } } } }
+        });
+    </script>
+</body>
+</html>
+"#
+    .replace("__TRENDS_DATA__", &data_json);
+    fs::write(
+        Path::new(&config.output_dir).join("trends.html"),
+        html_content,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! `--watchdog-secs`: a background thread that notices when the analysis loop has not
+//! reached a new segment for too long and dumps what it was doing.
+//!
+//! The dump holds the last segment the loop entered, the primality cache counters and
+//! memory sizes at that point, and the state of every thread of the process. On Linux the
+//! thread states come from `/proc/self/task` (run state and kernel wait channel); the
+//! standard library cannot capture another thread's backtrace, so `--watchdog-abort`
+//! aborts the process instead, leaving a core dump to inspect with a debugger.
+
+use crate::memory::MemoryUsage;
+use crate::sieve::CacheStats;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const WATCHDOG_LOG: &str = "watchdog.log";
+
+/// What the analysis loop reported when it last made progress.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Snapshot {
+    /// Every pair up to this prime was accounted for.
+    pub analyzed_up_to: u64,
+    /// Range of p the loop was working through.
+    pub segment: (u64, u64),
+    /// Range of sums S sieved for it.
+    pub sum_range: (u64, u64),
+    pub cache: CacheStats,
+    pub memory: MemoryUsage,
+}
+
+#[derive(Debug)]
+struct State {
+    snapshot: Snapshot,
+    last_progress: Instant,
+    /// Whether the current stall has already been reported.
+    reported: bool,
+    stopped: bool,
+}
+
+type Shared = (Mutex<State>, Condvar);
+
+/// Handle to the watchdog thread; dropping it without [`Watchdog::stop`] leaves it running.
+#[derive(Debug)]
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    handle: JoinHandle<()>,
+}
+
+impl Watchdog {
+    /// Starts watching: a stall is `timeout` without a call to [`Watchdog::progress`].
+    pub fn spawn(timeout: Duration, abort: bool, output_dir: &str) -> Self {
+        let shared: Arc<Shared> = Arc::new((
+            Mutex::new(State {
+                snapshot: Snapshot::default(),
+                last_progress: Instant::now(),
+                reported: false,
+                stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        let log_path = Path::new(output_dir).join(WATCHDOG_LOG);
+        let thread_shared = Arc::clone(&shared);
+        let handle = std::thread::Builder::new()
+            .name("watchdog".to_string())
+            .spawn(move || watch(&thread_shared, timeout, abort, &log_path))
+            .expect("failed to spawn the watchdog thread");
+        Self { shared, handle }
+    }
+
+    /// Records that the loop entered a new segment.
+    pub fn progress(&self, snapshot: Snapshot) {
+        let mut state = self.shared.0.lock().unwrap();
+        state.snapshot = snapshot;
+        state.last_progress = Instant::now();
+        state.reported = false;
+    }
+
+    /// Stops and joins the watchdog thread.
+    pub fn stop(self) {
+        self.shared.0.lock().unwrap().stopped = true;
+        self.shared.1.notify_all();
+        let _ = self.handle.join();
+    }
+}
+
+fn watch(shared: &Shared, timeout: Duration, abort: bool, log_path: &Path) {
+    let (lock, stop) = shared;
+    // Check a few times per timeout so a stall is reported soon after it starts.
+    let poll = (timeout / 4).max(Duration::from_millis(100));
+    let mut state = lock.lock().unwrap();
+    loop {
+        state = stop.wait_timeout(state, poll).unwrap().0;
+        if state.stopped {
+            return;
+        }
+        let stalled_for = state.last_progress.elapsed();
+        if stalled_for < timeout || state.reported {
+            continue;
+        }
+        state.reported = true;
+        let report = diagnostic_report(&state.snapshot, stalled_for);
+        eprintln!("{}", report);
+        if let Err(e) = append_log(log_path, &report) {
+            eprintln!("watchdog: could not write {}: {}", log_path.display(), e);
+        }
+        if abort {
+            eprintln!("watchdog: aborting (--watchdog-abort)");
+            std::process::abort();
+        }
+    }
+}
+
+fn diagnostic_report(snapshot: &Snapshot, stalled_for: Duration) -> String {
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "watchdog: no progress for {:.1}s",
+        stalled_for.as_secs_f64()
+    );
+    let _ = writeln!(report, "  analyzed up to p = {}", snapshot.analyzed_up_to);
+    let _ = writeln!(
+        report,
+        "  segment p in [{}, {}), sums S in [{}, {}]",
+        snapshot.segment.0, snapshot.segment.1, snapshot.sum_range.0, snapshot.sum_range.1
+    );
+    let _ = writeln!(
+        report,
+        "  primality cache: {} local hits, {} store hits, {} misses, {} evictions",
+        snapshot.cache.local_hits,
+        snapshot.cache.store_hits,
+        snapshot.cache.misses,
+        snapshot.cache.evictions
+    );
+    let _ = writeln!(report, "  {}", snapshot.memory.summary());
+    match thread_states() {
+        Some(threads) => {
+            let _ = writeln!(report, "  threads (tid name state wchan):");
+            for thread in threads {
+                let _ = writeln!(report, "    {}", thread);
+            }
+        }
+        None => {
+            let _ = writeln!(report, "  thread states are not available on this platform");
+        }
+    }
+    report
+}
+
+/// One line per thread of this process from `/proc/self/task`, on Linux.
+fn thread_states() -> Option<Vec<String>> {
+    let mut tasks: Vec<(u64, PathBuf)> = fs::read_dir("/proc/self/task")
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            Some((entry.file_name().to_str()?.parse().ok()?, entry.path()))
+        })
+        .collect();
+    tasks.sort();
+
+    let read = |dir: &Path, file: &str| {
+        fs::read_to_string(dir.join(file))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    Some(
+        tasks
+            .iter()
+            .map(|(tid, dir)| {
+                // The state follows the parenthesized command name, which may contain spaces.
+                let stat = read(dir, "stat");
+                let state = stat
+                    .rsplit_once(')')
+                    .and_then(|(_, rest)| rest.split_whitespace().next())
+                    .unwrap_or("?")
+                    .to_string();
+                let wchan = read(dir, "wchan");
+                format!(
+                    "{} {} {} {}",
+                    tid,
+                    read(dir, "comm"),
+                    state,
+                    // Running threads have no wait channel.
+                    if wchan.is_empty() || wchan == "0" {
+                        "-"
+                    } else {
+                        &wchan
+                    }
+                )
+            })
+            .collect(),
+    )
+}
+
+fn append_log(path: &Path, report: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", report)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_is_logged_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        let watchdog = Watchdog::spawn(Duration::from_millis(200), false, out);
+        watchdog.progress(Snapshot {
+            analyzed_up_to: 1_000_003,
+            segment: (1_000_003, 2_048_579),
+            ..Snapshot::default()
+        });
+        std::thread::sleep(Duration::from_millis(800));
+        watchdog.stop();
+
+        let log = fs::read_to_string(dir.path().join(WATCHDOG_LOG)).unwrap();
+        assert_eq!(log.matches("no progress").count(), 1);
+        assert!(log.contains("segment p in [1000003, 2048579)"));
+    }
+}
@@ -1,12 +1,12 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
 use std::process::Command;
-use tempfile::tempdir;
 
 #[test]
+#[cfg(feature = "report")]
 fn test_cli_smoke_test() -> Result<(), Box<dyn std::error::Error>> {
     // Create a temporary directory for the output
-    let temp_dir = tempdir()?;
+    let temp_dir = tempfile::tempdir()?;
     // Ensure the output directory is 'report' to match the application's output path
     let output_dir = temp_dir.path().join("report");
 
@@ -34,3 +34,646 @@ fn test_cli_smoke_test() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_self_test_matches_fixtures() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.arg("self-test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Self-test passed"));
+
+    Ok(())
+}
+
+#[test]
+fn test_quiet_run_prints_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["-E", "4", "-q", "--output-dir"])
+        .arg(temp_dir.path());
+
+    cmd.assert().success().stdout(predicate::str::is_empty());
+    assert!(temp_dir.path().join("global_stats.csv").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_columns_and_slim_trim_the_csv_outputs() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["-E", "4", "-q", "--slim", "--sink", "json", "--columns"])
+        .arg("gap_spectrum=gap_size,count,successes,success_rate,p_value")
+        .arg("--output-dir")
+        .arg(temp_dir.path());
+    cmd.assert().success();
+
+    let header = |file: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(temp_dir.path().join(file))?;
+        Ok(text.lines().next().unwrap_or_default().to_string())
+    };
+    assert_eq!(
+        header("gap_spectrum.csv")?,
+        "gap_size,count,successes,success_rate"
+    );
+    assert!(!header("conditional_rates.csv")?.contains("success_ci_low"));
+    // The other sinks get the same columns.
+    let ndjson = std::fs::read_to_string(temp_dir.path().join("gap_spectrum.ndjson"))?;
+    let row: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(ndjson.lines().next().unwrap_or_default())?;
+    assert_eq!(
+        row.keys().map(String::as_str).collect::<Vec<_>>(),
+        ["count", "gap_size", "success_rate", "successes"]
+    );
+
+    // An unknown column is rejected before anything is written.
+    let rejected = temp_dir.path().join("rejected");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args([
+        "-E",
+        "4",
+        "-q",
+        "--columns",
+        "gap_spectrum=gap",
+        "--output-dir",
+    ])
+    .arg(&rejected);
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("has no column 'gap'"));
+    assert!(!rejected.exists());
+
+    // A condition on a column left out is an argument error, not a failed threshold.
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["-E", "4", "-q", "--slim", "--fail-if", "gap4.boost < 1"])
+        .arg("--output-dir")
+        .arg(temp_dir.path());
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("theoretical_boost"));
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["-E", "4", "-q", "--columns"])
+        .arg("gap_spectrum=gap_size,count,successes,success_rate")
+        .args(["--fail-if", "gap4.rate < 0", "--output-dir"])
+        .arg(temp_dir.path());
+    cmd.assert().success();
+
+    Ok(())
+}
+
+#[test]
+fn test_ingest_matches_a_sieved_run() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let is_prime = |n: u64| {
+        n >= 2
+            && (2..n)
+                .take_while(|d| d * d <= n)
+                .all(|d| !n.is_multiple_of(d))
+    };
+    let primes: Vec<u64> = (2..=1000).filter(|&n| is_prime(n)).collect();
+    let mut pairs = String::from("p_prev,p_curr,s_is_prime\n");
+    for w in primes.windows(2) {
+        pairs += &format!(
+            "{},{},{}\n",
+            w[0],
+            w[1],
+            u8::from(is_prime(w[0] + w[1] - 1))
+        );
+    }
+    let input = temp_dir.path().join("pairs.csv");
+    std::fs::write(&input, pairs)?;
+
+    let sieved = temp_dir.path().join("sieved");
+    let ingested = temp_dir.path().join("ingested");
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .args(["-E", "3", "-q", "--output-dir"])
+        .arg(&sieved)
+        .assert()
+        .success();
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .arg("ingest")
+        .arg(&input)
+        .args(["-E", "3", "-q", "--output-dir"])
+        .arg(&ingested)
+        .assert()
+        .success();
+
+    for file in [
+        "gap_spectrum.csv",
+        "oscillation_series.csv",
+        "prime_counts.csv",
+    ] {
+        assert_eq!(
+            std::fs::read_to_string(sieved.join(file))?,
+            std::fs::read_to_string(ingested.join(file))?,
+            "{}",
+            file
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_exponent_list_writes_each_milestone() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let combined = temp_dir.path().join("combined");
+    let single = temp_dir.path().join("single");
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .args(["-E", "4,3.5", "-q", "--output-dir"])
+        .arg(&combined)
+        .assert()
+        .success();
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .args(["-E", "3.5", "-q", "--output-dir"])
+        .arg(&single)
+        .assert()
+        .success();
+
+    for file in [
+        "gap_spectrum.csv",
+        "oscillation_series.csv",
+        "streaks.csv",
+        "manifest.json",
+    ] {
+        assert_eq!(
+            std::fs::read_to_string(single.join(file))?,
+            std::fs::read_to_string(combined.join("e3.5").join(file))?,
+            "{}",
+            file
+        );
+    }
+    assert!(combined.join("gap_spectrum.csv").exists());
+    Ok(())
+}
+
+#[test]
+fn test_streamed_bins_match_a_held_run() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let run = |extra: &[&str], dir: &str| {
+        Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+            .args(["-E", "4", "-q", "--gaps", "2,4,6"])
+            .args(extra)
+            .arg("--output-dir")
+            .arg(temp_dir.path().join(dir))
+            .assert()
+            .success();
+    };
+    run(&[], "held");
+    run(&["--stream-bins"], "streamed");
+
+    for file in ["oscillation_series.csv", "gap_bins.csv"] {
+        let held = std::fs::read(temp_dir.path().join("held").join(file))?;
+        let streamed = std::fs::read(temp_dir.path().join("streamed").join(file))?;
+        assert!(!held.is_empty(), "{}", file);
+        assert!(held == streamed, "{}", file);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_gap_bins_add_up_to_the_spectrum() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .args(["-E", "4", "-q", "--gaps", "2,4,6,30", "--output-dir"])
+        .arg(temp_dir.path())
+        .assert()
+        .success();
+
+    // (gap, count, successes) per row, by column name.
+    let rows =
+        |file: &str, names: [&str; 3]| -> Result<Vec<[u64; 3]>, Box<dyn std::error::Error>> {
+            let text = std::fs::read_to_string(temp_dir.path().join(file))?;
+            let mut lines = text.lines();
+            let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+            let columns = names.map(|name| header.iter().position(|&c| c == name).unwrap());
+            let mut rows = Vec::new();
+            for line in lines {
+                let cells: Vec<&str> = line.split(',').collect();
+                rows.push([
+                    cells[columns[0]].parse()?,
+                    cells[columns[1]].parse()?,
+                    cells[columns[2]].parse()?,
+                ]);
+            }
+            Ok(rows)
+        };
+    let bins = rows("gap_bins.csv", ["gap", "occurrences", "successes"])?;
+    let spectrum = rows("gap_spectrum.csv", ["gap_size", "count", "successes"])?;
+    for gap in [2, 4, 6, 30] {
+        let summed = bins
+            .iter()
+            .filter(|row| row[0] == gap)
+            .fold([gap, 0, 0], |acc, row| {
+                [gap, acc[1] + row[1], acc[2] + row[2]]
+            });
+        let total = spectrum.iter().find(|row| row[0] == gap).copied();
+        assert!(summed[1] > 0, "gap {}", gap);
+        assert_eq!(Some(summed), total, "gap {}", gap);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_diff_reports_the_known_difference() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    for (exponent, dir) in [("3", "small"), ("4", "large")] {
+        Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+            .args(["-E", exponent, "-q", "--output-dir"])
+            .arg(temp_dir.path().join(dir))
+            .assert()
+            .success();
+    }
+    let diff = temp_dir.path().join("diff");
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .arg("diff")
+        .arg(temp_dir.path().join("small"))
+        .arg(temp_dir.path().join("large"))
+        .arg("--output-dir")
+        .arg(&diff)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("differ at p < 0.05"));
+
+    // 167 pairs below 10^3 and 1228 below 10^4, of which 35 and 205 are twin primes.
+    let text = std::fs::read_to_string(diff.join("diff_report.csv"))?;
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+    let column = |name: &str| header.iter().position(|&c| c == name).unwrap();
+    let row = |kind: &str, key: &str| {
+        text.lines()
+            .map(|line| line.split(',').collect::<Vec<_>>())
+            .find(|cells| cells[column("kind")] == kind && cells[column("key")] == key)
+            .map(|cells| {
+                (
+                    cells[column("trials_a")].to_string(),
+                    cells[column("trials_b")].to_string(),
+                )
+            })
+    };
+    let trials = |a: &str, b: &str| Some((a.to_string(), b.to_string()));
+    assert_eq!(row("global", "all pairs"), trials("167", "1228"));
+    assert_eq!(row("gap", "2"), trials("35", "205"));
+    // The bins of the two runs cover different ranges, so none of them line up.
+    assert!(lines.all(|line| !line.starts_with("bin,")));
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshots_match_a_run_up_to_each_milestone() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let run = |extra: &[&str], dir: &str| {
+        Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+            .arg("-q")
+            .args(extra)
+            .arg("--output-dir")
+            .arg(temp_dir.path().join(dir))
+            .assert()
+            .success();
+    };
+    run(&["-E", "4", "--snapshot-every", "10^3"], "run");
+    run(&["-E", "3"], "e3");
+
+    let index = std::fs::read_to_string(temp_dir.path().join("run/snapshots/index.csv"))?;
+    let milestones: Vec<&str> = index.lines().skip(1).collect();
+    assert_eq!(milestones.len(), 9);
+    assert_eq!(milestones[0], "1000,168,n_1000");
+    assert_eq!(milestones[8], "9000,1117,n_9000");
+
+    // The cumulative counts at 10^3 are those of a run that stops there; the early/late
+    // columns differ, as they split each run at half its own range.
+    let counts = |path: std::path::PathBuf| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(text
+            .lines()
+            .map(|line| line.split(',').take(3).collect::<Vec<_>>().join(","))
+            .collect())
+    };
+    assert_eq!(
+        counts(
+            temp_dir
+                .path()
+                .join("run/snapshots/n_1000/gap_spectrum.csv")
+        )?,
+        counts(temp_dir.path().join("e3/gap_spectrum.csv"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_bin_domain_s_bins_each_pair_by_its_sum() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .args([
+            "-E",
+            "3",
+            "-q",
+            "--bin-domain",
+            "s",
+            "--bins",
+            "10",
+            "--output-dir",
+        ])
+        .arg(temp_dir.path())
+        .assert()
+        .success();
+
+    // Ten bins of 200 over S up to 2N, each counting the pairs whose S falls in it.
+    let is_prime = |n: u64| {
+        n >= 2
+            && (2..n)
+                .take_while(|d| d * d <= n)
+                .all(|d| !n.is_multiple_of(d))
+    };
+    let primes: Vec<u64> = (2..=1000).filter(|&n| is_prime(n)).collect();
+    let mut expected = [(0, 0); 10];
+    for pair in primes.windows(2) {
+        let s = pair[0] + pair[1] - 1;
+        let bin = &mut expected[(s / 200) as usize];
+        bin.0 += 1;
+        bin.1 += u64::from(is_prime(s));
+    }
+    let expected: Vec<String> = expected
+        .iter()
+        .enumerate()
+        .map(|(i, (pairs, sums))| format!("{},{},{},{}", i * 200, i * 200 + 199, pairs, sums))
+        .collect();
+
+    let text = std::fs::read_to_string(temp_dir.path().join("oscillation_series.csv"))?;
+    let bins: Vec<String> = text
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').take(4).collect::<Vec<_>>().join(","))
+        .collect();
+    assert_eq!(bins, expected);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "report")]
+fn test_report_snapshots_cover_each_power_of_ten() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+        .args([
+            "-E",
+            "4",
+            "-q",
+            "--web-report",
+            "--report-snapshots",
+            "--output-dir",
+        ])
+        .arg(temp_dir.path())
+        .assert()
+        .success();
+
+    // π(10^k) primes make π(10^k) - 1 pairs.
+    for (k, pairs) in [(1, 3), (2, 24), (3, 167), (4, 1228)] {
+        let spectrum: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(
+            temp_dir.path().join(format!("gap_spectrum_e{}.json", k)),
+        )?)?;
+        let counted: u64 = spectrum
+            .iter()
+            .filter_map(|row| row["count"].as_u64())
+            .sum();
+        assert_eq!(counted, pairs, "10^{}", k);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_demo_writes_and_lists_the_outputs() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["demo", "--no-open", "--dir"])
+        .arg(temp_dir.path());
+
+    cmd.assert().success().stdout(
+        predicate::str::contains("gap_spectrum.csv")
+            .and(predicate::str::contains("almost_prime_spectrum.csv")),
+    );
+    assert!(temp_dir.path().join("manifest.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_sinks_receive_the_same_tables() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args([
+        "-E",
+        "4",
+        "--sink",
+        "json",
+        "--sink",
+        "stream",
+        "--output-dir",
+    ])
+    .arg(temp_dir.path());
+    let output = cmd.assert().success().get_output().stdout.clone();
+
+    // The stream implies --quiet: stdout holds only its rows.
+    let stream = String::from_utf8(output)?;
+    assert!(stream.lines().all(|line| line.starts_with("{\"table\":\"")));
+    let csv_rows = std::fs::read_to_string(temp_dir.path().join("gap_spectrum.csv"))?
+        .lines()
+        .count()
+        - 1;
+    let json_rows = std::fs::read_to_string(temp_dir.path().join("gap_spectrum.ndjson"))?
+        .lines()
+        .count();
+    let stream_rows = stream
+        .lines()
+        .filter(|line| line.starts_with("{\"table\":\"gap_spectrum\""))
+        .count();
+    assert_eq!((json_rows, stream_rows), (csv_rows, csv_rows));
+
+    // The shield primes that the CSV joins with commas are an array in JSON.
+    let gap_4: serde_json::Value =
+        std::fs::read_to_string(temp_dir.path().join("gap_spectrum.ndjson"))?
+            .lines()
+            .map(serde_json::from_str)
+            .find(|row: &Result<serde_json::Value, _>| {
+                row.as_ref().is_ok_and(|row| row["gap_size"] == 4)
+            })
+            .ok_or("no row of gap 4")??;
+    assert_eq!(gap_4["shield_primes"], serde_json::json!([3, 5]));
+
+    Ok(())
+}
+
+#[test]
+fn test_shards_add_up_to_the_full_run() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let run = |extra: &[&str], dir: &str| -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+        cmd.args(["-E", "4", "--quiet"])
+            .args(extra)
+            .arg("--output-dir")
+            .arg(temp_dir.path().join(dir));
+        cmd.assert().success();
+        Ok(())
+    };
+    run(&[], "full")?;
+    run(&["--shard", "0/2"], "sharded")?;
+    run(&["--shard", "1/2"], "sharded")?;
+
+    // The pair counts of gap_spectrum.csv, summed over its rows.
+    let counts = |path: std::path::PathBuf| -> Result<(u64, u64), Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+        let column = |name: &str| header.iter().position(|&c| c == name).unwrap();
+        let (count, successes) = (column("count"), column("successes"));
+        let mut total = (0, 0);
+        for line in lines {
+            let cells: Vec<&str> = line.split(',').collect();
+            total.0 += cells[count].parse::<u64>()?;
+            total.1 += cells[successes].parse::<u64>()?;
+        }
+        Ok(total)
+    };
+    let full = counts(temp_dir.path().join("full/gap_spectrum.csv"))?;
+    let first = counts(temp_dir.path().join("sharded/shard_0/gap_spectrum.csv"))?;
+    let second = counts(temp_dir.path().join("sharded/shard_1/gap_spectrum.csv"))?;
+    assert!(first.0 > 0 && second.0 > 0);
+    assert_eq!((first.0 + second.0, first.1 + second.1), full);
+
+    Ok(())
+}
+
+#[test]
+fn test_prime_dumps_read_back_as_the_sieve() -> Result<(), Box<dyn std::error::Error>> {
+    use prime_shield_analyzer::sieve::{base_primes, is_prime_u64, primes_in_window};
+    use std::io::Read;
+
+    let temp_dir = tempfile::tempdir()?;
+    let read = |dir: &str, file: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let gz = std::fs::File::open(temp_dir.path().join(dir).join(file))?;
+        let mut bytes = Vec::new();
+        flate2::read::GzDecoder::new(gz).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    };
+    for format in ["text", "binary"] {
+        Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"))
+            .args(["-E", "4", "-q", "--dump-primes", "--dump-s-primes"])
+            .args(["--dump-format", format, "--output-dir"])
+            .arg(temp_dir.path().join(format))
+            .assert()
+            .success();
+    }
+    let text = |file: &str| -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+        let bytes = String::from_utf8(read("text", file)?)?;
+        Ok(bytes.lines().map(str::parse).collect::<Result<_, _>>()?)
+    };
+    let binary = |file: &str| -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+        let bytes = read("binary", file)?;
+        assert_eq!(bytes.len() % 8, 0);
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    };
+
+    let primes = primes_in_window(0, 10_001, &base_primes(10_001));
+    let s_primes: Vec<u64> = primes
+        .windows(2)
+        .map(|pair| pair[0] + pair[1] - 1)
+        .filter(|&s| is_prime_u64(s))
+        .collect();
+    assert_eq!(primes.len(), 1229);
+    assert_eq!(text("primes.txt.gz")?, primes);
+    assert_eq!(binary("primes.bin.gz")?, primes);
+    assert_eq!(text("s_primes.txt.gz")?, s_primes);
+    assert_eq!(binary("s_primes.bin.gz")?, s_primes);
+
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_estimates_disk_usage() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["-E", "6", "--dry-run", "--dump-primes", "--output-dir"])
+        .arg(temp_dir.path().join("not_yet_created"));
+    let output = cmd.assert().success().get_output().stdout.clone();
+
+    // The directory does not exist yet, so the free space is that of its parent.
+    let disk = String::from_utf8(output)?
+        .lines()
+        .find(|line| line.trim_start().starts_with("disk:"))
+        .map(String::from)
+        .ok_or("no disk line")?;
+    assert!(!disk.contains("dumps 0 B"), "{}", disk);
+    assert!(disk.ends_with(" free"), "{}", disk);
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic_run_sieves_no_sum_segment_on_demand() -> Result<(), Box<dyn std::error::Error>>
+{
+    let temp_dir = tempfile::tempdir()?;
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    // Small segments give the run many spans, each prefetched while the previous is read.
+    cmd.args([
+        "-E",
+        "6",
+        "--segment-size-kb",
+        "8",
+        "--deterministic",
+        "--quiet",
+    ])
+    .arg("--output-dir")
+    .arg(temp_dir.path());
+    cmd.assert().success();
+
+    let perf: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("perf.json"))?)?;
+    assert_eq!(perf["primality_cache"]["misses"], 0);
+    assert!(perf["primality_cache"]["store_hits"].as_u64() > Some(10));
+
+    Ok(())
+}
+
+#[test]
+fn test_prune_keeps_the_checkpoints_of_a_reused_directory() -> Result<(), Box<dyn std::error::Error>>
+{
+    let temp_dir = tempfile::tempdir()?;
+    let registry = temp_dir.path().join("runs.json");
+    let output_dir = temp_dir.path().join("run");
+    let prune = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+        cmd.args(["prune", "--older-than", "0", "--registry"])
+            .arg(&registry)
+            .current_dir(std::env::temp_dir());
+        cmd
+    };
+
+    // Recorded from a relative path, and pruned from another working directory.
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("prime_shield_analyzer"));
+    cmd.args(["-E", "4", "-q", "--dump-primes", "--output-dir", "run"])
+        .arg("--registry")
+        .arg(&registry)
+        .current_dir(temp_dir.path());
+    cmd.assert().success();
+    assert!(output_dir.join("primes.txt.gz").exists());
+    prune().assert().success();
+    assert!(!output_dir.join("primes.txt.gz").exists());
+    assert!(output_dir.join("manifest.json").exists());
+
+    // A later run reusing the directory has written a checkpoint but no manifest yet.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    std::fs::write(output_dir.join("checkpoint.state"), b"in progress")?;
+    prune()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("reused by a later run"));
+    assert!(output_dir.join("checkpoint.state").exists());
+
+    Ok(())
+}